@@ -0,0 +1,602 @@
+//! Measures of a puzzle's whole state space, for telling easy puzzles from hard ones.
+
+use std::collections::hash_map::Entry;
+use std::collections::VecDeque;
+
+use crate::{
+    reachable_states, solve, winning_states, PieceType, Puzzle, PuzzleState, SolutionCount, Square,
+    SquareSet, StateMap, StateSet,
+};
+
+/// Solutions are counted up to this many; past that, one more hardly changes the difficulty.
+const SOLUTION_LIMIT: usize = 1000;
+
+/// How hard a puzzle is, as measured by `difficulty`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difficulty {
+    /// How many states are reachable from the start, including the start itself.
+    pub states: usize,
+    /// The average number of captures available in a reachable state, not counting won states.
+    pub branching: f64,
+    /// How many reachable states can no longer go on to win.
+    pub dead_ends: usize,
+    /// The fraction of the captures available at the start that lose, or `0.0` if there are none.
+    pub losing_first_moves: f64,
+    /// How many winning capture orders there are, counted up to a limit.
+    pub solutions: SolutionCount,
+    /// The overall score: how many bits of luck a player needs to win by picking each capture at
+    /// random from those available. That is, `-log2` of the chance that such a player wins. It's
+    /// `0.0` if every line of play wins, and infinite if none does.
+    pub score: f64,
+}
+
+/// Measures how hard a puzzle is. This explores every reachable state, so it costs about as much
+/// as a failed `solve`.
+///
+/// ```
+/// use echochess::analyze::difficulty;
+/// use echochess::{Puzzle, SolutionCount};
+///
+/// // The rook on a3 can take the knight on b1 and then the bishop on c3, or take the bishop
+/// // first and be stuck. That's three states to choose in, with two, one, and no captures.
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let d = difficulty(&puzzle);
+/// assert_eq!(d.states, 4);
+/// assert_eq!(d.branching, 1.0);
+/// assert_eq!(d.dead_ends, 1);
+/// assert_eq!(d.losing_first_moves, 0.5);
+/// assert_eq!(d.solutions, SolutionCount::Exactly(1));
+/// assert_eq!(d.score, 1.0);
+/// ```
+pub fn difficulty(p: &Puzzle) -> Difficulty {
+    let states = reachable_states(p);
+    let winning = winning_states(p);
+    let mut choices = 0;
+    let mut choosing = 0;
+    for state in states.iter().filter(|s| !s.done()) {
+        state
+            .next_states(p, |_| choices += 1)
+            .expect("reachable states are valid");
+        choosing += 1;
+    }
+    let initial = PuzzleState::initial(p);
+    let (mut first, mut losing) = (0, 0);
+    initial
+        .next_states(p, |next| {
+            first += 1;
+            if !winning.contains(&next) {
+                losing += 1;
+            }
+        })
+        .expect("initial state is valid");
+    Difficulty {
+        states: states.len(),
+        branching: ratio(choices, choosing),
+        dead_ends: states.iter().filter(|s| !winning.contains(s)).count(),
+        losing_first_moves: ratio(losing, first),
+        solutions: p.count_solutions(SOLUTION_LIMIT),
+        score: win_chance(p, initial, &mut StateMap::default())
+            .recip()
+            .log2(),
+    }
+}
+
+/// `n / d`, or `0.0` if `d` is zero.
+fn ratio(n: usize, d: usize) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n as f64 / d as f64
+    }
+}
+
+/// The chance that a player in `state` goes on to win, picking each capture at random.
+fn win_chance(p: &Puzzle, state: PuzzleState, memo: &mut StateMap<f64>) -> f64 {
+    if state.done() {
+        return 1.0;
+    }
+    if let Some(&chance) = memo.get(&state) {
+        return chance;
+    }
+    let mut nexts = Vec::new();
+    state
+        .next_states(p, |next| nexts.push(next))
+        .expect("reachable states are valid");
+    let total: f64 = nexts.iter().map(|&next| win_chance(p, next, memo)).sum();
+    let chance = if nexts.is_empty() {
+        0.0
+    } else {
+        total / nexts.len() as f64
+    };
+    memo.insert(state, chance);
+    chance
+}
+
+/// The states of a puzzle that can no longer win, and the captures that lead into them, as found
+/// by `dead_ends`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadEnds {
+    /// Every reachable state from which no line of play wins, nearest the start first.
+    pub states: Vec<PuzzleState>,
+    /// Every capture from a state that can still win into one that can't.
+    pub traps: Vec<Trap>,
+}
+
+/// A capture that throws away a win: made from a state that can still win, it leads to one that
+/// can't. See `dead_ends`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trap {
+    /// The state the capture is made from.
+    pub from: PuzzleState,
+    /// One shortest line of play (as piece indices captured in order) that reaches `from`.
+    pub line: Vec<u32>,
+    /// The piece (by index, or a wall's slot; see `Puzzle::wall_square`) whose capture is the
+    /// trap.
+    pub captured: u32,
+}
+
+/// Finds every reachable state that can't go on to win, and every trap: each capture that leads
+/// from a state that can still win into one of those. If the puzzle has no solution, every state
+/// is a dead end and there are no traps.
+///
+/// ```
+/// use echochess::analyze::dead_ends;
+/// use echochess::Puzzle;
+///
+/// // Taking the bishop on c3 first leaves the rook-turned-bishop with no way to the knight.
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let dead = dead_ends(&puzzle);
+/// assert_eq!(dead.states.len(), 1);
+/// assert_eq!(dead.traps.len(), 1);
+/// assert!(dead.traps[0].line.is_empty());
+/// assert_eq!(dead.traps[0].captured, 2);
+/// assert_eq!(dead.states[0].current_piece_idx(), 2);
+/// ```
+pub fn dead_ends(p: &Puzzle) -> DeadEnds {
+    let winning = winning_states(p);
+    let initial = PuzzleState::initial(p);
+    // Each state seen, with the one before it, in breadth-first order so that lines are short.
+    let mut parents: StateMap<Option<PuzzleState>> = StateMap::default();
+    parents.insert(initial, None);
+    let mut queue = VecDeque::from([initial]);
+    let mut res = DeadEnds {
+        states: Vec::new(),
+        traps: Vec::new(),
+    };
+    while let Some(state) = queue.pop_front() {
+        let wins = winning.contains(&state);
+        if !wins {
+            res.states.push(state);
+        }
+        let mut nexts = Vec::new();
+        state
+            .next_states(p, |next| nexts.push(next))
+            .expect("reachable states are valid");
+        for next in nexts {
+            if wins && !winning.contains(&next) {
+                res.traps.push(Trap {
+                    from: state,
+                    line: line_to(&parents, state),
+                    captured: next.current_piece_idx(),
+                });
+            }
+            if let Entry::Vacant(entry) = parents.entry(next) {
+                entry.insert(Some(state));
+                queue.push_back(next);
+            }
+        }
+    }
+    res
+}
+
+/// The pieces captured on the way to `state`, following `parents` back to the start.
+fn line_to(parents: &StateMap<Option<PuzzleState>>, mut state: PuzzleState) -> Vec<u32> {
+    let mut line = Vec::new();
+    while let Some(prev) = parents[&state] {
+        line.push(state.current_piece_idx());
+        state = prev;
+    }
+    line.reverse();
+    line
+}
+
+/// A puzzle's whole state space, as found by `state_graph`: every reachable state, and every
+/// capture from one to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Graph {
+    /// The reachable states, nearest the start first, so the initial state is node 0.
+    pub nodes: Vec<Node>,
+    /// The captures from one state to another.
+    pub edges: Vec<Edge>,
+}
+
+/// One capture in a `Graph`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Edge {
+    /// The index in `Graph::nodes` of the state before the capture.
+    pub from: usize,
+    /// The index in `Graph::nodes` of the state after the capture.
+    pub to: usize,
+    /// The piece captured, by index, or a wall's slot (see `Puzzle::wall_square`).
+    pub captured: u32,
+    /// The square of the capture.
+    pub square: Square,
+}
+
+/// One state in a `Graph`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// The state itself.
+    pub state: PuzzleState,
+    /// The type of piece the player is.
+    pub piece: PieceType,
+    /// How many pieces are left to capture, not counting walls.
+    pub remaining: u32,
+    /// Whether the state is won, or can still be.
+    pub outcome: Outcome,
+}
+
+/// Whether a state in a `Graph` is won, and if not, whether it still can be.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Outcome {
+    /// Every required piece has been captured.
+    Won,
+    /// Some line of play from here wins.
+    Winnable,
+    /// No line of play from here wins.
+    DeadEnd,
+}
+
+/// Builds the graph of every state reachable from the start of a puzzle. This is meant for
+/// looking at small puzzles: see `Graph::to_dot`.
+///
+/// ```
+/// use echochess::analyze::{state_graph, Outcome};
+/// use echochess::Puzzle;
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let graph = state_graph(&puzzle);
+/// assert_eq!(graph.nodes.len(), 4);
+/// assert_eq!(graph.edges.len(), 3);
+/// assert_eq!(graph.nodes[0].remaining, 2);
+/// let outcomes: Vec<Outcome> = graph.nodes.iter().map(|n| n.outcome).collect();
+/// assert!(outcomes.contains(&Outcome::Won));
+/// assert!(outcomes.contains(&Outcome::DeadEnd));
+/// assert!(graph.to_dot().contains("0 [label=\"rook\\n2 left\"];"));
+/// ```
+pub fn state_graph(p: &Puzzle) -> Graph {
+    let winning = winning_states(p);
+    let initial = PuzzleState::initial(p);
+    let mut ids: StateMap<usize> = StateMap::default();
+    ids.insert(initial, 0);
+    let mut graph = Graph {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+    };
+    // Nodes are numbered in the order they're found, so the queue is just the nodes not yet
+    // expanded.
+    let mut order = vec![initial];
+    while let Some(&state) = order.get(graph.nodes.len()) {
+        let outcome = if state.done() {
+            Outcome::Won
+        } else if winning.contains(&state) {
+            Outcome::Winnable
+        } else {
+            Outcome::DeadEnd
+        };
+        let id = graph.nodes.len();
+        graph.nodes.push(Node {
+            state,
+            piece: p
+                .mover(state.current_piece_idx())
+                .expect("reachable states are valid"),
+            remaining: (state.remaining_captures() & !p.wall_bits()).count_ones(),
+            outcome,
+        });
+        let phase = state.phase(p);
+        state
+            .next_states(p, |next| {
+                let to = *ids.entry(next).or_insert_with(|| {
+                    order.push(next);
+                    order.len() - 1
+                });
+                let captured = next.current_piece_idx();
+                graph.edges.push(Edge {
+                    from: id,
+                    to,
+                    captured,
+                    square: p.piece_loc_at(captured, phase),
+                });
+            })
+            .expect("reachable states are valid");
+    }
+    graph
+}
+
+impl Graph {
+    /// Writes the graph in Graphviz's DOT language, for rendering with `dot -Tsvg`. Each node is
+    /// labeled with the player's piece and how many pieces are left, and each edge with the
+    /// square of its capture; won states are filled green and dead ends red.
+    pub fn to_dot(&self) -> String {
+        let mut res = String::from("digraph {\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            let fill = match node.outcome {
+                Outcome::Won => ", style=filled, fillcolor=palegreen",
+                Outcome::Winnable => "",
+                Outcome::DeadEnd => ", style=filled, fillcolor=lightpink",
+            };
+            res.push_str(&format!(
+                "  {} [label=\"{}\\n{} left\"{}];\n",
+                id,
+                node.piece.name(),
+                node.remaining,
+                fill
+            ));
+        }
+        for edge in &self.edges {
+            res.push_str(&format!(
+                "  {} -> {} [label=\"{}\"];\n",
+                edge.from, edge.to, edge.square
+            ));
+        }
+        res.push_str("}\n");
+        res
+    }
+
+    /// Writes the graph as JSON, for visualizing in a browser: an object with a list of `nodes`,
+    /// each with its `id`, the `piece` the player is, how many pieces are `remaining`, and its
+    /// `outcome` (`"won"`, `"winnable"`, or `"dead-end"`), and a list of `edges`, each with the
+    /// `from` and `to` node ids and the `captured` piece's index and `square`.
+    ///
+    /// ```
+    /// use echochess::analyze::state_graph;
+    /// use echochess::Puzzle;
+    ///
+    /// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+    /// let json = state_graph(&puzzle).to_json();
+    /// assert!(json.contains(r#"{"from":0,"to":1,"captured":0,"square":"b1"}"#));
+    /// assert!(json.contains(r#"{"id":2,"piece":"bishop","remaining":1,"outcome":"dead-end"}"#));
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&crate::ser::GraphRepr::from(self)).expect("graphs serialize")
+    }
+}
+
+/// What comes of one possible first capture, as found by `first_moves`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FirstMove {
+    /// The piece captured, by index, or a wall's slot (see `Puzzle::wall_square`).
+    pub captured: u32,
+    /// The square of the capture.
+    pub square: Square,
+    /// The fewest total moves to win after starting with this capture, counting the capture
+    /// itself, or `None` if it loses.
+    pub best: Option<u32>,
+}
+
+/// Tries every legal first capture, and says whether the puzzle can still be won after it and
+/// in how few moves. This is what a hint needs, and shows authors which openings are traps.
+///
+/// ```
+/// use echochess::analyze::first_moves;
+/// use echochess::Puzzle;
+///
+/// // The rook on a3 can take the knight on b1, then the bishop on c3 as a knight: four moves
+/// // in all, as solving for the fewest moves finds. Taking the bishop first loses.
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let moves = first_moves(&puzzle);
+/// assert_eq!(moves.len(), 2);
+/// assert_eq!(moves[0].square.to_string(), "b1");
+/// assert_eq!(moves[0].best, Some(4));
+/// assert_eq!(moves[1].square.to_string(), "c3");
+/// assert_eq!(moves[1].best, None);
+/// ```
+pub fn first_moves(p: &Puzzle) -> Vec<FirstMove> {
+    let mut memo = StateMap::default();
+    let mut res = Vec::new();
+    PuzzleState::initial(p)
+        .next_states_with_moves(p, |square, moves, next| {
+            res.push(FirstMove {
+                captured: next.current_piece_idx(),
+                square,
+                best: moves_to_win(p, next, &mut memo).map(|rest| moves + rest),
+            });
+        })
+        .expect("initial state is valid");
+    res.sort_by_key(|m| m.captured);
+    res
+}
+
+/// The fewest moves to win from `state`, or `None` if it can't be won.
+fn moves_to_win(p: &Puzzle, state: PuzzleState, memo: &mut StateMap<Option<u32>>) -> Option<u32> {
+    if state.done() {
+        return Some(0);
+    }
+    if let Some(&moves) = memo.get(&state) {
+        return moves;
+    }
+    let mut nexts = Vec::new();
+    state
+        .next_states_with_moves(p, |_, moves, next| nexts.push((moves, next)))
+        .expect("reachable states are valid");
+    let best = nexts
+        .into_iter()
+        .filter_map(|(moves, next)| Some(moves + moves_to_win(p, next, memo)?))
+        .min();
+    memo.insert(state, best);
+    best
+}
+
+/// What every solution of a puzzle has in common, as found by `forced_order`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForcedOrder {
+    /// Pieces (by index) that every solution captures at the same position, with that position
+    /// in the captures as `solve` returns them, counting from 0, in order of position.
+    pub positions: Vec<(u32, u32)>,
+    /// Pairs of pieces `(a, b)` where every solution captures `a` before `b`, in order.
+    pub before: Vec<(u32, u32)>,
+}
+
+/// Finds how constrained a puzzle's solutions are: which pieces are captured at the same point in
+/// every solution, and which pairs of pieces are captured in the same order in every solution.
+/// Only required pieces are considered (see `Puzzle::required`), since the others can be skipped.
+/// Returns `None` if the puzzle has no solution.
+///
+/// This doesn't list the solutions, which can be very many; a capture appears in some solution
+/// exactly when it leads from one state that can still win to another, so it looks at those.
+///
+/// ```
+/// use echochess::analyze::{forced_order, ForcedOrder};
+/// use echochess::{Puzzle, SolutionCount};
+///
+/// // The rook on a3 has to take the bishop on b2 first, but then it can take the knights on a1
+/// // and c3 in either order.
+/// let puzzle = Puzzle::parse("R1n/1b1/n2").unwrap();
+/// assert_eq!(puzzle.count_solutions(10), SolutionCount::Exactly(2));
+/// assert_eq!(
+///     forced_order(&puzzle),
+///     Some(ForcedOrder {
+///         positions: vec![(1, 0)],
+///         before: vec![(1, 0), (1, 3)],
+///     })
+/// );
+/// ```
+pub fn forced_order(p: &Puzzle) -> Option<ForcedOrder> {
+    let winning = winning_states(p);
+    if !winning.contains(&PuzzleState::initial(p)) {
+        return None;
+    }
+    let pieces: Vec<u32> = (0..p.num_pieces())
+        .filter(|&i| i != p.player_start() && p.required(i))
+        .collect();
+    // For each piece, the positions at which some solution captures it, as a bitmask; and for
+    // each pair `(a, b)`, whether some solution captures `b` while `a` is still left.
+    let mut positions = vec![0u64; p.num_pieces() as usize];
+    let mut b_first = vec![vec![false; p.num_pieces() as usize]; p.num_pieces() as usize];
+    for &state in &winning {
+        let phase = state.phase(p);
+        state
+            .next_states(p, |next| {
+                let captured = next.current_piece_idx();
+                if !winning.contains(&next) || captured >= p.num_pieces() {
+                    return;
+                }
+                positions[captured as usize] |= 1 << phase;
+                for &a in &pieces {
+                    if state.remaining_captures() & (1 << a) != 0 {
+                        b_first[a as usize][captured as usize] = true;
+                    }
+                }
+            })
+            .expect("reachable states are valid");
+    }
+    let mut res = ForcedOrder::default();
+    for &i in &pieces {
+        if positions[i as usize].count_ones() == 1 {
+            res.positions
+                .push((i, positions[i as usize].trailing_zeros()));
+        }
+        for &j in &pieces {
+            if i != j && !b_first[i as usize][j as usize] {
+                res.before.push((i, j));
+            }
+        }
+    }
+    res.positions.sort_by_key(|&(i, pos)| (pos, i));
+    Some(res)
+}
+
+/// Counts the distinct states reachable at each depth, from 0 (just the initial state) up to
+/// `max_depth` captures, like a chess engine's perft. The list stops early if no state goes that
+/// deep. Symmetric states are counted separately, and broken walls count as captures.
+///
+/// This doesn't depend on how any solver searches, so it makes a fixed reference for checking
+/// changes to move generation.
+///
+/// ```
+/// use echochess::analyze::count_states;
+/// use echochess::Puzzle;
+///
+/// let puzzle = Puzzle::parse("R1n/1b1/n2").unwrap();
+/// assert_eq!(count_states(&puzzle, 10), vec![1, 3, 4, 2]);
+/// assert_eq!(count_states(&puzzle, 1), vec![1, 3]);
+/// ```
+pub fn count_states(p: &Puzzle, max_depth: usize) -> Vec<u64> {
+    let mut layer = vec![PuzzleState::initial(p)];
+    let mut res = Vec::new();
+    while !layer.is_empty() {
+        res.push(layer.len() as u64);
+        if res.len() > max_depth {
+            break;
+        }
+        let mut next_layer = StateSet::default();
+        for state in layer {
+            state
+                .next_states(p, |next| {
+                    next_layer.insert(next);
+                })
+                .expect("reachable states are valid");
+        }
+        layer = next_layer.into_iter().collect();
+    }
+    res
+}
+
+/// Strips a puzzle down: removes obstacles and optional pieces (see `Puzzle::required`) one at a
+/// time, keeping each removal that leaves the puzzle as solvable as it was, until none can go. If
+/// the puzzle had exactly one solution, every removal has to keep it that way; otherwise, it only
+/// has to stay solvable. Required pieces stay, since removing them would change what the puzzle
+/// asks for, and so do walls, ice, and the other features. An unsolvable puzzle comes back
+/// unchanged.
+///
+/// No single obstacle or piece can be removed from the result, but removing several at once
+/// might still work.
+///
+/// ```
+/// use echochess::analyze::minimize;
+/// use echochess::Puzzle;
+///
+/// // The obstacle on b3 and the optional knight on c1 can go without the puzzle getting a second
+/// // solution, but the obstacle on b2 has to stay.
+/// let puzzle = Puzzle::parse("Rxb/1x1/n1n?").unwrap();
+/// let small = minimize(&puzzle);
+/// assert_eq!(small, Puzzle::parse("R1b/1x1/n2").unwrap());
+/// assert!(small.count_solutions(2).is_unique());
+/// ```
+pub fn minimize(p: &Puzzle) -> Puzzle {
+    let keep: fn(&Puzzle) -> bool = if p.count_solutions(2).is_unique() {
+        |q| q.count_solutions(2).is_unique()
+    } else if solve(p).is_some() {
+        |q| solve(q).is_some()
+    } else {
+        return p.clone();
+    };
+    let mut res = p.clone();
+    loop {
+        let mut shrunk = false;
+        for sq in res.obstacles() & res.squares() {
+            let mut q = res.clone();
+            q.obstacles = q.obstacles & !SquareSet::from(sq);
+            if keep(&q) {
+                res = q;
+                shrunk = true;
+            }
+        }
+        // Go from the last piece down, so that removing one doesn't renumber those still to try.
+        for idx in (0..res.num_pieces()).rev() {
+            if res.required(idx) {
+                continue;
+            }
+            if let Some(q) = res.without_piece(idx).filter(keep) {
+                res = q;
+                shrunk = true;
+            }
+        }
+        if !shrunk {
+            return res;
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! A variant with Sokoban-style blocks: stepping onto a block pushes it one step further the same
+//! way, if the square beyond is free.
+//!
+//! Where blocks stand depends on every step the player has taken, not just on which pieces are
+//! left, so a `PuzzleState` can't describe a position. This search goes one step at a time
+//! instead, keeping the player's square and the blocks' squares in its states.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{Puzzle, Square, SquareSet};
+
+/// A position in the block-pushing search: the pieces still to be captured, which piece the
+/// player is and where it stands, and where the blocks are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BlockState {
+    remaining: u32,
+    player: u32,
+    player_loc: Square,
+    blocks: SquareSet,
+}
+
+/// The square one step past `to`, for a step from `from` to `to`, if it's on the board.
+fn beyond(p: &Puzzle, from: Square, to: Square) -> Option<Square> {
+    let file = 2 * i16::from(to.file()) - i16::from(from.file());
+    let rank = 2 * i16::from(to.rank()) - i16::from(from.rank());
+    let sq = Square::from_file_rank(u8::try_from(file).ok()?, u8::try_from(rank).ok()?)?;
+    p.squares().contains(sq).then_some(sq)
+}
+
+/// Solves a puzzle with blocks on the squares in `blocks`. Returns the piece indices to be
+/// captured in order to win, like `solve`, or `None` if there's no way. Of all the ways to win,
+/// this finds one with the fewest steps.
+///
+/// A step onto a block pushes the block on by the same step: one square for a king or a rook, a
+/// knight's leap for a knight. The push is only allowed if the block lands on an empty square,
+/// not on an obstacle, a piece, or another block. Blocks can't be captured, and like obstacles,
+/// they stop lines of movement and capture. With an exit, the player then has to walk there,
+/// pushing blocks as needed.
+///
+/// Walls stand for good, and patrols, portals, ice, one-way squares, and the other rule variants
+/// don't apply.
+///
+/// ```
+/// use echochess::blocks::solve_blocks;
+/// use echochess::{Puzzle, Square};
+///
+/// // The rook on a1 can take the knight along the first rank, unless a block is in the way.
+/// // It can push a block on b1 along, but not into the knight.
+/// let puzzle = Puzzle::parse("R1n").unwrap();
+/// let b1: Square = "b1".parse().unwrap();
+/// assert_eq!(solve_blocks(&puzzle, &[]), Some(vec![1]));
+/// assert_eq!(solve_blocks(&puzzle, &[b1]), None);
+///
+/// // Here the rook can only get past by going around to b3 and pushing the block down to b1,
+/// // unless there's already another block there.
+/// let puzzle = Puzzle::parse("2xx/R2n/x1xx").unwrap();
+/// let b2: Square = "b2".parse().unwrap();
+/// assert_eq!(solve_blocks(&puzzle, &[b2]), Some(vec![1]));
+/// assert_eq!(solve_blocks(&puzzle, &[b1, b2]), None);
+/// ```
+pub fn solve_blocks(p: &Puzzle, blocks: &[Square]) -> Option<Vec<u32>> {
+    let walls: SquareSet = p.walls().iter().copied().collect();
+    let obstacles = p.obstacles() | walls | !p.squares();
+    let start_idx = p.player_start();
+    let initial = BlockState {
+        remaining: ((1 << p.num_pieces()) - 1) & !(1 << start_idx),
+        player: start_idx,
+        player_loc: p.piece_loc(start_idx),
+        blocks: blocks.iter().copied().collect(),
+    };
+    let won = |state: &BlockState| {
+        state.remaining & p.required_bits() == 0
+            && p.exit().is_none_or(|exit| state.player_loc == exit)
+    };
+    if won(&initial) {
+        return Some(Vec::new());
+    }
+    // Each state seen, with the one before it and the piece captured in between, if any.
+    let mut parents: HashMap<BlockState, Option<(BlockState, Option<u32>)>> = HashMap::new();
+    parents.insert(initial, None);
+    let mut queue = VecDeque::from([initial]);
+    while let Some(state) = queue.pop_front() {
+        let ty = p.mover(state.player)?;
+        let targets: SquareSet = (0..p.num_pieces())
+            .filter(|&i| state.remaining & (1 << i) != 0)
+            .map(|i| p.piece_loc(i))
+            .collect();
+        let empty = !(obstacles | targets | state.blocks);
+        let from = SquareSet::from(state.player_loc);
+        let mut nexts = Vec::new();
+        for to in ty.move_reach(from, empty | state.blocks) {
+            let mut blocks = state.blocks;
+            if blocks.contains(to) {
+                let Some(pushed) = beyond(p, state.player_loc, to) else {
+                    continue;
+                };
+                if !empty.contains(pushed) {
+                    continue;
+                }
+                blocks = (blocks & !SquareSet::from(to)) | SquareSet::from(pushed);
+            }
+            let next = BlockState {
+                player_loc: to,
+                blocks,
+                ..state
+            };
+            nexts.push((next, None));
+        }
+        for to in ty.capture_reach(from, empty) & targets {
+            let captured = p.piece_on(to)?;
+            let next = BlockState {
+                remaining: state.remaining & !(1 << captured),
+                player: captured,
+                player_loc: to,
+                blocks: state.blocks,
+            };
+            nexts.push((next, Some(captured)));
+        }
+        for (next, captured) in nexts {
+            if parents.contains_key(&next) {
+                continue;
+            }
+            parents.insert(next, Some((state, captured)));
+            if won(&next) {
+                let mut res = Vec::new();
+                let mut cur = next;
+                while let Some((prev, captured)) = parents[&cur] {
+                    res.extend(captured);
+                    cur = prev;
+                }
+                res.reverse();
+                return Some(res);
+            }
+            queue.push_back(next);
+        }
+    }
+    None
+}
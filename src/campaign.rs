@@ -0,0 +1,211 @@
+use crate::{reachable_states, solve_until, PieceType, Puzzle};
+
+/// An ordered list of levels, where the piece type that the player ends each level as is the type
+/// that it starts the next level as (on that level's usual starting square). The first level is
+/// entered as its own starting piece type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Campaign {
+    pub levels: Vec<Puzzle>,
+}
+
+/// Outcome of solving a `Campaign`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CampaignReport {
+    /// For each level, which piece types it's solvable when entered as.
+    pub admits: Vec<Vec<PieceType>>,
+    /// For each level, the type it's entered as and a solution to it (as piece indices to be
+    /// captured) that ends as the type the next level needs, or `None` if there's no such chain.
+    pub chain: Option<Vec<(PieceType, Vec<u32>)>>,
+    /// If there's no chain, the first level that can't be won as any type that the levels before
+    /// it can be left as: the link where the campaign breaks.
+    pub broken: Option<usize>,
+}
+
+impl Campaign {
+    /// Copy of a level where the player starts as `entry` instead of its usual piece type.
+    fn entered_as(level: &Puzzle, entry: PieceType) -> Puzzle {
+        let mut level = level.clone();
+        level.piece_types[level.player_start as usize] =
+            Some(entry.on_board(level.width, level.height));
+        level
+    }
+
+    /// Which piece types can the player end a level as, when it starts as the level says?
+    fn exit_types(level: &Puzzle) -> Vec<PieceType> {
+        let mut res: Vec<PieceType> = reachable_states(level)
+            .into_iter()
+            .filter(|s| s.done() && s.current_piece_idx() != level.player_start)
+            .filter_map(|s| level.piece_types[s.current_piece_idx() as usize])
+            .collect();
+        res.sort_by_key(|&ty| Self::order(ty));
+        res.dedup();
+        res
+    }
+
+    /// Where `ty` comes in `PieceType::ALL`, with custom pieces after all of those, by letter.
+    fn order(ty: PieceType) -> (usize, char) {
+        match PieceType::ALL.iter().position(|&t| t == ty) {
+            Some(i) => (i, ' '),
+            None => (PieceType::ALL.len(), ty.fen_char()),
+        }
+    }
+
+    /// Every type a level could be entered as: the built-in ones, and any custom ones that turn
+    /// up in the campaign.
+    fn entry_types(&self) -> Vec<PieceType> {
+        let mut res = PieceType::ALL.to_vec();
+        for ty in self
+            .levels
+            .iter()
+            .flat_map(|level| level.piece_types.iter().flatten())
+        {
+            if !res.contains(ty) {
+                res.push(*ty);
+            }
+        }
+        res
+    }
+
+    /// Finds a chain of solutions through every level, preferring earlier piece types (in
+    /// `PieceType::ALL` order, then any custom ones) whenever there's a choice.
+    ///
+    /// ```
+    /// use echochess::campaign::Campaign;
+    /// use echochess::Puzzle;
+    ///
+    /// // The first level leaves the player as a knight, which can't reach b2 in the second.
+    /// let levels = ["R1n/3/3", "R2/1b1/3"].map(|fen| Puzzle::parse(fen).unwrap());
+    /// let report = Campaign { levels: levels.to_vec() }.solve();
+    /// assert_eq!(report.chain, None);
+    /// assert_eq!(report.broken, Some(1));
+    /// ```
+    pub fn solve(&self) -> CampaignReport {
+        let types = self.entry_types();
+        // `exits[i][t]`: types that level `i` can end as when entered as `types[t]`.
+        let exits: Vec<Vec<Vec<PieceType>>> = self
+            .levels
+            .iter()
+            .map(|level| {
+                types
+                    .iter()
+                    .map(|&entry| Self::exit_types(&Self::entered_as(level, entry)))
+                    .collect()
+            })
+            .collect();
+        let admits = exits
+            .iter()
+            .map(|by_entry| {
+                let solvable = types.iter().zip(by_entry);
+                solvable
+                    .filter(|(_, exits)| !exits.is_empty())
+                    .map(|(&ty, _)| ty)
+                    .collect()
+            })
+            .collect();
+
+        // Walk forward through the types that each level can be entered as, to find the first
+        // level that none of them can win.
+        let mut broken = None;
+        let mut entries: Vec<PieceType> = self
+            .levels
+            .first()
+            .and_then(|level| level.piece_types[level.player_start as usize])
+            .into_iter()
+            .collect();
+        for (i, by_entry) in exits.iter().enumerate() {
+            let mut next: Vec<PieceType> = types
+                .iter()
+                .zip(by_entry)
+                .filter(|(ty, _)| entries.contains(ty))
+                .flat_map(|(_, exits)| exits.iter().copied())
+                .collect();
+            next.sort_by_key(|&ty| Self::order(ty));
+            next.dedup();
+            if next.is_empty() {
+                broken = Some(i);
+                break;
+            }
+            entries = next;
+        }
+
+        // Work backward: `viable[i]` holds the entry types from which levels `i..` can all be
+        // solved. Any exit from the last level will do.
+        let mut viable: Vec<Vec<PieceType>> = vec![Vec::new(); self.levels.len() + 1];
+        viable[self.levels.len()] = types.clone();
+        for i in (0..self.levels.len()).rev() {
+            viable[i] = types
+                .iter()
+                .zip(&exits[i])
+                .filter(|(_, exits)| exits.iter().any(|t| viable[i + 1].contains(t)))
+                .map(|(&ty, _)| ty)
+                .collect();
+        }
+
+        // Then walk forward, picking an exit that keeps the rest of the campaign solvable.
+        let chain = (|| {
+            let mut entry = self.levels.first()?.piece_types[self.levels[0].player_start as usize]?;
+            let mut chain = Vec::new();
+            for (i, level) in self.levels.iter().enumerate() {
+                let t = types.iter().position(|&t| t == entry)?;
+                let exit = *exits[i][t].iter().find(|t| viable[i + 1].contains(t))?;
+                let level = Self::entered_as(level, entry);
+                let sol = solve_until(&level, |s| {
+                    s.done() && level.piece_types[s.current_piece_idx() as usize] == Some(exit)
+                })?;
+                chain.push((entry, sol));
+                entry = exit;
+            }
+            Some(chain)
+        })();
+        CampaignReport {
+            admits,
+            chain,
+            broken,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn greedy_choice_fails_but_another_chain_succeeds() {
+        let levels = ["1Rq/2q/b2", "2Q/nk1/r2", "Kn1/nk1/3"].map(|fen| Puzzle::parse(fen).unwrap());
+        // The first level can end as a bishop or a queen; the bishop comes first in
+        // `PieceType::ALL`, so a greedy choice would take it.
+        assert_eq!(
+            Campaign::exit_types(&levels[0]),
+            [PieceType::Bishop, PieceType::Queen]
+        );
+        // But the second level, entered as a bishop, can only be left as a rook or a knight, and
+        // the third can't be won as either.
+        let as_bishop = Campaign::entered_as(&levels[1], PieceType::Bishop);
+        assert_eq!(
+            Campaign::exit_types(&as_bishop),
+            [PieceType::Rook, PieceType::Knight]
+        );
+        let greedy = Campaign {
+            levels: vec![as_bishop, levels[2].clone()],
+        };
+        assert_eq!(greedy.solve().chain, None);
+
+        let report = Campaign {
+            levels: levels.to_vec(),
+        }
+        .solve();
+        assert_eq!(report.broken, None);
+        assert!(!report.admits[2].contains(&PieceType::Rook));
+        assert!(!report.admits[2].contains(&PieceType::Knight));
+        let chain = report.chain.unwrap();
+        let entries: Vec<PieceType> = chain.iter().map(|&(entry, _)| entry).collect();
+        assert_eq!(
+            entries,
+            [PieceType::Rook, PieceType::Queen, PieceType::King]
+        );
+        for (level, (entry, sol)) in levels.iter().zip(&chain) {
+            let level = Campaign::entered_as(level, *entry);
+            assert!(level.verify(sol).is_ok());
+        }
+    }
+}
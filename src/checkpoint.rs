@@ -0,0 +1,173 @@
+//! Snapshots of a breadth-first search between layers, so that a long search can pick up where it
+//! left off. See `Solver::checkpoint` and `Solver::resume`.
+//!
+//! A checkpoint file is the magic bytes `ECHOCKP1`, then little-endian `u64`s: the puzzle's
+//! fingerprint, whether states were merged under symmetry, the report's counters (nodes expanded,
+//! states visited, peak frontier, depth, pruned), and the lengths of the frontier and the visited
+//! set. Then come the frontier's states in order and the visited states, as little-endian `u32`s.
+//! The fingerprint is the same in every build, so a checkpoint outlives an upgrade.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{PuzzleState, SolveReport};
+
+const MAGIC: &[u8; 8] = b"ECHOCKP1";
+
+/// Everything a breadth-first search needs to carry on from the start of a layer.
+pub(crate) struct Checkpoint {
+    /// `Puzzle::fingerprint` of the puzzle being solved.
+    pub fingerprint: u64,
+    /// Were the states canonicalized under the puzzle's symmetries?
+    pub symmetric: bool,
+    pub report: SolveReport,
+    /// The layer to expand next, in the order the search reached it.
+    pub frontier: Vec<PuzzleState>,
+    pub visited: Vec<PuzzleState>,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint to `path`. It's written to a temporary file first and then moved
+    /// into place, so a crash partway through leaves the previous checkpoint intact.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp = PathBuf::from(path);
+        tmp.as_mut_os_string().push(".tmp");
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+        let r = &self.report;
+        for n in [
+            self.fingerprint,
+            self.symmetric as u64,
+            r.nodes_expanded as u64,
+            r.states_visited as u64,
+            r.frontier_peak as u64,
+            r.depth as u64,
+            r.pruned as u64,
+            self.frontier.len() as u64,
+            self.visited.len() as u64,
+        ] {
+            out.write_all(&n.to_le_bytes())?;
+        }
+        for s in self.frontier.iter().chain(&self.visited) {
+            out.write_all(&s.0.to_le_bytes())?;
+        }
+        out.into_inner()?.sync_all()?;
+        std::fs::rename(&tmp, path)
+    }
+
+    /// Reads a checkpoint from `path`, or returns `None` if there's no file there.
+    pub fn load(path: &Path) -> io::Result<Option<Checkpoint>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut input = BufReader::new(file);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a checkpoint file",
+            ));
+        }
+        let mut read_u64 = || -> io::Result<u64> {
+            let mut buf = [0; 8];
+            input.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        };
+        let fingerprint = read_u64()?;
+        let symmetric = read_u64()? != 0;
+        let report = SolveReport {
+            nodes_expanded: read_u64()? as usize,
+            states_visited: read_u64()? as usize,
+            frontier_peak: read_u64()? as usize,
+            depth: read_u64()? as usize,
+            pruned: read_u64()? as usize,
+            ..SolveReport::default()
+        };
+        let frontier_len = read_u64()? as usize;
+        let visited_len = read_u64()? as usize;
+        let mut read_states = |len: usize| -> io::Result<Vec<PuzzleState>> {
+            let mut buf = [0; 4];
+            (0..len)
+                .map(|_| {
+                    input.read_exact(&mut buf)?;
+                    Ok(PuzzleState(u32::from_le_bytes(buf)))
+                })
+                .collect()
+        };
+        let frontier = read_states(frontier_len)?;
+        let visited = read_states(visited_len)?;
+        Ok(Some(Checkpoint {
+            fingerprint,
+            symmetric,
+            report,
+            frontier,
+            visited,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Limit, Puzzle, SolveResult, Solver};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "echochess-checkpoint-{}-{}",
+            std::process::id(),
+            name
+        ))
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round-trip");
+        let saved = Checkpoint {
+            fingerprint: Puzzle::parse("R1n/3/b2").unwrap().fingerprint(),
+            symmetric: true,
+            report: SolveReport {
+                nodes_expanded: 7,
+                states_visited: 9,
+                frontier_peak: 3,
+                depth: 2,
+                pruned: 1,
+                ..SolveReport::default()
+            },
+            frontier: vec![PuzzleState(5), PuzzleState(3)],
+            visited: vec![PuzzleState(7), PuzzleState(5), PuzzleState(3)],
+        };
+        saved.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.fingerprint, 0x13a5_3b56_6cf2_e474);
+        assert!(loaded.symmetric);
+        assert_eq!(loaded.report, saved.report);
+        assert_eq!(loaded.frontier, saved.frontier);
+        assert_eq!(loaded.visited, saved.visited);
+        assert!(Checkpoint::load(&path).unwrap().is_none());
+    }
+
+    /// A puzzle parsed again matches its checkpoint, and a different one doesn't.
+    #[test]
+    fn resume_checks_the_puzzle() {
+        let path = temp_path("resume");
+        let fen = "rnbkbrn1/pp2pp2/8/3N4/8/2ppp3/8/8";
+        let first = Solver::new()
+            .checkpoint(&path)
+            .max_depth(5)
+            .solve(&Puzzle::parse(fen).unwrap());
+        assert!(matches!(first.result, SolveResult::GaveUp(_)));
+        let other = Puzzle::parse("rnbkbrn1/pp2pp2/8/3N4/8/2ppp3/8/7p").unwrap();
+        let wrong = Solver::new().resume(&path).solve(&other);
+        assert!(matches!(wrong.result, SolveResult::GaveUp(Limit::Disk)));
+        let rest = Solver::new()
+            .resume(&path)
+            .solve(&Puzzle::parse(fen).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(rest.result, SolveResult::Solved(_)));
+    }
+}
@@ -0,0 +1,130 @@
+//! Breadth-first search with worker threads sharing one visited set. See
+//! `Strategy::Concurrent`.
+
+use std::hash::{BuildHasher, BuildHasherDefault};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use rayon::prelude::*;
+
+use crate::{
+    depth_first, unwind, Limit, Puzzle, PuzzleState, SearchConfig, SolveReport, StateHasher,
+    StateSet, Symmetries,
+};
+
+/// How many shards `ShardedStates` splits into. More shards means less contention.
+const NUM_SHARDS: usize = 64;
+
+/// A set of states that many threads can add to at once. Each state belongs to one shard, picked
+/// by the top bits of its hash, and only that shard is locked to add it.
+struct ShardedStates {
+    shards: Vec<Mutex<StateSet>>,
+    len: AtomicUsize,
+}
+
+impl ShardedStates {
+    fn new() -> Self {
+        ShardedStates {
+            shards: (0..NUM_SHARDS).map(|_| Mutex::default()).collect(),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    fn shard(&self, state: PuzzleState) -> &Mutex<StateSet> {
+        let hash = BuildHasherDefault::<StateHasher>::default().hash_one(state);
+        &self.shards[(hash >> 58) as usize % NUM_SHARDS]
+    }
+
+    /// Adds a state, returning whether it's new.
+    fn insert(&self, state: PuzzleState) -> bool {
+        let new = self.shard(state).lock().unwrap().insert(state);
+        if new {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        new
+    }
+
+    fn contains(&self, state: PuzzleState) -> bool {
+        self.shard(state).lock().unwrap().contains(&state)
+    }
+
+    fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+}
+
+/// Like `search`, but expands each layer across threads, which claim new states in a shared
+/// `ShardedStates` as they go. Limits are checked between layers.
+pub(crate) fn search_concurrent<G: Fn(PuzzleState) -> bool + Sync>(
+    p: &Puzzle,
+    goal: G,
+    config: &SearchConfig,
+    report: &mut SolveReport,
+) -> Result<Option<Vec<u32>>, Limit> {
+    let visited = ShardedStates::new();
+    let mut frontier = vec![PuzzleState::initial(p)];
+    visited.insert(PuzzleState::initial(p));
+    report.states_visited = 1;
+    if goal(PuzzleState::initial(p)) {
+        return Ok(Some(Vec::new()));
+    }
+    while !frontier.is_empty() {
+        if config.too_deep(report.depth) {
+            return Err(Limit::Depth);
+        }
+        report.frontier_peak = report.frontier_peak.max(frontier.len());
+        report.depth += 1;
+        report.nodes_expanded += frontier.len();
+        frontier = frontier
+            .par_iter()
+            .flat_map_iter(|&state| {
+                let mut nexts = Vec::new();
+                state
+                    .next_states(p, |next| {
+                        if config.allows(p, state, next) && visited.insert(next) {
+                            nexts.push(next);
+                        }
+                    })
+                    .expect("reachable states are valid");
+                nexts
+            })
+            .collect();
+        report.states_visited = visited.len();
+        if let Some(&final_state) = frontier.par_iter().find_any(|&&s| goal(s)) {
+            return Ok(Some(unwind(
+                p,
+                |s| visited.contains(s),
+                final_state,
+                report.depth as u32,
+                &Symmetries::none(),
+            )));
+        }
+        if config
+            .solver
+            .memory_cap
+            .is_some_and(|cap| visited.len() > cap)
+        {
+            drop((visited, frontier));
+            report.fell_back = true;
+            return depth_first(p, goal, config, report);
+        }
+        config.check(report)?;
+    }
+    report.depth -= 1; // the last layer had no successors
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::assert_agrees_with_bfs;
+    use crate::{Puzzle, Solver, Strategy};
+
+    #[test]
+    fn agrees_with_breadth_first() {
+        let mut solver = Solver::new();
+        solver.strategy(Strategy::Concurrent);
+        for fen in ["R1b/3/1n1", "B2/3/1n1", "R1n/3/n1n", "Q1n1/2b1/r3/1n1b"] {
+            assert_agrees_with_bfs(&solver, &Puzzle::parse(fen).unwrap());
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! A cooperative variant with two player pieces on the board at once. Each turn, one of them
+//! makes a capture and turns into the piece it captured, as in the usual game, while the other
+//! waits where it is. Between them, they have to capture every other piece.
+//!
+//! A `PuzzleState` only has room for one current piece, so this search keeps its own states, with
+//! the piece and square of each player piece.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{Puzzle, Square, SquareSet};
+
+/// Which of the two player pieces may capture each turn.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum CoopTurns {
+    /// The two take turns, starting with the puzzle's usual player piece.
+    #[default]
+    Alternate,
+    /// Either one may capture, as often as it likes.
+    Free,
+}
+
+/// A position in the cooperative search: the pieces still to be captured, and which piece each
+/// player piece is and where it stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct CoopState {
+    remaining: u32,
+    players: [(u32, Square); 2],
+}
+
+/// One capture in a cooperative solution: which player piece made it, and the index of the piece
+/// captured. See `solve_coop`.
+pub type CoopCapture = (usize, u32);
+
+/// Solves a puzzle with two player pieces: the puzzle's usual one, `p.player_start()`, and
+/// piece `partner`, usually another of `Puzzle::starts`. Returns the captures in order, each as
+/// which player piece made it (`0` for the usual one, `1` for the partner) and the index of the
+/// piece captured, or `None` if there's no way to win, or `partner` isn't another piece.
+///
+/// Neither player piece can capture the other, and each blocks the other's way like an obstacle.
+/// Walls stand for good, and the puzzle's patrols and exit are ignored, along with the other rule
+/// variants.
+///
+/// ```
+/// use echochess::coop::{solve_coop, CoopTurns};
+/// use echochess::Puzzle;
+///
+/// // The king on a1 can take both rooks, one after the other, but the rook on a3 is walled in,
+/// // so it can't take a turn in between.
+/// let puzzle = Puzzle::parse("Rx1/x2/Krr").unwrap();
+/// assert_eq!(puzzle.player_start(), 0);
+/// assert_eq!(solve_coop(&puzzle, 3, CoopTurns::Free), Some(vec![(0, 1), (0, 2)]));
+/// assert_eq!(solve_coop(&puzzle, 3, CoopTurns::Alternate), None);
+/// ```
+pub fn solve_coop(p: &Puzzle, partner: u32, turns: CoopTurns) -> Option<Vec<CoopCapture>> {
+    let start_idx = p.player_start();
+    if partner == start_idx || partner >= p.num_pieces() {
+        return None;
+    }
+    let walls: SquareSet = p.walls().iter().copied().collect();
+    let obstacles = p.obstacles() | walls;
+    let initial = CoopState {
+        remaining: ((1 << p.num_pieces()) - 1) & !(1 << start_idx) & !(1 << partner),
+        players: [
+            (start_idx, p.piece_loc(start_idx)),
+            (partner, p.piece_loc(partner)),
+        ],
+    };
+    if initial.remaining & p.required_bits() == 0 {
+        return Some(Vec::new());
+    }
+    // Each state seen, with the one before it and the capture made in between.
+    let mut parents: HashMap<CoopState, Option<(CoopState, CoopCapture)>> = HashMap::new();
+    parents.insert(initial, None);
+    let mut queue = VecDeque::from([initial]);
+    while let Some(state) = queue.pop_front() {
+        let targets: SquareSet = (0..p.num_pieces())
+            .filter(|&i| state.remaining & (1 << i) != 0)
+            .map(|i| p.piece_loc(i))
+            .collect();
+        let actors = match turns {
+            CoopTurns::Alternate => {
+                let made = initial.remaining.count_ones() - state.remaining.count_ones();
+                vec![made as usize % 2]
+            }
+            CoopTurns::Free => vec![0, 1],
+        };
+        for actor in actors {
+            let (piece, loc) = state.players[actor];
+            let ty = p.mover(piece)?;
+            let (_, other) = state.players[1 - actor];
+            let blocked = obstacles | SquareSet::from(other);
+            let start = SquareSet::from(loc);
+            for target in p.captures(ty, start, blocked, targets, SquareSet(0)) {
+                let captured = p.piece_on(target)?;
+                let mut next = CoopState {
+                    remaining: state.remaining & !(1 << captured),
+                    players: state.players,
+                };
+                next.players[actor] = (captured, target);
+                if parents.contains_key(&next) {
+                    continue;
+                }
+                parents.insert(next, Some((state, (actor, captured))));
+                if next.remaining & p.required_bits() == 0 {
+                    let mut res = Vec::new();
+                    let mut cur = next;
+                    while let Some((prev, capture)) = parents[&cur] {
+                        res.push(capture);
+                        cur = prev;
+                    }
+                    res.reverse();
+                    return Some(res);
+                }
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,94 @@
+//! Statistics over a whole collection of puzzles, for curating level packs.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::custom::Pieces;
+use crate::pack::parse_pack_with;
+use crate::{solve_min_moves, Puzzle};
+
+/// What a collection of puzzles is like, as a whole. See `CorpusStats::of`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorpusStats {
+    /// How many puzzles there are.
+    pub puzzles: usize,
+    /// How many of them have a solution, starting as `Puzzle::player_start`.
+    pub solvable: usize,
+    /// For each move count, how many solvable puzzles take that many moves at the fewest.
+    pub optimal_moves: BTreeMap<u32, usize>,
+    /// For each piece type, by name, how many pieces of that type there are across all the
+    /// puzzles, counting the ones the player starts as.
+    pub piece_types: BTreeMap<String, usize>,
+}
+
+impl CorpusStats {
+    /// Solves each puzzle for the fewest moves, and tallies up the results.
+    ///
+    /// ```
+    /// use echochess::corpus::CorpusStats;
+    /// use echochess::Puzzle;
+    ///
+    /// let puzzles = ["R1n", "R1b/3/1n1", "Rxn"].map(|fen| Puzzle::parse(fen).unwrap());
+    /// let stats = CorpusStats::of(&puzzles);
+    /// assert_eq!(stats.puzzles, 3);
+    /// assert_eq!(stats.solvable, 2);
+    /// assert_eq!(stats.optimal_moves.into_iter().collect::<Vec<_>>(), vec![(2, 1), (4, 1)]);
+    /// assert_eq!(stats.piece_types["rook"], 3);
+    /// assert_eq!(stats.piece_types["knight"], 3);
+    /// assert_eq!(stats.piece_types["bishop"], 1);
+    /// ```
+    pub fn of<'a>(puzzles: impl IntoIterator<Item = &'a Puzzle>) -> CorpusStats {
+        let mut stats = CorpusStats::default();
+        for p in puzzles {
+            stats.add(p);
+        }
+        stats
+    }
+
+    /// Solves one more puzzle and counts it in.
+    pub fn add(&mut self, p: &Puzzle) {
+        self.puzzles += 1;
+        if let Some((_, moves)) = solve_min_moves(p) {
+            self.solvable += 1;
+            *self.optimal_moves.entry(moves).or_default() += 1;
+        }
+        for ty in (0..p.num_pieces()).filter_map(|i| p.piece_type(i)) {
+            *self.piece_types.entry(ty.name().to_string()).or_default() += 1;
+        }
+    }
+
+    /// The fraction of the puzzles that have a solution, or `0.0` if there are none.
+    pub fn solvable_fraction(&self) -> f64 {
+        if self.puzzles == 0 {
+            0.0
+        } else {
+            self.solvable as f64 / self.puzzles as f64
+        }
+    }
+
+    /// Writes these statistics as JSON, for tools that sort or compare packs.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("stats serialize")
+    }
+}
+
+/// Reads every puzzle from the `.txt` files directly in `dir`, in order of file name, as
+/// `pack::parse_pack_with` reads them.
+pub fn load_dir(dir: &Path, pieces: &Pieces) -> std::io::Result<Vec<Puzzle>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "txt") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    let mut res = Vec::new();
+    for path in paths {
+        let src = std::fs::read_to_string(&path)?;
+        res.extend(parse_pack_with(&src, pieces).into_iter().map(|(_, p)| p));
+    }
+    Ok(res)
+}
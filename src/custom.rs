@@ -0,0 +1,474 @@
+//! Pieces defined at runtime, for trying out fairy pieces without changing the crate.
+//!
+//! Describe each piece with a `PieceSpec`, collect them into `Pieces`, and parse puzzles with
+//! `Puzzle::parse_with` or `pack::parse_pack_with`. Each piece gets its own FEN letter, and
+//! becomes a `PieceType::Custom` that the solver handles like any other. Specs are read from JSON
+//! (see `Pieces::from_json`); there's no TOML reader.
+//!
+//! ```
+//! use echochess::custom::{PieceSpec, Pieces, Vector};
+//! use echochess::{solve, Puzzle};
+//!
+//! // A wazir steps one square orthogonally.
+//! let wazir = PieceSpec {
+//!     letter: 'w',
+//!     name: "wazir".to_string(),
+//!     moves: vec![Vector { step: (1, 0), range: 1 }],
+//!     captures: None,
+//!     symmetric: true,
+//! };
+//! let pieces = Pieces::new(&[wazir]).unwrap();
+//! let puzzle = Puzzle::parse_with("8/8/8/8/8/8/8/W1n5", &pieces).unwrap();
+//! assert_eq!(solve(&puzzle), Some(vec![1]));
+//! ```
+
+use std::sync::Mutex;
+
+use crate::{PieceType, SquareSet};
+
+/// One direction that a piece moves or captures in: `step` is how many files right and ranks up
+/// one step goes, and a capture can come from up to `range` steps down a clear line.
+///
+/// A piece walks one step at a time anyway, so for moves, `range` doesn't change where it can
+/// go. It only matters for captures, as with a queen's: see `Stepper::capture_reach`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vector {
+    pub step: (i8, i8),
+    #[cfg_attr(feature = "serde", serde(default = "one"))]
+    pub range: u8,
+}
+
+#[cfg(feature = "serde")]
+fn one() -> u8 {
+    1
+}
+
+/// How a custom piece moves. With the `serde` feature, this can be read from a file; a knight
+/// that also rides along ranks and files, written `y` in FEN, looks like this in JSON:
+///
+/// ```json
+/// {
+///     "letter": "y",
+///     "name": "knight-rook",
+///     "moves": [{"step": [1, 2]}, {"step": [1, 0], "range": 7}],
+///     "symmetric": true
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PieceSpec {
+    /// The lowercase FEN letter; uppercase marks the player's piece, as usual.
+    pub letter: char,
+    /// Lowercase English name, for prose.
+    pub name: String,
+    pub moves: Vec<Vector>,
+    /// How the piece captures, if not the same way it moves.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub captures: Option<Vec<Vector>>,
+    /// Also move and capture along every turn and reflection of each vector, so that, say,
+    /// `(1, 2)` alone makes a knight.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub symmetric: bool,
+}
+
+/// Letters that the FEN parser already gives a meaning.
+const BUILT_IN_LETTERS: &str = "pbrnkqxzacmgoev";
+
+/// The eight turns and reflections of a step, including itself.
+fn images((dx, dy): (i8, i8)) -> [(i8, i8); 8] {
+    [
+        (dx, dy),
+        (-dx, dy),
+        (dx, -dy),
+        (-dx, -dy),
+        (dy, dx),
+        (-dy, dx),
+        (dy, -dx),
+        (-dy, -dx),
+    ]
+}
+
+/// One direction of a `CustomPiece`, as a shift of the board and the squares that it can start
+/// from without leaving a board of the given size.
+#[derive(Debug, Clone)]
+struct Dir {
+    shift: i8,
+    from: SquareSet,
+    range: u8,
+}
+
+impl Dir {
+    fn new(v: &Vector, (width, height): (u8, u8)) -> Dir {
+        let (dx, dy) = v.step;
+        let (width, height) = (width as i8, height as i8);
+        let mut from = SquareSet(0);
+        for y in 0..height {
+            for x in 0..width {
+                if (0..width).contains(&(x + dx)) && (0..height).contains(&(y + dy)) {
+                    from.0 |= 1 << (8 * y + x);
+                }
+            }
+        }
+        Dir {
+            shift: 8 * dy + dx,
+            from,
+            range: v.range.min(7),
+        }
+    }
+
+    fn step(&self, from: SquareSet) -> SquareSet {
+        let from = from & self.from;
+        if self.shift >= 0 {
+            from << self.shift as u32
+        } else {
+            from >> -self.shift as u32
+        }
+    }
+}
+
+/// A `PieceSpec` made ready for the solver on a board of one size. See `PieceType::Custom`.
+///
+/// Pieces are compared and hashed by how they move, not by the board they were made ready for,
+/// so a piece carried from one puzzle to another of a different size is still the same piece.
+pub struct CustomPiece {
+    letter: char,
+    name: String,
+    /// The vectors from the spec, with their turns and reflections if it asked for them.
+    move_vectors: Vec<Vector>,
+    capture_vectors: Vec<Vector>,
+    /// Is every turn and reflection of each vector there too?
+    symmetric: bool,
+    /// The `(width, height)` of the board that `moves` and `captures` are for.
+    size: (u8, u8),
+    moves: Vec<Dir>,
+    captures: Vec<Dir>,
+}
+
+impl PartialEq for CustomPiece {
+    fn eq(&self, other: &CustomPiece) -> bool {
+        (
+            &self.letter,
+            &self.name,
+            &self.move_vectors,
+            &self.capture_vectors,
+        ) == (
+            &other.letter,
+            &other.name,
+            &other.move_vectors,
+            &other.capture_vectors,
+        )
+    }
+}
+
+impl Eq for CustomPiece {}
+
+impl std::hash::Hash for CustomPiece {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (
+            &self.letter,
+            &self.name,
+            &self.move_vectors,
+            &self.capture_vectors,
+        )
+            .hash(state)
+    }
+}
+
+/// Every `CustomPiece` made so far. `PieceType` is `Copy`, so puzzles refer to their pieces by
+/// `'static` references, and the pieces are never freed; interning them here means that loading
+/// the same specs again, or putting a piece on a board of a size it's been on before, reuses the
+/// piece from last time instead of leaking another copy.
+static INTERNED: Mutex<Vec<&'static CustomPiece>> = Mutex::new(Vec::new());
+
+impl CustomPiece {
+    fn new(spec: &PieceSpec) -> Result<CustomPiece, SpecError> {
+        let expand = |vectors: &[Vector]| -> Result<Vec<Vector>, SpecError> {
+            let mut res: Vec<Vector> = Vec::new();
+            for v in vectors {
+                let (dx, dy) = v.step;
+                if (dx, dy) == (0, 0) || !(-7..=7).contains(&dx) || !(-7..=7).contains(&dy) {
+                    return Err(SpecError::BadStep(dx, dy));
+                }
+                if v.range == 0 {
+                    return Err(SpecError::BadRange(v.range));
+                }
+                let steps = if spec.symmetric {
+                    images(v.step).to_vec()
+                } else {
+                    vec![v.step]
+                };
+                for step in steps {
+                    let v = Vector { step, ..*v };
+                    if !res.contains(&v) {
+                        res.push(v);
+                    }
+                }
+            }
+            Ok(res)
+        };
+        let moves = expand(&spec.moves)?;
+        let captures = match &spec.captures {
+            Some(captures) => expand(captures)?,
+            None => moves.clone(),
+        };
+        let symmetric = [&moves, &captures].iter().all(|vs| {
+            vs.iter().all(|v| {
+                images(v.step)
+                    .iter()
+                    .all(|&step| vs.contains(&Vector { step, ..*v }))
+            })
+        });
+        Ok(CustomPiece {
+            letter: spec.letter,
+            name: spec.name.clone(),
+            move_vectors: moves,
+            capture_vectors: captures,
+            symmetric,
+            size: (0, 0),
+            moves: Vec::new(),
+            captures: Vec::new(),
+        }
+        .sized(8, 8))
+    }
+
+    /// This piece made ready for a board of the given size.
+    fn sized(&self, width: u8, height: u8) -> CustomPiece {
+        let dirs = |vectors: &[Vector]| {
+            vectors
+                .iter()
+                .map(|v| Dir::new(v, (width, height)))
+                .collect()
+        };
+        CustomPiece {
+            letter: self.letter,
+            name: self.name.clone(),
+            move_vectors: self.move_vectors.clone(),
+            capture_vectors: self.capture_vectors.clone(),
+            symmetric: self.symmetric,
+            size: (width, height),
+            moves: dirs(&self.move_vectors),
+            captures: dirs(&self.capture_vectors),
+        }
+    }
+
+    /// The interned copy of `piece`. See `INTERNED`.
+    fn intern(piece: CustomPiece) -> &'static CustomPiece {
+        let mut interned = INTERNED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&p) = interned
+            .iter()
+            .find(|&&p| *p == piece && p.size == piece.size)
+        {
+            return p;
+        }
+        let p: &'static CustomPiece = Box::leak(Box::new(piece));
+        interned.push(p);
+        p
+    }
+
+    /// This piece made ready for a board of the given size, which puzzles do with their pieces
+    /// when they're assembled.
+    pub(crate) fn on_board(&'static self, width: u8, height: u8) -> &'static CustomPiece {
+        if self.size == (width, height) {
+            self
+        } else {
+            Self::intern(self.sized(width, height))
+        }
+    }
+
+    /// Lowercase FEN letter.
+    pub fn letter(&self) -> char {
+        self.letter
+    }
+
+    /// Lowercase English name, for prose.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Does the piece move the same after any turn or reflection of the board?
+    pub fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
+
+    /// Writes out how the piece moves and captures, for `Puzzle::fingerprint` and
+    /// `Puzzle::canonical_id`.
+    pub(crate) fn write_moves(&self, out: &mut Vec<u8>) {
+        for vectors in [&self.move_vectors, &self.capture_vectors] {
+            out.extend((vectors.len() as u32).to_le_bytes());
+            for v in vectors {
+                out.extend([v.step.0 as u8, v.step.1 as u8, v.range]);
+            }
+        }
+    }
+
+    /// Like `Stepper::move_steps`.
+    pub fn move_steps(&self, from: SquareSet) -> SquareSet {
+        self.moves
+            .iter()
+            .fold(SquareSet(0), |acc, d| acc | d.step(from))
+    }
+
+    /// Like `Stepper::capture_steps`.
+    pub fn capture_steps(&self, from: SquareSet) -> SquareSet {
+        self.captures
+            .iter()
+            .fold(SquareSet(0), |acc, d| acc | d.step(from))
+    }
+
+    /// Like `Stepper::fill`, one step at a time.
+    pub fn fill(&self, from: SquareSet, permeable: SquareSet) -> SquareSet {
+        let mut reachable = from & permeable;
+        loop {
+            let next = (reachable | self.move_steps(reachable)) & permeable;
+            if next == reachable {
+                return reachable;
+            }
+            reachable = next;
+        }
+    }
+
+    /// Like `Stepper::capture_reach`: each capture vector can go up to its range down a line of
+    /// squares in `permeable`.
+    pub fn capture_reach(&self, from: SquareSet, permeable: SquareSet) -> SquareSet {
+        let mut res = SquareSet(0);
+        for d in &self.captures {
+            let mut line = from;
+            for _ in 0..d.range {
+                let next = d.step(line);
+                res = res | next;
+                line = next & permeable;
+            }
+        }
+        res
+    }
+}
+
+/// Just the name, since the tables aren't much to read.
+impl std::fmt::Debug for CustomPiece {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self.name)
+    }
+}
+
+/// A table of custom pieces, by FEN letter.
+#[derive(Debug, Clone, Default)]
+pub struct Pieces(Vec<&'static CustomPiece>);
+
+impl Pieces {
+    /// Checks and prepares the given pieces. Puzzles refer to their pieces for as long as they
+    /// live, so the pieces are never freed, but they're interned: loading the same specs again
+    /// reuses the pieces from last time.
+    pub fn new(specs: &[PieceSpec]) -> Result<Pieces, SpecError> {
+        let mut res = Pieces::default();
+        for spec in specs {
+            let letter = spec.letter;
+            if !letter.is_ascii_lowercase() {
+                return Err(SpecError::BadLetter(letter));
+            }
+            if BUILT_IN_LETTERS.contains(letter) || res.get(letter).is_some() {
+                return Err(SpecError::LetterTaken(letter));
+            }
+            res.0.push(CustomPiece::intern(CustomPiece::new(spec)?));
+        }
+        Ok(res)
+    }
+
+    /// Reads a JSON list of `PieceSpec`s. JSON is the only format read.
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Pieces, SpecError> {
+        let specs: Vec<PieceSpec> =
+            serde_json::from_str(json).map_err(|e| SpecError::Json(e.to_string()))?;
+        Self::new(&specs)
+    }
+
+    /// The piece written with `letter`, in either case.
+    pub fn get(&self, letter: char) -> Option<PieceType> {
+        let letter = letter.to_ascii_lowercase();
+        self.0
+            .iter()
+            .find(|p| p.letter == letter)
+            .map(|&p| PieceType::Custom(p))
+    }
+}
+
+/// Why a `PieceSpec` was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecError {
+    /// The FEN letter isn't a lowercase ASCII letter.
+    BadLetter(char),
+    /// The FEN letter already means something else.
+    LetterTaken(char),
+    /// A step goes nowhere, or off any 8×8 board.
+    BadStep(i8, i8),
+    /// A vector's range is zero.
+    BadRange(u8),
+    /// The file isn't a valid list of specs.
+    Json(String),
+}
+
+impl std::fmt::Display for SpecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SpecError::BadLetter(c) => write!(f, "Piece letter {:?} isn't lowercase ASCII", c),
+            SpecError::LetterTaken(c) => write!(f, "Piece letter {:?} is already taken", c),
+            SpecError::BadStep(dx, dy) => write!(f, "Bad step: ({}, {})", dx, dy),
+            SpecError::BadRange(r) => write!(f, "Bad range: {}", r),
+            SpecError::Json(e) => write!(f, "Bad piece file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Puzzle, Square};
+
+    fn wazir() -> PieceSpec {
+        PieceSpec {
+            letter: 'w',
+            name: "wazir".to_string(),
+            moves: vec![Vector {
+                step: (1, 0),
+                range: 1,
+            }],
+            captures: None,
+            symmetric: true,
+        }
+    }
+
+    #[test]
+    fn loading_again_reuses_pieces() {
+        let first = Pieces::new(&[wazir()]).unwrap();
+        let second = Pieces::new(&[wazir()]).unwrap();
+        assert!(std::ptr::eq(first.0[0], second.0[0]));
+        let fen = "W1n/3/3";
+        let a = Puzzle::parse_with(fen, &first).unwrap();
+        let b = Puzzle::parse_with(fen, &second).unwrap();
+        let (PieceType::Custom(a), PieceType::Custom(b)) =
+            (a.piece_type(0).unwrap(), b.piece_type(0).unwrap())
+        else {
+            panic!("not custom pieces");
+        };
+        assert!(std::ptr::eq(a, b));
+    }
+
+    #[test]
+    fn steps_stay_on_the_board() {
+        let pieces = Pieces::new(&[wazir()]).unwrap();
+        let puzzle = Puzzle::parse_with("3/3/1W1", &pieces).unwrap();
+        let Some(PieceType::Custom(w)) = puzzle.piece_type(0) else {
+            panic!("not a custom piece");
+        };
+        assert_eq!(w.size, (3, 3));
+        let sq = |name: &str| SquareSet::from(name.parse::<Square>().unwrap());
+        assert_eq!(w.move_steps(sq("c1")), sq("b1") | sq("c2"));
+        assert_eq!(w.move_steps(sq("a3")), sq("b3") | sq("a2"));
+        // The same piece on a bigger board, as a campaign carries it over, steps further.
+        let wide = w.on_board(5, 3);
+        assert_eq!(wide, w);
+        assert_eq!(wide.move_steps(sq("c1")), sq("b1") | sq("d1") | sq("c2"));
+    }
+}
@@ -0,0 +1,279 @@
+use crate::explain::Unsolvable;
+use crate::{Heading, Puzzle, Square};
+
+/// Joins names as English prose: "a", "a and b", "a, b, and c".
+fn english_list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [one] => one.clone(),
+        [a, b] => format!("{} and {}", a, b),
+        [init @ .., last] => format!("{}, and {}", init.join(", "), last),
+    }
+}
+
+/// Describes a puzzle in prose, for screen readers: the board, obstacles rank by rank, every
+/// piece, and the player's starting piece. Everything that a diagram would show is spelled out.
+pub fn describe_puzzle(p: &Puzzle) -> String {
+    let mut res = String::new();
+    let (width, height) = (p.width(), p.height());
+    res.push_str(&format!(
+        "The board has {} files, a through {}, and {} ranks, 1 through {}.\n",
+        width,
+        char::from(b'a' + width - 1),
+        height,
+        height
+    ));
+    for rank in (0..height).rev() {
+        let obstacles: Vec<String> = (0..width)
+            .map(|file| Square::from_file_rank(file, rank).unwrap())
+            .filter(|&sq| p.obstacles.contains(sq))
+            .map(|sq| sq.to_string())
+            .collect();
+        let summary = match obstacles.len() {
+            0 => "no obstacles".to_string(),
+            n if n == width as usize => "obstacles on every square".to_string(),
+            _ => format!("obstacles on {}", english_list(&obstacles)),
+        };
+        res.push_str(&format!("Rank {}: {}.\n", rank + 1, summary));
+    }
+    let piece_names: Vec<String> = (0..p.num_pieces())
+        .filter(|&i| i != p.player_start && p.required(i))
+        .map(|i| describe_piece(p, i, 0))
+        .collect();
+    res.push_str(&format!(
+        "There are {} pieces to capture: {}.\n",
+        piece_names.len(),
+        english_list(&piece_names)
+    ));
+    let optional: Vec<String> = (0..p.num_pieces())
+        .filter(|&i| i != p.player_start && !p.required(i))
+        .map(|i| describe_piece(p, i, 0))
+        .collect();
+    if !optional.is_empty() {
+        res.push_str(&format!(
+            "You may also capture the {}, but you don't have to.\n",
+            english_list(&optional)
+        ));
+    }
+    res.push_str(&format!(
+        "You start as the {}.\n",
+        describe_piece(p, p.player_start, 0)
+    ));
+    let others: Vec<String> = p
+        .starts()
+        .iter()
+        .filter(|&&i| i != p.player_start)
+        .map(|&i| describe_piece(p, i, 0))
+        .collect();
+    if !others.is_empty() {
+        res.push_str(&format!(
+            "You could start as the {} instead.\n",
+            english_list(&others)
+        ));
+    }
+    if let Some(exit) = p.exit() {
+        res.push_str(&format!(
+            "The exit is on {}: after the last capture, walk there to finish.\n",
+            exit
+        ));
+    }
+    if !p.ice().is_empty() {
+        let squares: Vec<String> = p.ice().iter().map(|sq| sq.to_string()).collect();
+        res.push_str(&format!(
+            "There is ice on {}: a piece that steps onto it slides on until something stops it.\n",
+            english_list(&squares)
+        ));
+    }
+    for heading in Heading::ALL {
+        let squares: Vec<String> = p.one_way(heading).iter().map(|sq| sq.to_string()).collect();
+        if squares.is_empty() {
+            continue;
+        }
+        let way = match heading {
+            Heading::Up => "up, toward rank 8",
+            Heading::Right => "right, toward the h-file",
+            Heading::Down => "down, toward rank 1",
+            Heading::Left => "left, toward the a-file",
+        };
+        res.push_str(&format!(
+            "There are one-way squares on {}: a piece can only enter them heading {}.\n",
+            english_list(&squares),
+            way
+        ));
+    }
+    if !p.walls().is_empty() {
+        let squares: Vec<String> = p.walls().iter().map(Square::to_string).collect();
+        let breakers: Vec<String> = p
+            .wall_breakers()
+            .iter()
+            .map(|ty| format!("{}s", ty.name()))
+            .collect();
+        res.push_str(&format!(
+            "There are walls on {}, which only {} can break down.\n",
+            english_list(&squares),
+            english_list(&breakers)
+        ));
+    }
+    for &(sq, cost) in p.terrain() {
+        res.push_str(&format!(
+            "Stepping onto {} takes {} moves instead of one.\n",
+            sq, cost
+        ));
+    }
+    for &(a, b) in p.portals() {
+        res.push_str(&format!(
+            "Portals on {} and {} are linked: stepping onto either puts you on the other.\n",
+            a, b
+        ));
+    }
+    if p.patrolling() {
+        for (i, route) in &p.patrols {
+            let squares: Vec<String> = route.iter().map(Square::to_string).collect();
+            res.push_str(&format!(
+                "The {} patrols {} in a loop, moving one step after each of your captures.\n",
+                describe_piece(p, *i, 0),
+                english_list(&squares)
+            ));
+        }
+    }
+    res
+}
+
+/// "rook on e5": piece `piece_idx` where it stands after `phase` captures. For a wall's slot,
+/// that's the player standing where the wall was.
+fn describe_piece(p: &Puzzle, piece_idx: u32, phase: u32) -> String {
+    let ty = p.player_type(piece_idx).unwrap();
+    format!("{} on {}", ty.name(), p.piece_loc_at(piece_idx, phase))
+}
+
+/// "a" or "an", whichever goes before `word`.
+fn article(word: &str) -> &'static str {
+    if word.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+/// Narrates a solution (piece indices captured in order) one move per line, like "Move 3: your
+/// knight on c4 captures the rook on e5; you are now a rook on e5."
+pub fn describe_solution(p: &Puzzle, sol: &[u32]) -> String {
+    let mut res = String::new();
+    let mut player = p.player_start;
+    for (i, &piece_idx) in sol.iter().enumerate() {
+        let phase = i as u32;
+        if let Some(wall) = p.wall_square(piece_idx) {
+            res.push_str(&format!(
+                "Move {}: your {} breaks down the wall on {}.\n",
+                i + 1,
+                describe_piece(p, player, phase.saturating_sub(1)),
+                wall,
+            ));
+            player = piece_idx;
+            continue;
+        }
+        let now = describe_piece(p, piece_idx, phase);
+        res.push_str(&format!(
+            "Move {}: your {} captures the {}; you are now {} {}.\n",
+            i + 1,
+            describe_piece(p, player, phase.saturating_sub(1)),
+            now,
+            article(&now),
+            now,
+        ));
+        player = piece_idx;
+    }
+    if let (Some(exit), false) = (p.exit(), sol.is_empty()) {
+        res.push_str(&format!("Finally, walk to the exit on {}.\n", exit));
+    }
+    res
+}
+
+/// Explains in prose why a puzzle has no solution, like "The rook on h1 can never be captured."
+pub fn describe_unsolvable(p: &Puzzle, why: &Unsolvable) -> String {
+    let mut res = String::new();
+    let names = |pieces: &[u32]| -> Vec<String> {
+        pieces
+            .iter()
+            .map(|&i| format!("the {}", describe_piece(p, i, 0)))
+            .collect()
+    };
+    match why.never_captured.as_slice() {
+        [] => res.push_str(
+            "Every piece can be captured in some line of play, but no line captures them all.\n",
+        ),
+        [one] => res.push_str(&format!(
+            "The {} can never be captured.\n",
+            describe_piece(p, *one, 0)
+        )),
+        pieces => res.push_str(&format!(
+            "These pieces can never be captured: {}.\n",
+            english_list(&names(pieces))
+        )),
+    }
+    res.push_str(&format!(
+        "The most captures possible is {}, leaving {}.\n",
+        why.most_captures,
+        english_list(&names(&why.stranded))
+    ));
+    if let Some(exit) = p.exit() {
+        res.push_str(&format!(
+            "A last capture only counts if it leaves a way to the exit on {}.\n",
+            exit
+        ));
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demo_puzzle() {
+        // The puzzle that `echochess` solves when given no file.
+        let p =
+            Puzzle::parse("XXXXXXXX/Xxxxx1xX/Xxrnbx1X/Xpxpx1xX/XNrb3X/Xpx1xrxX/Xxp1nxxX/XXXXXXXX")
+                .unwrap();
+        assert_eq!(
+            describe_puzzle(&p),
+            "\
+The board has 8 files, a through h, and 8 ranks, 1 through 8.
+Rank 8: obstacles on every square.
+Rank 7: obstacles on a7, b7, c7, d7, e7, g7, and h7.
+Rank 6: obstacles on a6, b6, f6, and h6.
+Rank 5: obstacles on a5, c5, e5, g5, and h5.
+Rank 4: obstacles on a4 and h4.
+Rank 3: obstacles on a3, c3, e3, g3, and h3.
+Rank 2: obstacles on a2, b2, f2, g2, and h2.
+Rank 1: obstacles on every square.
+There are 11 pieces to capture: pawn on c2, knight on e2, pawn on b3, rook on f3, rook on c4, \
+bishop on d4, pawn on b5, pawn on d5, rook on c6, knight on d6, and bishop on e6.
+You start as the knight on b4.
+"
+        );
+    }
+
+    #[test]
+    fn two_move_solution() {
+        let p = Puzzle::parse("R1b/3/1n1").unwrap();
+        assert_eq!(
+            describe_puzzle(&p),
+            "\
+The board has 3 files, a through c, and 3 ranks, 1 through 3.
+Rank 3: no obstacles.
+Rank 2: no obstacles.
+Rank 1: no obstacles.
+There are 2 pieces to capture: knight on b1 and bishop on c3.
+You start as the rook on a3.
+"
+        );
+        assert_eq!(
+            describe_solution(&p, &[0, 2]),
+            "\
+Move 1: your rook on a3 captures the knight on b1; you are now a knight on b1.
+Move 2: your knight on b1 captures the bishop on c3; you are now a bishop on c3.
+"
+        );
+    }
+}
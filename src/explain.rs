@@ -0,0 +1,87 @@
+use crate::game::Game;
+use crate::grade::AttemptError;
+use crate::{reachable_states, solve_until, winning_states, Puzzle, PuzzleState, Square};
+
+/// Why a puzzle has no solution, as found by `explain_unsolvable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsolvable {
+    /// Required pieces (by index) that no line of play ever captures. If this is empty, every
+    /// required piece can be captured on its own, but no single line of play captures them all.
+    /// See `Puzzle::required`.
+    pub never_captured: Vec<u32>,
+    /// The most captures that any line of play makes.
+    pub most_captures: u32,
+    /// One line of play (as piece indices captured in order) that makes that many captures.
+    pub best_attempt: Vec<u32>,
+    /// The required pieces (by index) left over at the end of `best_attempt`.
+    pub stranded: Vec<u32>,
+}
+
+/// Explains why a puzzle has no solution, or returns `None` if it has one. This explores every
+/// reachable state, so it costs about as much as a failed `solve`.
+pub fn explain_unsolvable(p: &Puzzle) -> Option<Unsolvable> {
+    let states = reachable_states(p);
+    if states.iter().any(|s| s.done()) {
+        return None;
+    }
+    let initial = PuzzleState::initial(p);
+    let ever_captured = states.iter().fold(0, |acc, s| {
+        acc | (initial.remaining_captures() & !s.remaining_captures())
+    });
+    let never_captured = (0..p.num_pieces())
+        .filter(|&i| initial.remaining_captures() & (1 << i) != 0 && p.required(i))
+        .filter(|&i| ever_captured & (1 << i) == 0)
+        .collect();
+    // Ties are broken by the state's bits, so that the answer doesn't depend on hash order.
+    let deepest = *states
+        .iter()
+        .max_by_key(|s| (s.phase(p), std::cmp::Reverse(s.0)))
+        .unwrap();
+    let best_attempt = if deepest == initial {
+        Vec::new()
+    } else {
+        solve_until(p, |s| s == deepest).expect("deepest state is reachable")
+    };
+    let stranded = (0..p.num_pieces())
+        .filter(|&i| deepest.remaining_captures() & (1 << i) != 0 && p.required(i))
+        .collect();
+    Some(Unsolvable {
+        never_captured,
+        most_captures: deepest.phase(p),
+        best_attempt,
+        stranded,
+    })
+}
+
+/// Finds where an attempt went wrong: the first capture, by its index in `attempt` (a list of
+/// squares captured in order, as `grade::verify_attempt` takes), after which the puzzle can no
+/// longer be won. Returns `None` if it still can be, or if it never could. Fails like
+/// `verify_attempt` if some capture isn't legal.
+///
+/// ```
+/// use echochess::explain::first_mistake;
+/// use echochess::Puzzle;
+///
+/// // Taking the bishop first is a mistake: a bishop on c3 can never reach the knight on b1.
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let [b1, c3] = ["b1", "c3"].map(|sq| sq.parse().unwrap());
+/// assert_eq!(first_mistake(&puzzle, &[b1]), Ok(None));
+/// assert_eq!(first_mistake(&puzzle, &[b1, c3]), Ok(None));
+/// assert_eq!(first_mistake(&puzzle, &[c3]), Ok(Some(0)));
+/// ```
+pub fn first_mistake(
+    p: &Puzzle,
+    attempt: &[Square],
+) -> Result<Option<usize>, (usize, AttemptError)> {
+    let mut game = Game::new(p.clone());
+    let mut states = vec![game.state()];
+    for (step, &loc) in attempt.iter().enumerate() {
+        game.apply(loc).map_err(|e| (step, e))?;
+        states.push(game.state());
+    }
+    let winning = winning_states(p);
+    if !winning.contains(&states[0]) {
+        return Ok(None);
+    }
+    Ok(states[1..].iter().position(|s| !winning.contains(s)))
+}
@@ -0,0 +1,257 @@
+//! Breadth-first search that keeps its layers on disk, for puzzles whose state space doesn't fit
+//! in memory. See `Strategy::External`.
+//!
+//! Every capture removes a piece, so a state can only ever turn up in one layer of the search:
+//! the one numbered by its phase. That makes duplicate detection cheap to delay. Each layer's
+//! successors are collected into sorted runs of bounded size, written out, and then merged into
+//! the next layer's file, dropping duplicates along the way. Earlier layers are never consulted
+//! except to recover the solution at the end.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{
+    unwind, DeadStates, Limit, Puzzle, PuzzleState, SearchConfig, SolveReport, Symmetries,
+};
+
+/// How many states to sort in memory at once if the solver has no `memory_cap`: 16 MB worth.
+const DEFAULT_RUN_LEN: usize = 1 << 22;
+
+/// A scratch directory for one search, deleted with everything in it when dropped.
+struct SpillDir(PathBuf);
+
+impl SpillDir {
+    fn new(parent: &Path) -> io::Result<Self> {
+        // Tell apart searches running at once, in this process or another.
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = parent.join(format!("echochess-{}-{}", std::process::id(), id));
+        std::fs::create_dir_all(&path)?;
+        Ok(SpillDir(path))
+    }
+
+    fn file(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A file of distinct states in increasing order, each stored as a little-endian `u32`.
+struct Layer {
+    path: PathBuf,
+    len: usize,
+}
+
+impl Layer {
+    /// Writes `states` out as a layer, sorting and deduplicating them first.
+    fn write(path: PathBuf, states: &mut Vec<PuzzleState>) -> io::Result<Layer> {
+        states.sort_unstable_by_key(|s| s.0);
+        states.dedup();
+        let mut out = BufWriter::new(File::create(&path)?);
+        for s in states.iter() {
+            out.write_all(&s.0.to_le_bytes())?;
+        }
+        out.flush()?;
+        Ok(Layer {
+            path,
+            len: states.len(),
+        })
+    }
+
+    /// Merges sorted `runs` into one layer, dropping duplicates, and deletes the runs.
+    fn merge(path: PathBuf, runs: Vec<Layer>) -> io::Result<Layer> {
+        let mut readers = runs
+            .iter()
+            .map(|run| run.reader())
+            .collect::<io::Result<Vec<_>>>()?;
+        // The smallest unmerged state from each run, with the index of its run.
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some(s) = read_state(reader)? {
+                heap.push(Reverse((s.0, i)));
+            }
+        }
+        let mut out = BufWriter::new(File::create(&path)?);
+        let mut last = None;
+        let mut len = 0;
+        while let Some(Reverse((s, i))) = heap.pop() {
+            if last != Some(s) {
+                out.write_all(&s.to_le_bytes())?;
+                last = Some(s);
+                len += 1;
+            }
+            if let Some(next) = read_state(&mut readers[i])? {
+                heap.push(Reverse((next.0, i)));
+            }
+        }
+        out.flush()?;
+        for run in runs {
+            std::fs::remove_file(&run.path)?;
+        }
+        Ok(Layer { path, len })
+    }
+
+    fn reader(&self) -> io::Result<BufReader<File>> {
+        Ok(BufReader::new(File::open(&self.path)?))
+    }
+
+    /// Binary-searches the file for `state`.
+    fn contains(&self, state: PuzzleState) -> io::Result<bool> {
+        let mut file = File::open(&self.path)?;
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            file.seek(SeekFrom::Start(4 * mid as u64))?;
+            let found = read_state(&mut file)?.expect("index is in bounds");
+            match found.0.cmp(&state.0) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Reads the next state from a layer file, or `None` at the end.
+fn read_state(r: &mut impl Read) -> io::Result<Option<PuzzleState>> {
+    let mut buf = [0; 4];
+    match r.read_exact(&mut buf) {
+        Ok(()) => Ok(Some(PuzzleState(u32::from_le_bytes(buf)))),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Like `search`, but with each layer stored on disk rather than in a visited set. Layers are
+/// written in sorted order rather than the order states were reached, so this ignores
+/// `Solver::deterministic`. The state count is only updated, and so `Solver::max_states` only
+/// enforced, once each layer is complete.
+pub(crate) fn search_external<G: Fn(PuzzleState) -> bool>(
+    p: &Puzzle,
+    goal: G,
+    config: &SearchConfig,
+    report: &mut SolveReport,
+) -> Result<Option<Vec<u32>>, Limit> {
+    let parent = match &config.solver.spill_dir {
+        Some(dir) => dir.clone(),
+        None => std::env::temp_dir(),
+    };
+    let dir = SpillDir::new(&parent).map_err(|_| Limit::Disk)?;
+    let symmetries = if config.use_symmetry() {
+        Symmetries::of(p)
+    } else {
+        Symmetries::none()
+    };
+    report.symmetries = symmetries.0.len();
+    let dead = if config.solver.ignore_pruning {
+        DeadStates::none()
+    } else {
+        DeadStates::of(p)
+    };
+    let run_len = config.solver.memory_cap.unwrap_or(DEFAULT_RUN_LEN).max(1);
+    let initial = symmetries.canonical(PuzzleState::initial(p));
+    // `layers[k]` holds the states `k` captures in.
+    let mut layers =
+        vec![Layer::write(dir.file("0"), &mut vec![initial]).map_err(|_| Limit::Disk)?];
+    report.states_visited = 1;
+    if goal(initial) {
+        return Ok(Some(Vec::new()));
+    }
+    let mut buf = Vec::new();
+    let mut nexts = Vec::new();
+    while let Some(frontier) = layers.last().filter(|l| l.len > 0) {
+        if config.too_deep(report.depth) {
+            return Err(Limit::Depth);
+        }
+        report.frontier_peak = report.frontier_peak.max(frontier.len);
+        report.depth += 1;
+        let depth = report.depth;
+        let mut runs = Vec::new();
+        let mut reader = frontier.reader().map_err(|_| Limit::Disk)?;
+        while let Some(prev) = read_state(&mut reader).map_err(|_| Limit::Disk)? {
+            report.nodes_expanded += 1;
+            nexts.clear();
+            prev.next_states(p, |next| {
+                if config.allows(p, prev, next) {
+                    nexts.push(next);
+                }
+            })
+            .expect("reachable states are valid");
+            for &next in &nexts {
+                if dead.is_dead(next) {
+                    report.pruned += 1;
+                    continue;
+                }
+                let next = symmetries.canonical(next);
+                if goal(next) {
+                    let visited = |s: PuzzleState| {
+                        // A win forgets the walls still standing and the optional pieces left,
+                        // so states around it can count more captures than there are layers.
+                        layers.get(s.phase(p) as usize).is_some_and(|layer| {
+                            layer.contains(s).expect("layer files are readable")
+                        })
+                    };
+                    return Ok(Some(unwind(p, visited, next, depth as u32, &symmetries)));
+                }
+                buf.push(next);
+            }
+            if buf.len() >= run_len {
+                let path = dir.file(&format!("{}.{}", depth, runs.len()));
+                runs.push(Layer::write(path, &mut buf).map_err(|_| Limit::Disk)?);
+                buf.clear();
+            }
+            config.check(report)?;
+        }
+        let path = dir.file(&format!("{}.{}", depth, runs.len()));
+        runs.push(Layer::write(path, &mut buf).map_err(|_| Limit::Disk)?);
+        buf.clear();
+        let layer = Layer::merge(dir.file(&depth.to_string()), runs).map_err(|_| Limit::Disk)?;
+        report.states_visited += layer.len;
+        layers.push(layer);
+        config.check(report)?;
+    }
+    report.depth -= 1; // the last layer had no successors
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::assert_agrees_with_bfs;
+    use crate::{Puzzle, Solver, Strategy};
+
+    /// Runs of three states split every layer of these into several files to merge.
+    #[test]
+    fn agrees_with_breadth_first() {
+        let spill = std::env::temp_dir().join(format!("echochess-external-{}", std::process::id()));
+        let mut solver = Solver::new();
+        solver
+            .strategy(Strategy::External)
+            .memory_cap(3)
+            .spill_dir(&spill);
+        for fen in [
+            "R1b/3/1n1",
+            "B2/3/1n1",
+            "R1n/3/n1n",
+            "Q1n1/2b1/r3/1n1b",
+            // A wall left standing, and an optional piece left on the board.
+            "R1n+1",
+            "Rnn?",
+            "3~/1q2/p+A1/r1a1",
+        ] {
+            assert_agrees_with_bfs(&solver, &Puzzle::parse(fen).unwrap());
+        }
+        // Each search cleans up after itself.
+        assert_eq!(std::fs::read_dir(&spill).unwrap().count(), 0);
+        std::fs::remove_dir(&spill).unwrap();
+    }
+}
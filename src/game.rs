@@ -0,0 +1,128 @@
+use crate::grade::AttemptError;
+use crate::{Puzzle, PuzzleState, Square};
+
+/// A playthrough of a puzzle in progress, for frontends that let a person play. Captures are
+/// named by the square of the piece being captured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    puzzle: Puzzle,
+    state: PuzzleState,
+    /// Each capture made so far, with the state from just before it, so it can be undone.
+    history: Vec<(Square, PuzzleState)>,
+}
+
+impl Game {
+    /// Starts a new playthrough of `puzzle`.
+    pub fn new(puzzle: Puzzle) -> Self {
+        let state = PuzzleState::initial(&puzzle);
+        Game {
+            puzzle,
+            state,
+            history: Vec::new(),
+        }
+    }
+
+    /// The puzzle being played.
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+
+    /// Where the playthrough stands now.
+    pub fn state(&self) -> PuzzleState {
+        self.state
+    }
+
+    /// The squares captured so far, in order.
+    pub fn history(&self) -> impl ExactSizeIterator<Item = Square> + '_ {
+        self.history.iter().map(|&(loc, _)| loc)
+    }
+
+    /// Which square is the player on?
+    pub fn player_loc(&self) -> Square {
+        let phase = self.state.phase(&self.puzzle);
+        self.puzzle
+            .player_loc_at(self.state.current_piece_idx(), phase)
+    }
+
+    /// Has every opposing piece been captured?
+    pub fn is_won(&self) -> bool {
+        self.state.done()
+    }
+
+    /// The squares that the player can capture on right now, each with the fewest moves that the
+    /// capture takes, in square order. The last capture counts the walk to the exit, if there is
+    /// one. A capture listed here can still fail if a patroller then steps onto the player; see
+    /// `apply`.
+    pub fn legal_captures(&self) -> Vec<(Square, u32)> {
+        let mut res = Vec::new();
+        self.state
+            .capture_squares(&self.puzzle, |loc, moves| res.push((loc, moves)))
+            .expect("reachable states are valid");
+        res.sort();
+        res
+    }
+
+    /// Captures the piece on square `loc`, returning how many moves that took. On failure, the
+    /// game is left as it was.
+    pub fn apply(&mut self, loc: Square) -> Result<u32, AttemptError> {
+        let (_, moves) = self
+            .legal_captures()
+            .into_iter()
+            .find(|&(l, _)| l == loc)
+            .ok_or(AttemptError::IllegalCapture)?;
+        let next = self
+            .state
+            .capture(&self.puzzle, loc)
+            .ok_or(AttemptError::CaughtByPatroller)?;
+        self.history.push((loc, self.state));
+        self.state = next;
+        Ok(moves)
+    }
+
+    /// Takes back the last capture, returning its square, or `None` if there's nothing to undo.
+    pub fn undo(&mut self) -> Option<Square> {
+        let (loc, prev) = self.history.pop()?;
+        self.state = prev;
+        Some(loc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solve;
+
+    /// Playing out breadth-first search's solution wins, one legal capture at a time, in as many
+    /// moves as `Puzzle::verify` counts, and undoing it all gets back to the start.
+    #[test]
+    fn plays_out_breadth_first_solutions() {
+        for fen in [
+            "R1b/3/1n1",
+            "R1n/3/n1n",
+            "Q1n1/2b1/r3/1n1b",
+            "b~n/~~~/R~~",
+            "3/n>R",
+        ] {
+            let puzzle = Puzzle::parse(fen).unwrap();
+            let captures = solve(&puzzle).unwrap();
+            let mut game = Game::new(puzzle.clone());
+            let mut moves = 0;
+            for &piece in &captures {
+                assert!(!game.is_won(), "{}", fen);
+                let loc = puzzle.piece_loc(piece);
+                assert!(game.legal_captures().iter().any(|&(l, _)| l == loc));
+                moves += game.apply(loc).unwrap();
+            }
+            assert!(game.is_won(), "{}", fen);
+            assert_eq!(moves, puzzle.verify(&captures).unwrap().moves, "{}", fen);
+            while game.undo().is_some() {}
+            assert_eq!(game, Game::new(puzzle));
+        }
+        let mut game = Game::new(Puzzle::parse("B2/3/1n1").unwrap());
+        assert_eq!(game.legal_captures(), vec![]);
+        assert_eq!(
+            game.apply("b1".parse().unwrap()),
+            Err(AttemptError::IllegalCapture)
+        );
+    }
+}
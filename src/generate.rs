@@ -0,0 +1,1010 @@
+//! Random puzzles: boards with pieces and obstacles scattered at random, kept only if the solver
+//! can win them.
+
+use std::collections::HashSet;
+
+use crate::analyze::{difficulty, first_moves};
+use crate::pack::PuzzleMeta;
+use crate::{
+    solve, solve_min_moves, BuildError, King, PieceType, Puzzle, PuzzleBuilder, Square, SquareSet,
+    Stepper,
+};
+
+/// Settings for generating puzzles, in the style of a `Solver`: start from `Generator::new` and
+/// adjust what you need, then call `generate`. Settings that can't work are reported by `check`,
+/// like `PuzzleBuilder::build` does, and generating with them panics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generator {
+    width: u8,
+    height: u8,
+    /// How many opposing pieces of each type to place.
+    pieces: Vec<(PieceType, usize)>,
+    player: PieceType,
+    obstacle_density: f64,
+    attempts: usize,
+    unique: bool,
+    min_moves: Option<u32>,
+    min_traps: Option<usize>,
+    /// The range of `Difficulty::score` to accept, if limited.
+    score: Option<(f64, f64)>,
+    symmetry: Option<Symmetry>,
+    symmetric_pieces: bool,
+    layout: Layout,
+    /// The first problem with the settings, if any. See `check`.
+    error: Option<BuildError>,
+}
+
+/// How a generator lays out obstacles. See `Generator::layout`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// Obstacles on squares picked at random, as many as `Generator::obstacle_density` says.
+    #[default]
+    Scattered,
+    /// Corridors one square wide, carved by recursive division: each room is split in two by
+    /// a wall along an odd file or rank, with a single gap on an even square, and the halves
+    /// are split in turn until they're too narrow.
+    Maze,
+    /// Open caves, grown by a cellular automaton: fill squares at random, then repeatedly
+    /// make each square an obstacle if most of the squares around it, itself included, are.
+    Caverns,
+}
+
+/// A way for a generated board to look the same as its reflection or rotation. See
+/// `Generator::symmetry`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    /// The left half of the board mirrors the right half.
+    Mirror,
+    /// The board looks the same turned halfway around.
+    Rotational,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator {
+    /// The default settings: a 5×5 board, a rook to start as, two knights and two bishops to
+    /// capture, a fifth of the empty squares filled with obstacles, and up to 1000 tries, with
+    /// any number of solutions allowed.
+    pub fn new() -> Self {
+        Generator {
+            width: 5,
+            height: 5,
+            pieces: vec![(PieceType::Knight, 2), (PieceType::Bishop, 2)],
+            player: PieceType::Rook,
+            obstacle_density: 0.2,
+            attempts: 1000,
+            unique: false,
+            min_moves: None,
+            min_traps: None,
+            score: None,
+            symmetry: None,
+            symmetric_pieces: false,
+            layout: Layout::Scattered,
+            error: None,
+        }
+    }
+
+    /// Uses a board `width` files wide and `height` ranks high, each from 1 to 8, as with
+    /// `PuzzleBuilder::set_size`. Any other size is an error.
+    ///
+    /// ```
+    /// use echochess::generate::Generator;
+    /// use echochess::BuildError;
+    ///
+    /// assert_eq!(Generator::new().size(8, 3).check(), Ok(()));
+    /// assert_eq!(Generator::new().size(9, 9).check(), Err(BuildError::BadSize(9, 9)));
+    /// assert_eq!(Generator::new().size(0, 3).check(), Err(BuildError::BadSize(0, 3)));
+    /// ```
+    pub fn size(&mut self, width: u8, height: u8) -> &mut Self {
+        if !(1..=8).contains(&width) || !(1..=8).contains(&height) {
+            self.error.get_or_insert(BuildError::BadSize(width, height));
+        }
+        self.width = width.clamp(1, 8);
+        self.height = height.clamp(1, 8);
+        self
+    }
+
+    /// Checks that the settings can work, returning the first problem with them if not.
+    /// Generating panics with that problem instead.
+    ///
+    /// ```should_panic
+    /// use echochess::generate::Generator;
+    ///
+    /// // Panics with "Bad generator settings: Bad board size: 9x9 (max 8x8)".
+    /// Generator::new().size(9, 9).generate(1);
+    /// ```
+    pub fn check(&self) -> Result<(), BuildError> {
+        match &self.error {
+            Some(e) => Err(e.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// Panics if `check` finds a problem.
+    fn assert_valid(&self) {
+        if let Err(e) = self.check() {
+            panic!("Bad generator settings: {}", e);
+        }
+    }
+
+    /// Places `count` opposing pieces of type `ty`, instead of however many were set before.
+    pub fn pieces(&mut self, ty: PieceType, count: usize) -> &mut Self {
+        self.pieces.retain(|&(t, _)| t != ty);
+        if count > 0 {
+            self.pieces.push((ty, count));
+        }
+        self
+    }
+
+    /// Starts the player as a piece of type `ty`.
+    pub fn player(&mut self, ty: PieceType) -> &mut Self {
+        self.player = ty;
+        self
+    }
+
+    /// Fills this fraction of the squares left over after placing the pieces with obstacles,
+    /// from `0.0` for none to `1.0` for all of them.
+    pub fn obstacle_density(&mut self, density: f64) -> &mut Self {
+        self.obstacle_density = density.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Gives up after trying this many candidates without finding a solvable one.
+    pub fn attempts(&mut self, attempts: usize) -> &mut Self {
+        self.attempts = attempts;
+        self
+    }
+
+    /// Only keeps puzzles with exactly one winning capture order, as for a daily or competitive
+    /// puzzle, where a second way to win would be a flaw. These are rarer, so more candidates
+    /// get thrown away; consider raising `attempts`.
+    ///
+    /// ```
+    /// use echochess::generate::Generator;
+    ///
+    /// let puzzle = Generator::new().unique(true).generate(1).unwrap().puzzle;
+    /// assert!(puzzle.count_solutions(2).is_unique());
+    /// ```
+    pub fn unique(&mut self, unique: bool) -> &mut Self {
+        self.unique = unique;
+        self
+    }
+
+    /// Only keeps puzzles whose shortest solution takes at least `moves` moves.
+    pub fn min_moves(&mut self, moves: u32) -> &mut Self {
+        self.min_moves = Some(moves);
+        self
+    }
+
+    /// Only keeps puzzles where at least `traps` of the first captures lose. See
+    /// `analyze::first_moves`.
+    pub fn min_traps(&mut self, traps: usize) -> &mut Self {
+        self.min_traps = Some(traps);
+        self
+    }
+
+    /// Only keeps puzzles whose `Difficulty::score` is from `min` to `max`, inclusive, for
+    /// sorting puzzles into tiers. See `analyze::difficulty`.
+    ///
+    /// ```
+    /// use echochess::analyze::difficulty;
+    /// use echochess::generate::Generator;
+    /// use echochess::solve_min_moves;
+    ///
+    /// let easy = Generator::new().score(0.0, 1.0).generate(1).unwrap().puzzle;
+    /// assert!(difficulty(&easy).score <= 1.0);
+    /// let hard = Generator::new()
+    ///     .score(3.0, f64::INFINITY)
+    ///     .min_moves(8)
+    ///     .min_traps(1)
+    ///     .generate(1)
+    ///     .unwrap()
+    ///     .puzzle;
+    /// assert!(difficulty(&hard).score >= 3.0);
+    /// assert!(solve_min_moves(&hard).unwrap().1 >= 8);
+    /// ```
+    pub fn score(&mut self, min: f64, max: f64) -> &mut Self {
+        self.score = Some((min, max));
+        self
+    }
+
+    /// Lays out obstacles so that the board has this symmetry. The pieces still go anywhere,
+    /// unless `symmetric_pieces` is on too.
+    ///
+    /// ```
+    /// use echochess::generate::{Generator, Symmetry};
+    /// use echochess::Square;
+    ///
+    /// let puzzle = Generator::new()
+    ///     .symmetry(Symmetry::Rotational)
+    ///     .generate(3)
+    ///     .unwrap()
+    ///     .puzzle;
+    /// let obstacles = puzzle.obstacles();
+    /// for sq in puzzle.squares().iter() {
+    ///     let turned = Square::from_file_rank(4 - sq.file(), 4 - sq.rank()).unwrap();
+    ///     assert_eq!(obstacles.contains(sq), obstacles.contains(turned));
+    /// }
+    /// ```
+    pub fn symmetry(&mut self, symmetry: Symmetry) -> &mut Self {
+        self.symmetry = Some(symmetry);
+        self
+    }
+
+    /// With a `symmetry`, places the opposing pieces in matching pairs too, each the image of
+    /// the other, with any odd one out on a square that the symmetry leaves in place. The
+    /// player's piece has no partner: it goes on such a square if there's one free, and
+    /// otherwise leaves its image empty. This only applies to `generate`; `generate_backward`
+    /// still makes the obstacles symmetric, but has to put the pieces where the captures lead.
+    ///
+    /// ```
+    /// use echochess::generate::{Generator, Symmetry};
+    /// use echochess::Square;
+    ///
+    /// let puzzle = Generator::new()
+    ///     .symmetry(Symmetry::Mirror)
+    ///     .symmetric_pieces(true)
+    ///     .generate(1)
+    ///     .unwrap()
+    ///     .puzzle;
+    /// assert_eq!(puzzle.piece_loc(puzzle.player_start()).file(), 2);
+    /// for i in 0..puzzle.num_pieces() {
+    ///     let sq = puzzle.piece_loc(i);
+    ///     let mirrored = Square::from_file_rank(4 - sq.file(), sq.rank()).unwrap();
+    ///     let partner = puzzle.piece_on(mirrored).unwrap();
+    ///     assert_eq!(puzzle.piece_type(partner), puzzle.piece_type(i));
+    /// }
+    /// ```
+    pub fn symmetric_pieces(&mut self, symmetric_pieces: bool) -> &mut Self {
+        self.symmetric_pieces = symmetric_pieces;
+        self
+    }
+
+    /// Lays out obstacles this way instead of scattering them, for boards with corridors and
+    /// rooms like the original game's. The pieces then go on open squares at random. A maze
+    /// or caverns ignore `obstacle_density`, but still follow `symmetry`, by copying one half
+    /// of the layout onto the other.
+    ///
+    /// ```
+    /// use echochess::generate::{Generator, Layout};
+    ///
+    /// let puzzle = Generator::new()
+    ///     .size(7, 7)
+    ///     .layout(Layout::Maze)
+    ///     .generate(1)
+    ///     .unwrap()
+    ///     .puzzle;
+    /// let walls: Vec<_> = puzzle
+    ///     .squares()
+    ///     .iter()
+    ///     .filter(|&sq| puzzle.obstacles().contains(sq))
+    ///     .collect();
+    /// assert!(walls.len() >= 10);
+    /// assert!(walls.iter().all(|sq| sq.file() % 2 == 1 || sq.rank() % 2 == 1));
+    ///
+    /// let caves = Generator::new()
+    ///     .size(7, 7)
+    ///     .layout(Layout::Caverns)
+    ///     .generate(1)
+    ///     .unwrap()
+    ///     .puzzle;
+    /// assert!(echochess::solve(&caves).is_some());
+    /// ```
+    pub fn layout(&mut self, layout: Layout) -> &mut Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Generates a solvable puzzle. The same seed gives the same puzzle, so a daily puzzle can
+    /// use the date as its seed. Returns `None` if no candidate within the allowed attempts
+    /// was solvable, or if the pieces don't fit on the board.
+    ///
+    /// Each candidate is laid out from a seed of its own, the first being `seed` itself, and
+    /// the one that was kept comes back as `Generated::seed`. Generating from that seed, with
+    /// the same settings, gives the same puzzle again on the first try, so it's all that needs
+    /// to be shared to pass the puzzle along.
+    ///
+    /// ```
+    /// use echochess::generate::Generator;
+    /// use echochess::{solve, PieceType};
+    ///
+    /// let found = Generator::new()
+    ///     .size(4, 4)
+    ///     .pieces(PieceType::Knight, 3)
+    ///     .pieces(PieceType::Bishop, 0)
+    ///     .player(PieceType::Queen)
+    ///     .generate(2024)
+    ///     .unwrap();
+    /// let puzzle = &found.puzzle;
+    /// assert_eq!(puzzle.num_pieces(), 4);
+    /// assert_eq!(puzzle.piece_type(puzzle.player_start()), Some(PieceType::Queen));
+    /// assert!(solve(puzzle).is_some());
+    /// assert_eq!(Generator::new().generate(7), Generator::new().generate(7));
+    ///
+    /// let shared = format!("{:x}", found.seed);
+    /// let again = Generator::new()
+    ///     .size(4, 4)
+    ///     .pieces(PieceType::Knight, 3)
+    ///     .pieces(PieceType::Bishop, 0)
+    ///     .player(PieceType::Queen)
+    ///     .attempts(1)
+    ///     .generate(u64::from_str_radix(&shared, 16).unwrap())
+    ///     .unwrap();
+    /// assert_eq!(again, found);
+    /// ```
+    pub fn generate(&self, seed: u64) -> Option<Generated> {
+        self.search(seed, Self::candidate)
+    }
+
+    /// Like `generate`, but builds each candidate backwards from the end instead of at random,
+    /// so that it's solvable by construction, rather than throwing away the many that aren't.
+    /// This starts with the last piece to be captured, then keeps adding a piece that could have
+    /// captured the one added before it, ending with the player's piece. Obstacles go down
+    /// first, at random. The other requirements, like `unique`, are still checked after. Seeds
+    /// work as for `generate`.
+    ///
+    /// ```
+    /// use echochess::generate::Generator;
+    /// use echochess::{solve, PieceType};
+    ///
+    /// let puzzle = Generator::new()
+    ///     .size(6, 6)
+    ///     .pieces(PieceType::Knight, 4)
+    ///     .pieces(PieceType::Bishop, 4)
+    ///     .obstacle_density(0.4)
+    ///     .attempts(1)
+    ///     .generate_backward(5)
+    ///     .unwrap()
+    ///     .puzzle;
+    /// assert_eq!(puzzle.num_pieces(), 9);
+    /// assert!(solve(&puzzle).is_some());
+    /// ```
+    pub fn generate_backward(&self, seed: u64) -> Option<Generated> {
+        self.search(seed, Self::backward_candidate)
+    }
+
+    /// Generates `count` different puzzles, handing each to `sink` as soon as it's found, for
+    /// filling a large collection. Candidates are laid out and checked on as many threads as
+    /// the machine has cores. Puzzles that are reflections or rotations of one found before,
+    /// by `Puzzle::canonical_id`, are skipped. Returns how many were found, which is fewer
+    /// than `count` only if `attempts` candidates for each puzzle asked for weren't enough.
+    ///
+    /// Each candidate has its own seed, drawn from `seed`, so a puzzle can still be made again
+    /// from `Generated::seed` by `generate`. But which candidates win the race to the sink
+    /// depends on the threads, so the set of puzzles found, and their order, can vary from run
+    /// to run.
+    ///
+    /// ```
+    /// use echochess::generate::Generator;
+    /// use std::collections::HashSet;
+    ///
+    /// let generator = Generator::new();
+    /// let mut found = Vec::new();
+    /// assert_eq!(generator.generate_many(1, 5, |g| found.push(g)), 5);
+    /// let ids: HashSet<u64> = found.iter().map(|g| g.puzzle.canonical_id()).collect();
+    /// assert_eq!(ids.len(), 5);
+    /// for g in &found {
+    ///     let again = Generator::new().attempts(1).generate(g.seed).unwrap();
+    ///     assert_eq!(&again, g);
+    /// }
+    /// ```
+    pub fn generate_many<F: FnMut(Generated)>(&self, seed: u64, count: usize, sink: F) -> usize {
+        self.pipeline(seed, count, Self::candidate, HashSet::new(), sink)
+    }
+
+    /// Like `generate_many`, but builds candidates as `generate_backward` does. Seeds work as
+    /// for `generate_backward`.
+    pub fn generate_many_backward<F: FnMut(Generated)>(
+        &self,
+        seed: u64,
+        count: usize,
+        sink: F,
+    ) -> usize {
+        self.pipeline(seed, count, Self::backward_candidate, HashSet::new(), sink)
+    }
+
+    /// Makes a variation on `base` by changing one thing about it: moving a piece to an empty
+    /// square, changing what type a piece is, or adding or taking away an obstacle. The pieces
+    /// that the player can start as, and any that patrol, stay as they are, as do special
+    /// squares like portals. Only variations that meet the requirements, like being solvable,
+    /// are kept; the board size and pieces set on this generator don't matter here. Seeds work
+    /// as for `generate`, given the same `base`.
+    ///
+    /// Remixing the remixes makes variations that stray further from the original.
+    ///
+    /// ```
+    /// use echochess::generate::Generator;
+    /// use echochess::{solve, Puzzle};
+    ///
+    /// let base = Puzzle::parse("R1n2/5/2x1b/5/b3n").unwrap();
+    /// let remix = Generator::new().remix(&base, 1).unwrap();
+    /// assert_ne!(remix.puzzle, base);
+    /// assert!(solve(&remix.puzzle).is_some());
+    /// ```
+    pub fn remix(&self, base: &Puzzle, seed: u64) -> Option<Generated> {
+        self.search(seed, |_, rng| mutate(base, rng))
+    }
+
+    /// Makes `count` different variations on `base`, as `remix` does, and hands each to `sink`,
+    /// to fill out a family of related puzzles. This checks candidates on many threads and
+    /// skips duplicates, as `generate_many` does, including any that are the same as `base` up
+    /// to reflection or rotation.
+    ///
+    /// ```
+    /// use echochess::generate::Generator;
+    /// use echochess::Puzzle;
+    ///
+    /// let base = Puzzle::parse("R1n2/5/2x1b/5/b3n").unwrap();
+    /// let mut family = Vec::new();
+    /// assert_eq!(Generator::new().remix_many(&base, 1, 3, |g| family.push(g)), 3);
+    /// for g in &family {
+    ///     assert_ne!(g.puzzle.canonical_id(), base.canonical_id());
+    ///     assert_eq!(Generator::new().attempts(1).remix(&base, g.seed).as_ref(), Some(g));
+    /// }
+    /// ```
+    pub fn remix_many<F: FnMut(Generated)>(
+        &self,
+        base: &Puzzle,
+        seed: u64,
+        count: usize,
+        sink: F,
+    ) -> usize {
+        let seen = HashSet::from([base.canonical_id()]);
+        self.pipeline(seed, count, |_, rng| mutate(base, rng), seen, sink)
+    }
+
+    /// Generates a level pack of up to `count` puzzles that get harder as they go, ready to
+    /// write out with `pack::write_pack`. The player starts as a different type of piece from
+    /// one level to the next, beginning with the one set here, and the pieces to capture are as
+    /// set here. Each level is the easiest of several candidates whose `Difficulty::score` is
+    /// at least the one before's, preferring any that are strictly harder. The levels are
+    /// named in order, with notes giving their scores and seeds. The same seed gives the same
+    /// pack. It comes up short if no starting piece gives a puzzle hard enough within the
+    /// allowed attempts.
+    ///
+    /// ```
+    /// use echochess::analyze::difficulty;
+    /// use echochess::generate::Generator;
+    /// use echochess::pack::{parse_pack, write_pack};
+    ///
+    /// let levels = Generator::new().pack(1, 4);
+    /// assert_eq!(levels.len(), 4);
+    /// assert_eq!(levels[0].0.name.as_deref(), Some("Level 1"));
+    /// let scores: Vec<f64> = levels.iter().map(|(_, p)| difficulty(p).score).collect();
+    /// assert!(scores.windows(2).all(|w| w[0] <= w[1]));
+    /// let starts: Vec<_> = levels.iter().map(|(_, p)| p.piece_type(p.player_start())).collect();
+    /// assert!(starts.windows(2).all(|w| w[0] != w[1]));
+    /// assert_eq!(parse_pack(&write_pack(&levels)), levels);
+    /// assert_eq!(Generator::new().pack(1, 4), levels);
+    /// ```
+    pub fn pack(&self, seed: u64, count: usize) -> Vec<(PuzzleMeta, Puzzle)> {
+        self.assert_valid();
+        let mut players = vec![self.player];
+        players.extend(PACK_PLAYERS.into_iter().filter(|&ty| ty != self.player));
+        let (min, max) = self.score.unwrap_or((0.0, f64::INFINITY));
+        let mut seeds = SplitMix64(seed);
+        let mut seen = HashSet::new();
+        let mut floor = min;
+        let mut levels = Vec::new();
+        for level in 0..count {
+            let next = (0..players.len()).find_map(|k| {
+                let mut generator = self.clone();
+                generator
+                    .player(players[(level + k) % players.len()])
+                    .score(floor, max);
+                let choices = (0..PACK_CHOICES)
+                    .filter_map(|_| generator.generate(seeds.next()))
+                    .filter(|g| !seen.contains(&g.puzzle.canonical_id()))
+                    .map(|g| (difficulty(&g.puzzle).score, g));
+                // Ties with the level before only as a last resort.
+                let key = |score: f64| (level > 0 && score <= floor, score);
+                choices.min_by(|(a, _), (b, _)| key(*a).partial_cmp(&key(*b)).unwrap())
+            });
+            let Some((score, g)) = next else {
+                break;
+            };
+            floor = score;
+            seen.insert(g.puzzle.canonical_id());
+            let meta = PuzzleMeta {
+                name: Some(format!("Level {}", level + 1)),
+                notes: vec![
+                    format!("difficulty: {:.2}", score),
+                    format!("seed: {:x}", g.seed),
+                ],
+                ..PuzzleMeta::default()
+            };
+            levels.push((meta, g.puzzle));
+        }
+        levels
+    }
+
+    /// Runs `generate_many` with candidates from `build`, skipping any with a canonical ID in
+    /// `seen` to begin with.
+    fn pipeline<F: FnMut(Generated)>(
+        &self,
+        seed: u64,
+        count: usize,
+        build: impl Fn(&Self, &mut SplitMix64) -> Option<Puzzle> + Sync,
+        mut seen: HashSet<u64>,
+        mut sink: F,
+    ) -> usize {
+        use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+        self.assert_valid();
+        if count == 0 {
+            return 0;
+        }
+        let limit = self.attempts.saturating_mul(count) as u64;
+        let next = AtomicU64::new(0);
+        let stop = AtomicBool::new(false);
+        let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut found = 0;
+        std::thread::scope(|scope| {
+            // Each thread takes the next unclaimed candidate, until there are none left or
+            // enough have been found, and sends on the ones that pass.
+            for _ in 0..num_threads {
+                let tx = tx.clone();
+                let (next, stop, build) = (&next, &stop, &build);
+                scope.spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= limit {
+                            return;
+                        }
+                        // The `i`th output of a generator seeded with `seed`.
+                        let seed = SplitMix64(seed.wrapping_add(i.wrapping_mul(GAMMA))).next();
+                        let Some(puzzle) = build(self, &mut SplitMix64(seed)) else {
+                            continue;
+                        };
+                        if self.accepts(&puzzle) {
+                            let id = puzzle.canonical_id();
+                            if tx.send((id, Generated { puzzle, seed })).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+            drop(tx);
+            for (id, generated) in rx {
+                if seen.insert(id) {
+                    sink(generated);
+                    found += 1;
+                    if found == count {
+                        break;
+                    }
+                }
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+        found
+    }
+
+    /// Tries up to `attempts` candidates from `build`, each from its own seed, starting with
+    /// `seed`, and keeps the first one that `accepts`.
+    fn search(
+        &self,
+        seed: u64,
+        build: impl Fn(&Self, &mut SplitMix64) -> Option<Puzzle>,
+    ) -> Option<Generated> {
+        self.assert_valid();
+        let mut next = seed;
+        (0..self.attempts).find_map(|_| {
+            let seed = next;
+            next = SplitMix64(seed).next();
+            let puzzle = build(self, &mut SplitMix64(seed))?;
+            self.accepts(&puzzle).then_some(Generated { puzzle, seed })
+        })
+    }
+
+    /// Does `p` meet every requirement? The cheaper checks go first, so that most candidates
+    /// are thrown out early.
+    fn accepts(&self, p: &Puzzle) -> bool {
+        let solvable = if self.unique {
+            p.count_solutions(2).is_unique()
+        } else {
+            solve(p).is_some()
+        };
+        if !solvable {
+            return false;
+        }
+        if let Some(min) = self.min_moves {
+            if solve_min_moves(p).is_none_or(|(_, moves)| moves < min) {
+                return false;
+            }
+        }
+        if let Some(min) = self.min_traps {
+            if first_moves(p).iter().filter(|m| m.best.is_none()).count() < min {
+                return false;
+            }
+        }
+        if let Some((min, max)) = self.score {
+            let score = difficulty(p).score;
+            if score < min || score > max {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Lays out one random board, solvable or not.
+    fn candidate(&self, rng: &mut SplitMix64) -> Option<Puzzle> {
+        let mut squares = self.shuffled_squares(rng);
+        let carved = self.carve(rng);
+        if let Some(walls) = carved {
+            squares.retain(|&sq| !walls.contains(sq));
+        }
+        let mut orbits = self.orbits(&squares);
+        let mut builder = PuzzleBuilder::new();
+        builder.set_size(self.width, self.height);
+        if self.symmetric_pieces && self.symmetry.is_some() {
+            // The first free group of `len` squares.
+            let mut take = |len: usize| {
+                let i = orbits.iter().position(|o| o.len() == len)?;
+                Some(orbits.remove(i))
+            };
+            let player = take(1).or_else(|| take(2))?[0];
+            builder.add_piece(player, self.player).set_player(player);
+            for &(ty, count) in &self.pieces {
+                for _ in 0..count / 2 {
+                    for sq in take(2)? {
+                        builder.add_piece(sq, ty);
+                    }
+                }
+                if count % 2 == 1 {
+                    builder.add_piece(take(1)?[0], ty);
+                }
+            }
+        } else {
+            let mut squares = squares.into_iter();
+            let mut taken = SquareSet::default();
+            let player = squares.next()?;
+            builder.add_piece(player, self.player).set_player(player);
+            taken.insert(player);
+            for &(ty, count) in &self.pieces {
+                for _ in 0..count {
+                    let sq = squares.next()?;
+                    builder.add_piece(sq, ty);
+                    taken.insert(sq);
+                }
+            }
+            // Obstacles can only go where their images are free too.
+            orbits.retain(|o| o.iter().all(|&sq| !taken.contains(sq)));
+        }
+        let obstacles = match carved {
+            Some(walls) => walls.iter().collect(),
+            None => {
+                let spare = orbits.iter().map(Vec::len).sum();
+                self.take_obstacles(&mut orbits, spare)
+            }
+        };
+        for sq in obstacles {
+            builder.add_obstacle(sq);
+        }
+        builder.build().ok()
+    }
+
+    /// Lays out one board backwards from the last capture, as `generate_backward` describes.
+    /// Fails if some piece has nowhere to go.
+    fn backward_candidate(&self, rng: &mut SplitMix64) -> Option<Puzzle> {
+        let squares = self.shuffled_squares(rng);
+        let mut order: Vec<PieceType> = self
+            .pieces
+            .iter()
+            .flat_map(|&(ty, count)| std::iter::repeat_n(ty, count))
+            .collect();
+        shuffle(&mut order, rng);
+        let (obstacles, mut free): (Vec<Square>, Vec<Square>) = match self.carve(rng) {
+            Some(walls) => squares.into_iter().partition(|&sq| walls.contains(sq)),
+            None => {
+                let spare = squares.len().checked_sub(order.len() + 1)?;
+                let mut orbits = self.orbits(&squares);
+                let obstacles = self.take_obstacles(&mut orbits, spare);
+                (obstacles, orbits.into_iter().flatten().collect())
+            }
+        };
+        // The pieces placed so far, in the order they're captured, after the one that starts
+        // the line.
+        let mut line = vec![(free.pop()?, order.pop()?)];
+        let mut puzzle = None;
+        for ty in order.into_iter().rev().chain([self.player]) {
+            // Try each free square for the new piece, until one can start the line.
+            let (i, p) = free.iter().enumerate().find_map(|(i, &sq)| {
+                let p = self.line_puzzle(&obstacles, sq, ty, &line)?;
+                let captures: Vec<u32> =
+                    line.iter().filter_map(|&(sq, _)| p.piece_on(sq)).collect();
+                p.verify(&captures).is_ok().then_some((i, p))
+            })?;
+            line.insert(0, (free.remove(i), ty));
+            puzzle = Some(p);
+        }
+        puzzle
+    }
+
+    /// The board with `obstacles`, the player as a `ty` on `player`, and the pieces of `line`.
+    fn line_puzzle(
+        &self,
+        obstacles: &[Square],
+        player: Square,
+        ty: PieceType,
+        line: &[(Square, PieceType)],
+    ) -> Option<Puzzle> {
+        let mut builder = PuzzleBuilder::new();
+        builder.set_size(self.width, self.height);
+        builder.add_piece(player, ty).set_player(player);
+        for &(sq, ty) in line {
+            builder.add_piece(sq, ty);
+        }
+        for &sq in obstacles {
+            builder.add_obstacle(sq);
+        }
+        builder.build().ok()
+    }
+
+    /// Groups `squares` into the sets that `symmetry` maps to each other: pairs, or single
+    /// squares that it leaves in place. Without a symmetry, every square is on its own. The
+    /// groups keep the order of their first squares.
+    fn orbits(&self, squares: &[Square]) -> Vec<Vec<Square>> {
+        let mut seen = SquareSet::default();
+        let mut res = Vec::new();
+        for &sq in squares {
+            if !seen.insert(sq) {
+                continue;
+            }
+            let image = self.image(sq);
+            if seen.insert(image) {
+                res.push(vec![sq, image]);
+            } else {
+                res.push(vec![sq]);
+            }
+        }
+        res
+    }
+
+    /// Where `symmetry` takes square `sq`.
+    fn image(&self, sq: Square) -> Square {
+        let (f, r) = (sq.file(), sq.rank());
+        let (mf, mr) = (self.width - 1, self.height - 1);
+        match self.symmetry {
+            None => sq,
+            Some(Symmetry::Mirror) => Square::from_file_rank(mf - f, r).unwrap(),
+            Some(Symmetry::Rotational) => Square::from_file_rank(mf - f, mr - r).unwrap(),
+        }
+    }
+
+    /// The obstacles of a `layout` other than `Layout::Scattered`, made symmetric if need be.
+    fn carve(&self, rng: &mut SplitMix64) -> Option<SquareSet> {
+        let walls = match self.layout {
+            Layout::Scattered => return None,
+            Layout::Maze => self.maze(rng),
+            Layout::Caverns => self.caverns(rng),
+        };
+        // Each square follows whichever of it and its image comes first.
+        let squares = self.board().filter(|&sq| {
+            let image = self.image(sq);
+            walls.contains(if image.index() < sq.index() {
+                image
+            } else {
+                sq
+            })
+        });
+        Some(squares.collect())
+    }
+
+    /// Walls for `Layout::Maze`.
+    fn maze(&self, rng: &mut SplitMix64) -> SquareSet {
+        let mut walls = SquareSet::default();
+        let mut wall = |file, rank| walls.insert(Square::from_file_rank(file, rank).unwrap());
+        // Rooms still to split, as their lowest and highest files and ranks. Walls go on odd
+        // files and ranks, and gaps on even ones, so a gap is never blocked by a later wall.
+        let mut rooms = vec![(0, 0, self.width - 1, self.height - 1)];
+        while let Some((f0, r0, f1, r1)) = rooms.pop() {
+            let across = match (f1 - f0 >= 2, r1 - r0 >= 2) {
+                (false, false) => continue,
+                (true, false) => false,
+                (false, true) => true,
+                _ if f1 - f0 != r1 - r0 => r1 - r0 > f1 - f0,
+                _ => rng.below(2) == 0,
+            };
+            if across {
+                let rank = *pick(&(r0 + 1..r1).step_by(2).collect::<Vec<_>>(), rng).unwrap();
+                let gap = *pick(&(f0..=f1).step_by(2).collect::<Vec<_>>(), rng).unwrap();
+                for file in (f0..=f1).filter(|&f| f != gap) {
+                    wall(file, rank);
+                }
+                rooms.extend([(f0, r0, f1, rank - 1), (f0, rank + 1, f1, r1)]);
+            } else {
+                let file = *pick(&(f0 + 1..f1).step_by(2).collect::<Vec<_>>(), rng).unwrap();
+                let gap = *pick(&(r0..=r1).step_by(2).collect::<Vec<_>>(), rng).unwrap();
+                for rank in (r0..=r1).filter(|&r| r != gap) {
+                    wall(file, rank);
+                }
+                rooms.extend([(f0, r0, file - 1, r1), (file + 1, r0, f1, r1)]);
+            }
+        }
+        walls
+    }
+
+    /// Walls for `Layout::Caverns`.
+    fn caverns(&self, rng: &mut SplitMix64) -> SquareSet {
+        let board: SquareSet = self.board().collect();
+        let mut walls: SquareSet = self
+            .board()
+            .filter(|_| rng.below(100) < CAVERN_FILL_PERCENT)
+            .collect();
+        // The squares around each square, all 64 stepped at once.
+        let around: [SquareSet; 64] =
+            King::move_steps_batch(std::array::from_fn(|i| SquareSet(1 << i)));
+        for _ in 0..CAVERN_STEPS {
+            walls = self
+                .board()
+                .filter(|&sq| {
+                    let block = (around[sq.index() as usize] | sq.into()) & board;
+                    2 * (block & walls).count() > block.count()
+                })
+                .collect();
+        }
+        walls
+    }
+
+    /// Takes groups from the front of `orbits` to fill about `obstacle_density` of `spare`
+    /// squares with obstacles.
+    fn take_obstacles(&self, orbits: &mut Vec<Vec<Square>>, spare: usize) -> Vec<Square> {
+        let num_obstacles = (spare as f64 * self.obstacle_density).round() as usize;
+        let mut res = Vec::new();
+        let mut taken = 0;
+        while res.len() < num_obstacles && taken < orbits.len() {
+            res.extend_from_slice(&orbits[taken]);
+            taken += 1;
+        }
+        orbits.drain(..taken);
+        res
+    }
+
+    /// Every square of the board, in random order.
+    fn shuffled_squares(&self, rng: &mut SplitMix64) -> Vec<Square> {
+        let mut squares: Vec<Square> = self.board().collect();
+        shuffle(&mut squares, rng);
+        squares
+    }
+
+    /// Every square of the board, in order.
+    fn board(&self) -> impl Iterator<Item = Square> {
+        let width = self.width;
+        (0..self.height).flat_map(move |rank| {
+            (0..width).filter_map(move |file| Square::from_file_rank(file, rank))
+        })
+    }
+}
+
+/// A generated puzzle, along with the seed it was laid out from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generated {
+    pub puzzle: Puzzle,
+    /// Gives back this puzzle when passed to the same generator method, with the same settings.
+    pub seed: u64,
+}
+
+/// The types that the player starts as in a `Generator::pack`, after the generator's own.
+const PACK_PLAYERS: [PieceType; 5] = [
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+/// How many candidates `Generator::pack` picks each level from.
+const PACK_CHOICES: usize = 8;
+
+/// The percentage of squares that `Layout::Caverns` starts by filling.
+const CAVERN_FILL_PERCENT: u64 = 50;
+
+/// How many times `Layout::Caverns` smooths out its obstacles.
+const CAVERN_STEPS: usize = 3;
+
+/// The types that `mutate` can change a piece to.
+const REMIX_TYPES: [PieceType; 5] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+/// Changes one thing about `base`, at random, as `Generator::remix` describes. Returns `None`
+/// if the change picked isn't possible on this board.
+fn mutate(base: &Puzzle, rng: &mut SplitMix64) -> Option<Puzzle> {
+    let mut obstacles = base.obstacles & base.squares();
+    let mut pieces = [None; 64];
+    for i in 0..base.num_pieces() {
+        pieces[base.piece_loc(i).index() as usize] = base.piece_type(i);
+    }
+    // The squares free of pieces and anything special, with or without obstacles.
+    let plain: Vec<Square> = base
+        .squares()
+        .iter()
+        .filter(|&sq| {
+            base.piece_on(sq).is_none()
+                && base.exit != Some(sq)
+                && !base.portals.iter().any(|&(a, b)| a == sq || b == sq)
+                && !base.ice.contains(sq)
+                && !base.walls.contains(&sq)
+                && !base.one_way.iter().any(|squares| squares.contains(sq))
+                && !base.terrain.iter().any(|&(t, _)| t == sq)
+        })
+        .collect();
+    let movable: Vec<Square> = (0..base.num_pieces())
+        .filter(|i| !base.starts.contains(i) && !base.patrols.iter().any(|(j, _)| j == i))
+        .map(|i| base.piece_loc(i))
+        .collect();
+    let mut moved = None;
+    match rng.below(3) {
+        0 => {
+            let from = *pick(&movable, rng)?;
+            let empty: Vec<Square> = plain
+                .into_iter()
+                .filter(|&sq| !obstacles.contains(sq))
+                .collect();
+            let to = *pick(&empty, rng)?;
+            pieces[to.index() as usize] = pieces[from.index() as usize].take();
+            moved = Some((from, to));
+        }
+        1 => {
+            let sq = *pick(&movable, rng)?;
+            let slot = &mut pieces[sq.index() as usize];
+            let others: Vec<PieceType> = REMIX_TYPES
+                .into_iter()
+                .filter(|&ty| Some(ty) != *slot)
+                .collect();
+            *slot = Some(*pick(&others, rng)?);
+        }
+        _ => {
+            let sq = *pick(&plain, rng)?;
+            if obstacles.contains(sq) {
+                obstacles = obstacles & !SquareSet::from(sq);
+            } else {
+                obstacles.insert(sq);
+            }
+        }
+    }
+    base.with_board(obstacles, &pieces, moved)
+}
+
+/// One of `items`, at random, or `None` if there are none.
+fn pick<'a, T>(items: &'a [T], rng: &mut SplitMix64) -> Option<&'a T> {
+    items.get(rng.below(items.len() as u64) as usize)
+}
+
+/// Shuffles `items` in place, by Fisher–Yates.
+fn shuffle<T>(items: &mut [T], rng: &mut SplitMix64) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i as u64 + 1) as usize);
+    }
+}
+
+/// A small, fast pseudorandom number generator, good enough for laying out boards. See
+/// <https://prng.di.unimi.it/splitmix64.c>.
+struct SplitMix64(u64);
+
+/// How far `SplitMix64` steps its state for each number.
+const GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(GAMMA);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`, very nearly uniformly.
+    fn below(&mut self, n: u64) -> u64 {
+        ((u128::from(self.next()) * u128::from(n)) >> 64) as u64
+    }
+}
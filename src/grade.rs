@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use crate::game::Game;
+use crate::{solve_min_moves, Puzzle, Square};
+
+/// Why an attempted solution is invalid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AttemptError {
+    /// The player can't capture anything on that square from where it stands, or it's the last
+    /// capture and leaves no way to the exit.
+    IllegalCapture,
+    /// A patroller stepped onto the player right after that capture.
+    CaughtByPatroller,
+    /// The attempt ended with pieces still left to capture.
+    Unfinished,
+}
+
+/// Replays an attempted solution, given as the squares captured in order, and
+/// returns its total move count. On failure, returns the index of the offending step (or
+/// `attempt.len()` if the attempt is merely unfinished) and what went wrong.
+pub fn verify_attempt(p: &Puzzle, attempt: &[Square]) -> Result<u32, (usize, AttemptError)> {
+    let mut game = Game::new(p.clone());
+    let mut total_moves = 0;
+    for (step, &loc) in attempt.iter().enumerate() {
+        total_moves += game.apply(loc).map_err(|e| (step, e))?;
+    }
+    if !game.is_won() {
+        return Err((attempt.len(), AttemptError::Unfinished));
+    }
+    Ok(total_moves)
+}
+
+/// `p` with every step costing one move, so that moves count the squares stepped onto, or `None`
+/// if that's already so. See `Puzzle::terrain`.
+fn flat(p: &Puzzle) -> Option<Puzzle> {
+    if p.terrain().is_empty() {
+        return None;
+    }
+    let mut flat = p.clone();
+    for &(loc, _) in p.terrain() {
+        flat.set_move_cost(loc, 1);
+    }
+    Some(flat)
+}
+
+/// The result of grading an attempted solution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Grade {
+    /// The attempt doesn't solve the puzzle: `step` (from 0) is where it goes wrong.
+    Invalid { step: usize, error: AttemptError },
+    /// The attempt solves the puzzle in `moves` moves, where `optimal` is the fewest possible,
+    /// stepping onto `distance` squares, where `optimal_distance` is the fewest possible. Each
+    /// measure takes the best way to make the attempt's captures, so the two differ only on
+    /// puzzles with `Puzzle::terrain`, where a step can cost several moves. The letter is the
+    /// worse of the two measures' letters.
+    Valid {
+        moves: u32,
+        optimal: u32,
+        distance: u32,
+        optimal_distance: u32,
+        letter: char,
+    },
+}
+
+/// Maps an attempt's move count to a letter grade by how far it is over the optimum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GradeScale {
+    /// `(letter, percent)` pairs, best first. An attempt gets the letter of the first band whose
+    /// `percent` is at least the attempt's excess over optimal, as a percentage of optimal.
+    pub bands: Vec<(char, u32)>,
+    /// The letter for attempts that fall outside every band.
+    pub fallback: char,
+}
+
+impl Default for GradeScale {
+    fn default() -> Self {
+        GradeScale {
+            bands: vec![('S', 0), ('A', 10), ('B', 25), ('C', 50)],
+            fallback: 'D',
+        }
+    }
+}
+
+impl GradeScale {
+    pub fn letter(&self, moves: u32, optimal: u32) -> char {
+        self.bands
+            .get(self.band(moves, optimal))
+            .map_or(self.fallback, |&(letter, _)| letter)
+    }
+
+    /// The index of the band that `cost` falls into, or `bands.len()` for the fallback.
+    fn band(&self, cost: u32, optimal: u32) -> usize {
+        let (cost, optimal) = (u64::from(cost), u64::from(optimal));
+        self.bands
+            .iter()
+            .position(|&(_, percent)| 100 * cost <= optimal * (100 + u64::from(percent)))
+            .unwrap_or(self.bands.len())
+    }
+}
+
+/// The fewest moves and the fewest squares stepped onto that solve a puzzle.
+#[derive(Debug, Copy, Clone)]
+struct Optimum {
+    moves: u32,
+    distance: u32,
+}
+
+impl Optimum {
+    fn new(p: &Puzzle) -> Option<Optimum> {
+        let moves = solve_min_moves(p)?.1;
+        let distance = match flat(p) {
+            Some(flat) => solve_min_moves(&flat)?.1,
+            None => moves,
+        };
+        Some(Optimum { moves, distance })
+    }
+}
+
+/// Grades attempted solutions, remembering each puzzle's optimum so that grading many attempts
+/// at the same puzzle only solves it once.
+#[derive(Debug, Default)]
+pub struct Grader {
+    pub scale: GradeScale,
+    optimal: HashMap<Puzzle, Optimum>,
+}
+
+impl Grader {
+    pub fn grade(&mut self, p: &Puzzle, attempt: &[Square]) -> Grade {
+        let moves = match verify_attempt(p, attempt) {
+            Ok(moves) => moves,
+            Err((step, error)) => return Grade::Invalid { step, error },
+        };
+        // Lower costs only make more captures affordable, so a valid attempt stays valid.
+        let distance = match flat(p) {
+            Some(flat) => verify_attempt(&flat, attempt).expect("the attempt is valid"),
+            None => moves,
+        };
+        let optimum = match self.optimal.get(p) {
+            Some(&optimum) => optimum,
+            None => {
+                // A valid attempt is a solution, so the puzzle must be solvable.
+                let optimum = Optimum::new(p).expect("puzzle has a solution");
+                self.optimal.insert(p.clone(), optimum);
+                optimum
+            }
+        };
+        let band = self
+            .scale
+            .band(moves, optimum.moves)
+            .max(self.scale.band(distance, optimum.distance));
+        Grade::Valid {
+            moves,
+            optimal: optimum.moves,
+            distance,
+            optimal_distance: optimum.distance,
+            letter: self
+                .scale
+                .bands
+                .get(band)
+                .map_or(self.scale.fallback, |&(letter, _)| letter),
+        }
+    }
+}
+
+/// Grades an attempted solution on the default scale. See `Grader`.
+pub fn grade(p: &Puzzle, attempt: &[Square]) -> Grade {
+    Grader::default().grade(p, attempt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pack::parse_pack;
+
+    fn squares(names: &[&str]) -> Vec<Square> {
+        names.iter().map(|name| name.parse().unwrap()).collect()
+    }
+
+    /// Three knights in the corners, which a rook can take in 6, 8, or 10 moves.
+    fn corners() -> Puzzle {
+        Puzzle::parse("R1n/3/n1n").unwrap()
+    }
+
+    fn letter(grade: Grade) -> char {
+        match grade {
+            Grade::Valid { letter, .. } => letter,
+            Grade::Invalid { .. } => panic!("invalid: {:?}", grade),
+        }
+    }
+
+    #[test]
+    fn optimal_attempt_grades_s() {
+        assert_eq!(
+            grade(&corners(), &squares(&["c3", "c1", "a1"])),
+            Grade::Valid {
+                moves: 6,
+                optimal: 6,
+                distance: 6,
+                optimal_distance: 6,
+                letter: 'S',
+            }
+        );
+    }
+
+    #[test]
+    fn wasteful_attempts_grade_lower() {
+        let mut grader = Grader::default();
+        let p = corners();
+        assert_eq!(letter(grader.grade(&p, &squares(&["a1", "c3", "c1"]))), 'C');
+        assert_eq!(letter(grader.grade(&p, &squares(&["c1", "a1", "c3"]))), 'D');
+        let strict = GradeScale {
+            bands: vec![('S', 0)],
+            fallback: 'F',
+        };
+        assert_eq!(strict.letter(8, 6), 'F');
+    }
+
+    #[test]
+    fn illegal_attempts_report_the_failing_step() {
+        let p = corners();
+        assert_eq!(
+            grade(&p, &squares(&["a1", "b2"])),
+            Grade::Invalid {
+                step: 1,
+                error: AttemptError::IllegalCapture,
+            }
+        );
+        assert_eq!(
+            grade(&p, &squares(&["a1", "c1"])),
+            Grade::Invalid {
+                step: 2,
+                error: AttemptError::Unfinished,
+            }
+        );
+    }
+
+    #[test]
+    fn distance_counts_squares_over_terrain() {
+        // Going straight to c3 over the costly b3 is the shortest way, but going around is
+        // cheaper, and the attempt gets the best of each.
+        let (_, p) = parse_pack("; terrain: 151/111/111\nR1n/3/3\n").remove(0);
+        assert_eq!(
+            grade(&p, &squares(&["c3"])),
+            Grade::Valid {
+                moves: 4,
+                optimal: 4,
+                distance: 2,
+                optimal_distance: 2,
+                letter: 'S',
+            }
+        );
+    }
+
+    #[test]
+    fn grader_keeps_each_puzzle_apart() {
+        let mut grader = Grader::default();
+        let queen = Puzzle::parse("Q1n/3/n1n").unwrap();
+        let attempt = squares(&["c3", "c1", "a1"]);
+        for _ in 0..2 {
+            assert!(matches!(
+                grader.grade(&corners(), &attempt),
+                Grade::Valid { optimal: 6, .. }
+            ));
+            assert!(matches!(
+                grader.grade(&queen, &attempt),
+                Grade::Valid { optimal: 5, .. }
+            ));
+        }
+        assert_eq!(grader.optimal.len(), 2);
+    }
+}
@@ -0,0 +1,87 @@
+//! Movement over ice, where a piece can't stop: it keeps going the way it came until something
+//! blocks it or it's off the ice. See `Puzzle::ice`.
+//!
+//! Where a slide ends depends on which way the piece was going, not just on where it is, so the
+//! bitboard fills that move every square of a set at once don't apply. Instead, each step is taken
+//! from one square at a time, and each landing on ice is followed to its end. Between moves, the
+//! piece is at rest, so the searches themselves still only need to know its square.
+
+use crate::{oneway, PieceType, Puzzle, Square, SquareSet};
+
+/// Greatest common divisor, for reducing a step to its direction.
+fn gcd(a: i8, b: i8) -> i8 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// The direction of a step from `from` to `to`, one square at a time along its line: a rook's
+/// step is its own direction, a grasshopper's hop over three squares goes one at a time, and a
+/// knight, whose leap is no line, keeps leaping.
+fn direction(from: Square, to: Square) -> (i8, i8) {
+    let df = to.file() as i8 - from.file() as i8;
+    let dr = to.rank() as i8 - from.rank() as i8;
+    let g = gcd(df, dr);
+    (df / g, dr / g)
+}
+
+/// Where a piece that stepped from `from` onto `to` comes to rest: `to` itself if that isn't
+/// ice, and otherwise the last square down the line that it can slide to, through `permeable`
+/// and past any one-way squares facing against it.
+fn slide(p: &Puzzle, permeable: SquareSet, from: Square, to: Square) -> Square {
+    let (df, dr) = direction(from, to);
+    let permeable = oneway::open(p, permeable, (df.signum(), dr.signum()));
+    let mut cur = to;
+    while p.ice.contains(cur) {
+        let next =
+            Square::from_file_rank((cur.file() as i8 + df) as u8, (cur.rank() as i8 + dr) as u8);
+        match next {
+            Some(next) if permeable.contains(next) => cur = next,
+            _ => break,
+        }
+    }
+    cur
+}
+
+/// Like `Puzzle::step_reach`, but a step onto ice slides on to wherever it stops.
+pub(crate) fn move_reach(
+    p: &Puzzle,
+    ty: PieceType,
+    from: SquareSet,
+    permeable: SquareSet,
+) -> SquareSet {
+    let mut res = SquareSet(0);
+    for sq in from {
+        for to in p.step_reach(ty, sq.into(), permeable) {
+            res.insert(slide(p, permeable, sq, to));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::assert_strategies_agree;
+    use crate::{solve, Puzzle};
+
+    /// Without ice, the rook takes the bishop and then, as a bishop, takes the knight from b2.
+    /// On ice, the bishop can't stop on b2, and slides on to c1, so the knight has to go first.
+    #[test]
+    fn strategies_agree_with_breadth_first() {
+        assert_eq!(solve(&Puzzle::parse("b1n/3/R2").unwrap()), Some(vec![1, 2]));
+        assert_eq!(
+            solve(&Puzzle::parse("b~n/~~~/R~~").unwrap()),
+            Some(vec![2, 1])
+        );
+        for fen in [
+            "b~n/~~~/R~~",
+            "K~~~~~~n",
+            "n~~/~~b/N~~",
+            "n~~~/~~~~/~~b~/R~~~",
+        ] {
+            assert_strategies_agree(&Puzzle::parse(fen).unwrap());
+        }
+    }
+}
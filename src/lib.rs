@@ -0,0 +1,5858 @@
+//! A solver for Echo Chess puzzles.
+//!
+//! In Echo Chess, the player controls a single piece on a board with obstacles. Each move, the
+//! player's piece walks any number of steps and then captures an opposing piece, turning into
+//! that piece. The player wins by capturing every piece.
+//!
+//! Parse a puzzle with `Puzzle::from_compound_fen` (or a whole file with `pack::parse_pack`), then
+//! call `solve` to get a winning order of captures. Lower-level building blocks, like
+//! `PuzzleState::next_states` and the `Stepper` movement model, are public too.
+
+use std::collections::{HashMap, HashSet};
+
+pub mod analyze;
+pub mod blocks;
+pub mod campaign;
+mod checkpoint;
+#[cfg(feature = "parallel")]
+mod concurrent;
+pub mod coop;
+pub mod corpus;
+pub mod custom;
+pub mod describe;
+pub mod explain;
+mod external;
+pub mod game;
+pub mod generate;
+pub mod grade;
+mod ice;
+mod magic;
+pub mod notation;
+mod oneway;
+pub mod pack;
+pub mod pgn;
+pub mod protocol;
+#[cfg(feature = "png")]
+pub mod raster;
+pub mod render;
+pub mod report;
+#[cfg(feature = "serde")]
+mod ser;
+pub mod svg;
+mod terrain;
+pub mod turns;
+pub mod wide;
+
+/// Pawn movement: one step toward rank 8, capturing diagonally. See `Stepper`.
+pub struct Pawn;
+/// Pawn movement the other way: one step toward rank 1, capturing diagonally. See `Stepper` and
+/// `Rules::pawns_down`.
+pub struct PawnDown;
+/// Bishop movement: diagonal steps. See `Stepper`.
+pub struct Bishop;
+/// Rook movement: orthogonal steps. See `Stepper`.
+pub struct Rook;
+/// King movement: steps in all eight directions. See `Stepper`.
+pub struct King;
+/// Queen movement: steps in all eight directions, like a king, but also captures from the far
+/// end of a clear line. See `Stepper::capture_reach`.
+pub struct Queen;
+/// Kings and queens used to share this one type, since they reach the same squares.
+#[deprecated(note = "use `King` or `Queen`")]
+pub type Monarch = King;
+/// Knight movement: L-shaped leaps. See `Stepper`.
+pub struct Knight;
+/// Nightrider movement: knight leaps, but also captures from the far end of a line of leaps in
+/// the same direction, as a queen does down a line of steps. See `Stepper::capture_reach`.
+pub struct Nightrider;
+/// Archbishop movement: a bishop's steps and a knight's leaps. See `Stepper`.
+///
+/// Leaps change the color of the square, so unlike a bishop, an archbishop can reach the whole
+/// board:
+///
+/// ```
+/// use echochess::{Archbishop, Bishop, Square, SquareSet, Stepper};
+/// let a1: Square = "a1".parse().unwrap();
+/// let everywhere = SquareSet(!0);
+/// assert_eq!(Bishop::fill(a1.into(), everywhere).count(), 32);
+/// assert_eq!(Archbishop::fill(a1.into(), everywhere), everywhere);
+/// ```
+pub struct Archbishop;
+/// Chancellor movement: a rook's steps and a knight's leaps. See `Stepper`.
+///
+/// A chancellor can leap over whatever boxes in a rook:
+///
+/// ```
+/// use echochess::{Chancellor, Rook, Square, SquareSet, Stepper};
+/// let [a1, a2, b1]: [Square; 3] = ["a1", "a2", "b1"].map(|s| s.parse().unwrap());
+/// let open = !(SquareSet::from(a2) | SquareSet::from(b1));
+/// assert_eq!(Rook::fill(a1.into(), open), a1.into());
+/// assert_eq!(Chancellor::fill(a1.into(), open), open);
+/// ```
+pub struct Chancellor;
+/// Amazon movement: a queen's steps and captures and a knight's leaps. See `Stepper`.
+///
+/// ```
+/// use echochess::{Amazon, Queen, Square, SquareSet, Stepper};
+/// let [a1, a2, b1, b2]: [Square; 4] = ["a1", "a2", "b1", "b2"].map(|s| s.parse().unwrap());
+/// let open = !(SquareSet::from(a2) | SquareSet::from(b1) | SquareSet::from(b2));
+/// assert_eq!(Queen::fill(a1.into(), open), a1.into());
+/// assert_eq!(Amazon::fill(a1.into(), open), open);
+/// ```
+pub struct Amazon;
+/// Grasshopper movement: along queen lines, but only by hopping over the first piece or obstacle
+/// in the way, landing just beyond it. See `Stepper::move_reach`.
+///
+/// ```
+/// use echochess::{Grasshopper, Square, SquareSet, Stepper};
+/// let [a1, a5, a6]: [Square; 3] = ["a1", "a5", "a6"].map(|s| s.parse().unwrap());
+/// let open = !SquareSet::from(a5);
+/// assert_eq!(Grasshopper::move_reach(a1.into(), open), a6.into());
+/// ```
+pub struct Grasshopper;
+/// Cannon movement, as in xiangqi: orthogonal steps, but captures only by jumping along a line
+/// over exactly one piece or obstacle, the screen. See `Stepper::capture_reach`.
+///
+/// ```
+/// use echochess::{Cannon, Square, SquareSet, Stepper};
+/// let [a1, a4, a6, a7]: [Square; 4] = ["a1", "a4", "a6", "a7"].map(|s| s.parse().unwrap());
+/// let open = !(SquareSet::from(a4) | SquareSet::from(a6) | SquareSet::from(a7));
+/// assert_eq!(Cannon::capture_reach(a1.into(), open), a6.into());
+/// ```
+pub struct Cannon;
+
+/// A subset of the squares on a chess board.
+///
+/// Square in file `x` and rank `y` is indicated by bit `8 * y + x`. For instance, B1 is bit `1`
+/// and A2 is bit `8`.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "Vec<Square>", from = "Vec<Square>"))]
+pub struct SquareSet(pub u64);
+
+// Overloads for basic arithmetic on `SquareSet`s.
+impl std::ops::BitAnd<SquareSet> for SquareSet {
+    type Output = SquareSet;
+    fn bitand(self, rhs: SquareSet) -> SquareSet {
+        SquareSet(self.0 & rhs.0)
+    }
+}
+impl std::ops::BitOr<SquareSet> for SquareSet {
+    type Output = SquareSet;
+    fn bitor(self, rhs: SquareSet) -> SquareSet {
+        SquareSet(self.0 | rhs.0)
+    }
+}
+impl std::ops::Not for SquareSet {
+    type Output = SquareSet;
+    fn not(self) -> SquareSet {
+        SquareSet(!self.0)
+    }
+}
+impl std::ops::Shl<u32> for SquareSet {
+    type Output = SquareSet;
+    fn shl(self, rhs: u32) -> SquareSet {
+        SquareSet(self.0 << rhs)
+    }
+}
+impl std::ops::Shr<u32> for SquareSet {
+    type Output = SquareSet;
+    fn shr(self, rhs: u32) -> SquareSet {
+        SquareSet(self.0 >> rhs)
+    }
+}
+
+/// One square on a chess board: file `x` (`0..8`, for A through H) and rank `y` (`0..8`, for 1
+/// through 8) is `Square(8 * y + x)`, matching the bit layout of `SquareSet`.
+///
+/// Squares parse from and display as algebraic notation, like `e4`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "String", try_from = "String"))]
+pub struct Square(u8);
+
+impl Square {
+    /// The square with the given index (`0..64`), or `None` if it's out of range.
+    pub fn new(index: u8) -> Option<Square> {
+        (index < 64).then_some(Square(index))
+    }
+
+    /// The square on the given file (`0..8`) and rank (`0..8`), or `None` if either is out of
+    /// range.
+    pub fn from_file_rank(file: u8, rank: u8) -> Option<Square> {
+        (file < 8 && rank < 8).then(|| Square(8 * rank + file))
+    }
+
+    /// This square's index (`0..64`), which is also its bit in a `SquareSet`.
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    /// This square's file, from `0` (the A-file) to `7` (the H-file).
+    pub fn file(self) -> u8 {
+        self.0 % 8
+    }
+
+    /// This square's rank, from `0` (rank 1) to `7` (rank 8).
+    pub fn rank(self) -> u8 {
+        self.0 / 8
+    }
+}
+
+impl From<Square> for SquareSet {
+    fn from(sq: Square) -> SquareSet {
+        SquareSet(1 << sq.0)
+    }
+}
+
+impl TryFrom<SquareSet> for Square {
+    type Error = SquareSet;
+    /// Converts a set with exactly one square to that square, or gives back any other set.
+    fn try_from(set: SquareSet) -> Result<Square, SquareSet> {
+        match set.count() {
+            1 => Ok(set.iter().next().unwrap()),
+            _ => Err(set),
+        }
+    }
+}
+
+impl std::fmt::Display for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", char::from(b'a' + self.file()), self.rank() + 1)
+    }
+}
+
+/// Why a string isn't an algebraic square name like `e4`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSquareError(pub String);
+
+impl std::fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Bad square: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl std::str::FromStr for Square {
+    type Err = ParseSquareError;
+    fn from_str(name: &str) -> Result<Square, ParseSquareError> {
+        match name.as_bytes() {
+            &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Ok(Square(8 * (rank - b'1') + (file - b'a')))
+            }
+            _ => Err(ParseSquareError(name.to_string())),
+        }
+    }
+}
+
+impl SquareSet {
+    /// Whether this set has no squares.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// How many squares are in this set?
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub fn contains(self, sq: Square) -> bool {
+        self.0 & (1 << sq.0) != 0
+    }
+
+    /// Adds a square to this set, returning whether it was newly added.
+    pub fn insert(&mut self, sq: Square) -> bool {
+        let added = !self.contains(sq);
+        self.0 |= 1 << sq.0;
+        added
+    }
+
+    /// Iterates over the squares in this set, in increasing order of index.
+    pub fn iter(self) -> SquareSetIter {
+        SquareSetIter(self.0)
+    }
+}
+
+/// Iterator over the squares in a `SquareSet`. See `SquareSet::iter`.
+#[derive(Debug, Clone)]
+pub struct SquareSetIter(u64);
+
+impl Iterator for SquareSetIter {
+    type Item = Square;
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+        let sq = Square(self.0.trailing_zeros() as u8);
+        self.0 &= self.0 - 1;
+        Some(sq)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.0.count_ones() as usize;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for SquareSetIter {}
+
+impl IntoIterator for SquareSet {
+    type Item = Square;
+    type IntoIter = SquareSetIter;
+    fn into_iter(self) -> SquareSetIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<Square> for SquareSet {
+    fn from_iter<I: IntoIterator<Item = Square>>(iter: I) -> SquareSet {
+        let mut res = SquareSet(0);
+        for sq in iter {
+            res.insert(sq);
+        }
+        res
+    }
+}
+
+/// A piece type that moves by zero or more "move steps" followed by exactly one "capture step".
+/// This precisely describes the behavior of every chess piece when the piece is allowed to move an
+/// unbounded number of times and then must capture, as in Echo Chess.
+///
+/// (For pieces other than pawns, `move_steps` and `capture_steps` are the same.)
+///
+/// Some pieces' steps depend on what else is on the board. Those override `move_reach` and
+/// `capture_reach`, which see the empty squares, and the solver only goes through those two.
+pub trait Stepper {
+    /// Does this piece need something to hop over? If so, clearing pieces off the board can
+    /// strand it rather than free it, so the solver can't assume that an emptier board is easier.
+    const HOPS: bool = false;
+
+    /// If a piece is on one of the given squares, which squares can it move to in one step?
+    fn move_steps(from: SquareSet) -> SquareSet;
+    /// If a piece is on one of the given squares, which squares can it capture in one step?
+    fn capture_steps(from: SquareSet) -> SquareSet;
+
+    /// If a piece is on one of the squares in `from`, which empty squares can it move to in one
+    /// step, where the squares in `permeable` are the empty ones? For most pieces, that's just
+    /// the `move_steps` that are empty, but a grasshopper needs something to hop over.
+    fn move_reach(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        Self::move_steps(from) & permeable
+    }
+
+    /// If a piece is on one of the squares in `from`, which squares can it reach in any number of
+    /// steps, moving only onto squares in `permeable`? The default takes one step at a time;
+    /// sliding pieces override this to cover whole lines at once.
+    fn fill(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        let mut reachable = from & permeable;
+        loop {
+            let next = (reachable | Self::move_reach(reachable, permeable)) & permeable;
+            if next == reachable {
+                return reachable;
+            }
+            reachable = next;
+        }
+    }
+
+    /// If a piece is on one of the squares in `from`, which squares can it capture in one move,
+    /// where the squares in `permeable` are the empty ones? For most pieces, that's just
+    /// `capture_steps`, but a queen can capture from the far end of a clear line, a grasshopper
+    /// can only capture by hopping, and a cannon only by jumping a screen.
+    fn capture_reach(from: SquareSet, _permeable: SquareSet) -> SquareSet {
+        Self::capture_steps(from)
+    }
+
+    /// `move_steps` as a list of shifts, if that's all it is, as it is for every built-in piece.
+    /// `move_steps_batch` uses these to step several sets at once.
+    const MOVE_SHIFTS: Option<&'static [Shift]> = None;
+    /// `capture_steps` as a list of shifts, like `MOVE_SHIFTS`.
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = None;
+
+    /// Like `move_steps`, for many sets at once. See `batch`.
+    ///
+    /// ```
+    /// use echochess::{King, SquareSet, Stepper};
+    /// let sets = [SquareSet(1), SquareSet(1 << 27), SquareSet(1 << 63), SquareSet(0)];
+    /// assert_eq!(King::move_steps_batch(sets), sets.map(King::move_steps));
+    /// ```
+    fn move_steps_batch<const N: usize>(from: [SquareSet; N]) -> [SquareSet; N] {
+        match Self::MOVE_SHIFTS {
+            Some(shifts) => batch(from, shifts),
+            None => from.map(Self::move_steps),
+        }
+    }
+
+    /// Like `capture_steps`, for many sets at once. See `batch`.
+    fn capture_steps_batch<const N: usize>(from: [SquareSet; N]) -> [SquareSet; N] {
+        match Self::CAPTURE_SHIFTS {
+            Some(shifts) => batch(from, shifts),
+            None => from.map(Self::capture_steps),
+        }
+    }
+}
+
+/// One way to step that's just a shift: a piece on one of the squares in `from` moves `by`
+/// squares toward higher indices, or toward lower ones if `by` is negative. `from` leaves out the
+/// squares where the step would wrap around the edge of the board.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Shift {
+    pub from: SquareSet,
+    pub by: i8,
+}
+
+impl Shift {
+    /// Where pieces on `set` end up after this step.
+    pub fn apply(self, set: SquareSet) -> SquareSet {
+        let moving = set & self.from;
+        if self.by >= 0 {
+            moving << self.by as u32
+        } else {
+            moving >> self.by.unsigned_abs() as u32
+        }
+    }
+}
+
+/// Where pieces on `set` end up after any one of `shifts`.
+#[inline(always)]
+fn shift_all(set: SquareSet, shifts: &[Shift]) -> SquareSet {
+    shifts
+        .iter()
+        .fold(SquareSet(0), |res, shift| res | shift.apply(set))
+}
+
+/// Steps each of `sets` by `shifts`. With the `simd` feature on x86-64, this uses AVX2 if the CPU
+/// has it, shifting and masking four sets per instruction; otherwise, it's one set at a time.
+fn batch<const N: usize>(sets: [SquareSet; N], shifts: &[Shift]) -> [SquareSet; N] {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // SAFETY: the CPU supports AVX2, as just checked.
+        return unsafe { batch_avx2(sets, shifts) };
+    }
+    sets.map(|set| shift_all(set, shifts))
+}
+
+/// `batch`, four sets to a 256-bit register. Any sets left over past a multiple of four are
+/// stepped one at a time.
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn batch_avx2<const N: usize>(sets: [SquareSet; N], shifts: &[Shift]) -> [SquareSet; N] {
+    use std::arch::x86_64::*;
+
+    let mut res = sets;
+    let mut chunks = res.chunks_exact_mut(4);
+    for chunk in &mut chunks {
+        let lanes = [chunk[0].0, chunk[1].0, chunk[2].0, chunk[3].0];
+        let from = _mm256_loadu_si256(lanes.as_ptr().cast());
+        let mut to = _mm256_setzero_si256();
+        for shift in shifts {
+            let moving = _mm256_and_si256(from, _mm256_set1_epi64x(shift.from.0 as i64));
+            let by = _mm256_set1_epi64x(shift.by.unsigned_abs() as i64);
+            let moved = if shift.by >= 0 {
+                _mm256_sllv_epi64(moving, by)
+            } else {
+                _mm256_srlv_epi64(moving, by)
+            };
+            to = _mm256_or_si256(to, moved);
+        }
+        let mut lanes = [0u64; 4];
+        _mm256_storeu_si256(lanes.as_mut_ptr().cast(), to);
+        for (set, bits) in chunk.iter_mut().zip(lanes) {
+            *set = SquareSet(bits);
+        }
+    }
+    for set in chunks.into_remainder() {
+        *set = shift_all(*set, shifts);
+    }
+    res
+}
+
+/// Given that a piece of type `S` is on one of the squares in `from`, and may not move onto or
+/// through the squares in `obstacles`, which of the `targets` can it capture?
+pub fn captures<S: Stepper>(
+    from: SquareSet,
+    obstacles: SquareSet,
+    targets: SquareSet,
+) -> SquareSet {
+    let permeable = !(obstacles | targets);
+    let reachable = S::fill(from, permeable);
+    if S::HOPS {
+        S::capture_reach(reachable, permeable) & targets
+    } else {
+        S::capture_steps(reachable) & targets
+    }
+}
+
+/// Like `captures`, but also counts moves: calls `consume(loc, moves)` for each capturable target
+/// square `loc`, where `moves` is the fewest steps, including the final capture step,
+/// that it takes to capture there.
+pub fn capture_moves<S: Stepper, F: FnMut(Square, u32)>(
+    from: SquareSet,
+    obstacles: SquareSet,
+    targets: SquareSet,
+    consume: F,
+) {
+    capture_moves_with(
+        from,
+        obstacles,
+        targets,
+        consume,
+        S::move_reach,
+        S::capture_reach,
+    )
+}
+
+/// `capture_moves`, for a piece whose `move_reach` and `capture_reach` are given at runtime.
+fn capture_moves_with<F: FnMut(Square, u32)>(
+    from: SquareSet,
+    obstacles: SquareSet,
+    targets: SquareSet,
+    mut consume: F,
+    move_reach: impl Fn(SquareSet, SquareSet) -> SquareSet,
+    capture_reach: impl Fn(SquareSet, SquareSet) -> SquareSet,
+) {
+    let permeable = !(obstacles | targets);
+    let mut reachable = from & permeable;
+    let mut found = SquareSet(0);
+    let mut moves = 1;
+    loop {
+        let capturable = capture_reach(reachable, permeable) & targets;
+        for sq in capturable & !found {
+            consume(sq, moves);
+        }
+        found = found | capturable;
+        let next = (reachable | move_reach(reachable, permeable)) & permeable;
+        if next == reachable {
+            break;
+        }
+        reachable = next;
+        moves += 1;
+    }
+}
+
+/// Finds one shortest way for a piece at `from` to capture on `to`: the squares that it steps
+/// onto, in order, ending with `to` itself. (A queen's capture from down a line is one step.)
+/// Returns `None` if `to` isn't a capturable target.
+pub fn capture_path<S: Stepper>(
+    from: Square,
+    obstacles: SquareSet,
+    targets: SquareSet,
+    to: Square,
+) -> Option<Vec<Square>> {
+    capture_path_with(
+        from,
+        obstacles,
+        targets,
+        to,
+        S::move_reach,
+        S::capture_reach,
+    )
+}
+
+/// `capture_path`, for a piece whose `move_reach` and `capture_reach` are given at runtime.
+fn capture_path_with(
+    from: Square,
+    obstacles: SquareSet,
+    targets: SquareSet,
+    to: Square,
+    move_reach: impl Fn(SquareSet, SquareSet) -> SquareSet,
+    capture_reach: impl Fn(SquareSet, SquareSet) -> SquareSet,
+) -> Option<Vec<Square>> {
+    let permeable = !(obstacles | targets);
+    let to_set = SquareSet::from(to);
+    if (targets & to_set).is_empty() {
+        return None;
+    }
+    // `layers[k]` is every square reachable in at most `k` non-capturing steps.
+    let mut layers = vec![SquareSet::from(from) & permeable];
+    loop {
+        let reachable = *layers.last().unwrap();
+        if !(capture_reach(reachable, permeable) & to_set).is_empty() {
+            break;
+        }
+        let next = (reachable | move_reach(reachable, permeable)) & permeable;
+        if next == reachable {
+            return None;
+        }
+        layers.push(next);
+    }
+    let mut path = vec![to];
+    let mut current = layers
+        .last()
+        .unwrap()
+        .iter()
+        .find(|&sq| capture_reach(sq.into(), permeable).contains(to))
+        .unwrap();
+    // Walk back one layer at a time. Since the capture wasn't possible any sooner, `current` is
+    // never in an earlier layer than the one it's found from.
+    for reachable in layers.iter().rev().skip(1) {
+        path.push(current);
+        current = reachable
+            .iter()
+            .find(|&sq| move_reach(sq.into(), permeable).contains(current))
+            .unwrap();
+    }
+    path.reverse();
+    Some(path)
+}
+
+mod can_move {
+    use super::SquareSet;
+
+    pub(crate) const LEFT: SquareSet = SquareSet(!0x0101010101010101);
+    pub(crate) const RIGHT: SquareSet = SquareSet(!0x8080808080808080);
+    pub(crate) const TWO_LEFT: SquareSet = SquareSet(!0x0303030303030303);
+    pub(crate) const TWO_RIGHT: SquareSet = SquareSet(!0xc0c0c0c0c0c0c0c0);
+}
+
+/// The steps of the built-in pieces, as shifts. Pieces that combine others' steps concatenate
+/// their lists.
+mod shifts {
+    use super::{can_move, Shift, SquareSet};
+
+    const ALL: SquareSet = SquareSet(!0);
+    const LEFT: SquareSet = can_move::LEFT;
+    const RIGHT: SquareSet = can_move::RIGHT;
+    const TWO_LEFT: SquareSet = can_move::TWO_LEFT;
+    const TWO_RIGHT: SquareSet = can_move::TWO_RIGHT;
+
+    const fn shift(from: SquareSet, by: i8) -> Shift {
+        Shift { from, by }
+    }
+
+    /// `a` followed by `b`. `C` has to be `A + B`, or this fails to compile.
+    const fn concat<const A: usize, const B: usize, const C: usize>(
+        a: [Shift; A],
+        b: [Shift; B],
+    ) -> [Shift; C] {
+        let mut res = [shift(ALL, 0); C];
+        let mut i = 0;
+        while i < C {
+            res[i] = if i < A { a[i] } else { b[i - A] };
+            i += 1;
+        }
+        assert!(A + B == C);
+        res
+    }
+
+    pub(crate) const PAWN_MOVE: [Shift; 1] = [shift(ALL, 8)];
+    pub(crate) const PAWN_CAPTURE: [Shift; 2] = [shift(LEFT, 7), shift(RIGHT, 9)];
+    pub(crate) const PAWN_DOWN_MOVE: [Shift; 1] = [shift(ALL, -8)];
+    pub(crate) const PAWN_DOWN_CAPTURE: [Shift; 2] = [shift(LEFT, -9), shift(RIGHT, -7)];
+    pub(crate) const ROOK: [Shift; 4] = [
+        shift(ALL, -8),
+        shift(LEFT, -1),
+        shift(RIGHT, 1),
+        shift(ALL, 8),
+    ];
+    pub(crate) const BISHOP: [Shift; 4] = [
+        shift(LEFT, -9),
+        shift(RIGHT, -7),
+        shift(LEFT, 7),
+        shift(RIGHT, 9),
+    ];
+    pub(crate) const KNIGHT: [Shift; 8] = [
+        shift(LEFT, -17),
+        shift(RIGHT, -15),
+        shift(TWO_LEFT, -10),
+        shift(TWO_RIGHT, -6),
+        shift(TWO_LEFT, 6),
+        shift(TWO_RIGHT, 10),
+        shift(LEFT, 15),
+        shift(RIGHT, 17),
+    ];
+    pub(crate) const KING: [Shift; 8] = concat(ROOK, BISHOP);
+    pub(crate) const ARCHBISHOP: [Shift; 12] = concat(BISHOP, KNIGHT);
+    pub(crate) const CHANCELLOR: [Shift; 12] = concat(ROOK, KNIGHT);
+    pub(crate) const AMAZON: [Shift; 16] = concat(KING, KNIGHT);
+}
+
+/// Kogge–Stone fills for sliding pieces. Each function extends `gen` as far as it can go in one
+/// direction through `pro`, in three shifts rather than up to seven single steps. The masks
+/// keep squares from wrapping around between the a- and h-files. Captures along lines from only a
+/// square or two, like a queen's from where it stands, are looked up in the `magic` tables instead.
+mod slide {
+    use super::{can_move, magic, SquareSet};
+
+    /// Fills toward higher squares, `shift` at a time, where `mask` is the squares that such a
+    /// step can land on.
+    fn up(mut gen: SquareSet, pro: SquareSet, shift: u32, mask: SquareSet) -> SquareSet {
+        let mut pro = pro & mask;
+        gen = gen | (pro & (gen << shift));
+        pro = pro & (pro << shift);
+        gen = gen | (pro & (gen << (2 * shift)));
+        pro = pro & (pro << (2 * shift));
+        gen | (pro & (gen << (4 * shift)))
+    }
+
+    /// Like `up`, toward lower squares.
+    fn down(mut gen: SquareSet, pro: SquareSet, shift: u32, mask: SquareSet) -> SquareSet {
+        let mut pro = pro & mask;
+        gen = gen | (pro & (gen >> shift));
+        pro = pro & (pro >> shift);
+        gen = gen | (pro & (gen >> (2 * shift)));
+        pro = pro & (pro >> (2 * shift));
+        gen | (pro & (gen >> (4 * shift)))
+    }
+
+    // A step toward the h-file can't land on the a-file, and vice versa.
+    const NOT_A: SquareSet = can_move::LEFT;
+    const NOT_H: SquareSet = can_move::RIGHT;
+    const ALL: SquareSet = SquareSet(!0);
+
+    /// At most this many squares of `gen` are looked up one at a time in the `magic` tables when
+    /// capturing along lines. With any more, filling every line at once is faster; `cargo bench
+    /// --bench fill` compares the two for one square.
+    pub(crate) const LOOKUPS: u32 = 2;
+
+    pub(crate) fn orthogonal(gen: SquareSet, pro: SquareSet) -> SquareSet {
+        up(gen, pro, 8, ALL)
+            | down(gen, pro, 8, ALL)
+            | up(gen, pro, 1, NOT_A)
+            | down(gen, pro, 1, NOT_H)
+    }
+
+    pub(crate) fn diagonal(gen: SquareSet, pro: SquareSet) -> SquareSet {
+        up(gen, pro, 9, NOT_A)
+            | up(gen, pro, 7, NOT_H)
+            | down(gen, pro, 7, NOT_A)
+            | down(gen, pro, 9, NOT_H)
+    }
+
+    /// Squares that a piece on `gen` can capture by sliding along a line through `pro` and then
+    /// one step further, in any of the eight directions.
+    pub(crate) fn ray_captures(gen: SquareSet, pro: SquareSet) -> SquareSet {
+        if gen.count() <= LOOKUPS {
+            let occupied = !pro;
+            return gen.iter().fold(SquareSet(0), |res, sq| {
+                res | magic::orthogonal(sq, occupied) | magic::diagonal(sq, occupied)
+            });
+        }
+        (up(gen, pro, 8, ALL) << 8)
+            | (down(gen, pro, 8, ALL) >> 8)
+            | ((up(gen, pro, 1, NOT_A) << 1) & NOT_A)
+            | ((down(gen, pro, 1, NOT_H) >> 1) & NOT_H)
+            | ((up(gen, pro, 9, NOT_A) << 9) & NOT_A)
+            | ((up(gen, pro, 7, NOT_H) << 7) & NOT_H)
+            | ((down(gen, pro, 7, NOT_A) >> 7) & NOT_A)
+            | ((down(gen, pro, 9, NOT_H) >> 9) & NOT_H)
+    }
+
+    /// Squares that a piece on `gen` can capture by leaping like a knight through `pro`, any
+    /// number of times in the same direction, and then once more. No line of leaps fits more than
+    /// three on the board, so each direction just leaps three times rather than doubling up.
+    pub(crate) fn leap_captures(gen: SquareSet, pro: SquareSet) -> SquareSet {
+        const NOT_AB: SquareSet = can_move::TWO_LEFT;
+        const NOT_GH: SquareSet = can_move::TWO_RIGHT;
+        let mut res = SquareSet(0);
+        for (shift, mask) in [(17, NOT_A), (15, NOT_H), (10, NOT_AB), (6, NOT_GH)] {
+            let mut line = gen;
+            for _ in 0..3 {
+                line = line | (pro & mask & (line << shift));
+            }
+            res = res | ((line << shift) & mask);
+        }
+        for (shift, mask) in [(17, NOT_H), (15, NOT_A), (10, NOT_GH), (6, NOT_AB)] {
+            let mut line = gen;
+            for _ in 0..3 {
+                line = line | (pro & mask & (line >> shift));
+            }
+            res = res | ((line >> shift) & mask);
+        }
+        res
+    }
+
+    /// Squares that a piece on `gen` can land on by sliding along a line through `pro`, hopping
+    /// over the first square that's not in `pro`, and landing just beyond it, in any of the
+    /// eight directions.
+    pub(crate) fn hops(gen: SquareSet, pro: SquareSet) -> SquareSet {
+        let mut res = SquareSet(0);
+        for (shift, mask) in [(8, ALL), (1, NOT_A), (9, NOT_A), (7, NOT_H)] {
+            let hurdles = (up(gen, pro, shift, mask) << shift) & mask & !pro;
+            res = res | ((hurdles << shift) & mask);
+        }
+        for (shift, mask) in [(8, ALL), (1, NOT_H), (9, NOT_H), (7, NOT_A)] {
+            let hurdles = (down(gen, pro, shift, mask) >> shift) & mask & !pro;
+            res = res | ((hurdles >> shift) & mask);
+        }
+        res
+    }
+
+    /// Squares that a piece on `gen` can capture by sliding along a rank or file through `pro`,
+    /// jumping the first square that's not in `pro`, and sliding on through `pro` to the next.
+    /// Looking up a square finds the screens first, then, with them taken away, what lies beyond.
+    pub(crate) fn screen_captures(gen: SquareSet, pro: SquareSet) -> SquareSet {
+        if gen.count() <= LOOKUPS {
+            let occupied = !pro;
+            return gen.iter().fold(SquareSet(0), |res, sq| {
+                let near = magic::orthogonal(sq, occupied);
+                let screens = near & occupied;
+                let far = magic::orthogonal(sq, occupied & !screens);
+                res | (far & !near & occupied)
+            });
+        }
+        let mut res = SquareSet(0);
+        for (shift, mask) in [(8, ALL), (1, NOT_A)] {
+            let screens = (up(gen, pro, shift, mask) << shift) & mask & !pro;
+            res = res | ((up(screens, pro, shift, mask) << shift) & mask & !pro);
+        }
+        for (shift, mask) in [(8, ALL), (1, NOT_H)] {
+            let screens = (down(gen, pro, shift, mask) >> shift) & mask & !pro;
+            res = res | ((down(screens, pro, shift, mask) >> shift) & mask & !pro);
+        }
+        res
+    }
+
+    /// Repeats a set of line fills until no more squares turn up, since the piece can turn
+    /// corners between moves.
+    pub(crate) fn fill(
+        from: SquareSet,
+        permeable: SquareSet,
+        lines: impl Fn(SquareSet, SquareSet) -> SquareSet,
+    ) -> SquareSet {
+        let mut reachable = from & permeable;
+        loop {
+            let next = lines(reachable, permeable);
+            if next == reachable {
+                return reachable;
+            }
+            reachable = next;
+        }
+    }
+}
+
+impl Stepper for Pawn {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::PAWN_MOVE);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::PAWN_CAPTURE);
+    fn move_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::PAWN_MOVE)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::PAWN_CAPTURE)
+    }
+}
+
+impl Stepper for PawnDown {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::PAWN_DOWN_MOVE);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::PAWN_DOWN_CAPTURE);
+    fn move_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::PAWN_DOWN_MOVE)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::PAWN_DOWN_CAPTURE)
+    }
+}
+
+impl Stepper for Bishop {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::BISHOP);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Self::MOVE_SHIFTS;
+    fn move_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::BISHOP)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        Self::move_steps(from)
+    }
+    fn fill(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::fill(from, permeable, slide::diagonal)
+    }
+}
+
+impl Stepper for Rook {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::ROOK);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Self::MOVE_SHIFTS;
+    fn move_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::ROOK)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        Self::move_steps(from)
+    }
+    fn fill(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::fill(from, permeable, slide::orthogonal)
+    }
+}
+
+impl Stepper for King {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::KING);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Self::MOVE_SHIFTS;
+    fn move_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::KING)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        Self::move_steps(from)
+    }
+    fn fill(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::fill(from, permeable, |gen, pro| {
+            slide::orthogonal(gen, pro) | slide::diagonal(gen, pro)
+        })
+    }
+}
+
+impl Stepper for Queen {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = King::MOVE_SHIFTS;
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = King::CAPTURE_SHIFTS;
+    fn move_steps(from: SquareSet) -> SquareSet {
+        King::move_steps(from)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        King::capture_steps(from)
+    }
+    fn fill(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        King::fill(from, permeable)
+    }
+    fn capture_reach(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::ray_captures(from, permeable)
+    }
+}
+
+impl Stepper for Knight {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::KNIGHT);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Self::MOVE_SHIFTS;
+    fn move_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::KNIGHT)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        Self::move_steps(from)
+    }
+}
+
+impl Stepper for Nightrider {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Knight::MOVE_SHIFTS;
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Knight::CAPTURE_SHIFTS;
+    fn move_steps(from: SquareSet) -> SquareSet {
+        Knight::move_steps(from)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        Knight::capture_steps(from)
+    }
+    fn capture_reach(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::leap_captures(from, permeable)
+    }
+}
+
+impl Stepper for Archbishop {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::ARCHBISHOP);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Self::MOVE_SHIFTS;
+    fn move_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::ARCHBISHOP)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        Self::move_steps(from)
+    }
+    fn fill(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::fill(from, permeable, |gen, pro| {
+            slide::diagonal(gen, pro) | (Knight::move_steps(gen) & pro)
+        })
+    }
+}
+
+impl Stepper for Chancellor {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::CHANCELLOR);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Self::MOVE_SHIFTS;
+    fn move_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::CHANCELLOR)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        Self::move_steps(from)
+    }
+    fn fill(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::fill(from, permeable, |gen, pro| {
+            slide::orthogonal(gen, pro) | (Knight::move_steps(gen) & pro)
+        })
+    }
+}
+
+impl Stepper for Amazon {
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&shifts::AMAZON);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Self::MOVE_SHIFTS;
+    fn move_steps(from: SquareSet) -> SquareSet {
+        shift_all(from, &shifts::AMAZON)
+    }
+    fn capture_steps(from: SquareSet) -> SquareSet {
+        Self::move_steps(from)
+    }
+    fn fill(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::fill(from, permeable, |gen, pro| {
+            slide::orthogonal(gen, pro)
+                | slide::diagonal(gen, pro)
+                | (Knight::move_steps(gen) & pro)
+        })
+    }
+    fn capture_reach(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        Queen::capture_reach(from, permeable) | Knight::capture_steps(from)
+    }
+}
+
+impl Stepper for Grasshopper {
+    const HOPS: bool = true;
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Some(&[]);
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Some(&[]);
+
+    // On an empty board, there's nothing to hop over.
+    fn move_steps(_from: SquareSet) -> SquareSet {
+        SquareSet(0)
+    }
+    fn capture_steps(_from: SquareSet) -> SquareSet {
+        SquareSet(0)
+    }
+    fn move_reach(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::hops(from, permeable) & permeable
+    }
+    fn capture_reach(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::hops(from, permeable)
+    }
+}
+
+impl Stepper for Cannon {
+    const HOPS: bool = true;
+    const MOVE_SHIFTS: Option<&'static [Shift]> = Rook::MOVE_SHIFTS;
+    const CAPTURE_SHIFTS: Option<&'static [Shift]> = Some(&[]);
+
+    fn move_steps(from: SquareSet) -> SquareSet {
+        Rook::move_steps(from)
+    }
+    // On an empty board, there's nothing to jump.
+    fn capture_steps(_from: SquareSet) -> SquareSet {
+        SquareSet(0)
+    }
+    fn fill(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        Rook::fill(from, permeable)
+    }
+    fn capture_reach(from: SquareSet, permeable: SquareSet) -> SquareSet {
+        slide::screen_captures(from, permeable)
+    }
+}
+
+/// The type of a piece. Kings and queens reach the same squares when they can take any number of
+/// steps, but a queen can capture from down a line in one move, where a king has to walk up to
+/// its target first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum PieceType {
+    Pawn,
+    /// A pawn facing toward rank 1. It's written `p` in FEN, like any pawn; see
+    /// `Rules::pawns_down` for turning a whole puzzle's pawns around.
+    #[cfg_attr(feature = "serde", serde(rename = "pawn-down"))]
+    PawnDown,
+    Bishop,
+    Rook,
+    /// Serialized as `"king"`, or read from the old `"monarch"`.
+    #[cfg_attr(feature = "serde", serde(alias = "monarch"))]
+    King,
+    Knight,
+    Queen,
+    /// A fairy piece, written `z` in FEN.
+    Nightrider,
+    /// A fairy piece, written `a` in FEN.
+    Archbishop,
+    /// A fairy piece, written `c` in FEN.
+    Chancellor,
+    /// A fairy piece, written `m` in FEN.
+    Amazon,
+    /// A fairy piece, written `g` in FEN.
+    Grasshopper,
+    /// The xiangqi piece, written `o` in FEN, since `c` is the chancellor.
+    Cannon,
+    /// A piece defined at runtime; see the `custom` module. These can't be serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(&'static custom::CustomPiece),
+}
+
+impl PieceType {
+    /// Every built-in piece type, in declaration order.
+    pub const ALL: [PieceType; 13] = [
+        PieceType::Pawn,
+        PieceType::PawnDown,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::King,
+        PieceType::Knight,
+        PieceType::Queen,
+        PieceType::Nightrider,
+        PieceType::Archbishop,
+        PieceType::Chancellor,
+        PieceType::Amazon,
+        PieceType::Grasshopper,
+        PieceType::Cannon,
+    ];
+
+    /// This type, made ready to move on a board of the given size. Only custom pieces care.
+    fn on_board(self, width: u8, height: u8) -> PieceType {
+        match self {
+            PieceType::Custom(c) => PieceType::Custom(c.on_board(width, height)),
+            ty => ty,
+        }
+    }
+
+    /// Lowercase FEN letter.
+    pub fn fen_char(self) -> char {
+        match self {
+            PieceType::Pawn | PieceType::PawnDown => 'p',
+            PieceType::Bishop => 'b',
+            PieceType::Rook => 'r',
+            PieceType::King => 'k',
+            PieceType::Knight => 'n',
+            PieceType::Queen => 'q',
+            PieceType::Nightrider => 'z',
+            PieceType::Archbishop => 'a',
+            PieceType::Chancellor => 'c',
+            PieceType::Amazon => 'm',
+            PieceType::Grasshopper => 'g',
+            PieceType::Cannon => 'o',
+            PieceType::Custom(c) => c.letter(),
+        }
+    }
+
+    /// Squares reachable by one non-capturing step of this piece from any square in `from`. See
+    /// `Stepper::move_steps`.
+    pub fn move_steps(self, from: SquareSet) -> SquareSet {
+        match self {
+            PieceType::Pawn => Pawn::move_steps(from),
+            PieceType::PawnDown => PawnDown::move_steps(from),
+            PieceType::Bishop => Bishop::move_steps(from),
+            PieceType::Rook => Rook::move_steps(from),
+            PieceType::King => King::move_steps(from),
+            PieceType::Knight => Knight::move_steps(from),
+            PieceType::Queen => Queen::move_steps(from),
+            PieceType::Nightrider => Nightrider::move_steps(from),
+            PieceType::Archbishop => Archbishop::move_steps(from),
+            PieceType::Chancellor => Chancellor::move_steps(from),
+            PieceType::Amazon => Amazon::move_steps(from),
+            PieceType::Grasshopper => Grasshopper::move_steps(from),
+            PieceType::Cannon => Cannon::move_steps(from),
+            PieceType::Custom(c) => c.move_steps(from),
+        }
+    }
+
+    /// Squares reachable by one capturing step of this piece from any square in `from`. See
+    /// `Stepper::capture_steps`.
+    pub fn capture_steps(self, from: SquareSet) -> SquareSet {
+        match self {
+            PieceType::Pawn => Pawn::capture_steps(from),
+            PieceType::PawnDown => PawnDown::capture_steps(from),
+            PieceType::Bishop => Bishop::capture_steps(from),
+            PieceType::Rook => Rook::capture_steps(from),
+            PieceType::King => King::capture_steps(from),
+            PieceType::Knight => Knight::capture_steps(from),
+            PieceType::Queen => Queen::capture_steps(from),
+            PieceType::Nightrider => Nightrider::capture_steps(from),
+            PieceType::Archbishop => Archbishop::capture_steps(from),
+            PieceType::Chancellor => Chancellor::capture_steps(from),
+            PieceType::Amazon => Amazon::capture_steps(from),
+            PieceType::Grasshopper => Grasshopper::capture_steps(from),
+            PieceType::Cannon => Cannon::capture_steps(from),
+            PieceType::Custom(c) => c.capture_steps(from),
+        }
+    }
+
+    /// Empty squares reachable by one non-capturing step from any square in `from`, where the
+    /// squares in `permeable` are the empty ones. See `Stepper::move_reach`.
+    pub fn move_reach(self, from: SquareSet, permeable: SquareSet) -> SquareSet {
+        match self {
+            PieceType::Pawn => Pawn::move_reach(from, permeable),
+            PieceType::PawnDown => PawnDown::move_reach(from, permeable),
+            PieceType::Bishop => Bishop::move_reach(from, permeable),
+            PieceType::Rook => Rook::move_reach(from, permeable),
+            PieceType::King => King::move_reach(from, permeable),
+            PieceType::Knight => Knight::move_reach(from, permeable),
+            PieceType::Queen => Queen::move_reach(from, permeable),
+            PieceType::Nightrider => Nightrider::move_reach(from, permeable),
+            PieceType::Archbishop => Archbishop::move_reach(from, permeable),
+            PieceType::Chancellor => Chancellor::move_reach(from, permeable),
+            PieceType::Amazon => Amazon::move_reach(from, permeable),
+            PieceType::Grasshopper => Grasshopper::move_reach(from, permeable),
+            PieceType::Cannon => Cannon::move_reach(from, permeable),
+            PieceType::Custom(c) => c.move_steps(from) & permeable,
+        }
+    }
+
+    /// Squares reachable from `from` in any number of steps through `permeable`. See
+    /// `Stepper::fill`.
+    pub fn fill(self, from: SquareSet, permeable: SquareSet) -> SquareSet {
+        match self {
+            PieceType::Pawn => Pawn::fill(from, permeable),
+            PieceType::PawnDown => PawnDown::fill(from, permeable),
+            PieceType::Bishop => Bishop::fill(from, permeable),
+            PieceType::Rook => Rook::fill(from, permeable),
+            PieceType::King => King::fill(from, permeable),
+            PieceType::Knight => Knight::fill(from, permeable),
+            PieceType::Queen => Queen::fill(from, permeable),
+            PieceType::Nightrider => Nightrider::fill(from, permeable),
+            PieceType::Archbishop => Archbishop::fill(from, permeable),
+            PieceType::Chancellor => Chancellor::fill(from, permeable),
+            PieceType::Amazon => Amazon::fill(from, permeable),
+            PieceType::Grasshopper => Grasshopper::fill(from, permeable),
+            PieceType::Cannon => Cannon::fill(from, permeable),
+            PieceType::Custom(c) => c.fill(from, permeable),
+        }
+    }
+
+    /// Squares capturable in one move from any square in `from`, passing through `permeable`. See
+    /// `Stepper::capture_reach`.
+    pub fn capture_reach(self, from: SquareSet, permeable: SquareSet) -> SquareSet {
+        match self {
+            PieceType::Pawn => Pawn::capture_reach(from, permeable),
+            PieceType::PawnDown => PawnDown::capture_reach(from, permeable),
+            PieceType::Bishop => Bishop::capture_reach(from, permeable),
+            PieceType::Rook => Rook::capture_reach(from, permeable),
+            PieceType::King => King::capture_reach(from, permeable),
+            PieceType::Knight => Knight::capture_reach(from, permeable),
+            PieceType::Queen => Queen::capture_reach(from, permeable),
+            PieceType::Nightrider => Nightrider::capture_reach(from, permeable),
+            PieceType::Archbishop => Archbishop::capture_reach(from, permeable),
+            PieceType::Chancellor => Chancellor::capture_reach(from, permeable),
+            PieceType::Amazon => Amazon::capture_reach(from, permeable),
+            PieceType::Grasshopper => Grasshopper::capture_reach(from, permeable),
+            PieceType::Cannon => Cannon::capture_reach(from, permeable),
+            PieceType::Custom(c) => c.capture_reach(from, permeable),
+        }
+    }
+
+    /// Like the free function `captures`, for a piece of this type.
+    pub fn captures(self, from: SquareSet, obstacles: SquareSet, targets: SquareSet) -> SquareSet {
+        match self {
+            PieceType::Pawn => captures::<Pawn>(from, obstacles, targets),
+            PieceType::PawnDown => captures::<PawnDown>(from, obstacles, targets),
+            PieceType::Bishop => captures::<Bishop>(from, obstacles, targets),
+            PieceType::Rook => captures::<Rook>(from, obstacles, targets),
+            PieceType::King => captures::<King>(from, obstacles, targets),
+            PieceType::Knight => captures::<Knight>(from, obstacles, targets),
+            PieceType::Queen => captures::<Queen>(from, obstacles, targets),
+            PieceType::Nightrider => captures::<Nightrider>(from, obstacles, targets),
+            PieceType::Archbishop => captures::<Archbishop>(from, obstacles, targets),
+            PieceType::Chancellor => captures::<Chancellor>(from, obstacles, targets),
+            PieceType::Amazon => captures::<Amazon>(from, obstacles, targets),
+            PieceType::Grasshopper => captures::<Grasshopper>(from, obstacles, targets),
+            PieceType::Cannon => captures::<Cannon>(from, obstacles, targets),
+            PieceType::Custom(c) => {
+                let permeable = !(obstacles | targets);
+                c.capture_reach(c.fill(from, permeable), permeable) & targets
+            }
+        }
+    }
+
+    /// Like the free function `capture_moves`, for a piece of this type.
+    pub fn capture_moves<F: FnMut(Square, u32)>(
+        self,
+        from: SquareSet,
+        obstacles: SquareSet,
+        targets: SquareSet,
+        consume: F,
+    ) {
+        match self {
+            PieceType::Pawn => capture_moves::<Pawn, _>(from, obstacles, targets, consume),
+            PieceType::PawnDown => capture_moves::<PawnDown, _>(from, obstacles, targets, consume),
+            PieceType::Bishop => capture_moves::<Bishop, _>(from, obstacles, targets, consume),
+            PieceType::Rook => capture_moves::<Rook, _>(from, obstacles, targets, consume),
+            PieceType::King => capture_moves::<King, _>(from, obstacles, targets, consume),
+            PieceType::Knight => capture_moves::<Knight, _>(from, obstacles, targets, consume),
+            PieceType::Queen => capture_moves::<Queen, _>(from, obstacles, targets, consume),
+            PieceType::Nightrider => {
+                capture_moves::<Nightrider, _>(from, obstacles, targets, consume)
+            }
+            PieceType::Archbishop => {
+                capture_moves::<Archbishop, _>(from, obstacles, targets, consume)
+            }
+            PieceType::Chancellor => {
+                capture_moves::<Chancellor, _>(from, obstacles, targets, consume)
+            }
+            PieceType::Amazon => capture_moves::<Amazon, _>(from, obstacles, targets, consume),
+            PieceType::Grasshopper => {
+                capture_moves::<Grasshopper, _>(from, obstacles, targets, consume)
+            }
+            PieceType::Cannon => capture_moves::<Cannon, _>(from, obstacles, targets, consume),
+            PieceType::Custom(c) => capture_moves_with(
+                from,
+                obstacles,
+                targets,
+                consume,
+                |from, permeable| c.move_steps(from) & permeable,
+                |from, permeable| c.capture_reach(from, permeable),
+            ),
+        }
+    }
+
+    /// Like the free function `capture_path`, for a piece of this type.
+    pub fn capture_path(
+        self,
+        from: Square,
+        obstacles: SquareSet,
+        targets: SquareSet,
+        to: Square,
+    ) -> Option<Vec<Square>> {
+        match self {
+            PieceType::Pawn => capture_path::<Pawn>(from, obstacles, targets, to),
+            PieceType::PawnDown => capture_path::<PawnDown>(from, obstacles, targets, to),
+            PieceType::Bishop => capture_path::<Bishop>(from, obstacles, targets, to),
+            PieceType::Rook => capture_path::<Rook>(from, obstacles, targets, to),
+            PieceType::King => capture_path::<King>(from, obstacles, targets, to),
+            PieceType::Knight => capture_path::<Knight>(from, obstacles, targets, to),
+            PieceType::Queen => capture_path::<Queen>(from, obstacles, targets, to),
+            PieceType::Nightrider => capture_path::<Nightrider>(from, obstacles, targets, to),
+            PieceType::Archbishop => capture_path::<Archbishop>(from, obstacles, targets, to),
+            PieceType::Chancellor => capture_path::<Chancellor>(from, obstacles, targets, to),
+            PieceType::Amazon => capture_path::<Amazon>(from, obstacles, targets, to),
+            PieceType::Grasshopper => capture_path::<Grasshopper>(from, obstacles, targets, to),
+            PieceType::Cannon => capture_path::<Cannon>(from, obstacles, targets, to),
+            PieceType::Custom(c) => capture_path_with(
+                from,
+                obstacles,
+                targets,
+                to,
+                |from, permeable| c.move_steps(from) & permeable,
+                |from, permeable| c.capture_reach(from, permeable),
+            ),
+        }
+    }
+
+    /// Does this piece need something to hop over? See `Stepper::HOPS`.
+    pub fn hops(self) -> bool {
+        match self {
+            PieceType::Pawn => Pawn::HOPS,
+            PieceType::PawnDown => PawnDown::HOPS,
+            PieceType::Bishop => Bishop::HOPS,
+            PieceType::Rook => Rook::HOPS,
+            PieceType::King => King::HOPS,
+            PieceType::Knight => Knight::HOPS,
+            PieceType::Queen => Queen::HOPS,
+            PieceType::Nightrider => Nightrider::HOPS,
+            PieceType::Archbishop => Archbishop::HOPS,
+            PieceType::Chancellor => Chancellor::HOPS,
+            PieceType::Amazon => Amazon::HOPS,
+            PieceType::Grasshopper => Grasshopper::HOPS,
+            PieceType::Cannon => Cannon::HOPS,
+            PieceType::Custom(_) => false,
+        }
+    }
+
+    /// Lowercase English name, for prose.
+    pub fn name(self) -> &'static str {
+        match self {
+            PieceType::Pawn | PieceType::PawnDown => "pawn",
+            PieceType::Bishop => "bishop",
+            PieceType::Rook => "rook",
+            PieceType::King => "king",
+            PieceType::Knight => "knight",
+            PieceType::Queen => "queen",
+            PieceType::Nightrider => "nightrider",
+            PieceType::Archbishop => "archbishop",
+            PieceType::Chancellor => "chancellor",
+            PieceType::Amazon => "amazon",
+            PieceType::Grasshopper => "grasshopper",
+            PieceType::Cannon => "cannon",
+            PieceType::Custom(c) => c.name(),
+        }
+    }
+}
+
+/// The most pieces, including the player's, that a `Puzzle` can have.
+pub const MAX_PIECES: usize = 27;
+
+/// The most pairs of portals that a `Puzzle` can have, one for each of `PORTAL_GLYPHS`.
+pub const MAX_PORTALS: usize = PORTAL_GLYPHS.len();
+
+/// The FEN glyphs for portals, in the order of `Puzzle::portals`. Each marks one linked pair.
+pub const PORTAL_GLYPHS: [char; 4] = ['@', '*', '$', '%'];
+
+/// A way across the board, which a one-way square only lets pieces enter. See
+/// `Puzzle::one_way`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Heading {
+    /// Toward rank 8.
+    Up,
+    /// Toward the h-file.
+    Right,
+    /// Toward rank 1.
+    Down,
+    /// Toward the a-file.
+    Left,
+}
+
+impl Heading {
+    pub const ALL: [Heading; 4] = [Heading::Up, Heading::Right, Heading::Down, Heading::Left];
+
+    /// The FEN glyph for a one-way square that only lets pieces in heading this way: `^`, `>`,
+    /// `v`, or `<`.
+    pub fn glyph(self) -> char {
+        match self {
+            Heading::Up => '^',
+            Heading::Right => '>',
+            Heading::Down => 'v',
+            Heading::Left => '<',
+        }
+    }
+
+    /// One step this way, as a change in file and rank.
+    fn step(self) -> (i8, i8) {
+        match self {
+            Heading::Up => (0, 1),
+            Heading::Right => (1, 0),
+            Heading::Down => (0, -1),
+            Heading::Left => (-1, 0),
+        }
+    }
+}
+
+/// Concise, solver-friendly description of a puzzle with up to 27 pieces.
+///
+/// Pieces in this puzzle are indexed from 0 in order of ascending board location, in rank-major
+/// order. That is, piece A comes before piece B if it is either on a smaller rank (1 is smallest,
+/// 8 is largest), or on the same rank and a smaller file (A is smallest, H is largest).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(into = "ser::PuzzleRepr", try_from = "ser::PuzzleRepr")
+)]
+pub struct Puzzle {
+    /// Which squares have obstacles?
+    obstacles: SquareSet,
+    /// Maps piece index (`0..27`) to piece type, or `None` if there is no such piece.
+    piece_types: [Option<PieceType>; 32],
+    /// Maps piece index (`0..27`) to board square (`0..64`), or `0xff` if there is no such piece.
+    piece_locs: [u8; 32],
+    /// Maps board square (`0..64`) to piece index (`0..27`), or `0xff` if there is no piece at
+    /// that location.
+    pieces_by_loc: [u8; 64],
+    /// Which piece (`0..27`) is initially controlled by the player?
+    player_start: u32,
+    /// Patrol routes, as `(piece_idx, route)` pairs. See `Puzzle::add_patrol`.
+    patrols: Vec<(u32, Vec<Square>)>,
+    /// Which optional rule variants are in effect?
+    rules: Rules,
+    /// The board is files `0..width` and ranks `0..height`. Every square off the board is an
+    /// obstacle, so movement never needs to check the dimensions.
+    width: u8,
+    height: u8,
+    /// Where the player has to walk after the last capture, if anywhere. See `Puzzle::exit`.
+    exit: Option<Square>,
+    /// The most total moves that a solution may take, if limited. See `Puzzle::budget`.
+    budget: Option<u32>,
+    /// Pairs of linked portal squares. See `Puzzle::portals`.
+    portals: Vec<(Square, Square)>,
+    /// Which squares are icy? See `Puzzle::ice`.
+    ice: SquareSet,
+    /// The pieces that the player could start as, in index order. See `Puzzle::starts`.
+    starts: Vec<u32>,
+    /// The one-way squares facing each way, indexed by `Heading`. See `Puzzle::one_way`.
+    one_way: [SquareSet; 4],
+    /// Breakable walls, in square order. See `Puzzle::walls`.
+    walls: Vec<Square>,
+    /// Which piece types can break walls? See `Puzzle::wall_breakers`.
+    wall_breakers: Vec<PieceType>,
+    /// The squares that cost more than one move to step onto, in square order, with their
+    /// costs. See `Puzzle::terrain`.
+    terrain: Vec<(Square, u32)>,
+    /// Bitmask of the pieces that needn't be captured. See `Puzzle::required`.
+    optional: u32,
+}
+
+/// Optional rule variants. The default is standard Echo Chess.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Rules {
+    /// If set, pieces with patrol routes advance one step along them after each of the player's
+    /// captures. Otherwise, patrol routes are ignored and every piece stays put.
+    pub patrols: bool,
+    /// If set, queens capture one step at a time, like kings, as they did before the two were
+    /// told apart. That only changes how moves are counted, not which puzzles can be solved.
+    pub step_queens: bool,
+    /// If set, pawns move and capture toward rank 1 instead of rank 8, as black's pawns do.
+    pub pawns_down: bool,
+    /// If set, the player can't stop on a square that a piece still to be captured attacks: one
+    /// that the piece could capture on if it were the player. So it can't capture a piece that
+    /// another defends, either. It can still pass over attacked squares, as when capturing from
+    /// down a line or sliding over ice, unless `threats_block_lines` is set too.
+    ///
+    /// ```
+    /// use echochess::{solve_min_moves, Puzzle, Rules};
+    ///
+    /// // The queen can't stop on b2, where the rook attacks it, so it captures from a1 instead.
+    /// let mut puzzle = Puzzle::parse("2xn/Q3/2r1/4").unwrap();
+    /// assert_eq!(solve_min_moves(&puzzle), Some((vec![2, 0], 3)));
+    /// let threats = Rules { threats: true, ..Rules::default() };
+    /// puzzle.set_rules(threats);
+    /// assert_eq!(solve_min_moves(&puzzle), Some((vec![2, 0], 4)));
+    /// // Capturing from a1 passes b2 and c3, which the rook attacks too.
+    /// puzzle.set_rules(Rules { threats_block_lines: true, ..threats });
+    /// assert_eq!(solve_min_moves(&puzzle), None);
+    /// ```
+    pub threats: bool,
+    /// If set along with `threats`, attacked squares block the player like obstacles, so it
+    /// can't pass over them either.
+    pub threats_block_lines: bool,
+}
+
+impl Puzzle {
+    /// A hash of everything that affects how this puzzle plays, for caching per-puzzle results
+    /// and for telling whether saved progress belongs to it. Unlike `canonical_id`, it tells
+    /// apart puzzles that only differ in how their pieces are numbered or which piece the player
+    /// starts as. It's the same in every build and on every platform, so it's safe to store.
+    pub fn fingerprint(&self) -> u64 {
+        let mut bytes = self.canonical_bytes(|f, r, _, _| (f, r));
+        for i in 0..self.num_pieces() {
+            bytes.push(self.piece_loc(i).index());
+        }
+        bytes.extend(self.player_start.to_le_bytes());
+        for (piece_idx, _) in &self.patrols {
+            bytes.extend(piece_idx.to_le_bytes());
+        }
+        fnv1a(&bytes)
+    }
+
+    /// A hash of this puzzle that's the same for any reflection or rotation of it, for spotting
+    /// duplicates in a collection. It only counts the reflections and rotations that the pieces
+    /// play the same under, so a puzzle with pawns only matches its mirror image, and one with
+    /// custom pieces that aren't symmetric only matches itself. When the player could start as
+    /// any of several pieces, it doesn't matter which of them `player_start` picks. Like
+    /// `fingerprint`, it's the same in every build and on every platform.
+    ///
+    /// ```
+    /// use echochess::Puzzle;
+    ///
+    /// let puzzle = Puzzle::parse("R1n/3/b2").unwrap();
+    /// let mirrored = Puzzle::parse("n1R/3/2b").unwrap();
+    /// let turned = Puzzle::parse("b1R/3/2n").unwrap();
+    /// assert_ne!(puzzle.fingerprint(), mirrored.fingerprint());
+    /// assert_eq!(puzzle.canonical_id(), mirrored.canonical_id());
+    /// assert_eq!(puzzle.canonical_id(), turned.canonical_id());
+    /// assert_ne!(puzzle.canonical_id(), Puzzle::parse("R1n/3/1b1").unwrap().canonical_id());
+    ///
+    /// // One-way squares turn with the board.
+    /// let one_way = Puzzle::parse("R>n").unwrap();
+    /// assert_eq!(one_way.canonical_id(), Puzzle::parse("n<R").unwrap().canonical_id());
+    /// assert_ne!(one_way.canonical_id(), Puzzle::parse("n>R").unwrap().canonical_id());
+    /// ```
+    pub fn canonical_id(&self) -> u64 {
+        std::iter::once((|f, r, _, _| (f, r)) as Transform)
+            .chain(self.transforms().iter().copied())
+            .map(|t| fnv1a(&self.canonical_bytes(t)))
+            .min()
+            .unwrap()
+    }
+
+    /// This puzzle as it would be after transform `t`, written out as bytes with everything
+    /// listed in an order that doesn't depend on how it was laid out before. Numbers are
+    /// little-endian, and each list starts with its length.
+    fn canonical_bytes(&self, t: Transform) -> Vec<u8> {
+        let map = |sq: Square| self.transform(t, sq).index();
+        let map_set = |set: SquareSet| set.iter().map(|sq| 1 << map(sq)).fold(0u64, |a, b| a | b);
+        fn sorted<T: Ord>(mut v: Vec<T>) -> Vec<T> {
+            v.sort();
+            v
+        }
+        fn list<T>(out: &mut Vec<u8>, items: Vec<T>, mut write: impl FnMut(&mut Vec<u8>, T)) {
+            out.extend((items.len() as u32).to_le_bytes());
+            for item in items {
+                write(out, item);
+            }
+        }
+        let mut out = vec![self.width, self.height];
+        out.extend(map_set(self.obstacles & self.squares()).to_le_bytes());
+        let pieces = (0..self.num_pieces()).map(|i| {
+            let ty = self.piece_type(i).unwrap();
+            let start = self.starts.contains(&i) || i == self.player_start;
+            let mut piece = vec![map(self.piece_loc(i))];
+            piece.extend((ty.fen_char() as u32).to_le_bytes());
+            piece.extend([self.required(i) as u8, start as u8]);
+            if let PieceType::Custom(c) = ty {
+                c.write_moves(&mut piece);
+            }
+            piece
+        });
+        list(&mut out, sorted(pieces.collect()), |out, piece| {
+            out.extend(piece)
+        });
+        let patrols = self
+            .patrols
+            .iter()
+            .map(|(_, route)| route.iter().copied().map(map).collect::<Vec<u8>>());
+        list(&mut out, sorted(patrols.collect()), |out, route| {
+            list(out, route, |out, sq| out.push(sq))
+        });
+        let Rules {
+            patrols,
+            step_queens,
+            pawns_down,
+            threats,
+            threats_block_lines,
+        } = self.rules;
+        out.extend(
+            [
+                patrols,
+                step_queens,
+                pawns_down,
+                threats,
+                threats_block_lines,
+            ]
+            .map(u8::from),
+        );
+        match self.budget {
+            Some(budget) => {
+                out.push(1);
+                out.extend(budget.to_le_bytes());
+            }
+            None => out.push(0),
+        }
+        match self.exit {
+            Some(exit) => out.extend([1, map(exit)]),
+            None => out.push(0),
+        }
+        let portals = self.portals.iter().map(|&(a, b)| {
+            let (a, b) = (map(a), map(b));
+            (a.min(b), a.max(b))
+        });
+        list(&mut out, sorted(portals.collect()), |out, (a, b)| {
+            out.extend([a, b])
+        });
+        out.extend(map_set(self.ice).to_le_bytes());
+        let mut one_way = [0u64; 4];
+        for heading in Heading::ALL {
+            // Turn the heading's step about the middle of a 3x3 board.
+            let (df, dr) = heading.step();
+            let (f, r) = t((1 + df) as u8, (1 + dr) as u8, 2, 2);
+            let turned = Heading::ALL
+                .into_iter()
+                .find(|h| h.step() == (f as i8 - 1, r as i8 - 1))
+                .unwrap();
+            one_way[turned as usize] = map_set(self.one_way(heading));
+        }
+        for set in one_way {
+            out.extend(set.to_le_bytes());
+        }
+        let walls = self.walls.iter().copied().map(map).collect();
+        list(&mut out, sorted(walls), |out, sq| out.push(sq));
+        let breakers = self.wall_breakers.iter().map(|ty| ty.fen_char() as u32);
+        list(&mut out, sorted(breakers.collect()), |out, c| {
+            out.extend(c.to_le_bytes())
+        });
+        let terrain = self.terrain.iter().map(|&(sq, cost)| (map(sq), cost));
+        list(&mut out, sorted(terrain.collect()), |out, (sq, cost)| {
+            out.push(sq);
+            out.extend(cost.to_le_bytes());
+        });
+        out
+    }
+
+    /// Which squares have obstacles? This includes every square off the board.
+    pub fn obstacles(&self) -> SquareSet {
+        self.obstacles
+    }
+
+    /// How many files the board has, from `1` to `8`.
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    /// How many ranks the board has, from `1` to `8`.
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    /// The squares on the board.
+    pub fn squares(&self) -> SquareSet {
+        board_squares(self.width, self.height)
+    }
+
+    /// The piece type whose movement piece `piece_idx` follows. This is its own type, except
+    /// under some rule variants: queens move as kings under `Rules::step_queens`, and pawns face
+    /// the other way under `Rules::pawns_down`.
+    pub fn mover(&self, piece_idx: u32) -> Option<PieceType> {
+        match self.player_type(piece_idx)? {
+            PieceType::Queen if self.rules.step_queens => Some(PieceType::King),
+            PieceType::Pawn if self.rules.pawns_down => Some(PieceType::PawnDown),
+            PieceType::PawnDown if self.rules.pawns_down => Some(PieceType::Pawn),
+            ty => Some(ty),
+        }
+    }
+
+    /// The type of piece `piece_idx` (`0..27`), or `None` if there is no such piece.
+    pub fn piece_type(&self, piece_idx: u32) -> Option<PieceType> {
+        self.piece_types.get(piece_idx as usize).copied().flatten()
+    }
+
+    /// The starting square of piece `piece_idx`, which must exist.
+    pub fn piece_loc(&self, piece_idx: u32) -> Square {
+        Square(self.piece_locs[piece_idx as usize])
+    }
+
+    /// Which piece (`0..27`) starts on square `loc`, if any?
+    pub fn piece_on(&self, loc: Square) -> Option<u32> {
+        match self.pieces_by_loc[loc.0 as usize] {
+            0xff => None,
+            idx => Some(u32::from(idx)),
+        }
+    }
+
+    /// Which piece (`0..27`) is initially controlled by the player?
+    pub fn player_start(&self) -> u32 {
+        self.player_start
+    }
+
+    /// The pieces that the player could start as, in index order, including `player_start`. A
+    /// puzzle written with several uppercase pieces can start as any of them; the solver plays
+    /// from `player_start`, and `solve_starts` tries them all.
+    ///
+    /// ```
+    /// use echochess::{solve_starts, Puzzle};
+    ///
+    /// // Starting as the bishop wins by way of the rook, but starting as the rook doesn't.
+    /// let puzzle = Puzzle::parse("2n/R1B").unwrap();
+    /// assert_eq!(puzzle.starts(), &[0, 1]);
+    /// assert_eq!(puzzle.player_start(), 1);
+    /// assert_eq!(solve_starts(&puzzle).winnable, vec![1]);
+    /// ```
+    pub fn starts(&self) -> &[u32] {
+        &self.starts
+    }
+
+    /// A copy of this puzzle where the player starts as piece `piece_idx`, which is then its only
+    /// start, or `None` if there's no such piece or it patrols.
+    pub fn with_start(&self, piece_idx: u32) -> Option<Puzzle> {
+        self.piece_type(piece_idx)?;
+        if self.patrols.iter().any(|&(i, _)| i == piece_idx) {
+            return None;
+        }
+        let mut res = self.clone();
+        res.player_start = piece_idx;
+        res.starts = vec![piece_idx];
+        Some(res)
+    }
+
+    /// A copy of this puzzle without piece `piece_idx`, which can't be one the player could start
+    /// as. The pieces after it move down one index, and the walls' slots with them.
+    fn without_piece(&self, piece_idx: u32) -> Option<Puzzle> {
+        self.piece_type(piece_idx)?;
+        if self.starts.contains(&piece_idx) {
+            return None;
+        }
+        let shift = |i: u32| if i > piece_idx { i - 1 } else { i };
+        let mut res = self.clone();
+        let i = piece_idx as usize;
+        res.piece_types[i..].rotate_left(1);
+        res.piece_types[31] = None;
+        res.piece_locs[i..].rotate_left(1);
+        res.piece_locs[31] = 0xff;
+        for z in &mut res.pieces_by_loc {
+            if u32::from(*z) == piece_idx {
+                *z = 0xff;
+            } else if *z != 0xff {
+                *z = shift(u32::from(*z)) as u8;
+            }
+        }
+        res.player_start = shift(self.player_start);
+        res.starts = self.starts.iter().map(|&i| shift(i)).collect();
+        res.patrols = self
+            .patrols
+            .iter()
+            .filter(|(i, _)| *i != piece_idx)
+            .map(|(i, route)| (shift(*i), route.clone()))
+            .collect();
+        let below = (1 << piece_idx) - 1;
+        res.optional = (self.optional & below) | ((self.optional >> 1) & !below);
+        Some(res)
+    }
+
+    /// A copy of this puzzle with these obstacles and pieces instead, keeping everything else.
+    /// Starts, patrols, and optional pieces stay with the pieces on their squares, except that
+    /// the piece on `moved.0`, if given, is now on `moved.1`. Returns `None` if a start or a
+    /// patrolling piece is gone.
+    fn with_board(
+        &self,
+        obstacles: SquareSet,
+        pieces: &[Option<PieceType>; 64],
+        moved: Option<(Square, Square)>,
+    ) -> Option<Puzzle> {
+        let loc = |i: u32| match (self.piece_loc(i), moved) {
+            (sq, Some((from, to))) if sq == from => to,
+            (sq, _) => sq,
+        };
+        let player = loc(self.player_start);
+        pieces[player.0 as usize]?;
+        let board = Puzzle::from_board(obstacles, pieces, player, (self.width, self.height));
+        let reindex = |i: u32| board.piece_on(loc(i));
+        let mut res = self.clone();
+        res.obstacles = board.obstacles;
+        res.piece_types = board.piece_types;
+        res.piece_locs = board.piece_locs;
+        res.pieces_by_loc = board.pieces_by_loc;
+        res.player_start = board.player_start;
+        res.starts = self
+            .starts
+            .iter()
+            .map(|&i| reindex(i))
+            .collect::<Option<_>>()?;
+        res.starts.sort();
+        res.patrols = self
+            .patrols
+            .iter()
+            .map(|(i, route)| Some((reindex(*i)?, route.clone())))
+            .collect::<Option<_>>()?;
+        res.optional = (0..self.num_pieces())
+            .filter(|&i| !self.required(i))
+            .filter_map(reindex)
+            .fold(0, |optional, i| optional | 1 << i);
+        Some(res)
+    }
+
+    /// Which optional rule variants are in effect?
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Changes which optional rule variants are in effect.
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+    }
+
+    /// The most total moves that a solution may take, if limited, counting moves as
+    /// `solve_min_moves` does. `Solver::solve` and the functions built on it then search for the
+    /// fewest moves, whatever the strategy, and report `SolveResult::OverBudget` with the fewest
+    /// possible if that's more than the budget. Other analyses, like `solve_all`, ignore it.
+    ///
+    /// ```
+    /// use echochess::{solve, Puzzle, SolveResult, Solver};
+    ///
+    /// let mut puzzle = Puzzle::parse("8/8/8/8/8/8/8/R1n5").unwrap();
+    /// puzzle.set_budget(Some(1));
+    /// assert_eq!(solve(&puzzle), None);
+    /// assert_eq!(Solver::new().solve(&puzzle).result, SolveResult::OverBudget(2));
+    /// puzzle.set_budget(Some(2));
+    /// assert_eq!(solve(&puzzle), Some(vec![1]));
+    /// ```
+    pub fn budget(&self) -> Option<u32> {
+        self.budget
+    }
+
+    /// Limits how many total moves a solution may take, or lifts the limit. See `Puzzle::budget`.
+    pub fn set_budget(&mut self, budget: Option<u32>) {
+        self.budget = budget;
+    }
+
+    /// The squares that cost more than one move to step onto, like mud, with their costs. A step
+    /// onto any other square is one move. Only the squares stepped onto count, so a queen's
+    /// capture from down a line costs whatever the captured piece's square does. Terrain doesn't
+    /// change which puzzles can be solved, only how many moves solutions take, and so which ones
+    /// `solve_min_moves` finds and whether they're within the `budget`.
+    ///
+    /// ```
+    /// use echochess::{solve_min_moves, Puzzle};
+    ///
+    /// // The king walks past b2 to take the knight, unless the whole b-file is muddy.
+    /// let mut puzzle = Puzzle::parse("3/K1n/3").unwrap();
+    /// assert_eq!(solve_min_moves(&puzzle), Some((vec![1], 2)));
+    /// for sq in ["b1", "b2", "b3"] {
+    ///     puzzle.set_move_cost(sq.parse().unwrap(), 3);
+    /// }
+    /// assert_eq!(puzzle.move_cost("b2".parse().unwrap()), 3);
+    /// assert_eq!(solve_min_moves(&puzzle), Some((vec![1], 4)));
+    /// ```
+    pub fn terrain(&self) -> &[(Square, u32)] {
+        &self.terrain
+    }
+
+    /// How many moves a step onto square `loc` costs. See `Puzzle::terrain`.
+    pub fn move_cost(&self, loc: Square) -> u32 {
+        match self.terrain.iter().find(|&&(sq, _)| sq == loc) {
+            Some(&(_, cost)) => cost,
+            None => 1,
+        }
+    }
+
+    /// Sets how many moves a step onto square `loc` costs. Every step is at least one move, so a
+    /// cost of `0` counts as `1` here, though `PuzzleBuilder::set_move_cost` and puzzle files
+    /// reject it. See `Puzzle::terrain`.
+    pub fn set_move_cost(&mut self, loc: Square, cost: u32) {
+        self.terrain.retain(|&(sq, _)| sq != loc);
+        if cost > 1 {
+            self.terrain.push((loc, cost));
+            self.terrain.sort();
+        }
+    }
+
+    /// Does piece `piece_idx` have to be captured to win? By default every piece does, but a
+    /// puzzle can leave some optional, marked in the FEN with a `?` after the piece's letter. The
+    /// player wins once every required piece is captured, and any optional ones left over are
+    /// forgotten, as walls still standing are. Until then, they're pieces like any other, which
+    /// the player can capture to turn into them.
+    ///
+    /// ```
+    /// use echochess::{solve, Puzzle};
+    ///
+    /// // The knight on c1 is out of the rook's reach, but it's optional.
+    /// let puzzle = Puzzle::parse("R1b/xxx/2n?").unwrap();
+    /// assert!(!puzzle.required(0));
+    /// assert_eq!(solve(&puzzle), Some(vec![2]));
+    /// assert_eq!(solve(&Puzzle::parse("R1b/xxx/2n").unwrap()), None);
+    /// ```
+    pub fn required(&self, piece_idx: u32) -> bool {
+        self.optional & (1 << piece_idx) == 0
+    }
+
+    /// Sets whether piece `piece_idx` has to be captured to win. See `Puzzle::required`.
+    pub fn set_required(&mut self, piece_idx: u32, required: bool) {
+        if required {
+            self.optional &= !(1 << piece_idx);
+        } else {
+            self.optional |= 1 << piece_idx;
+        }
+    }
+
+    /// Bitmask of the pieces that have to be captured to win. This counts the player's own piece
+    /// if it's required, which does no harm, as that piece is never left to capture. It has to:
+    /// merging symmetric states can swap the player's starting piece with one still on the board.
+    fn required_bits(&self) -> u32 {
+        ((1 << self.num_pieces()) - 1) & !self.optional
+    }
+
+    /// The exit square, if the puzzle has one. Then capturing every piece isn't enough: the
+    /// player has to walk to the exit afterward, and a last capture that leaves no way there
+    /// isn't a legal move. The walk counts toward the total moves, and a `Solution` includes it.
+    ///
+    /// ```
+    /// use echochess::{solve, Puzzle};
+    ///
+    /// // Taking the knight leaves a knight that can leap to the exit on c1...
+    /// let puzzle = Puzzle::from_compound_fen("Rn1/3/2E");
+    /// assert_eq!(puzzle.exit(), "c1".parse().ok());
+    /// assert_eq!(solve(&puzzle), Some(vec![1]));
+    /// // ...but a bishop on b3 never gets to c1.
+    /// assert_eq!(solve(&Puzzle::from_compound_fen("Rb1/3/2E")), None);
+    /// ```
+    pub fn exit(&self) -> Option<Square> {
+        self.exit
+    }
+
+    /// Can piece `piece_idx`, having made the last capture on square `loc`, walk to the exit past
+    /// the walls still standing on `walls`? Always true if there's no exit.
+    fn can_exit(&self, piece_idx: u32, loc: Square, walls: SquareSet) -> bool {
+        let Some(exit) = self.exit else {
+            return true;
+        };
+        let ty = self.mover(piece_idx).unwrap();
+        self.fill(ty, loc.into(), !(self.obstacles | walls))
+            .contains(exit)
+    }
+
+    /// One shortest walk (by moves, over `terrain`) for piece `piece_idx`, having made the last
+    /// capture on square `loc`, to the exit: the squares that it steps onto, ending with the
+    /// exit. Walls still standing on `walls` are in the way, as the player can't break walls once
+    /// it's done capturing. This is empty if there's no exit or it's already there, and `None` if
+    /// it can't get there.
+    pub fn exit_path(&self, piece_idx: u32, loc: Square, walls: SquareSet) -> Option<Vec<Square>> {
+        let Some(exit) = self.exit else {
+            return Some(Vec::new());
+        };
+        let ty = self.mover(piece_idx)?;
+        let permeable = !(self.obstacles | walls);
+        if !self.terrain.is_empty() {
+            return terrain::walk(self, ty, loc, permeable, exit);
+        }
+        // `layers[k]` is every square reachable in at most `k` steps.
+        let mut layers = vec![SquareSet::from(loc) & permeable];
+        while !layers.last().unwrap().contains(exit) {
+            let reachable = *layers.last().unwrap();
+            let next = (reachable | self.move_reach(ty, reachable, permeable)) & permeable;
+            if next == reachable {
+                return None;
+            }
+            layers.push(next);
+        }
+        let mut path = Vec::new();
+        let mut current = exit;
+        for reachable in layers.iter().rev().skip(1) {
+            path.push(current);
+            current = reachable
+                .iter()
+                .find(|&sq| self.move_reach(ty, sq.into(), permeable).contains(current))
+                .unwrap();
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Pairs of linked portal squares. A piece that steps onto either square of a pair lands on
+    /// the other one instead, as part of the same step. Portals are otherwise empty squares: a
+    /// queen's capture down a line passes right over them.
+    ///
+    /// ```
+    /// use echochess::{solve_min_moves, Puzzle};
+    ///
+    /// // The rook steps onto the portal on b1 and comes out on g8, next to the knight.
+    /// let puzzle = Puzzle::parse("6@n/8/8/8/8/8/8/R@x5").unwrap();
+    /// assert_eq!(puzzle.portals().len(), 1);
+    /// assert_eq!(solve_min_moves(&puzzle), Some((vec![1], 2)));
+    /// ```
+    pub fn portals(&self) -> &[(Square, Square)] {
+        &self.portals
+    }
+
+    /// Which squares are icy? A piece that steps onto ice keeps sliding the same way, as part of
+    /// the same move, until the next square is blocked or it's off the ice. A knight keeps
+    /// leaping, and a grasshopper slides on one square at a time. Captures are made as usual.
+    ///
+    /// ```
+    /// use echochess::{solve_min_moves, Puzzle};
+    ///
+    /// // The king steps onto the ice and slides to g1, next to the knight.
+    /// let puzzle = Puzzle::parse("K~~~~~~n").unwrap();
+    /// assert_eq!(puzzle.ice().count(), 6);
+    /// assert_eq!(solve_min_moves(&puzzle), Some((vec![1], 2)));
+    /// // Without the ice, it walks the whole way.
+    /// assert_eq!(solve_min_moves(&Puzzle::parse("K6n").unwrap()), Some((vec![1], 7)));
+    /// ```
+    pub fn ice(&self) -> SquareSet {
+        self.ice
+    }
+
+    /// Which squares are one-way squares facing `heading`? A piece can only enter one of them
+    /// with a step heading that way: straight, or on a diagonal that heads that way in part. A
+    /// move or capture down a line, like a queen's, can't pass through one facing against it,
+    /// though a grasshopper or cannon can still hop over it, as over anything else in its way.
+    /// Leaving a one-way square is always free.
+    ///
+    /// ```
+    /// use echochess::{solve_min_moves, Heading, Puzzle};
+    ///
+    /// // The rook can't go left through b1, so it has to go around through the top rank.
+    /// let puzzle = Puzzle::parse("3/n>R").unwrap();
+    /// assert_eq!(puzzle.one_way(Heading::Right).count(), 1);
+    /// assert_eq!(solve_min_moves(&puzzle), Some((vec![0], 4)));
+    /// // Facing the other way, b1 lets it straight through.
+    /// assert_eq!(solve_min_moves(&Puzzle::parse("3/n<R").unwrap()), Some((vec![0], 2)));
+    /// ```
+    pub fn one_way(&self, heading: Heading) -> SquareSet {
+        self.one_way[heading as usize]
+    }
+
+    /// Every one-way square, whichever way it faces.
+    fn one_way_squares(&self) -> SquareSet {
+        self.one_way
+            .iter()
+            .fold(SquareSet(0), |acc, &squares| acc | squares)
+    }
+
+    /// The squares of the breakable walls. A wall blocks every piece, but a piece of one of the
+    /// `wall_breakers` types can capture it to break it down. That takes a move like any other
+    /// capture, but the player stays the same type, now standing where the wall was. Walls don't
+    /// need to be broken to win, and any still standing block the walk to the exit.
+    ///
+    /// The search tells which walls are still standing by giving each wall a bit in
+    /// `PuzzleState::remaining_captures`, alongside the pieces. While the player stands on a
+    /// broken wall, its current index is one of the wall's slots, one for each breaker type, just
+    /// past the pieces. So with walls, a puzzle can have fewer than `MAX_PIECES` pieces: every
+    /// slot counts as one.
+    ///
+    /// ```
+    /// use echochess::{solve_min_moves, PieceType, Puzzle};
+    ///
+    /// // The rook breaks through the wall on b1 to reach the knight. A bishop can't.
+    /// let mut puzzle = Puzzle::parse("R+n/xxx").unwrap();
+    /// assert_eq!(puzzle.walls().len(), 1);
+    /// assert_eq!(solve_min_moves(&puzzle), Some((vec![2, 1], 2)));
+    /// assert_eq!(puzzle.wall_square(2), "b2".parse().ok());
+    /// puzzle.set_wall_breakers(&[PieceType::Bishop]).unwrap();
+    /// assert_eq!(solve_min_moves(&puzzle), None);
+    /// ```
+    pub fn walls(&self) -> &[Square] {
+        &self.walls
+    }
+
+    /// Which piece types can break walls? By default, only rooks. See `Puzzle::walls`.
+    pub fn wall_breakers(&self) -> &[PieceType] {
+        &self.wall_breakers
+    }
+
+    /// Changes which piece types can break walls. This changes how many slots the walls take, so
+    /// it fails if there wouldn't be room for them all. See `Puzzle::walls`.
+    pub fn set_wall_breakers(&mut self, types: &[PieceType]) -> Result<(), BuildError> {
+        let slots = self.num_pieces() as usize + self.walls.len() * types.len().max(1);
+        if slots > MAX_PIECES {
+            return Err(BuildError::TooManyPieces(slots));
+        }
+        self.wall_breakers = types.to_vec();
+        Ok(())
+    }
+
+    /// If `piece_idx` is a wall's slot rather than a piece, the square of the wall, which is
+    /// where the player stands after breaking it. See `Puzzle::walls`.
+    pub fn wall_square(&self, piece_idx: u32) -> Option<Square> {
+        self.wall_slot(piece_idx).map(|(wall, _)| self.walls[wall])
+    }
+
+    /// How many piece indices a state can use: the pieces, then the walls' slots.
+    fn num_slots(&self) -> u32 {
+        self.num_pieces() + self.walls.len() as u32 * self.wall_stride()
+    }
+
+    /// How many slots each wall takes: one per breaker type, but at least one for its bit.
+    fn wall_stride(&self) -> u32 {
+        self.wall_breakers.len().max(1) as u32
+    }
+
+    /// If `piece_idx` is a wall's slot, the index of the wall and of the breaker type.
+    fn wall_slot(&self, piece_idx: u32) -> Option<(usize, usize)> {
+        if self.walls.is_empty() || self.piece_type(piece_idx).is_some() {
+            return None;
+        }
+        let k = piece_idx.checked_sub(self.num_pieces())?;
+        let (wall, breaker) = (
+            (k / self.wall_stride()) as usize,
+            (k % self.wall_stride()) as usize,
+        );
+        (wall < self.walls.len() && breaker < self.wall_breakers.len()).then_some((wall, breaker))
+    }
+
+    /// The slot of wall `wall` for breaker type `breaker`. The first slot of each wall also holds
+    /// its bit in `PuzzleState::remaining_captures`.
+    fn wall_slot_idx(&self, wall: usize, breaker: usize) -> u32 {
+        self.num_pieces() + wall as u32 * self.wall_stride() + breaker as u32
+    }
+
+    /// Bitmask of the walls' bits in `PuzzleState::remaining_captures`.
+    fn wall_bits(&self) -> u32 {
+        (0..self.walls.len()).fold(0, |acc, wall| acc | 1 << self.wall_slot_idx(wall, 0))
+    }
+
+    /// Which wall, if any, is still standing on square `loc`, given the `remaining` bitmask?
+    fn standing_wall(&self, loc: Square, remaining: u32) -> Option<usize> {
+        let wall = self.walls.iter().position(|&sq| sq == loc)?;
+        (remaining & (1 << self.wall_slot_idx(wall, 0)) != 0).then_some(wall)
+    }
+
+    /// The type of the player when it's `piece_idx`, which may be a wall's slot.
+    pub(crate) fn player_type(&self, piece_idx: u32) -> Option<PieceType> {
+        match self.wall_slot(piece_idx) {
+            Some((_, breaker)) => Some(self.wall_breakers[breaker]),
+            None => self.piece_type(piece_idx),
+        }
+    }
+
+    /// Can the player break walls when it's `piece_idx`?
+    fn breaks_walls(&self, piece_idx: u32) -> bool {
+        self.player_type(piece_idx)
+            .is_some_and(|ty| self.wall_breakers.contains(&ty))
+    }
+
+    /// Does every piece move just as its `PieceType` says, with no portals, ice, or one-way
+    /// squares to change where its steps go? If so, the types' own bitboard fills apply.
+    fn plain(&self) -> bool {
+        self.portals.is_empty() && self.ice.is_empty() && self.one_way_squares().is_empty()
+    }
+
+    /// Every square that holds a portal.
+    fn portal_squares(&self) -> SquareSet {
+        self.portals.iter().flat_map(|&(a, b)| [a, b]).collect()
+    }
+
+    /// Sends each square in `squares` that holds a portal to its twin, as a piece landing there
+    /// would be.
+    fn warp(&self, squares: SquareSet) -> SquareSet {
+        if self.portals.is_empty() {
+            return squares;
+        }
+        let mut res = squares & !self.portal_squares();
+        for &(a, b) in &self.portals {
+            if squares.contains(a) {
+                res.insert(b);
+            }
+            if squares.contains(b) {
+                res.insert(a);
+            }
+        }
+        res
+    }
+
+    /// Like `PieceType::move_reach`, but a step can't enter a one-way square facing against it.
+    fn step_reach(&self, ty: PieceType, from: SquareSet, permeable: SquareSet) -> SquareSet {
+        if self.one_way_squares().is_empty() {
+            ty.move_reach(from, permeable)
+        } else {
+            oneway::reach(self, from, permeable, |from, permeable| {
+                ty.move_reach(from, permeable)
+            })
+        }
+    }
+
+    /// Like `PieceType::capture_reach`, but a capture can't pass through a one-way square facing
+    /// against it.
+    fn capture_reach(&self, ty: PieceType, from: SquareSet, permeable: SquareSet) -> SquareSet {
+        if self.one_way_squares().is_empty() {
+            ty.capture_reach(from, permeable)
+        } else {
+            oneway::reach(self, from, permeable, |from, permeable| {
+                ty.capture_reach(from, permeable)
+            })
+        }
+    }
+
+    /// Like `PieceType::move_reach`, over this puzzle's ice and one-way squares and through its
+    /// portals.
+    fn move_reach(&self, ty: PieceType, from: SquareSet, permeable: SquareSet) -> SquareSet {
+        if self.ice.is_empty() {
+            self.warp(self.step_reach(ty, from, permeable))
+        } else {
+            self.warp(ice::move_reach(self, ty, from, permeable))
+        }
+    }
+
+    /// Like `PieceType::fill`, over this puzzle's ice and one-way squares and through its
+    /// portals.
+    fn fill(&self, ty: PieceType, from: SquareSet, permeable: SquareSet) -> SquareSet {
+        self.fill_avoiding(ty, from, permeable, SquareSet(0))
+    }
+
+    /// Like `fill`, but no step can end on a square in `avoid`, though it can pass over one.
+    fn fill_avoiding(
+        &self,
+        ty: PieceType,
+        from: SquareSet,
+        permeable: SquareSet,
+        avoid: SquareSet,
+    ) -> SquareSet {
+        if self.plain() && avoid.is_empty() {
+            return ty.fill(from, permeable);
+        }
+        let mut reachable = from & permeable;
+        loop {
+            let steps = self.move_reach(ty, reachable, permeable) & !avoid;
+            let next = (reachable | steps) & permeable;
+            if next == reachable {
+                return reachable;
+            }
+            reachable = next;
+        }
+    }
+
+    /// Like `PieceType::captures`, on this puzzle's board, where no step can end on a square in
+    /// `avoid`.
+    fn captures(
+        &self,
+        ty: PieceType,
+        from: SquareSet,
+        obstacles: SquareSet,
+        targets: SquareSet,
+        avoid: SquareSet,
+    ) -> SquareSet {
+        if self.plain() && avoid.is_empty() {
+            return ty.captures(from, obstacles, targets);
+        }
+        let permeable = !(obstacles | targets);
+        let reachable = self.fill_avoiding(ty, from, permeable, avoid);
+        self.capture_reach(ty, reachable, permeable) & targets
+    }
+
+    /// Like `PieceType::capture_moves`, on this puzzle's board, where no step can end on a
+    /// square in `avoid`.
+    fn capture_moves<F: FnMut(Square, u32)>(
+        &self,
+        ty: PieceType,
+        from: SquareSet,
+        obstacles: SquareSet,
+        targets: SquareSet,
+        avoid: SquareSet,
+        consume: F,
+    ) {
+        if !self.terrain.is_empty() {
+            return terrain::capture_moves(self, ty, from, obstacles, targets, avoid, consume);
+        }
+        if self.plain() && avoid.is_empty() {
+            return ty.capture_moves(from, obstacles, targets, consume);
+        }
+        capture_moves_with(
+            from,
+            obstacles,
+            targets,
+            consume,
+            |from, permeable| self.move_reach(ty, from, permeable) & !avoid,
+            |from, permeable| self.capture_reach(ty, from, permeable),
+        )
+    }
+
+    /// Like `PieceType::capture_path`, on this puzzle's board, where no step can end on a
+    /// square in `avoid`.
+    fn capture_path(
+        &self,
+        ty: PieceType,
+        from: Square,
+        obstacles: SquareSet,
+        targets: SquareSet,
+        avoid: SquareSet,
+        to: Square,
+    ) -> Option<Vec<Square>> {
+        if !self.terrain.is_empty() {
+            return terrain::capture_path(self, ty, from, obstacles, targets, avoid, to);
+        }
+        if self.plain() && avoid.is_empty() {
+            return ty.capture_path(from, obstacles, targets, to);
+        }
+        capture_path_with(
+            from,
+            obstacles,
+            targets,
+            to,
+            |from, permeable| self.move_reach(ty, from, permeable) & !avoid,
+            |from, permeable| self.capture_reach(ty, from, permeable),
+        )
+    }
+
+    /// Patrol routes, as `(piece_idx, route)` pairs. See `Puzzle::add_patrol`.
+    pub fn patrols(&self) -> &[(u32, Vec<Square>)] {
+        &self.patrols
+    }
+
+    /// How many pieces are on the board, including the player's?
+    pub fn num_pieces(&self) -> u32 {
+        self.piece_locs.iter().take_while(|z| **z != 0xff).count() as u32
+    }
+
+    /// Whether any piece actually moves in this puzzle.
+    pub fn patrolling(&self) -> bool {
+        self.rules.patrols && !self.patrols.is_empty()
+    }
+
+    /// The patrol route of piece `piece_idx`, if it has one and `rules.patrols` is set.
+    pub fn patrol_route(&self, piece_idx: u32) -> Option<&[Square]> {
+        if !self.rules.patrols {
+            return None;
+        }
+        let (_, route) = self.patrols.iter().find(|(i, _)| *i == piece_idx)?;
+        Some(route)
+    }
+
+    /// Which square is piece `piece_idx` on after the player has made `phase` captures,
+    /// assuming that it hasn't been captured itself?
+    pub fn piece_loc_at(&self, piece_idx: u32, phase: u32) -> Square {
+        if let Some(loc) = self.wall_square(piece_idx) {
+            return loc;
+        }
+        match self.patrol_route(piece_idx) {
+            Some(route) => route[phase as usize % route.len()],
+            None => Square(self.piece_locs[piece_idx as usize]),
+        }
+    }
+
+    /// Which square is the player on after `phase` captures, given that it is currently piece
+    /// `player_idx`? The player stays wherever it made its last capture.
+    pub fn player_loc_at(&self, player_idx: u32, phase: u32) -> Square {
+        // The starting piece never patrols, so this is right for `phase == 0`, too.
+        self.piece_loc_at(player_idx, phase.saturating_sub(1))
+    }
+
+    /// A copy of this puzzle as it stands in `state`, for drawing: the pieces still to be
+    /// captured on their squares as of then, the player's piece on its square as whatever it has
+    /// become, which is then the only start, and any broken walls gone. Patrol routes are left
+    /// out, since the patrollers have moved along them.
+    ///
+    /// ```
+    /// use echochess::game::Game;
+    /// use echochess::Puzzle;
+    ///
+    /// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+    /// let mut game = Game::new(puzzle.clone());
+    /// assert_eq!(puzzle.position(game.state()), puzzle);
+    /// game.apply("c3".parse().unwrap()).unwrap();
+    /// assert_eq!(puzzle.position(game.state()).to_fen(), "2B/3/1n1");
+    /// ```
+    pub fn position(&self, state: PuzzleState) -> Puzzle {
+        let phase = state.phase(self);
+        let remaining = state.remaining_captures();
+        let player_idx = state.current_piece_idx();
+        let player = self.player_loc_at(player_idx, phase);
+        let mut pieces = [None; 64];
+        for i in (0..self.num_pieces()).filter(|i| remaining & (1 << i) != 0) {
+            pieces[self.piece_loc_at(i, phase).0 as usize] = self.piece_type(i);
+        }
+        pieces[player.0 as usize] = self.player_type(player_idx);
+        let board = Puzzle::from_board(self.obstacles, &pieces, player, (self.width, self.height));
+        let mut res = self.clone();
+        res.piece_types = board.piece_types;
+        res.piece_locs = board.piece_locs;
+        res.pieces_by_loc = board.pieces_by_loc;
+        res.player_start = board.player_start;
+        res.starts = vec![board.player_start];
+        res.patrols = Vec::new();
+        res.walls = state.standing_walls(self).iter().collect();
+        res.optional = (0..self.num_pieces())
+            .filter(|&i| remaining & (1 << i) != 0 && !self.required(i))
+            .filter_map(|i| board.piece_on(self.piece_loc_at(i, phase)))
+            .fold(0, |optional, i| optional | 1 << i);
+        res
+    }
+
+    /// Which piece among `remaining` (a bitmask of piece indices) is on square `loc` after
+    /// `phase` captures? If a patroller is passing through a square that also
+    /// holds a stationary piece, the stationary piece is the one that gets captured. Panics if
+    /// there is no such piece.
+    pub(crate) fn piece_at(&self, loc: Square, remaining: u32, phase: u32) -> u32 {
+        let idx = u32::from(self.pieces_by_loc[loc.0 as usize]);
+        if idx != 0xff && remaining & (1 << idx) != 0 && self.patrol_route(idx).is_none() {
+            return idx;
+        }
+        self.patrols
+            .iter()
+            .map(|(i, _)| *i)
+            .filter(|&i| remaining & (1 << i) != 0 && self.piece_loc_at(i, phase) == loc)
+            .min()
+            .unwrap_or_else(|| panic!("no piece on square {} in phase {}", loc, phase))
+    }
+
+    /// Does any patroller among `remaining` (a bitmask of piece indices) stand on square `loc`
+    /// after `phase` captures?
+    pub(crate) fn patroller_arrives(&self, remaining: u32, phase: u32, loc: Square) -> bool {
+        self.patrols
+            .iter()
+            .any(|&(i, _)| remaining & (1 << i) != 0 && self.piece_loc_at(i, phase) == loc)
+    }
+
+    /// Makes piece `piece_idx` patrol a fixed cycle of squares: after each of the
+    /// player's captures, it advances one step along `route`, wrapping around at the end. Only
+    /// takes effect when `rules.patrols` is set.
+    ///
+    /// The route must start on the piece's own square, avoid obstacles, and have each square
+    /// (including the first, after the last) one move step of the piece away from the square
+    /// before it. The player's starting piece can't patrol.
+    pub fn add_patrol(&mut self, piece_idx: u32, route: &[Square]) -> Result<(), PatrolError> {
+        let ty = self
+            .mover(piece_idx)
+            .ok_or(PatrolError::NoSuchPiece(piece_idx))?;
+        if piece_idx == self.player_start {
+            return Err(PatrolError::PlayerCannotPatrol);
+        }
+        if self.patrols.iter().any(|(i, _)| *i == piece_idx) {
+            return Err(PatrolError::AlreadyPatrols(piece_idx));
+        }
+        if route.first() != Some(&self.piece_loc(piece_idx)) {
+            return Err(PatrolError::WrongStart);
+        }
+        for (i, &loc) in route.iter().enumerate() {
+            if self.obstacles.contains(loc) {
+                return Err(PatrolError::HitsObstacle(loc));
+            }
+            if route.len() == 1 {
+                break;
+            }
+            let here = SquareSet::from(loc);
+            let next = route[(i + 1) % route.len()];
+            if !ty.move_steps(here).contains(next) {
+                return Err(PatrolError::NotConnected(loc));
+            }
+        }
+        self.patrols.push((piece_idx, route.to_vec()));
+        Ok(())
+    }
+}
+
+/// Why a patrol route was rejected. See `Puzzle::add_patrol`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatrolError {
+    /// There's no piece with the given index.
+    NoSuchPiece(u32),
+    /// The player's starting piece can't patrol.
+    PlayerCannotPatrol,
+    /// The piece with the given index already has a patrol route.
+    AlreadyPatrols(u32),
+    /// The route doesn't start on the patroller's square.
+    WrongStart,
+    /// The route runs into an obstacle on the given square.
+    HitsObstacle(Square),
+    /// The patroller can't step from the given square to the next one on its route.
+    NotConnected(Square),
+}
+
+impl std::fmt::Display for PatrolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PatrolError::NoSuchPiece(idx) => write!(f, "No piece {} to patrol", idx),
+            PatrolError::PlayerCannotPatrol => write!(f, "The player can't patrol"),
+            PatrolError::AlreadyPatrols(idx) => write!(f, "Piece {} already patrols", idx),
+            PatrolError::WrongStart => write!(f, "Patrol route must start on the piece's square"),
+            PatrolError::HitsObstacle(sq) => write!(f, "Patrol route hits obstacle at {}", sq),
+            PatrolError::NotConnected(sq) => {
+                write!(f, "Patrol route not connected after square {}", sq)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatrolError {}
+
+/// Bits 0 through 26 (inclusive) indicate which pieces still need to be captured. The integer
+/// formed by bits 27 through 31 (i.e., the value of `z >> 27`) indicates which piece is currently
+/// the player.
+///
+/// Thus, this type can represent puzzles with up to 27 distinct pieces across both colors. The
+/// initial state is `(((1 << num_pieces) - 1) & !(1 << player_start)) | (player_start << 27)`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PuzzleState(u32);
+
+impl PuzzleState {
+    /// Computes the initial state for a puzzle.
+    pub fn initial(p: &Puzzle) -> Self {
+        let num_pieces = p.piece_locs.iter().take_while(|z| **z != 0xff).count();
+        let to_capture = ((1 << num_pieces) - 1) & !(1 << p.player_start);
+        if to_capture & p.required_bits() == 0 {
+            return PuzzleState(p.player_start << 27);
+        }
+        PuzzleState(to_capture | p.wall_bits() | (p.player_start << 27))
+    }
+
+    /// Checks whether the player has won: i.e., if all required opposing pieces have been
+    /// captured. Any walls still standing then are forgotten, along with any optional pieces
+    /// left, so that this needn't know which bits are which.
+    pub fn done(self) -> bool {
+        self.remaining_captures() == 0
+    }
+
+    /// Which piece (`0..27`) is the player currently?
+    pub fn current_piece_idx(self) -> u32 {
+        self.0 >> 27
+    }
+
+    /// Bitmask of the piece indices (`0..27`) that still need to be captured, and of the walls
+    /// still standing. See `Puzzle::walls`.
+    pub fn remaining_captures(self) -> u32 {
+        self.0 & 0x07ffffff
+    }
+
+    /// How many captures has the player made so far, counting broken walls? Patrollers'
+    /// positions are a function of this phase, so it needn't be stored separately.
+    pub fn phase(self, p: &Puzzle) -> u32 {
+        p.num_pieces() - 1 + p.walls.len() as u32 - self.remaining_captures().count_ones()
+    }
+
+    /// Computes the player's square, the squares that it can't move through, the squares of all
+    /// pieces still to be captured, and the squares that it can pass over but not stop on. Walls
+    /// still standing are targets if the player can break them and obstacles otherwise. Under
+    /// `Rules::threats`, attacked squares are to be avoided, or are obstacles too under
+    /// `Rules::threats_block_lines`, and defended pieces are obstacles rather than targets.
+    fn setup(self, p: &Puzzle) -> Setup {
+        let phase = if p.patrolling() { self.phase(p) } else { 0 };
+        let start = SquareSet::from(p.player_loc_at(self.current_piece_idx(), phase));
+        let mut targets = SquareSet(0);
+        let mut remaining = self.remaining_captures();
+        while remaining != 0 {
+            let i = remaining.trailing_zeros();
+            // `i` (0..27) is the index of a piece that still needs to be captured
+            targets.insert(p.piece_loc_at(i, phase));
+            remaining &= remaining - 1;
+        }
+        let (mut obstacles, mut targets) =
+            if p.walls.is_empty() || p.breaks_walls(self.current_piece_idx()) {
+                (p.obstacles, targets)
+            } else {
+                let walls = self.standing_walls(p);
+                (p.obstacles | walls, targets & !walls)
+            };
+        if !p.rules.threats {
+            return (start, obstacles, targets, SquareSet(0));
+        }
+        let attacked = self.attacked(p, phase);
+        obstacles = obstacles | (targets & attacked);
+        targets = targets & !attacked;
+        if p.rules.threats_block_lines {
+            // The player can always leave its own square, attacked or not.
+            (
+                start,
+                obstacles | (attacked & !start),
+                targets,
+                SquareSet(0),
+            )
+        } else {
+            (start, obstacles, targets, attacked)
+        }
+    }
+
+    /// The squares that the pieces still to be captured attack after `phase` captures: the ones
+    /// that each could capture on if it were the player, with the player's own square no
+    /// obstacle, as it's about to move. Walls attack nothing. See `Rules::threats`.
+    fn attacked(self, p: &Puzzle, phase: u32) -> SquareSet {
+        let mut enemies = self.remaining_captures() & !p.wall_bits();
+        let mut locs = SquareSet(0);
+        let mut bits = enemies;
+        while bits != 0 {
+            locs.insert(p.piece_loc_at(bits.trailing_zeros(), phase));
+            bits &= bits - 1;
+        }
+        let permeable = !(p.obstacles | self.standing_walls(p) | locs);
+        let mut res = SquareSet(0);
+        while enemies != 0 {
+            let i = enemies.trailing_zeros();
+            let ty = p.mover(i).expect("remaining pieces exist");
+            res = res | p.capture_reach(ty, p.piece_loc_at(i, phase).into(), permeable);
+            enemies &= enemies - 1;
+        }
+        res
+    }
+
+    /// Every state that might come right before this one, though not all of them need be valid
+    /// or lead here: the last capture put its piece's bit (or its wall's) back, and the player
+    /// was one of the pieces or broken walls left over. A win forgets which walls were standing,
+    /// so before a win, any of them might have been.
+    fn maybe_previous(self, p: &Puzzle) -> Vec<PuzzleState> {
+        let current = self.current_piece_idx();
+        let captured = match p.wall_slot(current) {
+            Some((wall, _)) => p.wall_slot_idx(wall, 0),
+            None => current,
+        };
+        let before = self.remaining_captures() | (1 << captured);
+        let mut standings = vec![0];
+        if self.done() && captured == current {
+            let all = (p.wall_bits() | p.optional) & !(1 << captured);
+            let mut walls = all;
+            while walls != 0 {
+                standings.push(walls);
+                walls = (walls - 1) & all;
+            }
+        }
+        let mut res = Vec::new();
+        for before in standings.into_iter().map(|walls| before | walls) {
+            for prev_idx in (0..p.num_slots()).filter(|&i| before & (1 << i) == 0) {
+                let broken = match p.wall_slot(prev_idx) {
+                    Some((wall, _)) => before & (1 << p.wall_slot_idx(wall, 0)) == 0,
+                    None => p.piece_type(prev_idx).is_some(),
+                };
+                if broken {
+                    res.push(PuzzleState(before | (prev_idx << 27)));
+                }
+            }
+        }
+        res
+    }
+
+    /// The squares of the walls still standing.
+    fn standing_walls(self, p: &Puzzle) -> SquareSet {
+        (0..p.walls.len())
+            .filter(|&wall| self.remaining_captures() & (1 << p.wall_slot_idx(wall, 0)) != 0)
+            .map(|wall| p.walls[wall])
+            .collect()
+    }
+
+    /// Computes the state after capturing whatever is on square `loc`, which
+    /// must hold a piece that still needs to be captured. Returns `None` if a patroller steps
+    /// onto the player right after the capture, ending the run, or if it's the last capture and
+    /// leaves the player no way to the exit.
+    pub(crate) fn capture(self, p: &Puzzle, loc: Square) -> Option<PuzzleState> {
+        let remaining = self.remaining_captures();
+        if let Some(wall) = p.standing_wall(loc, remaining) {
+            let ty = p.player_type(self.current_piece_idx())?;
+            let breaker = p.wall_breakers.iter().position(|&b| b == ty)?;
+            let new_captures = remaining & !(1 << p.wall_slot_idx(wall, 0));
+            if p.patrolling() && p.patroller_arrives(new_captures, self.phase(p) + 1, loc) {
+                return None;
+            }
+            return Some(Self(new_captures | (p.wall_slot_idx(wall, breaker) << 27)));
+        }
+        let (piece_idx, mut new_captures) = if !p.patrolling() {
+            let piece_idx = u32::from(p.pieces_by_loc[loc.0 as usize]);
+            (piece_idx, remaining & !(1 << piece_idx))
+        } else {
+            let phase = self.phase(p);
+            let piece_idx = p.piece_at(loc, remaining, phase);
+            let new_captures = remaining & !(1 << piece_idx);
+            if p.patroller_arrives(new_captures, phase + 1, loc) {
+                return None;
+            }
+            (piece_idx, new_captures)
+        };
+        if new_captures & p.required_bits() == 0 {
+            if !p.can_exit(piece_idx, loc, self.standing_walls(p)) {
+                return None;
+            }
+            new_captures = 0;
+        }
+        Some(Self(new_captures | (piece_idx << 27)))
+    }
+
+    /// Calls `consume(next_state)` for each successor state. Fails if this state's current piece
+    /// doesn't exist in `p`, which can only happen if the state came from a different puzzle.
+    pub fn next_states<F: FnMut(PuzzleState)>(
+        self,
+        p: &Puzzle,
+        mut consume: F,
+    ) -> Result<(), NoSuchPieceError> {
+        let player_idx = self.current_piece_idx();
+        let ty = p.mover(player_idx).ok_or(NoSuchPieceError(player_idx))?;
+        let (start, obstacles, targets, avoid) = self.setup(p);
+        for sq in p.captures(ty, start, obstacles, targets, avoid) {
+            // `sq` holds a piece that can be captured
+            if let Some(new_state) = self.capture(p, sq) {
+                consume(new_state);
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls `consume(loc, moves)` for each square `loc` holding a piece that the player can
+    /// capture, where `moves` is the fewest steps that the capture takes. For the last capture,
+    /// that includes the walk to the exit, and a last capture with no way to the exit is skipped.
+    pub(crate) fn capture_squares<F: FnMut(Square, u32)>(
+        self,
+        p: &Puzzle,
+        mut consume: F,
+    ) -> Result<(), NoSuchPieceError> {
+        let player_idx = self.current_piece_idx();
+        let ty = p.mover(player_idx).ok_or(NoSuchPieceError(player_idx))?;
+        let (start, obstacles, targets, avoid) = self.setup(p);
+        let enemies = self.remaining_captures() & p.required_bits();
+        if enemies == 0 {
+            return Ok(()); // already won, so there's nothing left to capture
+        }
+        if p.exit.is_none() || enemies.count_ones() > 1 {
+            p.capture_moves(ty, start, obstacles, targets, avoid, consume);
+            return Ok(());
+        }
+        let walls = self.standing_walls(p);
+        let last = enemies.trailing_zeros();
+        let last_loc = p.piece_loc_at(last, self.phase(p));
+        p.capture_moves(ty, start, obstacles, targets, avoid, |loc, moves| {
+            if loc != last_loc {
+                consume(loc, moves); // not the last capture, so no walk to the exit
+            } else if let Some(walk) = p.exit_path(last, loc, walls) {
+                consume(
+                    loc,
+                    moves + walk.iter().map(|&sq| p.move_cost(sq)).sum::<u32>(),
+                );
+            }
+        });
+        Ok(())
+    }
+
+    /// One shortest path for the player to capture on square `loc`, as in `capture_path`, or
+    /// `None` if it can't capture there.
+    pub(crate) fn capture_path(self, p: &Puzzle, loc: Square) -> Option<Vec<Square>> {
+        let player_idx = self.current_piece_idx();
+        let ty = p.mover(player_idx)?;
+        let (start, obstacles, targets, avoid) = self.setup(p);
+        let from = Square::try_from(start).expect("player is on one square");
+        p.capture_path(ty, from, obstacles, targets, avoid, loc)
+    }
+
+    /// Like `next_states`, but looks up the captures in `cache` first, recording hits and misses
+    /// in `report`.
+    fn next_states_cached<F: FnMut(PuzzleState)>(
+        self,
+        p: &Puzzle,
+        cache: &mut CaptureCache,
+        report: &mut SolveReport,
+        mut consume: F,
+    ) -> Result<(), NoSuchPieceError> {
+        let player_idx = self.current_piece_idx();
+        let ty = p.mover(player_idx).ok_or(NoSuchPieceError(player_idx))?;
+        let captures = cache.get(p, ty, self.setup(p), report);
+        for sq in captures {
+            if let Some(new_state) = self.capture(p, sq) {
+                consume(new_state);
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `next_states`, but calls `consume(loc, moves, next_state)`, where `loc` is the square
+    /// of the captured piece and `moves` is the fewest steps that the capture takes.
+    pub fn next_states_with_moves<F: FnMut(Square, u32, PuzzleState)>(
+        self,
+        p: &Puzzle,
+        mut consume: F,
+    ) -> Result<(), NoSuchPieceError> {
+        self.capture_squares(p, |loc, moves| {
+            if let Some(new_state) = self.capture(p, loc) {
+                consume(loc, moves, new_state);
+            }
+        })
+    }
+}
+
+/// What `PuzzleState::setup` computes for a state: the player's square, the obstacles, the
+/// targets, and the squares to avoid stopping on.
+type Setup = (SquareSet, SquareSet, SquareSet, SquareSet);
+
+/// A `PuzzleState` named a current piece that the puzzle doesn't have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoSuchPieceError(pub u32);
+
+impl std::fmt::Display for NoSuchPieceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "No piece {}", self.0)
+    }
+}
+
+impl std::error::Error for NoSuchPieceError {}
+
+/// A hasher for `PuzzleState`s, which are single `u32`s, so the solver doesn't pay for SipHash on
+/// every lookup. This is the multiply-and-rotate scheme from rustc's `FxHasher`: not resistant to
+/// deliberate collisions, but the solver only hashes states it generated itself.
+#[derive(Default)]
+struct StateHasher(u64);
+
+impl std::hash::Hasher for StateHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u64(u64::from(b));
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write_u64(u64::from(n));
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+type StateSet = HashSet<PuzzleState, std::hash::BuildHasherDefault<StateHasher>>;
+type StateMap<V> = HashMap<PuzzleState, V, std::hash::BuildHasherDefault<StateHasher>>;
+
+/// Puzzles with at most this many pieces get a `VisitedStates::Table`, which takes
+/// `n * 2^n` bits: 2.5 MB at this size.
+const MAX_TABLE_PIECES: u32 = 20;
+
+/// The set of states that a breadth-first search has seen. For small puzzles, this is a bit array
+/// indexed directly by state, which is much faster than hashing; otherwise, it's a hash set.
+enum VisitedStates {
+    Table {
+        bits: Vec<u64>,
+        num_pieces: u32,
+        len: usize,
+    },
+    Hashed(StateSet),
+}
+
+impl VisitedStates {
+    fn new(p: &Puzzle) -> Self {
+        let num_pieces = p.num_slots();
+        if num_pieces > MAX_TABLE_PIECES {
+            return VisitedStates::Hashed(StateSet::default());
+        }
+        let num_bits = (num_pieces as usize) << num_pieces;
+        VisitedStates::Table {
+            bits: vec![0; num_bits.div_ceil(64)],
+            num_pieces,
+            len: 0,
+        }
+    }
+
+    /// Where `state` lives in a table for a puzzle with `num_pieces` pieces.
+    fn index(state: PuzzleState, num_pieces: u32) -> usize {
+        ((state.current_piece_idx() as usize) << num_pieces) | state.remaining_captures() as usize
+    }
+
+    /// Adds a state, returning whether it's new.
+    fn insert(&mut self, state: PuzzleState) -> bool {
+        match self {
+            VisitedStates::Table {
+                bits,
+                num_pieces,
+                len,
+            } => {
+                let i = Self::index(state, *num_pieces);
+                let (word, mask) = (&mut bits[i / 64], 1 << (i % 64));
+                let new = *word & mask == 0;
+                *word |= mask;
+                *len += new as usize;
+                new
+            }
+            VisitedStates::Hashed(set) => set.insert(state),
+        }
+    }
+
+    fn contains(&self, state: PuzzleState) -> bool {
+        match self {
+            VisitedStates::Table {
+                bits, num_pieces, ..
+            } => {
+                let i = Self::index(state, *num_pieces);
+                bits[i / 64] & (1 << (i % 64)) != 0
+            }
+            VisitedStates::Hashed(set) => set.contains(&state),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            VisitedStates::Table { len, .. } => *len,
+            VisitedStates::Hashed(set) => set.len(),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = PuzzleState> + '_> {
+        match self {
+            VisitedStates::Table {
+                bits, num_pieces, ..
+            } => {
+                let n = *num_pieces;
+                Box::new(bits.iter().enumerate().flat_map(move |(w, &word)| {
+                    (0..64).filter(move |b| word & (1 << b) != 0).map(move |b| {
+                        let i = w * 64 + b;
+                        let remaining = i as u32 & ((1 << n) - 1);
+                        PuzzleState(remaining | ((i >> n) as u32) << 27)
+                    })
+                }))
+            }
+            VisitedStates::Hashed(set) => Box::new(set.iter().copied()),
+        }
+    }
+}
+
+/// A solution to a puzzle, with every step spelled out, in a form that can be saved alongside it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Solution {
+    pub moves: Vec<Move>,
+    /// The squares that the player steps onto after the last capture to reach the exit, if the
+    /// puzzle has one. See `Puzzle::exit`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub exit_path: Vec<Square>,
+}
+
+/// One capture in a `Solution`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Move {
+    /// The squares that the player steps onto, in order, ending with the captured piece's square.
+    /// This is one of the shortest such paths.
+    pub path: Vec<Square>,
+    /// Index of the piece captured.
+    pub captured: u32,
+}
+
+impl Solution {
+    /// Fills in the paths for a list of piece indices to be captured in order, as returned by
+    /// `solve`. Returns `None` if that isn't a valid line of play, even if it's unfinished: in
+    /// particular, if an index isn't a piece or wall of the puzzle, or names one that has already
+    /// been captured. See `Puzzle::verify` for why.
+    ///
+    /// ```
+    /// use echochess::{Puzzle, Solution};
+    ///
+    /// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+    /// assert_eq!(Solution::from_captures(&puzzle, &[0, 2]).unwrap().num_steps(), 4);
+    /// assert!(Solution::from_captures(&puzzle, &[0]).is_some());
+    /// // Out of range, already captured, the player's own piece, and past the win:
+    /// for bad in [&[5][..], &[31], &[100], &[0, 0], &[1], &[0, 2, 2]] {
+    ///     assert_eq!(Solution::from_captures(&puzzle, bad), None);
+    /// }
+    /// ```
+    pub fn from_captures(p: &Puzzle, captures: &[u32]) -> Option<Solution> {
+        let mut state = PuzzleState::initial(p);
+        let mut walls = state.standing_walls(p);
+        let mut moves = Vec::with_capacity(captures.len());
+        for &captured in captures {
+            if state.done() || captured >= p.num_slots() {
+                return None;
+            }
+            let bit = match p.wall_slot(captured) {
+                Some((wall, _)) => p.wall_slot_idx(wall, 0),
+                None => captured,
+            };
+            if state.remaining_captures() & (1 << bit) == 0 {
+                return None;
+            }
+            walls = state.standing_walls(p);
+            let loc = p.piece_loc_at(captured, state.phase(p));
+            let path = state.capture_path(p, loc)?;
+            let next = state.capture(p, loc)?;
+            if next.current_piece_idx() != captured {
+                return None;
+            }
+            moves.push(Move { path, captured });
+            state = next;
+        }
+        let exit_path = match moves.last() {
+            Some(last) if state.done() => {
+                p.exit_path(last.captured, *last.path.last().unwrap(), walls)?
+            }
+            _ => Vec::new(),
+        };
+        Some(Solution { moves, exit_path })
+    }
+
+    /// Indices of the pieces captured, in order.
+    pub fn captures(&self) -> Vec<u32> {
+        self.moves.iter().map(|m| m.captured).collect()
+    }
+
+    /// Total number of steps taken, counting each capture as one, and including the walk to the
+    /// exit.
+    pub fn num_steps(&self) -> u32 {
+        let captures: u32 = self.moves.iter().map(|m| m.path.len() as u32).sum();
+        captures + self.exit_path.len() as u32
+    }
+
+    /// Total number of moves taken in puzzle `p`, like `num_steps` but with each step costing
+    /// what its square does. See `Puzzle::terrain`.
+    pub fn num_moves(&self, p: &Puzzle) -> u32 {
+        let paths = self.moves.iter().map(|m| &m.path).chain([&self.exit_path]);
+        paths.flatten().map(|&sq| p.move_cost(sq)).sum()
+    }
+}
+
+/// Writes the result of solving `p` as JSON, for scripts and web frontends. `sol` is the solution
+/// found, if any, and `report` the solver's statistics, if they were kept (see `Solver::stats`).
+///
+/// The format is stable: fields may be added, but not renamed or removed without bumping
+/// `version`. It's an object with
+///
+/// - `version`: `1`;
+/// - `fen`: the board, as `Puzzle::to_fen` writes it;
+/// - `puzzle`: the whole puzzle, as its `Serialize` impl writes it;
+/// - `solvable`: whether `sol` was given;
+/// - `captures`: for each move, the `piece` captured by index, the type the `player` moved as,
+///   the type `captured` (or `"wall"` for a wall broken), the `square` of the capture, and the
+///   `path` of squares stepped onto to get there;
+/// - `exit_path`: the squares stepped onto after the last capture to reach the exit, if any;
+/// - `stats`: the total `moves` (see `Solution::num_moves`), or `null` if there's no solution,
+///   and `search`, with the solver's `nodes_expanded`, `states_visited`, `frontier_peak`,
+///   `depth`, and `elapsed_ms`, or `null` if there's no report.
+///
+/// ```
+/// use echochess::{solution_json, solve_with_paths, Puzzle};
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let json = solution_json(&puzzle, solve_with_paths(&puzzle).as_ref(), None);
+/// assert!(json.starts_with(r#"{"version":1,"fen":"R1b/3/1n1","puzzle":{"#));
+/// assert!(json.contains(concat!(
+///     r#""captures":[{"piece":0,"player":"rook","captured":"knight","#,
+///     r#""square":"b1","path":["a2","a1","b1"]},"#,
+/// )));
+/// assert!(json.ends_with(r#""exit_path":[],"stats":{"moves":4,"search":null}}"#));
+///
+/// let json = solution_json(&Puzzle::parse("Rxn").unwrap(), None, None);
+/// assert!(json.contains(r#""solvable":false,"captures":[],"exit_path":[]"#));
+/// ```
+#[cfg(feature = "serde")]
+pub fn solution_json(p: &Puzzle, sol: Option<&Solution>, report: Option<&SolveReport>) -> String {
+    serde_json::to_string(&ser::SolutionRepr::new(p, sol, report)).expect("solutions serialize")
+}
+
+/// A line of play that `Puzzle::verify` found to win, with its paths and total moves.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VerifiedSolution {
+    pub solution: Solution,
+    /// The fewest total moves that the line of play can take, over any `terrain`.
+    pub moves: u32,
+}
+
+/// Why `Puzzle::verify` rejected a line of play, and at which step (counting from 0).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VerifyError {
+    /// The puzzle has no piece (or wall slot) with this index.
+    NoSuchPiece { step: usize, piece: u32 },
+    /// This piece was already captured, or is the piece that the player started as.
+    AlreadyCaptured { step: usize, piece: u32 },
+    /// The player can't capture this piece from where it stands.
+    Unreachable { step: usize, piece: u32 },
+    /// A patroller stepped onto the player right after this capture.
+    CaughtByPatroller { step: usize },
+    /// This was the last capture, but it leaves no way to the exit.
+    NoWayOut { step: usize },
+    /// The puzzle was already won before this step.
+    AlreadyWon { step: usize },
+    /// The line of play ended with pieces still left to capture. The step is the number of
+    /// captures made.
+    Unfinished { step: usize },
+}
+
+impl VerifyError {
+    /// The step at which the line of play went wrong.
+    pub fn step(self) -> usize {
+        match self {
+            VerifyError::NoSuchPiece { step, .. }
+            | VerifyError::AlreadyCaptured { step, .. }
+            | VerifyError::Unreachable { step, .. }
+            | VerifyError::CaughtByPatroller { step }
+            | VerifyError::NoWayOut { step }
+            | VerifyError::AlreadyWon { step }
+            | VerifyError::Unfinished { step } => step,
+        }
+    }
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let step = self.step() + 1;
+        match *self {
+            VerifyError::NoSuchPiece { piece, .. } => {
+                write!(f, "Step {}: no piece {}", step, piece)
+            }
+            VerifyError::AlreadyCaptured { piece, .. } => {
+                write!(f, "Step {}: piece {} is already gone", step, piece)
+            }
+            VerifyError::Unreachable { piece, .. } => {
+                write!(f, "Step {}: can't capture piece {}", step, piece)
+            }
+            VerifyError::CaughtByPatroller { .. } => {
+                write!(f, "Step {}: caught by a patroller", step)
+            }
+            VerifyError::NoWayOut { .. } => write!(f, "Step {}: no way to the exit", step),
+            VerifyError::AlreadyWon { .. } => write!(f, "Step {}: already won", step),
+            VerifyError::Unfinished { .. } => write!(f, "Unfinished after {} captures", step - 1),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+impl Puzzle {
+    /// Replays a proposed line of play, given as piece indices captured in order, as `solve`
+    /// returns. If it wins, returns it with its paths and total moves; if not, says which step
+    /// is the first to go wrong, and how. Breaking a wall is given as one of the wall's slots, as
+    /// in `Puzzle::walls`.
+    ///
+    /// ```
+    /// use echochess::{Puzzle, VerifyError};
+    ///
+    /// // The rook is walled in with the knight, so it has to take that first.
+    /// let puzzle = Puzzle::parse("Rxb/1x1/n2").unwrap();
+    /// let verified = puzzle.verify(&[0, 2]).unwrap();
+    /// assert_eq!(verified.solution.captures(), vec![0, 2]);
+    /// assert_eq!(verified.moves, 6);
+    /// assert_eq!(
+    ///     puzzle.verify(&[2, 0]),
+    ///     Err(VerifyError::Unreachable { step: 0, piece: 2 })
+    /// );
+    /// assert_eq!(
+    ///     puzzle.verify(&[0, 0]),
+    ///     Err(VerifyError::AlreadyCaptured { step: 1, piece: 0 })
+    /// );
+    /// assert_eq!(puzzle.verify(&[0]), Err(VerifyError::Unfinished { step: 1 }));
+    /// ```
+    pub fn verify(&self, captures: &[u32]) -> Result<VerifiedSolution, VerifyError> {
+        let p = self;
+        let mut state = PuzzleState::initial(p);
+        let mut walls = state.standing_walls(p);
+        let mut moves = Vec::with_capacity(captures.len());
+        for (step, &piece) in captures.iter().enumerate() {
+            if state.done() {
+                return Err(VerifyError::AlreadyWon { step });
+            }
+            if piece >= p.num_slots() {
+                return Err(VerifyError::NoSuchPiece { step, piece });
+            }
+            let bit = match p.wall_slot(piece) {
+                Some((wall, _)) => p.wall_slot_idx(wall, 0),
+                None => piece,
+            };
+            let remaining = state.remaining_captures();
+            if remaining & (1 << bit) == 0 {
+                return Err(VerifyError::AlreadyCaptured { step, piece });
+            }
+            walls = state.standing_walls(p);
+            let phase = state.phase(p);
+            let loc = p.piece_loc_at(piece, phase);
+            let unreachable = VerifyError::Unreachable { step, piece };
+            let path = state.capture_path(p, loc).ok_or(unreachable)?;
+            let Some(next) = state.capture(p, loc) else {
+                let after = remaining & !(1 << bit);
+                return Err(if p.patroller_arrives(after, phase + 1, loc) {
+                    VerifyError::CaughtByPatroller { step }
+                } else {
+                    VerifyError::NoWayOut { step }
+                });
+            };
+            if next.current_piece_idx() != piece {
+                return Err(unreachable);
+            }
+            moves.push(Move {
+                path,
+                captured: piece,
+            });
+            state = next;
+        }
+        if !state.done() {
+            let step = captures.len();
+            return Err(VerifyError::Unfinished { step });
+        }
+        let exit_path = match moves.last() {
+            Some(last) => p
+                .exit_path(last.captured, *last.path.last().unwrap(), walls)
+                .expect("last capture leaves a way to the exit"),
+            None => Vec::new(),
+        };
+        let solution = Solution { moves, exit_path };
+        let moves = solution.num_moves(p);
+        Ok(VerifiedSolution { solution, moves })
+    }
+}
+
+/// Solves a puzzle, returning a list of piece indices to be captured in order to win, or returns
+/// `None` if no solution is possible. This is `Solver::new().solve(p)` without the extras.
+pub fn solve(p: &Puzzle) -> Option<Vec<u32>> {
+    match Solver::new().solve(p).result {
+        SolveResult::Solved(captures) => Some(captures),
+        SolveResult::Unsolvable | SolveResult::OverBudget(_) => None,
+        SolveResult::GaveUp(_) => unreachable!("no limits were set"),
+    }
+}
+
+/// Like `solve`, but of all the solutions, returns the one that is smallest lexicographically by
+/// piece index. (`solve` returns whichever it finds first.)
+pub fn solve_lexicographic(p: &Puzzle) -> Option<Vec<u32>> {
+    match Solver::new().deterministic(true).solve(p).result {
+        SolveResult::Solved(captures) => Some(captures),
+        SolveResult::Unsolvable | SolveResult::OverBudget(_) => None,
+        SolveResult::GaveUp(_) => unreachable!("no limits were set"),
+    }
+}
+
+/// Like `solve`, but returns the full path that the player takes for each capture.
+pub fn solve_with_paths(p: &Puzzle) -> Option<Solution> {
+    let captures = solve(p)?;
+    Some(Solution::from_captures(p, &captures).expect("solver output is valid"))
+}
+
+/// Solves many puzzles at once, spread across threads, for checking a whole pack or a generator's
+/// output. Each result is `solve_with_paths` for the corresponding puzzle. To give each puzzle a
+/// time limit, use `Solver::solve_batch` with `Solver::timeout`.
+///
+/// ```
+/// use echochess::{solve_batch, Puzzle};
+/// let puzzles = ["8/8/8/8/8/8/8/R1n5", "8/8/8/8/8/8/P7/n7"].map(|f| Puzzle::parse(f).unwrap());
+/// let solutions = solve_batch(&puzzles);
+/// assert_eq!(solutions[0].as_ref().unwrap().captures(), vec![1]);
+/// assert!(solutions[1].is_none());
+/// ```
+pub fn solve_batch(puzzles: &[Puzzle]) -> Vec<Option<Solution>> {
+    Solver::new()
+        .solve_batch(puzzles)
+        .into_iter()
+        .zip(puzzles)
+        .map(|(outcome, p)| match outcome.result {
+            SolveResult::Solved(captures) => {
+                Some(Solution::from_captures(p, &captures).expect("solver output is valid"))
+            }
+            SolveResult::Unsolvable | SolveResult::OverBudget(_) => None,
+            SolveResult::GaveUp(_) => unreachable!("no limits were set"),
+        })
+        .collect()
+}
+
+/// Like `solve`, but searches for a path to any state satisfying `goal` (which should only
+/// accept `done` states for the result to be a solution).
+pub fn solve_until<G: Fn(PuzzleState) -> bool>(p: &Puzzle, goal: G) -> Option<Vec<u32>> {
+    // `goal` needn't treat symmetric states alike, so they can't be merged, and it may accept
+    // states that can't go on to win, so those can't be pruned.
+    let solver = Solver::new().symmetry(false).prune(false).clone();
+    let config = SearchConfig::new(&solver, std::time::Instant::now());
+    search(p, goal, &config, &mut SolveReport::default()).expect("no limits were set")
+}
+
+/// Statistics about one run of the solver, for profiling. See `Solver::stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SolveReport {
+    /// How many states had their successors generated.
+    pub nodes_expanded: usize,
+    /// How many distinct states were seen, including the initial state. After falling back to
+    /// depth-first search, states seen more than once are counted each time.
+    pub states_visited: usize,
+    /// The most states waiting to be expanded at any one time.
+    pub frontier_peak: usize,
+    /// How many captures deep the search went, which is the number of captures in the solution if
+    /// there is one.
+    pub depth: usize,
+    /// Wall time taken.
+    pub elapsed: std::time::Duration,
+    /// Did the search hit `Solver::memory_cap` and fall back to depth-first search?
+    pub fell_back: bool,
+    /// How many states were dropped because they could never win. See `Solver::prune`.
+    pub pruned: usize,
+    /// How many symmetries of the puzzle the search took advantage of, not counting the identity.
+    pub symmetries: usize,
+    /// How many times the depth-first search reused a remembered set of captures for a state
+    /// that it had already been to.
+    pub cache_hits: usize,
+    /// How many times the depth-first search had to compute a set of captures.
+    pub cache_misses: usize,
+}
+
+impl SolveReport {
+    /// The fraction of capture computations answered from the cache, or `None` if there were
+    /// none, as in a breadth-first search that never fell back.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let total = self.cache_hits + self.cache_misses;
+        (total > 0).then(|| self.cache_hits as f64 / total as f64)
+    }
+}
+
+/// Like `solve`, but also reports statistics about the search.
+pub fn solve_with_report(p: &Puzzle) -> (Option<Vec<u32>>, SolveReport) {
+    let outcome = Solver::new().stats(true).solve(p);
+    let res = match outcome.result {
+        SolveResult::Solved(captures) => Some(captures),
+        _ => None,
+    };
+    (res, outcome.report.expect("stats were requested"))
+}
+
+/// How a `Solver` searches.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// Breadth-first search over capture orders, as in `solve`.
+    #[default]
+    BreadthFirst,
+    /// A* search for the fewest total moves, as in `solve_min_moves`.
+    MinMoves,
+    /// Breadth-first search where worker threads claim states in a shared, sharded visited set as
+    /// they go. This scales better than `Solver::parallel` on big puzzles, but which solution it
+    /// finds can vary from run to run, and it ignores `Solver::deterministic`.
+    #[cfg(feature = "parallel")]
+    Concurrent,
+    /// Best-first search that always expands the state furthest along, which finds a solution
+    /// much sooner than breadth-first search on puzzles that have plenty of them. It doesn't find
+    /// the lexicographically smallest solution, so it ignores `Solver::deterministic`.
+    BestFirst,
+    /// Anytime beam search for solutions with few moves. It searches layer by layer, keeping only
+    /// the most promising states in each, and then starts over with twice as many each time, so
+    /// it finds some solution quickly and better ones the longer it runs. Give it a `max_states`
+    /// or `timeout` budget; without one, it runs until it's sure of the best solution. See
+    /// `Solver::solve_anytime`.
+    Beam,
+    /// Breadth-first search that keeps its layers in files rather than memory, for puzzles too
+    /// big to search otherwise. See `Solver::spill_dir` and `Solver::memory_cap`. It ignores
+    /// `Solver::deterministic`.
+    External,
+}
+
+/// A rule about the order of captures, for `Solver::require`. Pieces are given by index, and
+/// breaking down a wall doesn't count as capturing a piece.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CaptureOrder {
+    /// This piece is captured before any other.
+    First(u32),
+    /// This piece is captured after every other required piece. Optional pieces can be left on
+    /// the board, since capturing the last required piece wins.
+    Last(u32),
+    /// The first piece is captured before the second.
+    Before(u32, u32),
+}
+
+impl CaptureOrder {
+    /// Does capturing piece `captured`, while the pieces in `remaining` (including `captured`)
+    /// are still on the board, keep to this rule? The pieces in `optional` needn't be captured.
+    fn allows(self, captured: u32, remaining: u32, optional: u32) -> bool {
+        match self {
+            CaptureOrder::First(i) => captured == i || remaining & (1 << i) == 0,
+            CaptureOrder::Last(i) => captured != i || remaining & !optional & !(1 << i) == 0,
+            CaptureOrder::Before(a, b) => captured != b || remaining & (1 << a) == 0,
+        }
+    }
+}
+
+/// A configurable solver. `solve` is the default configuration.
+///
+/// ```
+/// use echochess::{Solver, SolveResult, Strategy};
+/// let puzzle = echochess::Puzzle::parse("8/8/8/8/8/8/8/R1n5").unwrap();
+/// let outcome = Solver::new()
+///     .strategy(Strategy::MinMoves)
+///     .max_states(1000)
+///     .solve(&puzzle);
+/// assert_eq!(outcome.result, SolveResult::Solved(vec![1]));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Solver {
+    max_depth: Option<usize>,
+    max_states: Option<usize>,
+    memory_cap: Option<usize>,
+    spill_dir: Option<std::path::PathBuf>,
+    checkpoint: Option<std::path::PathBuf>,
+    resume: Option<std::path::PathBuf>,
+    timeout: Option<std::time::Duration>,
+    cancel: Option<CancellationToken>,
+    deterministic: bool,
+    /// Set by `symmetry(false)`. This is negated so that the derived default is to use symmetry.
+    ignore_symmetry: bool,
+    /// Set by `prune(false)`, negated like `ignore_symmetry`.
+    ignore_pruning: bool,
+    strategy: Strategy,
+    stats: bool,
+    end_as: Option<PieceType>,
+    order: Vec<CaptureOrder>,
+    forbidden: Vec<PieceType>,
+    #[cfg(feature = "parallel")]
+    parallel: bool,
+}
+
+/// What a `Solver` found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolveOutcome {
+    pub result: SolveResult,
+    /// Statistics about the search, if `Solver::stats` was set.
+    pub report: Option<SolveReport>,
+}
+
+/// Whether a `Solver` found a solution. See `SolveOutcome`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SolveResult {
+    /// The piece indices to be captured in order to win.
+    Solved(Vec<u32>),
+    /// The whole search space was explored, and there's no solution.
+    Unsolvable,
+    /// There are solutions, but the one with the fewest moves takes this many, more than
+    /// `Puzzle::budget` allows.
+    OverBudget(u32),
+    /// The search hit one of the solver's limits before finding a solution.
+    GaveUp(Limit),
+}
+
+/// One of the limits that a `Solver` can be configured with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Limit {
+    /// `Solver::max_depth`.
+    Depth,
+    /// `Solver::max_states`.
+    States,
+    /// `Solver::timeout`.
+    Timeout,
+    /// `Solver::cancel_on`.
+    Cancelled,
+    /// The solver couldn't read or write its files, say because the disk is full: either
+    /// `Strategy::External`'s layers or a checkpoint. A checkpoint for a different puzzle or
+    /// symmetry setting counts too. See `Solver::resume`.
+    Disk,
+}
+
+/// A flag for stopping a `Solver` from another thread. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks every solve watching this token to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+impl Solver {
+    /// The default configuration: breadth-first, with no limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gives up rather than look at states more than `max_depth` captures in.
+    pub fn max_depth(&mut self, max_depth: usize) -> &mut Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Gives up once more than `max_states` distinct states have been seen.
+    pub fn max_states(&mut self, max_states: usize) -> &mut Self {
+        self.max_states = Some(max_states);
+        self
+    }
+
+    /// With `Strategy::BreadthFirst`, once more than `memory_cap` states are stored, throws them
+    /// away and starts over with an iterative-deepening search, which stores the current line of
+    /// play and at most `memory_cap` states that it knows lead nowhere. That can take
+    /// exponentially longer, since it forgets most of where it's been, but memory use stays
+    /// bounded. Breaking walls and capturing optional pieces can make some solutions longer than
+    /// others, and deepening one capture at a time still finds one with the fewest captures.
+    ///
+    /// With `Strategy::External`, this is how many states to sort in memory before writing them
+    /// out to a file.
+    pub fn memory_cap(&mut self, memory_cap: usize) -> &mut Self {
+        self.memory_cap = Some(memory_cap);
+        self
+    }
+
+    /// With `Strategy::External`, where to write the search's files: a fresh directory is made
+    /// inside `dir` and removed when the search is done. The default is the system's temporary
+    /// directory.
+    pub fn spill_dir(&mut self, dir: impl Into<std::path::PathBuf>) -> &mut Self {
+        self.spill_dir = Some(dir.into());
+        self
+    }
+
+    /// With any strategy but `Strategy::MinMoves` or `Strategy::Concurrent`, treats states that
+    /// are mirror images or rotations of each other as one, if the puzzle is symmetric. This is on
+    /// by default, except in deterministic breadth-first search, where it's ignored.
+    pub fn symmetry(&mut self, enabled: bool) -> &mut Self {
+        self.ignore_symmetry = !enabled;
+        self
+    }
+
+    /// With any strategy but `Strategy::MinMoves` or `Strategy::Concurrent`, drops states as soon
+    /// as some remaining piece can't be captured by anything left on the board. This is on by
+    /// default.
+    pub fn prune(&mut self, enabled: bool) -> &mut Self {
+        self.ignore_pruning = !enabled;
+        self
+    }
+
+    /// With `Strategy::BreadthFirst`, saves the search's progress to `path` after each layer, so
+    /// that a later solve with `resume` can carry on from there.
+    pub fn checkpoint(&mut self, path: impl Into<std::path::PathBuf>) -> &mut Self {
+        self.checkpoint = Some(path.into());
+        self
+    }
+
+    /// With `Strategy::BreadthFirst`, starts from a checkpoint saved at `path` by an earlier
+    /// solve of the same puzzle (see `checkpoint`), or from scratch if there's no file there.
+    /// The report's counts then include the earlier work, except for the time taken. The
+    /// checkpoint has to be for the same puzzle, by `Puzzle::fingerprint`, and have been saved
+    /// with the same `symmetry` setting, or the solver gives up with `Limit::Disk`.
+    ///
+    /// ```
+    /// use echochess::{Puzzle, SolveResult, Solver};
+    /// let puzzle = Puzzle::parse("rnbkbrn1/pp2pp2/8/3N4/8/2ppp3/8/8").unwrap();
+    /// let path = std::env::temp_dir().join(format!("echochess-doctest-{}", std::process::id()));
+    /// let first = Solver::new().checkpoint(&path).max_depth(5).solve(&puzzle);
+    /// assert!(matches!(first.result, SolveResult::GaveUp(_)));
+    /// let rest = Solver::new().resume(&path).stats(true).solve(&puzzle);
+    /// assert!(matches!(rest.result, SolveResult::Solved(_)));
+    /// assert_eq!(rest.report.unwrap().depth, 14);
+    /// std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn resume(&mut self, path: impl Into<std::path::PathBuf>) -> &mut Self {
+        self.resume = Some(path.into());
+        self
+    }
+
+    /// Gives up once the search has taken longer than `timeout`.
+    pub fn timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Gives up once `token` is cancelled, which can be done from another thread.
+    pub fn cancel_on(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// With `Strategy::BreadthFirst`, breaks ties lexicographically by piece index, so that of
+    /// all the solutions, the solver returns the smallest. A* search is reproducible either way,
+    /// but doesn't break ties lexicographically.
+    pub fn deterministic(&mut self, deterministic: bool) -> &mut Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Chooses how to search. The default is breadth-first.
+    pub fn strategy(&mut self, strategy: Strategy) -> &mut Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// With `Strategy::BreadthFirst`, generates the successors of each layer's states across
+    /// threads. The results are merged in the same order as in a serial search, so the solution
+    /// is the same either way.
+    ///
+    /// ```
+    /// use echochess::{Puzzle, Solver};
+    /// let puzzle = Puzzle::parse("rnbkbrn1/pp2pp2/8/3N4/8/2ppp3/8/8").unwrap();
+    /// for deterministic in [false, true] {
+    ///     let serial = Solver::new().deterministic(deterministic).solve(&puzzle);
+    ///     let parallel = Solver::new()
+    ///         .deterministic(deterministic)
+    ///         .parallel(true)
+    ///         .solve(&puzzle);
+    ///     assert_eq!(serial.result, parallel.result);
+    /// }
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn parallel(&mut self, parallel: bool) -> &mut Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Collects a `SolveReport` about the search.
+    pub fn stats(&mut self, stats: bool) -> &mut Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Only counts a win if the last capture leaves the player as a piece of type `ty`, as when
+    /// the next level of a campaign needs it. Symmetric states aren't merged then, as a symmetry
+    /// can swap pieces of different types that move alike.
+    ///
+    /// ```
+    /// use echochess::{PieceType, Puzzle, SolveResult, Solver};
+    /// let puzzle = Puzzle::parse("R1n/3/b2").unwrap();
+    /// let outcome = Solver::new().end_as(PieceType::Knight).solve(&puzzle);
+    /// assert_eq!(outcome.result, SolveResult::Solved(vec![0, 2]));
+    /// let outcome = Solver::new().end_as(PieceType::Bishop).solve(&puzzle);
+    /// assert_eq!(outcome.result, SolveResult::Solved(vec![2, 0]));
+    /// ```
+    pub fn end_as(&mut self, ty: PieceType) -> &mut Self {
+        self.end_as = Some(ty);
+        self
+    }
+
+    /// Only explores capture orders that keep to `rule`, as when checking that a puzzle's
+    /// intended story can be played out. Each call adds another rule. Symmetric states aren't
+    /// merged then, as a symmetry can swap the pieces that a rule names.
+    ///
+    /// ```
+    /// use echochess::{CaptureOrder, Puzzle, SolveResult, Solver};
+    /// let puzzle = Puzzle::parse("R1n/3/b2").unwrap();
+    /// let outcome = Solver::new().require(CaptureOrder::First(2)).solve(&puzzle);
+    /// assert_eq!(outcome.result, SolveResult::Solved(vec![2, 0]));
+    /// let outcome = Solver::new().require(CaptureOrder::Before(0, 2)).solve(&puzzle);
+    /// assert_eq!(outcome.result, SolveResult::Solved(vec![0, 2]));
+    /// let outcome = Solver::new()
+    ///     .require(CaptureOrder::Last(0))
+    ///     .require(CaptureOrder::Last(2))
+    ///     .solve(&puzzle);
+    /// assert_eq!(outcome.result, SolveResult::Unsolvable);
+    /// ```
+    pub fn require(&mut self, rule: CaptureOrder) -> &mut Self {
+        self.order.push(rule);
+        self
+    }
+
+    /// Never lets the player become a piece of type `ty`, by skipping any capture of one, as in a
+    /// themed puzzle or to see how much a solution leans on that type. Each call forbids another
+    /// type. Symmetric states aren't merged then, as under `end_as`.
+    ///
+    /// ```
+    /// use echochess::{PieceType, Puzzle, SolveResult, Solver};
+    /// let puzzle = Puzzle::parse("R1n/3/b2").unwrap();
+    /// let outcome = Solver::new().forbid(PieceType::Knight).solve(&puzzle);
+    /// assert_eq!(outcome.result, SolveResult::Unsolvable);
+    /// ```
+    pub fn forbid(&mut self, ty: PieceType) -> &mut Self {
+        self.forbidden.push(ty);
+        self
+    }
+
+    /// Solves a puzzle with this configuration. If the solver gives up, the report (if any)
+    /// covers the work done so far. If the puzzle has a `Puzzle::budget`, this searches for the
+    /// fewest moves instead, as with `Strategy::MinMoves`.
+    pub fn solve(&self, p: &Puzzle) -> SolveOutcome {
+        if let Some(budget) = p.budget() {
+            let mut fewest = None;
+            let mut outcome = self.run(|config, report| {
+                let res = search_min_moves(p, config, report)?;
+                Ok(res.map(|(captures, moves)| {
+                    fewest = Some(moves);
+                    captures
+                }))
+            });
+            if let Some(moves) = fewest.filter(|&moves| moves > budget) {
+                outcome.result = SolveResult::OverBudget(moves);
+            }
+            return outcome;
+        }
+        self.run(|config, report| match self.strategy {
+            Strategy::BreadthFirst => search(p, |s| config.won(p, s), config, report),
+            Strategy::MinMoves => {
+                search_min_moves(p, config, report).map(|res| res.map(|(captures, _)| captures))
+            }
+            #[cfg(feature = "parallel")]
+            Strategy::Concurrent => {
+                concurrent::search_concurrent(p, |s| config.won(p, s), config, report)
+            }
+            Strategy::BestFirst => search_best_first(p, config, report),
+            Strategy::External => {
+                external::search_external(p, |s| config.won(p, s), config, report)
+            }
+            Strategy::Beam => search_beam(p, config, report, |_, _| {}),
+        })
+    }
+
+    /// Solves a puzzle with `Strategy::Beam`, whatever the configured strategy, calling
+    /// `on_improvement(captures, moves)` each time it finds a solution with fewer moves than any
+    /// before. If the solver hits a limit after finding a solution, the result is the best
+    /// solution so far rather than `SolveResult::GaveUp`.
+    ///
+    /// ```
+    /// use echochess::{Puzzle, Solver};
+    /// let puzzle = Puzzle::parse("rnbkbrn1/pp2pp2/8/3N4/8/2ppp3/8/8").unwrap();
+    /// let mut moves = Vec::new();
+    /// Solver::new()
+    ///     .max_states(10_000)
+    ///     .solve_anytime(&puzzle, |_, m| moves.push(m));
+    /// assert!(!moves.is_empty());
+    /// assert!(moves.windows(2).all(|w| w[1] < w[0]));
+    /// ```
+    pub fn solve_anytime<F: FnMut(&[u32], u32)>(
+        &self,
+        p: &Puzzle,
+        on_improvement: F,
+    ) -> SolveOutcome {
+        self.run(|config, report| search_beam(p, config, report, on_improvement))
+    }
+
+    /// Solves each of `puzzles` with this configuration, spread across as many threads as the
+    /// machine has cores. Limits like `timeout` apply to each puzzle separately.
+    pub fn solve_batch(&self, puzzles: &[Puzzle]) -> Vec<SolveOutcome> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let next = AtomicUsize::new(0);
+        let num_threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(puzzles.len());
+        let mut outcomes = vec![None; puzzles.len()];
+        std::thread::scope(|scope| {
+            // Each thread takes the next unclaimed puzzle until there are none left.
+            let workers: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(p) = puzzles.get(i) else {
+                                return done;
+                            };
+                            done.push((i, self.solve(p)));
+                        }
+                    })
+                })
+                .collect();
+            for worker in workers {
+                for (i, outcome) in worker.join().expect("solver thread panicked") {
+                    outcomes[i] = Some(outcome);
+                }
+            }
+        });
+        outcomes.into_iter().map(Option::unwrap).collect()
+    }
+
+    /// Runs `search` under this configuration, timing it and packaging up what it found.
+    fn run<S>(&self, search: S) -> SolveOutcome
+    where
+        S: FnOnce(&SearchConfig, &mut SolveReport) -> Result<Option<Vec<u32>>, Limit>,
+    {
+        let start = std::time::Instant::now();
+        let mut report = SolveReport::default();
+        let config = SearchConfig::new(self, start);
+        let res = search(&config, &mut report);
+        report.elapsed = start.elapsed();
+        SolveOutcome {
+            result: match res {
+                Ok(Some(captures)) => SolveResult::Solved(captures),
+                Ok(None) => SolveResult::Unsolvable,
+                Err(limit) => SolveResult::GaveUp(limit),
+            },
+            report: self.stats.then_some(report),
+        }
+    }
+}
+
+/// A `Solver` as seen by the search functions, with its timeout turned into a deadline.
+struct SearchConfig<'a> {
+    solver: &'a Solver,
+    deadline: Option<std::time::Instant>,
+}
+
+impl SearchConfig<'_> {
+    /// Configures a search that starts at `start`.
+    fn new(solver: &Solver, start: std::time::Instant) -> SearchConfig<'_> {
+        SearchConfig {
+            solver,
+            deadline: solver.timeout.map(|t| start + t),
+        }
+    }
+
+    /// Generates the successors of every state in `layer` up front, across threads, if the solver
+    /// is parallel. Otherwise, the search generates them one state at a time.
+    #[cfg(feature = "parallel")]
+    fn expand_layer(&self, p: &Puzzle, layer: &[PuzzleState]) -> Option<Vec<Vec<PuzzleState>>> {
+        use rayon::prelude::*;
+        if !self.solver.parallel {
+            return None;
+        }
+        let expand = |&state: &PuzzleState| {
+            let mut nexts = Vec::new();
+            state
+                .next_states(p, |next| {
+                    if self.allows(p, state, next) {
+                        nexts.push(next);
+                    }
+                })
+                .expect("reachable states are valid");
+            nexts
+        };
+        Some(layer.par_iter().map(expand).collect())
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn expand_layer(&self, _p: &Puzzle, _layer: &[PuzzleState]) -> Option<Vec<Vec<PuzzleState>>> {
+        None
+    }
+
+    /// Has the player won in `state`, ending as whatever `Solver::end_as` asks?
+    fn won(&self, p: &Puzzle, state: PuzzleState) -> bool {
+        state.done()
+            && self
+                .solver
+                .end_as
+                .is_none_or(|ty| p.piece_type(state.current_piece_idx()) == Some(ty))
+    }
+
+    /// Should the search merge symmetric states? See `Solver::symmetry`, `Solver::end_as`,
+    /// `Solver::require`, and `Solver::forbid`.
+    fn use_symmetry(&self) -> bool {
+        let solver = self.solver;
+        !solver.ignore_symmetry
+            && solver.end_as.is_none()
+            && solver.order.is_empty()
+            && solver.forbidden.is_empty()
+    }
+
+    /// Does the capture that takes `state` to `next` keep to every `Solver::require` rule, and
+    /// not turn the player into a type that `Solver::forbid` rules out?
+    fn allows(&self, p: &Puzzle, state: PuzzleState, next: PuzzleState) -> bool {
+        let captured = next.current_piece_idx();
+        if captured >= p.num_pieces() {
+            return true; // broke down a wall
+        }
+        if p.piece_type(captured)
+            .is_some_and(|ty| self.solver.forbidden.contains(&ty))
+        {
+            return false;
+        }
+        let remaining = state.remaining_captures() & !p.wall_bits();
+        self.solver
+            .order
+            .iter()
+            .all(|rule| rule.allows(captured, remaining, p.optional))
+    }
+
+    fn too_deep(&self, depth: usize) -> bool {
+        self.solver.max_depth.is_some_and(|max| depth >= max)
+    }
+
+    /// Checks the limits that can be hit partway through expanding a layer: state count, time, and
+    /// cancellation. The clock is only checked every so often, since it's not free.
+    fn check(&self, report: &SolveReport) -> Result<(), Limit> {
+        if self
+            .solver
+            .max_states
+            .is_some_and(|max| report.states_visited > max)
+        {
+            return Err(Limit::States);
+        }
+        if report.nodes_expanded % 256 == 1 {
+            if self
+                .solver
+                .cancel
+                .as_ref()
+                .is_some_and(|c| c.is_cancelled())
+            {
+                return Err(Limit::Cancelled);
+            }
+            if self
+                .deadline
+                .is_some_and(|d| std::time::Instant::now() >= d)
+            {
+                return Err(Limit::Timeout);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Breadth-first search behind `solve_until`, counting its work into `report`.
+fn search<G: Fn(PuzzleState) -> bool>(
+    p: &Puzzle,
+    goal: G,
+    config: &SearchConfig,
+    report: &mut SolveReport,
+) -> Result<Option<Vec<u32>>, Limit> {
+    // Equivalent states are merged into one canonical state, unless that would interfere with
+    // picking the lexicographically smallest solution.
+    let symmetries = if config.use_symmetry() && !config.solver.deterministic {
+        Symmetries::of(p)
+    } else {
+        Symmetries::none()
+    };
+    report.symmetries = symmetries.0.len();
+    let dead = if config.solver.ignore_pruning {
+        DeadStates::none()
+    } else {
+        DeadStates::of(p)
+    };
+    let initial = symmetries.canonical(PuzzleState::initial(p));
+    // Only the set of visited states is kept, not their predecessors, which `unwind` can recover.
+    let mut visited = VisitedStates::new(p);
+    // Each layer lists states in the order they were first reached, which (when deterministic)
+    // is the lexicographic order of their smallest capture sequences.
+    let mut frontier: Vec<PuzzleState> = vec![initial];
+    let mut new_frontier: Vec<PuzzleState> = Vec::new();
+    let mut nexts = Vec::new();
+    visited.insert(initial);
+    report.states_visited = 1;
+    // A puzzle with no required pieces is won before the first capture.
+    if goal(initial) {
+        return Ok(Some(Vec::new()));
+    }
+    let symmetric = !symmetries.0.is_empty();
+    if let Some(path) = &config.solver.resume {
+        if let Some(saved) = checkpoint::Checkpoint::load(path).map_err(|_| Limit::Disk)? {
+            if saved.fingerprint != p.fingerprint() || saved.symmetric != symmetric {
+                return Err(Limit::Disk);
+            }
+            for state in saved.visited {
+                visited.insert(state);
+            }
+            frontier = saved.frontier;
+            *report = SolveReport {
+                symmetries: report.symmetries,
+                ..saved.report
+            };
+        }
+    }
+    while !frontier.is_empty() {
+        if config.too_deep(report.depth) {
+            return Err(Limit::Depth);
+        }
+        report.frontier_peak = report.frontier_peak.max(frontier.len());
+        report.depth += 1;
+        let expanded = config.expand_layer(p, &frontier);
+        for (i, &prev) in frontier.iter().enumerate() {
+            report.nodes_expanded += 1;
+            nexts.clear();
+            match &expanded {
+                Some(layer) => nexts.extend_from_slice(&layer[i]),
+                None => prev
+                    .next_states(p, |next| {
+                        if config.allows(p, prev, next) {
+                            nexts.push(next);
+                        }
+                    })
+                    .expect("reachable states are valid"),
+            }
+            if config.solver.deterministic {
+                nexts.sort_by_key(|s| s.current_piece_idx());
+            }
+            let mut done = None;
+            for &next in &nexts {
+                if dead.is_dead(next) {
+                    report.pruned += 1;
+                    continue;
+                }
+                let next = symmetries.canonical(next);
+                if visited.insert(next) {
+                    new_frontier.push(next);
+                }
+                if goal(next) {
+                    done = Some(next);
+                    break;
+                }
+            }
+            report.states_visited = visited.len();
+            if let Some(final_state) = done {
+                return Ok(Some(unwind(
+                    p,
+                    |s| visited.contains(s),
+                    final_state,
+                    report.depth as u32,
+                    &symmetries,
+                )));
+            }
+            if config
+                .solver
+                .memory_cap
+                .is_some_and(|cap| visited.len() > cap)
+            {
+                drop((visited, frontier, new_frontier));
+                report.fell_back = true;
+                return depth_first(p, goal, config, report);
+            }
+            config.check(report)?;
+        }
+        frontier.clear();
+        std::mem::swap(&mut frontier, &mut new_frontier);
+        if let Some(path) = &config.solver.checkpoint {
+            let saved = checkpoint::Checkpoint {
+                fingerprint: p.fingerprint(),
+                symmetric,
+                report: report.clone(),
+                frontier: frontier.clone(),
+                visited: visited.iter().collect(),
+            };
+            saved.save(path).map_err(|_| Limit::Disk)?;
+        }
+    }
+    report.depth -= 1; // the last layer had no successors
+    Ok(None)
+}
+
+/// Recovers the lexicographically smallest line of play from the initial state to `target`,
+/// given a test for whether the search visited a state. A state's possible predecessors differ
+/// from it only in which piece the player was before the last capture, so there are few to try
+/// (see `PuzzleState::maybe_previous`).
+///
+/// If the search merged states under `symmetries`, then `target` and the visited states are
+/// canonical, and the line of play ends on some state equivalent to `target`.
+///
+/// The line has exactly `depth` captures, the depth at which the search found `target`. Every
+/// state but a win has made as many captures as its `phase`, so each step back only considers
+/// states one phase earlier. A win forgets the walls still standing and the optional pieces
+/// left, so without that, it could be traced back through a state that made more captures than
+/// it needed to.
+fn unwind<V: Fn(PuzzleState) -> bool>(
+    p: &Puzzle,
+    visited: V,
+    target: PuzzleState,
+    depth: u32,
+    symmetries: &Symmetries,
+) -> Vec<u32> {
+    let initial = PuzzleState::initial(p);
+    // Every visited state from which `target` can be reached in as many captures as it's short
+    // of `depth`, found one layer at a time.
+    let mut ancestors = StateSet::default();
+    ancestors.insert(target);
+    let mut layer = vec![target];
+    for phase in (0..depth).rev() {
+        assert!(!layer.is_empty(), "target should be reachable");
+        let mut prev_layer = Vec::new();
+        for state in layer.iter().flat_map(|&s| symmetries.images(s)) {
+            for prev in state.maybe_previous(p) {
+                if prev.phase(p) != phase {
+                    continue;
+                }
+                let canonical = symmetries.canonical(prev);
+                if ancestors.contains(&canonical) || !visited(canonical) {
+                    continue;
+                }
+                let mut leads_here = false;
+                prev.next_states(p, |next| leads_here |= next == state)
+                    .expect("reachable states are valid");
+                if leads_here {
+                    ancestors.insert(canonical);
+                    prev_layer.push(canonical);
+                }
+            }
+        }
+        layer = prev_layer;
+    }
+    // Walk forward, always taking the smallest capture that stays on course.
+    let mut res = Vec::new();
+    let mut current = initial;
+    while symmetries.canonical(current) != target {
+        let mut best: Option<PuzzleState> = None;
+        current
+            .next_states(p, |next| {
+                if ancestors.contains(&symmetries.canonical(next))
+                    && best.is_none_or(|b| next.current_piece_idx() < b.current_piece_idx())
+                {
+                    best = Some(next);
+                }
+            })
+            .expect("reachable states are valid");
+        current = best.expect("ancestors lead to the target");
+        res.push(current.current_piece_idx());
+    }
+    res
+}
+
+/// A quick test for states that can never win, for pruning the search. For each piece, this
+/// works out which other pieces could ever capture it: piece `k` could if, once the player
+/// becomes piece `k` (on `k`'s square), it could walk and then capture onto the piece's square on
+/// a board with no pieces in the way. That overestimates what the player can do, so a state where
+/// some remaining piece has no remaining would-be captor (counting the player) is truly dead. A
+/// piece that hops needs pieces in the way, so it's counted as a would-be captor of everything.
+struct DeadStates {
+    /// For each piece, a bitmask of the pieces that could ever capture it.
+    captors: [u32; 32],
+    /// Patrollers move, on ice, pieces in the way can be what stops a slide where it needs to,
+    /// and walls don't need capturing, so the test doesn't apply to puzzles with any of them.
+    active: bool,
+}
+
+impl DeadStates {
+    fn none() -> Self {
+        DeadStates {
+            captors: [0; 32],
+            active: false,
+        }
+    }
+
+    fn of(p: &Puzzle) -> Self {
+        if p.patrolling() || !p.ice.is_empty() || !p.walls.is_empty() || p.optional != 0 {
+            return Self::none();
+        }
+        let open = !p.obstacles;
+        let mut captors = [0; 32];
+        for k in 0..p.num_pieces() {
+            let ty = p.mover(k).unwrap();
+            let from = SquareSet::from(p.piece_loc(k));
+            let reach = ty.capture_reach(p.fill(ty, from, open), open);
+            for x in 0..p.num_pieces() {
+                if x != k && (ty.hops() || reach.contains(p.piece_loc(x))) {
+                    captors[x as usize] |= 1 << k;
+                }
+            }
+        }
+        DeadStates {
+            captors,
+            active: true,
+        }
+    }
+
+    fn is_dead(&self, state: PuzzleState) -> bool {
+        if !self.active {
+            return false;
+        }
+        let present = state.remaining_captures() | (1 << state.current_piece_idx());
+        let mut bits = state.remaining_captures();
+        while bits != 0 {
+            if self.captors[bits.trailing_zeros() as usize] & present == 0 {
+                return true;
+            }
+            bits &= bits - 1;
+        }
+        false
+    }
+
+    /// How close `state` is to being dead: the fewest would-be captors left for any remaining
+    /// piece, or `u32::MAX` if the test doesn't apply.
+    fn slack(&self, state: PuzzleState) -> u32 {
+        if !self.active {
+            return u32::MAX;
+        }
+        let present = state.remaining_captures() | (1 << state.current_piece_idx());
+        let mut bits = state.remaining_captures();
+        let mut slack = u32::MAX;
+        while bits != 0 {
+            let captors = self.captors[bits.trailing_zeros() as usize] & present;
+            slack = slack.min(captors.count_ones());
+            bits &= bits - 1;
+        }
+        slack
+    }
+}
+
+/// A reflection or rotation of the board. Each takes a file and rank, and the highest file and
+/// rank on the board. The ones that swap files with ranks only apply to square boards.
+type Transform = fn(u8, u8, u8, u8) -> (u8, u8);
+
+/// The 64-bit FNV-1a hash of `bytes`. Unlike `DefaultHasher`'s, it's the same in every build and
+/// on every platform, for hashes that get saved or shared.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Every `Transform` but the identity.
+const TRANSFORMS: [Transform; 7] = [
+    |f, r, mf, _| (mf - f, r), // mirror left to right: keep this one first
+    |f, r, _, mr| (f, mr - r),
+    |f, r, mf, mr| (mf - f, mr - r),
+    |f, r, _, _| (r, f),
+    |f, r, mf, _| (mf - r, mf - f),
+    |f, r, mf, _| (r, mf - f),
+    |f, r, mf, _| (mf - r, f),
+];
+
+impl Puzzle {
+    /// The transforms, other than the identity, that the pieces' movement looks the same under.
+    /// Pawns only move one way along the files, so a puzzle with pawns can at most be mirrored
+    /// left to right, and custom pieces that aren't symmetric rule out all of them. Only square
+    /// boards can swap files with ranks.
+    fn transforms(&self) -> &'static [Transform] {
+        let lopsided =
+            |i| matches!(self.piece_type(i), Some(PieceType::Custom(c)) if !c.is_symmetric());
+        let has_pawns = |i| {
+            matches!(
+                self.piece_type(i),
+                Some(PieceType::Pawn | PieceType::PawnDown)
+            )
+        };
+        if (0..self.num_pieces()).any(lopsided) {
+            &[]
+        } else if (0..self.num_pieces()).any(has_pawns) {
+            &TRANSFORMS[..1]
+        } else if self.width != self.height {
+            &TRANSFORMS[..3]
+        } else {
+            &TRANSFORMS[..]
+        }
+    }
+
+    /// Where `t` takes square `sq` on this board.
+    fn transform(&self, t: Transform, sq: Square) -> Square {
+        let (f, r) = t(sq.file(), sq.rank(), self.width - 1, self.height - 1);
+        Square::from_file_rank(f, r).unwrap()
+    }
+}
+
+/// The symmetries of a puzzle: board reflections and rotations that map obstacles to obstacles
+/// and each piece to a piece of the same type. Each is stored as the permutation of piece
+/// indices that it induces; the identity is left out.
+struct Symmetries(Vec<[u8; 32]>);
+
+impl Symmetries {
+    /// No symmetries, so that every state is its own canonical form.
+    fn none() -> Self {
+        Symmetries(Vec::new())
+    }
+
+    /// Finds the symmetries of a puzzle, among those `Puzzle::transforms` allows. Patrols aren't
+    /// checked further, so a puzzle with them gets none, as does one with walls, one-way squares,
+    /// or terrain. An exit has to stay put, and portals have to map to
+    /// linked portals.
+    fn of(p: &Puzzle) -> Self {
+        if p.patrolling()
+            || !p.walls.is_empty()
+            || !p.one_way_squares().is_empty()
+            || !p.terrain.is_empty()
+        {
+            return Self::none();
+        }
+        let num_pieces = p.num_pieces();
+        let apply = |t: Transform, sq: Square| p.transform(t, sq);
+        let mut res = Vec::new();
+        'transforms: for &t in p.transforms() {
+            if p.exit.is_some_and(|exit| apply(t, exit) != exit) {
+                continue;
+            }
+            for &(a, b) in &p.portals {
+                let (ta, tb) = (apply(t, a), apply(t, b));
+                if !p.portals.contains(&(ta, tb)) && !p.portals.contains(&(tb, ta)) {
+                    continue 'transforms;
+                }
+            }
+            for sq in p.squares() {
+                if p.obstacles.contains(sq) != p.obstacles.contains(apply(t, sq))
+                    || p.ice.contains(sq) != p.ice.contains(apply(t, sq))
+                {
+                    continue 'transforms;
+                }
+            }
+            let mut perm = [0xff; 32];
+            for i in 0..num_pieces {
+                match p.piece_on(apply(t, p.piece_loc(i))) {
+                    Some(j) if p.mover(j) == p.mover(i) && p.required(j) == p.required(i) => {
+                        perm[i as usize] = j as u8
+                    }
+                    _ => continue 'transforms,
+                }
+            }
+            res.push(perm);
+        }
+        Symmetries(res)
+    }
+
+    fn apply(perm: &[u8; 32], state: PuzzleState) -> PuzzleState {
+        let mut remaining = 0;
+        let mut bits = state.remaining_captures();
+        while bits != 0 {
+            remaining |= 1 << perm[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+        let current = u32::from(perm[state.current_piece_idx() as usize]);
+        PuzzleState(remaining | (current << 27))
+    }
+
+    /// The smallest state equivalent to `state`, which stands for all of them.
+    fn canonical(&self, state: PuzzleState) -> PuzzleState {
+        self.0
+            .iter()
+            .map(|perm| Self::apply(perm, state))
+            .fold(state, |a, b| if b.0 < a.0 { b } else { a })
+    }
+
+    /// Every state equivalent to `state`, including itself, possibly with repeats.
+    fn images(&self, state: PuzzleState) -> impl Iterator<Item = PuzzleState> + '_ {
+        std::iter::once(state).chain(self.0.iter().map(move |perm| Self::apply(perm, state)))
+    }
+}
+
+/// Remembers `PieceType::captures` results within one solve, keyed on the piece type and what
+/// `PuzzleState::setup` gives: its square, the obstacles, the targets, and the squares to avoid.
+/// Breadth-first search expands each state once, so this only pays off when states are
+/// revisited, as in `depth_first`.
+struct CaptureCache {
+    map: HashMap<(PieceType, Setup), SquareSet, std::hash::BuildHasherDefault<StateHasher>>,
+    /// Once the cache holds more than this many entries, it starts over.
+    max_len: usize,
+}
+
+impl CaptureCache {
+    fn new(max_len: usize) -> Self {
+        CaptureCache {
+            map: HashMap::default(),
+            max_len,
+        }
+    }
+
+    fn get(
+        &mut self,
+        p: &Puzzle,
+        ty: PieceType,
+        setup: Setup,
+        report: &mut SolveReport,
+    ) -> SquareSet {
+        if let Some(&captures) = self.map.get(&(ty, setup)) {
+            report.cache_hits += 1;
+            return captures;
+        }
+        report.cache_misses += 1;
+        if self.map.len() >= self.max_len {
+            self.map.clear();
+        }
+        let (start, obstacles, targets, avoid) = setup;
+        let captures = p.captures(ty, start, obstacles, targets, avoid);
+        self.map.insert((ty, setup), captures);
+        captures
+    }
+}
+
+/// Iterative-deepening depth-first search, for when `search` runs out of memory. Each round
+/// tries every line of play up to one capture longer than the last round did, storing only the
+/// current line and the untried alternatives along it, so the first solution found has as few
+/// captures as any.
+///
+/// Every capture advances the phase by one, so a state always turns up at the same depth. Once a
+/// round has tried everything below a state without winning, it can skip that state wherever
+/// else it turns up. Those states are remembered, up to `Solver::memory_cap` of them per round.
+fn depth_first<G: Fn(PuzzleState) -> bool>(
+    p: &Puzzle,
+    goal: G,
+    config: &SearchConfig,
+    report: &mut SolveReport,
+) -> Result<Option<Vec<u32>>, Limit> {
+    let initial = PuzzleState::initial(p);
+    if goal(initial) {
+        return Ok(Some(Vec::new()));
+    }
+    // Keep what's remembered within the same bound as the search that ran out of memory.
+    let cap = config.solver.memory_cap.unwrap_or(usize::MAX);
+    let mut cache = CaptureCache::new(cap);
+    let mut exhausted = StateSet::default();
+    for limit in 1.. {
+        if config.too_deep(limit - 1) {
+            return Err(Limit::Depth);
+        }
+        exhausted.clear();
+        // Did this round leave any state unexpanded for being at the limit?
+        let mut cut_off = false;
+        // `stack[k]` holds the untried states `k` captures in, in reverse order; `path[k]` is
+        // the one being tried.
+        let mut stack = vec![vec![initial]];
+        let mut path: Vec<PuzzleState> = Vec::new();
+        while let Some(level) = stack.last_mut() {
+            let Some(state) = level.pop() else {
+                stack.pop();
+                if let Some(tried) = path.pop() {
+                    if exhausted.len() < cap {
+                        exhausted.insert(tried);
+                    }
+                }
+                continue;
+            };
+            if exhausted.contains(&state) {
+                continue;
+            }
+            let depth = path.len();
+            path.push(state);
+            if goal(state) {
+                return Ok(Some(
+                    path[1..].iter().map(|s| s.current_piece_idx()).collect(),
+                ));
+            }
+            report.depth = report.depth.max(depth);
+            let mut nexts = Vec::new();
+            if depth == limit {
+                cut_off |= !state.done();
+            } else {
+                report.nodes_expanded += 1;
+                state
+                    .next_states_cached(p, &mut cache, report, |next| {
+                        if config.allows(p, state, next) {
+                            nexts.push(next);
+                        }
+                    })
+                    .expect("reachable states are valid");
+                report.states_visited += nexts.len();
+                if config.solver.deterministic {
+                    nexts.sort_by_key(|s| std::cmp::Reverse(s.current_piece_idx()));
+                } else {
+                    nexts.reverse();
+                }
+                config.check(report)?;
+            }
+            stack.push(nexts);
+        }
+        if !cut_off {
+            break;
+        }
+    }
+    Ok(None)
+}
+
+/// Best-first search behind `Strategy::BestFirst`, counting its work into `report`. States with
+/// fewer pieces left come first; among those, states where every remaining piece still has
+/// plenty of would-be captors (see `DeadStates`) come before ones that are close to a dead end.
+/// Each state is expanded at most once, so on an unsolvable puzzle this does about as much work
+/// as `search`.
+fn search_best_first(
+    p: &Puzzle,
+    config: &SearchConfig,
+    report: &mut SolveReport,
+) -> Result<Option<Vec<u32>>, Limit> {
+    use std::collections::BinaryHeap;
+    let symmetries = if config.use_symmetry() {
+        Symmetries::of(p)
+    } else {
+        Symmetries::none()
+    };
+    report.symmetries = symmetries.0.len();
+    let dead = if config.solver.ignore_pruning {
+        DeadStates::none()
+    } else {
+        DeadStates::of(p)
+    };
+    let priority = |s: PuzzleState| (s.phase(p), dead.slack(s), s.0);
+    let initial = symmetries.canonical(PuzzleState::initial(p));
+    let mut visited = VisitedStates::new(p);
+    visited.insert(initial);
+    report.states_visited = 1;
+    if config.won(p, initial) {
+        return Ok(Some(Vec::new()));
+    }
+    let mut queue = BinaryHeap::new();
+    queue.push(priority(initial));
+    let mut nexts = Vec::new();
+    // Did we skip any states for being too deep?
+    let mut truncated = false;
+    while let Some((depth, _, state)) = queue.pop() {
+        let (state, depth) = (PuzzleState(state), depth as usize);
+        report.depth = report.depth.max(depth);
+        if config.too_deep(depth) {
+            truncated = true;
+            continue;
+        }
+        report.nodes_expanded += 1;
+        nexts.clear();
+        state
+            .next_states(p, |next| {
+                if config.allows(p, state, next) {
+                    nexts.push(next);
+                }
+            })
+            .expect("reachable states are valid");
+        for &next in &nexts {
+            if dead.is_dead(next) {
+                report.pruned += 1;
+                continue;
+            }
+            let next = symmetries.canonical(next);
+            if !visited.insert(next) {
+                continue;
+            }
+            if config.won(p, next) {
+                report.states_visited = visited.len();
+                report.depth = report.depth.max(depth + 1);
+                return Ok(Some(unwind(
+                    p,
+                    |s| visited.contains(s),
+                    next,
+                    depth as u32 + 1,
+                    &symmetries,
+                )));
+            }
+            queue.push(priority(next));
+        }
+        report.states_visited = visited.len();
+        report.frontier_peak = report.frontier_peak.max(queue.len());
+        config.check(report)?;
+    }
+    if truncated {
+        return Err(Limit::Depth);
+    }
+    Ok(None)
+}
+
+/// Outcome of `solve_starts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartReport {
+    /// Which of `Puzzle::starts` the player can win from, within the budget if there is one.
+    pub winnable: Vec<u32>,
+    /// The start whose solution takes the fewest moves, earliest first on a tie, with that
+    /// solution's captures and total moves, as from `solve_min_moves`.
+    pub best: Option<(u32, Vec<u32>, u32)>,
+}
+
+/// Solves a puzzle from each of its `Puzzle::starts` in turn.
+pub fn solve_starts(p: &Puzzle) -> StartReport {
+    let mut report = StartReport {
+        winnable: Vec::new(),
+        best: None,
+    };
+    for &start in p.starts() {
+        let Some(puz) = p.with_start(start) else {
+            continue;
+        };
+        let Some((captures, moves)) = solve_min_moves(&puz) else {
+            continue;
+        };
+        if puz.budget().is_some_and(|budget| moves > budget) {
+            continue;
+        }
+        report.winnable.push(start);
+        if report
+            .best
+            .as_ref()
+            .is_none_or(|&(_, _, best)| moves < best)
+        {
+            report.best = Some((start, captures, moves));
+        }
+    }
+    report
+}
+
+/// Solves a puzzle in as few total moves as possible, where each step of the player's piece
+/// (including the capturing step) is one move. Returns the list of piece indices to be captured
+/// and the total move count, or `None` if no solution is possible.
+pub fn solve_min_moves(p: &Puzzle) -> Option<(Vec<u32>, u32)> {
+    search_min_moves(
+        p,
+        &SearchConfig::new(&Solver::new(), std::time::Instant::now()),
+        &mut SolveReport::default(),
+    )
+    .expect("no limits were set")
+}
+
+/// Beam search behind `Strategy::Beam`, counting its work into `report` and calling
+/// `on_improvement` with each new best solution and its move count.
+///
+/// Each pass keeps the `width` states in each layer with the smallest cost so far plus
+/// `MoveBound::estimate`, and skips states that can't beat the best solution so far. A pass that
+/// never has to drop states for lack of room has covered everything, so its best solution is
+/// optimal; otherwise, the next pass doubles `width`.
+fn search_beam<F: FnMut(&[u32], u32)>(
+    p: &Puzzle,
+    config: &SearchConfig,
+    report: &mut SolveReport,
+    mut on_improvement: F,
+) -> Result<Option<Vec<u32>>, Limit> {
+    /// A state in the beam, with its cost and its predecessor's index in the previous layer.
+    struct Node {
+        state: PuzzleState,
+        cost: u32,
+        parent: usize,
+    }
+    let bound = MoveBound::of(p);
+    let initial = PuzzleState::initial(p);
+    let mut best: Option<(Vec<u32>, u32)> = None;
+    let mut width = 1;
+    loop {
+        let mut layers = vec![vec![Node {
+            state: initial,
+            cost: 0,
+            parent: 0,
+        }]];
+        // Did this pass drop any states for lack of room?
+        let mut truncated = false;
+        while let Some(layer) = layers.last().filter(|l| !l.is_empty()) {
+            // A win can leave walls standing or optional pieces on the board, so the states in a
+            // layer needn't all finish together, and not all that do need end as
+            // `Solver::end_as` asks. Finished states aren't expanded, and the rest carry on.
+            let won = layer
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| config.won(p, n.state))
+                .min_by_key(|(_, n)| n.cost);
+            if let Some((mut i, node)) = won {
+                if best.as_ref().is_none_or(|&(_, moves)| node.cost < moves) {
+                    let cost = node.cost;
+                    let mut captures = Vec::new();
+                    for layer in layers[1..].iter().rev() {
+                        captures.push(layer[i].state.current_piece_idx());
+                        i = layer[i].parent;
+                    }
+                    captures.reverse();
+                    on_improvement(&captures, cost);
+                    best = Some((captures, cost));
+                }
+            }
+            if layer.iter().all(|n| n.state.done()) {
+                break;
+            }
+            let depth = layers.len() - 1;
+            report.depth = report.depth.max(depth);
+            if config.too_deep(depth) {
+                return best.map(|(captures, _)| Some(captures)).ok_or(Limit::Depth);
+            }
+            // The cheapest known way to each successor, and its estimated total cost.
+            let mut nexts: StateMap<(u32, u32, usize)> = StateMap::default();
+            for (i, node) in layer.iter().enumerate() {
+                if node.state.done() {
+                    continue;
+                }
+                report.nodes_expanded += 1;
+                node.state
+                    .next_states_with_moves(p, |_, moves, next| {
+                        if !config.allows(p, node.state, next) {
+                            return;
+                        }
+                        let cost = node.cost + moves;
+                        let Some(estimate) = bound.estimate(next) else {
+                            return; // can't be won
+                        };
+                        if best
+                            .as_ref()
+                            .is_some_and(|&(_, moves)| cost + estimate >= moves)
+                        {
+                            return; // can't beat the best so far
+                        }
+                        let entry = nexts.entry(next).or_insert((cost, cost + estimate, i));
+                        if cost < entry.0 {
+                            *entry = (cost, cost + estimate, i);
+                        }
+                    })
+                    .expect("reachable states are valid");
+                if let Err(limit) = config.check(report) {
+                    return best.map(|(captures, _)| Some(captures)).ok_or(limit);
+                }
+            }
+            report.states_visited += nexts.len();
+            let mut next_layer: Vec<_> = nexts.into_iter().collect();
+            next_layer.sort_unstable_by_key(|&(state, (_, estimate, _))| (estimate, state.0));
+            if next_layer.len() > width {
+                next_layer.truncate(width);
+                truncated = true;
+            }
+            report.frontier_peak = report.frontier_peak.max(next_layer.len());
+            layers.push(
+                next_layer
+                    .into_iter()
+                    .map(|(state, (cost, _, parent))| Node {
+                        state,
+                        cost,
+                        parent,
+                    })
+                    .collect(),
+            );
+        }
+        if !truncated {
+            return Ok(best.map(|(captures, _)| captures));
+        }
+        width *= 2;
+    }
+}
+
+/// A lower bound on how many more moves a state needs to win, for steering `search_min_moves`.
+/// Every remaining piece has to be captured by some piece still on the board (counting the
+/// player), starting from that piece's square, and that takes at least as many moves as it would
+/// with no other pieces in the way. Those captures are separate legs of the route, so their
+/// shortest lengths add up to a bound.
+struct MoveBound {
+    /// `dist[k][x]` is the fewest moves for piece `k`, from its own square, to capture piece `x`
+    /// on an otherwise empty board, or `None` if it can't. A piece that hops might need the other
+    /// pieces in place, so all it gets is the one move that every capture takes.
+    dist: Vec<[Option<u32>; 32]>,
+    /// Patrollers move, ice can make an emptier board slower, and walls have no distance of
+    /// their own, so the distances don't apply to puzzles with any of them.
+    active: bool,
+}
+
+impl MoveBound {
+    fn of(p: &Puzzle) -> Self {
+        let n = p.num_pieces();
+        let mut dist = vec![[None; 32]; n as usize];
+        if p.patrolling() || !p.ice.is_empty() || !p.walls.is_empty() || p.optional != 0 {
+            return MoveBound {
+                dist,
+                active: false,
+            };
+        }
+        for k in 0..n {
+            let ty = p.mover(k).unwrap();
+            let from = SquareSet::from(p.piece_loc(k));
+            for x in (0..n).filter(|&x| x != k) {
+                if ty.hops() {
+                    dist[k as usize][x as usize] = Some(1);
+                    continue;
+                }
+                let target = SquareSet::from(p.piece_loc(x));
+                p.capture_moves(ty, from, p.obstacles, target, SquareSet(0), |_, moves| {
+                    dist[k as usize][x as usize] = Some(moves);
+                });
+            }
+        }
+        MoveBound { dist, active: true }
+    }
+
+    /// A lower bound on the moves left to win from `state`, or `None` if it can't be won.
+    fn estimate(&self, state: PuzzleState) -> Option<u32> {
+        if !self.active {
+            return Some(0);
+        }
+        let present = state.remaining_captures() | (1 << state.current_piece_idx());
+        let mut total = 0;
+        let mut bits = state.remaining_captures();
+        while bits != 0 {
+            let x = bits.trailing_zeros() as usize;
+            let mut captors = present & !(1 << x);
+            let mut best = None;
+            while captors != 0 {
+                let k = captors.trailing_zeros() as usize;
+                if let Some(d) = self.dist[k][x] {
+                    best = Some(best.map_or(d, |b: u32| b.min(d)));
+                }
+                captors &= captors - 1;
+            }
+            total += best?;
+            bits &= bits - 1;
+        }
+        Some(total)
+    }
+}
+
+/// A* search behind `solve_min_moves`, counting its work into `report`. States are explored in
+/// order of their cost so far plus `MoveBound::estimate`, which never overestimates, so the first
+/// finished state to come off the queue was reached as cheaply as possible. The estimate isn't
+/// always consistent, so a state may be expanded again if a cheaper way to it turns up.
+fn search_min_moves(
+    p: &Puzzle,
+    config: &SearchConfig,
+    report: &mut SolveReport,
+) -> Result<Option<(Vec<u32>, u32)>, Limit> {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+    // Maps each state to the cheapest known way to get there: its cost and predecessor.
+    let mut best: StateMap<(u32, Option<PuzzleState>)> = StateMap::default();
+    let bound = MoveBound::of(p);
+    // Queue entries are `(estimated total cost, cost so far, state)`.
+    let mut queue = BinaryHeap::new();
+    let initial = PuzzleState::initial(p);
+    best.insert(initial, (0, None));
+    if let Some(estimate) = bound.estimate(initial) {
+        queue.push(Reverse((estimate, 0, initial.0)));
+    }
+    // Did we skip any states for being too deep?
+    let mut truncated = false;
+    while let Some(Reverse((_, cost, state))) = queue.pop() {
+        let state = PuzzleState(state);
+        if best[&state].0 < cost {
+            continue; // stale queue entry
+        }
+        let depth = state.phase(p) as usize;
+        report.depth = report.depth.max(depth);
+        if config.won(p, state) {
+            let mut res = Vec::new();
+            let mut current = state;
+            while let Some(prev) = best[&current].1 {
+                res.push(current.current_piece_idx());
+                current = prev;
+            }
+            res.reverse();
+            return Ok(Some((res, cost)));
+        }
+        if config.too_deep(depth) {
+            truncated = true;
+            continue;
+        }
+        report.nodes_expanded += 1;
+        state
+            .next_states_with_moves(p, |_, moves, next| {
+                if !config.allows(p, state, next) {
+                    return;
+                }
+                let new_cost = cost + moves;
+                let Some(estimate) = bound.estimate(next) else {
+                    return; // can't be won
+                };
+                if best
+                    .get(&next)
+                    .is_none_or(|&(old_cost, _)| new_cost < old_cost)
+                {
+                    best.insert(next, (new_cost, Some(state)));
+                    queue.push(Reverse((new_cost + estimate, new_cost, next.0)));
+                }
+            })
+            .expect("reachable states are valid");
+        report.states_visited = best.len();
+        report.frontier_peak = report.frontier_peak.max(queue.len());
+        config.check(report)?;
+    }
+    if truncated {
+        return Err(Limit::Depth);
+    }
+    Ok(None)
+}
+
+/// Finds every state reachable from the start of a puzzle, including the initial state.
+pub fn reachable_states(p: &Puzzle) -> HashSet<PuzzleState> {
+    let initial = PuzzleState::initial(p);
+    let mut seen: HashSet<PuzzleState> = HashSet::new();
+    let mut stack = vec![initial];
+    seen.insert(initial);
+    while let Some(state) = stack.pop() {
+        state
+            .next_states(p, |next| {
+                if seen.insert(next) {
+                    stack.push(next);
+                }
+            })
+            .expect("reachable states are valid");
+    }
+    seen
+}
+
+/// Finds every state that can still go on to win, i.e., from which some `done` state is
+/// reachable (including `done` states themselves).
+fn winning_states(p: &Puzzle) -> StateSet {
+    fn visit(p: &Puzzle, state: PuzzleState, seen: &mut StateSet, winning: &mut StateSet) -> bool {
+        if !seen.insert(state) {
+            return winning.contains(&state);
+        }
+        let mut wins = state.done();
+        let mut nexts = Vec::new();
+        state
+            .next_states(p, |next| nexts.push(next))
+            .expect("reachable states are valid");
+        for next in nexts {
+            // Visit every successor, even after finding a win, so that each gets classified.
+            wins |= visit(p, next, seen, winning);
+        }
+        if wins {
+            winning.insert(state);
+        }
+        wins
+    }
+    let mut winning = StateSet::default();
+    visit(
+        p,
+        PuzzleState::initial(p),
+        &mut StateSet::default(),
+        &mut winning,
+    );
+    winning
+}
+
+/// Enumerates every distinct winning order of captures, lazily, in lexicographic order of piece
+/// indices. There can be very many, so take only as many as you need.
+pub fn solve_all(p: &Puzzle) -> Solutions<'_> {
+    let winning = winning_states(p);
+    let initial = PuzzleState::initial(p);
+    let mut solutions = Solutions {
+        p,
+        winning,
+        stack: Vec::new(),
+        path: Vec::new(),
+    };
+    if solutions.winning.contains(&initial) {
+        solutions.stack.push(vec![initial]);
+    }
+    solutions
+}
+
+/// Iterator over the solutions to a puzzle. See `solve_all`.
+pub struct Solutions<'a> {
+    p: &'a Puzzle,
+    /// States that can still win; the search never enters any others.
+    winning: StateSet,
+    /// For each depth, the successors not yet explored, in reverse order.
+    stack: Vec<Vec<PuzzleState>>,
+    /// The states along the current line of play, from the initial state on, one fewer than
+    /// `stack.len()`.
+    path: Vec<PuzzleState>,
+}
+
+impl Solutions<'_> {
+    /// Successors of `state` that can still win, with the lowest capture index last.
+    fn children(&self, state: PuzzleState) -> Vec<PuzzleState> {
+        let mut res = Vec::new();
+        state
+            .next_states(self.p, |next| {
+                if self.winning.contains(&next) {
+                    res.push(next);
+                }
+            })
+            .expect("reachable states are valid");
+        res.sort_by_key(|s| std::cmp::Reverse(s.current_piece_idx()));
+        res
+    }
+}
+
+impl Iterator for Solutions<'_> {
+    type Item = Solution;
+
+    fn next(&mut self) -> Option<Solution> {
+        loop {
+            let Some(state) = self.stack.last_mut()?.pop() else {
+                self.stack.pop();
+                self.path.pop();
+                continue;
+            };
+            if state.done() {
+                // The initial state comes first, and wasn't a capture. It can be a win itself,
+                // if the puzzle has no required pieces.
+                let captures: Vec<u32> = self
+                    .path
+                    .iter()
+                    .chain([&state])
+                    .skip(1)
+                    .map(|s| s.current_piece_idx())
+                    .collect();
+                return Some(
+                    Solution::from_captures(self.p, &captures).expect("solver output is valid"),
+                );
+            }
+            self.path.push(state);
+            let children = self.children(state);
+            self.stack.push(children);
+        }
+    }
+}
+
+/// How many winning capture orders a puzzle has, as counted by `Puzzle::count_solutions`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SolutionCount {
+    /// Exactly this many, which is less than the limit.
+    Exactly(usize),
+    /// At least this many: the limit was reached, so counting stopped.
+    AtLeast(usize),
+}
+
+impl SolutionCount {
+    /// Is there exactly one solution?
+    pub fn is_unique(self) -> bool {
+        self == SolutionCount::Exactly(1)
+    }
+}
+
+impl Puzzle {
+    /// Counts the distinct winning capture orders, giving up once there are `limit` of them. Use
+    /// a `limit` of 2 to check cheaply whether a puzzle has a unique solution.
+    pub fn count_solutions(&self, limit: usize) -> SolutionCount {
+        /// Solutions from `state`, capped at `limit`.
+        fn count(
+            p: &Puzzle,
+            state: PuzzleState,
+            limit: usize,
+            memo: &mut StateMap<usize>,
+        ) -> usize {
+            if state.done() {
+                return 1;
+            }
+            if let Some(&n) = memo.get(&state) {
+                return n;
+            }
+            let mut nexts = Vec::new();
+            state
+                .next_states(p, |next| nexts.push(next))
+                .expect("reachable states are valid");
+            let mut total = 0;
+            for next in nexts {
+                total += count(p, next, limit, memo);
+                if total >= limit {
+                    total = limit;
+                    break;
+                }
+            }
+            memo.insert(state, total);
+            total
+        }
+        let n = count(
+            self,
+            PuzzleState::initial(self),
+            limit,
+            &mut StateMap::default(),
+        );
+        if n >= limit {
+            SolutionCount::AtLeast(limit)
+        } else {
+            SolutionCount::Exactly(n)
+        }
+    }
+}
+
+// Everything below this point is shoddy frontend code :-)
+
+impl SquareSet {
+    /// Draws this set as an 8x8 grid, with `*` for members and `.` for non-members. See
+    /// `render::draw_squares` for other styles.
+    pub fn draw(self: SquareSet) -> String {
+        render::draw_squares(self, render::Style::PLAIN)
+    }
+}
+
+/// Draws the board as a grid, top rank first: `#` for obstacles, `.` for empty squares, and a
+/// FEN letter for each piece, uppercase for the ones that the player could start as. The
+/// alternate form, `{:#}`, uses chess glyphs instead. See `render::draw_puzzle`.
+impl std::fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let style = if f.alternate() {
+            render::Style::UNICODE
+        } else {
+            render::Style::PLAIN
+        };
+        f.write_str(&render::draw_puzzle(self, style))
+    }
+}
+
+impl Puzzle {
+    /// Parses "compound FEN" (FEN but `X`/`x` is a boundary), or panics on invalid FEN. See
+    /// `Puzzle::parse` for a version that returns errors instead.
+    pub fn from_compound_fen(fen: &str) -> Puzzle {
+        Self::parse(fen).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Parses "compound FEN" (FEN but `X`/`x` is a boundary). The player's piece is the one
+    /// given in uppercase. If there are several, each is one of `Puzzle::starts`, and the player
+    /// starts as the last one unless told otherwise.
+    ///
+    /// Boards smaller than 8×8 are written with fewer ranks or files: the board has as many ranks
+    /// as the FEN, numbered from 1 at the bottom, and as many files as its longest rank. Shorter
+    /// ranks are padded with empty squares.
+    ///
+    /// ```
+    /// let puzzle = echochess::Puzzle::parse("Rxn/3/b2").unwrap();
+    /// assert_eq!((puzzle.width(), puzzle.height()), (3, 3));
+    /// assert_eq!(puzzle.to_string(), "3 Rxn\n2 ...\n1 b..\n  abc\n".replace('x', "#"));
+    /// ```
+    pub fn parse(fen: &str) -> Result<Puzzle, FenError> {
+        Self::parse_with(fen, &custom::Pieces::default())
+    }
+
+    /// Like `parse`, but also accepts the letters of the given custom pieces.
+    pub fn parse_with(fen: &str, pieces: &custom::Pieces) -> Result<Puzzle, FenError> {
+        let mut obstacles = SquareSet(0);
+        let mut piece_types_by_loc: [Option<PieceType>; 64] = [None; 64];
+        let mut player_locs = Vec::new();
+        let mut exit = None;
+        let mut portal_ends: [Vec<Square>; PORTAL_GLYPHS.len()] = Default::default();
+        let mut ice = SquareSet(0);
+        let mut walls = Vec::new();
+        let mut one_way = [SquareSet(0); 4];
+        let mut optional = Vec::new();
+        let height = fen.split('/').count() as u32;
+        if height > 8 {
+            return Err(FenError::TooManyRanks);
+        }
+        let mut y = height - 1;
+        let mut x = 0;
+        let mut width = 0;
+        for (index, c) in fen.chars().enumerate() {
+            // Only used when `x < 8`, so always in range.
+            let loc = Square(8 * y as u8 + x as u8);
+            use PieceType::*;
+            match c {
+                '/' => {
+                    y -= 1;
+                    width = width.max(x);
+                    x = 0;
+                    continue;
+                }
+                '0'..='9' => {
+                    x += c as u32 - '0' as u32;
+                    if x > 8 {
+                        return Err(FenError::TooManyFiles { rank: y + 1 });
+                    }
+                    continue;
+                }
+                _ if x >= 8
+                    && (c.is_ascii_alphabetic()
+                        || "~+^><".contains(c)
+                        || PORTAL_GLYPHS.contains(&c)) =>
+                {
+                    return Err(FenError::TooManyFiles { rank: y + 1 });
+                }
+                '?' => {
+                    // Marks the piece just before it as optional.
+                    let prev = x.checked_sub(1).map(|x| Square(8 * y as u8 + x as u8));
+                    match prev.filter(|sq| piece_types_by_loc[sq.0 as usize].is_some()) {
+                        Some(sq) => optional.push(sq),
+                        None => return Err(FenError::UnexpectedChar { c, index }),
+                    }
+                    continue;
+                }
+                'X' | 'x' => {
+                    obstacles = obstacles | SquareSet::from(loc);
+                }
+                'E' | 'e' => {
+                    if exit.replace(loc).is_some() {
+                        return Err(FenError::SeveralExits);
+                    }
+                }
+                '~' => {
+                    ice.insert(loc);
+                }
+                '+' => walls.push(loc),
+                '^' | '>' | 'v' | '<' => {
+                    let heading = Heading::ALL.into_iter().find(|h| h.glyph() == c).unwrap();
+                    one_way[heading as usize].insert(loc);
+                }
+                _ if PORTAL_GLYPHS.contains(&c) => {
+                    let i = PORTAL_GLYPHS.iter().position(|&g| g == c).unwrap();
+                    portal_ends[i].push(loc);
+                }
+                'P' | 'p' => piece_types_by_loc[loc.0 as usize] = Some(Pawn),
+                'B' | 'b' => piece_types_by_loc[loc.0 as usize] = Some(Bishop),
+                'R' | 'r' => piece_types_by_loc[loc.0 as usize] = Some(Rook),
+                'N' | 'n' => piece_types_by_loc[loc.0 as usize] = Some(Knight),
+                'K' | 'k' => piece_types_by_loc[loc.0 as usize] = Some(King),
+                'Q' | 'q' => piece_types_by_loc[loc.0 as usize] = Some(Queen),
+                'Z' | 'z' => piece_types_by_loc[loc.0 as usize] = Some(Nightrider),
+                'A' | 'a' => piece_types_by_loc[loc.0 as usize] = Some(Archbishop),
+                'C' | 'c' => piece_types_by_loc[loc.0 as usize] = Some(Chancellor),
+                'M' | 'm' => piece_types_by_loc[loc.0 as usize] = Some(Amazon),
+                'G' | 'g' => piece_types_by_loc[loc.0 as usize] = Some(Grasshopper),
+                'O' | 'o' => piece_types_by_loc[loc.0 as usize] = Some(Cannon),
+                other => match pieces.get(other) {
+                    Some(ty) => piece_types_by_loc[loc.0 as usize] = Some(ty),
+                    None => return Err(FenError::UnexpectedChar { c: other, index }),
+                },
+            }
+            if c.is_ascii_uppercase() && !matches!(c, 'X' | 'E') {
+                player_locs.push(loc);
+            }
+            x += 1;
+        }
+        let player_loc = *player_locs.last().ok_or(FenError::MissingPlayer)?;
+        // Each wall takes a slot for its one default breaker, the rook.
+        let num_pieces = piece_types_by_loc.iter().flatten().count() + walls.len();
+        if num_pieces > MAX_PIECES {
+            return Err(FenError::TooManyPieces(num_pieces));
+        }
+        let (width, height) = (width.max(x) as u8, height as u8);
+        let mut puz = Self::from_board(obstacles, &piece_types_by_loc, player_loc, (width, height));
+        puz.exit = exit;
+        puz.ice = ice;
+        puz.one_way = one_way;
+        puz.starts = player_locs
+            .iter()
+            .filter_map(|&loc| puz.piece_on(loc))
+            .collect();
+        puz.starts.sort();
+        walls.sort();
+        puz.walls = walls;
+        for loc in optional {
+            let piece_idx = puz.piece_on(loc).expect("optional piece was just placed");
+            puz.set_required(piece_idx, false);
+        }
+        for (ends, &glyph) in portal_ends.iter().zip(&PORTAL_GLYPHS) {
+            match ends[..] {
+                [] => (),
+                [a, b] => puz.portals.push((a, b)),
+                _ => return Err(FenError::UnpairedPortal(glyph)),
+            }
+        }
+        Ok(puz)
+    }
+
+    /// Writes the board as compound FEN, which `parse` reads back. The player's start comes
+    /// out uppercase, along with any other `starts`, so it has to be the last of them in FEN
+    /// order, top rank first, to be read back as the start. What FEN has no room for, like
+    /// rules, patrols, and terrain, is left out; `pack::write_pack` writes those as comments.
+    ///
+    /// ```
+    /// use echochess::Puzzle;
+    ///
+    /// for fen in ["Rxn/3/b2", "R1n?3/E~+^2/4@@", "8/8/8/8/8/8/8/R1n5"] {
+    ///     assert_eq!(Puzzle::parse(fen).unwrap().to_fen(), fen);
+    /// }
+    /// ```
+    pub fn to_fen(&self) -> String {
+        let mut res = String::new();
+        for rank in (0..self.height).rev() {
+            if rank + 1 < self.height {
+                res.push('/');
+            }
+            let mut empty = 0;
+            for file in 0..self.width {
+                let sq = Square::from_file_rank(file, rank).unwrap();
+                let c = match self.piece_on(sq) {
+                    Some(idx) if self.starts.contains(&idx) => self.piece_types[idx as usize]
+                        .unwrap()
+                        .fen_char()
+                        .to_ascii_uppercase(),
+                    Some(idx) => self.piece_types[idx as usize].unwrap().fen_char(),
+                    None if self.obstacles.contains(sq) => 'x',
+                    None if self.exit == Some(sq) => 'E',
+                    None if self.ice.contains(sq) => '~',
+                    None if self.walls.contains(&sq) => '+',
+                    None if self.one_way_squares().contains(sq) => Heading::ALL
+                        .into_iter()
+                        .find(|&h| self.one_way(h).contains(sq))
+                        .unwrap()
+                        .glyph(),
+                    None => match self.portals.iter().position(|&(a, b)| sq == a || sq == b) {
+                        Some(i) => PORTAL_GLYPHS[i],
+                        None => {
+                            empty += 1;
+                            continue;
+                        }
+                    },
+                };
+                if empty > 0 {
+                    res.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                res.push(c);
+                if self.piece_on(sq).is_some_and(|idx| !self.required(idx)) {
+                    res.push('?');
+                }
+            }
+            if empty > 0 {
+                res.push_str(&empty.to_string());
+            }
+        }
+        res
+    }
+
+    /// Assembles a puzzle from a board, indexed by square, with the given `(width, height)`.
+    /// There must be a piece on `player_loc`, every piece must be on the board, and there can be
+    /// at most `MAX_PIECES` pieces in all.
+    fn from_board(
+        obstacles: SquareSet,
+        piece_types_by_loc: &[Option<PieceType>; 64],
+        player_loc: Square,
+        (width, height): (u8, u8),
+    ) -> Puzzle {
+        let mut pz = Puzzle {
+            obstacles: obstacles | !board_squares(width, height),
+            piece_types: [None; 32],
+            piece_locs: [0xff; 32],
+            pieces_by_loc: [0xff; 64],
+            player_start: 0xff,
+            patrols: Vec::new(),
+            rules: Rules::default(),
+            width,
+            height,
+            exit: None,
+            budget: None,
+            portals: Vec::new(),
+            ice: SquareSet(0),
+            starts: Vec::new(),
+            one_way: [SquareSet(0); 4],
+            walls: Vec::new(),
+            wall_breakers: vec![PieceType::Rook],
+            terrain: Vec::new(),
+            optional: 0,
+        };
+        let mut piece_idx = 0;
+        for (loc, piece_type) in piece_types_by_loc.iter().enumerate() {
+            let Some(piece_type) = *piece_type else {
+                continue;
+            };
+            pz.piece_types[piece_idx] = Some(piece_type.on_board(width, height));
+            pz.piece_locs[piece_idx] = loc as u8;
+            pz.pieces_by_loc[loc] = piece_idx as u8;
+            if loc == player_loc.0 as usize {
+                pz.player_start = piece_idx as u32;
+            }
+            piece_idx += 1;
+        }
+        pz.starts = vec![pz.player_start];
+        pz
+    }
+}
+
+/// The squares in the bottom-left `width` files and `height` ranks of the board.
+fn board_squares(width: u8, height: u8) -> SquareSet {
+    let rank = (1u64 << width) - 1;
+    let ranks = (0..height).fold(0, |acc, y| acc | rank << (8 * y));
+    SquareSet(ranks)
+}
+
+/// Why a compound FEN string couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// Character `c`, at (0-based, in `char`s) `index` in the input, isn't valid FEN.
+    UnexpectedChar { c: char, index: usize },
+    /// There are more than 8 ranks.
+    TooManyRanks,
+    /// The given rank, counting up from `1` at the bottom of the board, has more than 8 files.
+    TooManyFiles { rank: u32 },
+    /// No piece is marked (in uppercase) as the player's.
+    MissingPlayer,
+    /// There are this many pieces, but a puzzle can have at most `MAX_PIECES`.
+    TooManyPieces(usize),
+    /// More than one square is marked (with `E`) as the exit.
+    SeveralExits,
+    /// This portal glyph appears other than exactly twice.
+    UnpairedPortal(char),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FenError::UnexpectedChar { c, index } => {
+                write!(f, "Unrecognized char in FEN at index {}: {:?}", index, c)
+            }
+            FenError::TooManyRanks => write!(f, "Too many ranks in FEN (max 8)"),
+            FenError::TooManyFiles { rank } => {
+                write!(f, "Too many files on rank {} in FEN (max 8)", rank)
+            }
+            FenError::MissingPlayer => write!(f, "No player location"),
+            FenError::TooManyPieces(n) => {
+                write!(f, "Too many pieces in FEN: {} (max {})", n, MAX_PIECES)
+            }
+            FenError::SeveralExits => write!(f, "More than one exit in FEN"),
+            FenError::UnpairedPortal(c) => write!(f, "Portal {:?} isn't used exactly twice", c),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// Builds a `Puzzle` square by square, as an alternative to writing out compound FEN.
+///
+/// Mistakes like putting two things on one square are reported by `build`, so calls can be
+/// chained freely:
+///
+/// ```
+/// use echochess::PuzzleBuilder;
+/// use echochess::PieceType::*;
+///
+/// let sq = |name: &str| name.parse().unwrap();
+/// let puzzle = PuzzleBuilder::new()
+///     .add_piece(sq("a1"), Rook)
+///     .add_piece(sq("c1"), Knight)
+///     .add_obstacle(sq("b2"))
+///     .set_player(sq("a1"))
+///     .build()
+///     .unwrap();
+/// assert_eq!(echochess::solve(&puzzle), Some(vec![1]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct PuzzleBuilder {
+    obstacles: SquareSet,
+    pieces: [Option<PieceType>; 64],
+    player: Option<Square>,
+    starts: Vec<Square>,
+    size: (u8, u8),
+    exit: Option<Square>,
+    portals: Vec<(Square, Square)>,
+    ice: SquareSet,
+    one_way: [SquareSet; 4],
+    walls: Vec<Square>,
+    wall_breakers: Option<Vec<PieceType>>,
+    terrain: Vec<(Square, u32)>,
+    /// The first mistake made, if any, to be reported by `build`.
+    error: Option<BuildError>,
+}
+
+/// Why a `PuzzleBuilder` couldn't build a puzzle, or why a `generate::Generator`'s settings
+/// can't work.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// A piece or obstacle was added to the given square, which already had one.
+    SquareTaken(Square),
+    /// The player was never set.
+    MissingPlayer,
+    /// The player was set to the given square, which has no piece.
+    NoPieceForPlayer(Square),
+    /// There are this many pieces, but a puzzle can have at most `MAX_PIECES`.
+    TooManyPieces(usize),
+    /// A piece was added to the given square, which is off the board. See `set_size`.
+    OffBoard(Square),
+    /// The board was given this width and height, but each must be from 1 to 8.
+    BadSize(u8, u8),
+    /// More than `MAX_PORTALS` pairs of portals were added.
+    TooManyPortals,
+    /// The given square was set to cost no moves to step onto, but every step is at least one.
+    /// See `set_move_cost`.
+    ZeroMoveCost(Square),
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BuildError::SquareTaken(loc) => write!(f, "Square {} is already taken", loc),
+            BuildError::MissingPlayer => write!(f, "No player location"),
+            BuildError::NoPieceForPlayer(loc) => write!(f, "No piece for player on square {}", loc),
+            BuildError::TooManyPieces(n) => {
+                write!(f, "Too many pieces: {} (max {})", n, MAX_PIECES)
+            }
+            BuildError::OffBoard(loc) => write!(f, "Square {} is off the board", loc),
+            BuildError::BadSize(w, h) => write!(f, "Bad board size: {}x{} (max 8x8)", w, h),
+            BuildError::TooManyPortals => {
+                write!(f, "Too many pairs of portals (max {})", MAX_PORTALS)
+            }
+            BuildError::ZeroMoveCost(loc) => write!(f, "Square {} can't cost 0 moves", loc),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl Default for PuzzleBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PuzzleBuilder {
+    /// Starts with an empty board.
+    pub fn new() -> Self {
+        PuzzleBuilder {
+            obstacles: SquareSet(0),
+            pieces: [None; 64],
+            player: None,
+            starts: Vec::new(),
+            size: (8, 8),
+            exit: None,
+            portals: Vec::new(),
+            ice: SquareSet(0),
+            one_way: [SquareSet(0); 4],
+            walls: Vec::new(),
+            wall_breakers: None,
+            terrain: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Checks that square `loc` is still empty.
+    fn claim(&mut self, loc: Square) -> bool {
+        let taken = self.pieces[loc.0 as usize].is_some() || self.obstacles.contains(loc);
+        if taken {
+            self.error.get_or_insert(BuildError::SquareTaken(loc));
+        }
+        !taken
+    }
+
+    /// Puts a piece of the given type on square `loc`.
+    pub fn add_piece(&mut self, loc: Square, piece_type: PieceType) -> &mut Self {
+        if self.claim(loc) {
+            self.pieces[loc.0 as usize] = Some(piece_type);
+        }
+        self
+    }
+
+    /// Puts an obstacle on square `loc`.
+    pub fn add_obstacle(&mut self, loc: Square) -> &mut Self {
+        if self.claim(loc) {
+            self.obstacles = self.obstacles | SquareSet::from(loc);
+        }
+        self
+    }
+
+    /// Shrinks the board to files `0..width` and ranks `0..height`, each at most 8. The default is
+    /// the full 8×8 board. Squares off the board become obstacles, and can't hold pieces.
+    pub fn set_size(&mut self, width: u8, height: u8) -> &mut Self {
+        if !(1..=8).contains(&width) || !(1..=8).contains(&height) {
+            self.error.get_or_insert(BuildError::BadSize(width, height));
+        }
+        self.size = (width.clamp(1, 8), height.clamp(1, 8));
+        self
+    }
+
+    /// Makes the piece on square `loc` the player's starting piece. The piece may be added before
+    /// or after this call.
+    pub fn set_player(&mut self, loc: Square) -> &mut Self {
+        self.player = Some(loc);
+        self
+    }
+
+    /// Lets the player start as the piece on square `loc` instead, as one of `Puzzle::starts`.
+    /// The piece may be added before or after this call.
+    pub fn add_start(&mut self, loc: Square) -> &mut Self {
+        self.starts.push(loc);
+        self
+    }
+
+    /// Makes square `loc` the exit, which must be an empty square on the board. See
+    /// `Puzzle::exit`.
+    pub fn set_exit(&mut self, loc: Square) -> &mut Self {
+        self.exit = Some(loc);
+        self
+    }
+
+    /// Makes the empty square `loc` icy. See `Puzzle::ice`.
+    pub fn add_ice(&mut self, loc: Square) -> &mut Self {
+        self.ice.insert(loc);
+        self
+    }
+
+    /// Makes the empty square `loc` a one-way square that pieces can only enter heading
+    /// `heading`. See `Puzzle::one_way`.
+    pub fn add_one_way(&mut self, loc: Square, heading: Heading) -> &mut Self {
+        self.one_way[heading as usize].insert(loc);
+        self
+    }
+
+    /// Puts a breakable wall on square `loc`. See `Puzzle::walls`.
+    pub fn add_wall(&mut self, loc: Square) -> &mut Self {
+        self.walls.push(loc);
+        self
+    }
+
+    /// Sets which piece types can break walls, rather than just rooks. See
+    /// `Puzzle::wall_breakers`.
+    pub fn set_wall_breakers(&mut self, types: &[PieceType]) -> &mut Self {
+        self.wall_breakers = Some(types.to_vec());
+        self
+    }
+
+    /// Sets how many moves a step onto square `loc` costs, which must be at least one. See
+    /// `Puzzle::terrain`.
+    ///
+    /// ```
+    /// use echochess::{BuildError, PieceType, PuzzleBuilder, Square};
+    ///
+    /// let a1 = Square::from_file_rank(0, 0).unwrap();
+    /// let b1 = Square::from_file_rank(1, 0).unwrap();
+    /// let mut builder = PuzzleBuilder::new();
+    /// builder.add_piece(a1, PieceType::Rook).set_player(a1);
+    /// assert_eq!(builder.clone().set_move_cost(b1, 3).build().unwrap().move_cost(b1), 3);
+    /// assert_eq!(builder.set_move_cost(b1, 0).build(), Err(BuildError::ZeroMoveCost(b1)));
+    /// ```
+    pub fn set_move_cost(&mut self, loc: Square, cost: u32) -> &mut Self {
+        if cost == 0 {
+            self.error.get_or_insert(BuildError::ZeroMoveCost(loc));
+        }
+        self.terrain.push((loc, cost));
+        self
+    }
+
+    /// Links empty squares `a` and `b` as a pair of portals. There can be at most
+    /// `MAX_PORTALS` pairs. See `Puzzle::portals`.
+    pub fn add_portal(&mut self, a: Square, b: Square) -> &mut Self {
+        if self.portals.len() == MAX_PORTALS {
+            self.error.get_or_insert(BuildError::TooManyPortals);
+        }
+        if a == b {
+            self.error.get_or_insert(BuildError::SquareTaken(b));
+        }
+        self.portals.push((a, b));
+        self
+    }
+
+    /// Checks the board and builds the puzzle.
+    pub fn build(&self) -> Result<Puzzle, BuildError> {
+        if let Some(err) = &self.error {
+            return Err(err.clone());
+        }
+        let player = self.player.ok_or(BuildError::MissingPlayer)?;
+        for &loc in std::iter::once(&player).chain(&self.starts) {
+            if self.pieces[loc.0 as usize].is_none() {
+                return Err(BuildError::NoPieceForPlayer(loc));
+            }
+        }
+        let num_pieces = self.pieces.iter().flatten().count();
+        if num_pieces > MAX_PIECES {
+            return Err(BuildError::TooManyPieces(num_pieces));
+        }
+        let board = board_squares(self.size.0, self.size.1);
+        if let Some(loc) = (0..64)
+            .map(Square)
+            .find(|&loc| self.pieces[loc.0 as usize].is_some() && !board.contains(loc))
+        {
+            return Err(BuildError::OffBoard(loc));
+        }
+        let mut specials = SquareSet(0);
+        let portals = self.portals.iter().flat_map(|&(a, b)| [a, b]);
+        let walls = self.walls.iter().copied();
+        let one_way = self.one_way.iter().flat_map(|&squares| squares);
+        for loc in self
+            .exit
+            .into_iter()
+            .chain(portals)
+            .chain(self.ice)
+            .chain(walls)
+            .chain(one_way)
+        {
+            if !board.contains(loc) {
+                return Err(BuildError::OffBoard(loc));
+            }
+            if self.pieces[loc.0 as usize].is_some()
+                || self.obstacles.contains(loc)
+                || !specials.insert(loc)
+            {
+                return Err(BuildError::SquareTaken(loc));
+            }
+        }
+        let mut puz = Puzzle::from_board(self.obstacles, &self.pieces, player, self.size);
+        puz.exit = self.exit;
+        puz.portals = self.portals.clone();
+        puz.ice = self.ice;
+        puz.one_way = self.one_way;
+        let starts: Vec<u32> = self
+            .starts
+            .iter()
+            .filter_map(|&loc| puz.piece_on(loc))
+            .collect();
+        puz.starts.extend(starts);
+        puz.starts.sort();
+        puz.starts.dedup();
+        puz.walls = self.walls.clone();
+        puz.walls.sort();
+        let breakers = self.wall_breakers.as_deref().unwrap_or(&[PieceType::Rook]);
+        puz.set_wall_breakers(breakers)?;
+        for &(loc, cost) in &self.terrain {
+            if !board.contains(loc) {
+                return Err(BuildError::OffBoard(loc));
+            }
+            puz.set_move_cost(loc, cost);
+        }
+        Ok(puz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The fewest captures that win `p`, found by trying every line of play with
+    /// `Puzzle::verify`, without any of the searches, or `None` if none do.
+    fn fewest_captures(p: &Puzzle) -> Option<usize> {
+        fn extend(p: &Puzzle, line: &mut Vec<u32>, best: &mut Option<usize>) {
+            for piece in 0..p.num_slots() {
+                line.push(piece);
+                match p.verify(line) {
+                    Ok(_) => *best = Some(best.map_or(line.len(), |b| b.min(line.len()))),
+                    Err(VerifyError::Unfinished { .. }) => extend(p, line, best),
+                    Err(_) => {}
+                }
+                line.pop();
+            }
+        }
+        if p.verify(&[]).is_ok() {
+            return Some(0);
+        }
+        let mut best = None;
+        extend(p, &mut Vec::new(), &mut best);
+        best
+    }
+
+    /// Checks that plain breadth-first search wins `p` in the fewest captures that any line of
+    /// play does, and that `solver` finds a solution exactly when it does, with as many
+    /// captures, that plays out.
+    pub(crate) fn assert_agrees_with_bfs(solver: &Solver, p: &Puzzle) {
+        let expected = solve(p);
+        assert_eq!(expected.as_ref().map(Vec::len), fewest_captures(p), "{}", p);
+        match (expected, solver.solve(p).result) {
+            (Some(expected), SolveResult::Solved(found)) => {
+                assert_eq!(found.len(), expected.len(), "{}: {:?}", p, found);
+                assert!(p.verify(&found).is_ok(), "{}: {:?}", p, found);
+            }
+            (None, SolveResult::Unsolvable) => {}
+            (expected, found) => panic!("{}: expected {:?}, found {:?}", p, expected, found),
+        }
+    }
+
+    /// A rook must slip along rank 1 while the patrolling knight steps away to e3, and can only
+    /// do so after a waiting capture on a2. Without patrols, the knight walls off h1 and there's
+    /// no solution.
+    #[test]
+    fn patrols() {
+        let pack = pack::parse_pack(
+            "\
+            ; name: Patrol Crossing\n\
+            ; rules: patrols\n\
+            ; patrol: d1 e3\n\
+            XXXXXXXX/XXXXXXXX/XXXXXXXX/XXXXXXXX/XXXXXXXX/XXXX1XXX/rXXXXXXX/R2n3r\n\
+            ",
+        );
+        let (_, mut puz) = pack.into_iter().next().unwrap();
+        assert_eq!(solve(&puz), Some(vec![3, 2, 1]));
+        puz.rules.patrols = false;
+        assert_eq!(solve(&puz), None);
+    }
+
+    /// A win forgets which optional pieces were left, so it can be reached from states that made
+    /// different numbers of captures. Capturing the optional knight on the way takes a fourth
+    /// capture, and the solution mustn't pick that up when tracing the win back.
+    #[test]
+    fn optional_pieces_unwind_shortest() {
+        let puzzle = Puzzle::parse("m1x/2n/2>/xNa/1xb?").unwrap();
+        assert_eq!(solve(&puzzle), Some(vec![2, 3, 4]));
+        assert_eq!(solve_lexicographic(&puzzle), Some(vec![2, 3, 4]));
+        assert_strategies_agree(&puzzle);
+    }
+
+    /// With no required pieces, the puzzle starts out won, and every way of solving it says so.
+    #[test]
+    fn won_from_the_start() {
+        for fen in ["Rn?", "R3/3/3", "x111/1111/11RE/1r?^1"] {
+            let puzzle = Puzzle::parse(fen).unwrap();
+            assert_eq!(solve(&puzzle), Some(vec![]), "{}", fen);
+            assert!(analyze::first_moves(&puzzle).is_empty(), "{}", fen);
+            assert_eq!(solve_lexicographic(&puzzle), Some(vec![]), "{}", fen);
+            let all: Vec<_> = solve_all(&puzzle).map(|s| s.captures()).collect();
+            assert_eq!(all, vec![Vec::<u32>::new()], "{}", fen);
+            assert_eq!(
+                puzzle.count_solutions(2),
+                SolutionCount::Exactly(1),
+                "{}",
+                fen
+            );
+            assert_strategies_agree(&puzzle);
+        }
+    }
+
+    /// Falling back to depth-first search still finds a solution with the fewest captures, even
+    /// where walls or optional pieces let some wins take more.
+    #[test]
+    fn depth_first_fallback_is_shortest() {
+        for fen in [
+            "R1b/3/1n1",
+            "Q1n1/2b1/r3/1n1b",
+            "b111/1+b1/+1kR/1111",
+            "1q?1Q/b?r?11/1111/n1q1",
+        ] {
+            let puzzle = Puzzle::parse(fen).unwrap();
+            let mut solver = Solver::new();
+            solver.memory_cap(1).stats(true);
+            assert_agrees_with_bfs(&solver, &puzzle);
+            assert!(solver.solve(&puzzle).report.unwrap().fell_back, "{}", fen);
+            let outcome = solver.deterministic(true).solve(&puzzle);
+            let expected = solve_lexicographic(&puzzle).unwrap();
+            assert_eq!(outcome.result, SolveResult::Solved(expected), "{}", fen);
+        }
+    }
+
+    /// Merging symmetric states can swap the player's starting piece with an enemy, so the
+    /// start piece's bit can stand for a piece still to capture.
+    #[test]
+    fn symmetric_player() {
+        for fen in ["Q7/8/8/8/8/8/8/q6q", "R1r/3/r2"] {
+            let puzzle = Puzzle::parse(fen).unwrap();
+            assert_eq!(solve(&puzzle).map(|s| s.len()), Some(2), "{}", fen);
+            assert_strategies_agree(&puzzle);
+        }
+    }
+
+    /// A piece can be required last without capturing the optional pieces before it.
+    #[test]
+    fn last_leaves_optional_pieces() {
+        let puzzle = Puzzle::parse("Rnr?").unwrap();
+        let outcome = Solver::new().require(CaptureOrder::Last(1)).solve(&puzzle);
+        assert_eq!(outcome.result, SolveResult::Solved(vec![1]));
+    }
+
+    /// One step from several squares at once, near the edges and away from them.
+    #[test]
+    fn steps() {
+        let start = SquareSet(0x8040_2010_0804_0201);
+        assert_eq!(Pawn::move_steps(start), SquareSet(0x4020_1008_0402_0100));
+        assert_eq!(Pawn::capture_steps(start), SquareSet(0xa050_2814_0a05_0200));
+
+        let start = SquareSet(0x4000_0010_0000_0001);
+        assert_eq!(Bishop::move_steps(start), SquareSet(0x00a0_2800_2800_0200));
+        assert_eq!(Rook::move_steps(start), SquareSet(0xa040_1028_1000_0102));
+        assert_eq!(King::move_steps(start), SquareSet(0xa0e0_3828_3800_0302));
+
+        let start = SquareSet(0x0000_0010_0000_0000);
+        assert_eq!(Knight::move_steps(start), SquareSet(0x0028_4400_4428_0000));
+        let start = SquareSet(0x4000_0000_0400_0000);
+        assert_eq!(Knight::move_steps(start), SquareSet(0x0010_aa11_0011_0a00));
+    }
+
+    /// A square or two are looked up in the `magic` tables, and more are filled all at once, so
+    /// capturing from many squares has to find everything that capturing from each would.
+    #[test]
+    fn lookups_agree_with_fills() {
+        let lines: [fn(SquareSet, SquareSet) -> SquareSet; 2] =
+            [slide::ray_captures, slide::screen_captures];
+        let mut bits: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..200 {
+            bits ^= bits << 13;
+            bits ^= bits >> 7;
+            bits ^= bits << 17;
+            let pro = SquareSet(bits | bits.rotate_left(23));
+            let gen = SquareSet(bits.rotate_left(41) & bits.rotate_left(7)) & pro;
+            if gen.count() <= slide::LOOKUPS {
+                continue;
+            }
+            for line in lines {
+                let one_at_a_time = gen
+                    .iter()
+                    .fold(SquareSet(0), |res, sq| res | line(sq.into(), pro));
+                assert_eq!(line(gen, pro), one_at_a_time, "{:x} {:x}", gen.0, pro.0);
+            }
+        }
+    }
+
+    #[test]
+    fn batches() {
+        fn check<S: Stepper>(sets: [SquareSet; 70]) {
+            assert_eq!(S::move_steps_batch(sets), sets.map(S::move_steps));
+            assert_eq!(S::capture_steps_batch(sets), sets.map(S::capture_steps));
+        }
+        // Every square on its own, and then a few sets left over past a multiple of four.
+        let mut sets = [SquareSet(!0); 70];
+        for (i, set) in sets.iter_mut().enumerate().take(64) {
+            *set = SquareSet(1 << i);
+        }
+        sets[64] = SquareSet(0x8142_2418_1824_4281);
+        sets[65] = SquareSet(0);
+        check::<Pawn>(sets);
+        check::<PawnDown>(sets);
+        check::<Bishop>(sets);
+        check::<Rook>(sets);
+        check::<King>(sets);
+        check::<Queen>(sets);
+        check::<Knight>(sets);
+        check::<Nightrider>(sets);
+        check::<Archbishop>(sets);
+        check::<Chancellor>(sets);
+        check::<Amazon>(sets);
+        check::<Grasshopper>(sets);
+        check::<Cannon>(sets);
+    }
+
+    /// The IDs are saved in checkpoints and shared between collections, so they mustn't change
+    /// between builds, platforms or releases without notice.
+    #[test]
+    fn stable_ids() {
+        let puzzle = Puzzle::parse("R1n/3/b2").unwrap();
+        assert_eq!(puzzle.canonical_id(), 0x1672_3d52_d415_ebe5);
+        assert_eq!(puzzle.fingerprint(), 0x13a5_3b56_6cf2_e474);
+        let turned = Puzzle::parse("b1R/3/2n").unwrap();
+        assert_eq!(turned.canonical_id(), 0x1672_3d52_d415_ebe5);
+    }
+
+    /// Like `assert_agrees_with_bfs`, for every strategy.
+    pub(crate) fn assert_strategies_agree(p: &Puzzle) {
+        for strategy in [
+            Strategy::BreadthFirst,
+            Strategy::MinMoves,
+            Strategy::BestFirst,
+            Strategy::Beam,
+            Strategy::External,
+            #[cfg(feature = "parallel")]
+            Strategy::Concurrent,
+        ] {
+            assert_agrees_with_bfs(Solver::new().strategy(strategy), p);
+        }
+    }
+
+    /// Without a budget, beam search runs until it's sure of the best solution, so it wins when
+    /// breadth-first search does, in as few moves as `solve_min_moves`.
+    #[test]
+    fn beam_agrees_with_breadth_first() {
+        let mut solver = Solver::new();
+        solver.strategy(Strategy::Beam);
+        for fen in [
+            "R1b/3/1n1",
+            "B2/3/1n1",
+            "R1n/3/n1n",
+            "Q1n1/2b1/r3/1n1b",
+            // Wins that leave optional pieces or walls behind finish in the same layer as states
+            // that still have captures to make.
+            "3Zg?/4p/1~1o?1",
+            "1o1R/xv1+/x3/o3/1p1g",
+            // Finished states mustn't be expanded, even with an exit to walk to.
+            "1111/E111/11rn?/Q1n1",
+            "111r/11Nn/E11r/11+1",
+        ] {
+            let puzzle = Puzzle::parse(fen).unwrap();
+            assert_agrees_with_bfs(&solver, &puzzle);
+            let mut best = None;
+            solver.solve_anytime(&puzzle, |_, moves| best = Some(moves));
+            assert_eq!(
+                best,
+                solve_min_moves(&puzzle).map(|(_, moves)| moves),
+                "{}",
+                fen
+            );
+        }
+    }
+}
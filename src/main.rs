@@ -1,415 +1,938 @@
-use std::collections::{HashMap, HashSet};
+//! Command-line frontend for the `echochess` library. This is shoddy frontend code :-)
 
-struct Pawn;
-struct Bishop;
-struct Rook;
-struct Monarch;
-struct Knight;
+use echochess::analyze::{
+    count_states, dead_ends, difficulty, first_moves, forced_order, state_graph,
+};
+use echochess::campaign::Campaign;
+use echochess::corpus::{load_dir, CorpusStats};
+use echochess::custom::Pieces;
+use echochess::describe::{describe_puzzle, describe_solution, describe_unsolvable};
+use echochess::explain::explain_unsolvable;
+use echochess::generate::Generator;
+use echochess::grade::{grade, Grade};
+use echochess::notation::{notate_solution, read_moves};
+#[cfg(feature = "net")]
+use echochess::pack::parse_any_with;
+use echochess::pack::{parse_pack_with, try_parse_pack_with, write_pack, PuzzleMeta};
+use echochess::pgn::write_pgn;
+use echochess::protocol::serve;
+#[cfg(feature = "gif")]
+use echochess::raster::solution_gif;
+#[cfg(feature = "png")]
+use echochess::raster::{puzzle_png, solution_pngs};
+use echochess::render::{animate, draw_puzzle, steps, Style};
+use echochess::report::{write_report, Format};
+#[cfg(feature = "serde")]
+use echochess::solution_json;
+use echochess::svg::{puzzle_svg, solution_svgs};
+use echochess::{
+    solve, solve_lexicographic, solve_min_moves, solve_starts, solve_with_paths, solve_with_report,
+    wide, Puzzle, Solution, Square,
+};
 
-/// A subset of the squares on a chess board.
-///
-/// Square in file `x` and rank `y` is indicated by bit `8 * y + x`. For instance, B1 is bit `1`
-/// and A2 is bit `8`.
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-struct SquareSet(u64);
-
-// Overloads for basic arithmetic on `SquareSet`s.
-impl std::ops::BitAnd<SquareSet> for SquareSet {
-    type Output = SquareSet;
-    fn bitand(self, rhs: SquareSet) -> SquareSet {
-        SquareSet(self.0 & rhs.0)
-    }
-}
-impl std::ops::BitOr<SquareSet> for SquareSet {
-    type Output = SquareSet;
-    fn bitor(self, rhs: SquareSet) -> SquareSet {
-        SquareSet(self.0 | rhs.0)
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let flags = Flags {
+        describe: args.iter().any(|a| a == "--describe"),
+        min_moves: args.iter().any(|a| a == "--min-moves"),
+        deterministic: args.iter().any(|a| a == "--deterministic"),
+        stats: args.iter().any(|a| a == "--stats"),
+        style: Style {
+            unicode: args.iter().any(|a| a == "--unicode" || a == "--color"),
+            color: args.iter().any(|a| a == "--color"),
+        },
+        json: args.iter().any(|a| a == "--json"),
+        pgn: args.iter().any(|a| a == "--pgn"),
+        steps: args.iter().any(|a| a == "--steps"),
+        animate: args
+            .iter()
+            .find_map(|a| match a.strip_prefix("--animate")? {
+                "" => Some(std::time::Duration::from_millis(500)),
+                ms => Some(std::time::Duration::from_millis(
+                    ms.strip_prefix('=')
+                        .and_then(|ms| ms.parse().ok())
+                        .expect("usage: --animate[=<ms>]"),
+                )),
+            }),
+    };
+    args.retain(|a| {
+        !matches!(
+            a.as_str(),
+            "--describe"
+                | "--min-moves"
+                | "--deterministic"
+                | "--stats"
+                | "--unicode"
+                | "--color"
+                | "--pgn"
+                | "--steps"
+        ) && !a.starts_with("--animate")
+    });
+    let pieces = match args.iter().position(|a| a == "--pieces") {
+        Some(i) => {
+            let path = args.get(i + 1).expect("usage: --pieces <file>").clone();
+            args.drain(i..i + 2);
+            load_pieces(&path)
+        }
+        None => Pieces::default(),
+    };
+    match args.first().map(String::as_str) {
+        Some("grade") => grade_main(&args[1..], &pieces),
+        Some("analyze") => analyze_main(&args[1..], &pieces),
+        Some("stats") => stats_main(&args[1..], &pieces),
+        Some("campaign") => campaign_main(&args[1..], &pieces),
+        Some("generate") => generate_main(&args[1..]),
+        Some("svg") => svg_main(&args[1..], &pieces),
+        Some("png") => png_main(&args[1..], &pieces),
+        Some("gif") => gif_main(&args[1..], &pieces),
+        Some("report") => report_main(&args[1..], &pieces),
+        Some("protocol") => {
+            serve(std::io::stdin().lock(), std::io::stdout().lock(), &pieces)
+                .expect("reading stdin and writing stdout");
+        }
+        Some("fetch") => {
+            args.retain(|a| a != "--json");
+            fetch_main(&args[1..], flags, &pieces)
+        }
+        _ => {
+            // Subcommands like `analyze` take their own `--json`, so it's only global for the
+            // commands that solve.
+            args.retain(|a| a != "--json");
+            solve_main(args.first().map(String::as_str), flags, &pieces)
+        }
     }
 }
-impl std::ops::Not for SquareSet {
-    type Output = SquareSet;
-    fn not(self) -> SquareSet {
-        SquareSet(!self.0)
-    }
+
+/// `--pieces <file>`: reads custom piece definitions, a JSON list of `PieceSpec`s.
+#[cfg(feature = "serde")]
+fn load_pieces(path: &str) -> Pieces {
+    let src = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    Pieces::from_json(&src).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(2);
+    })
 }
-impl std::ops::Shl<u32> for SquareSet {
-    type Output = SquareSet;
-    fn shl(self, rhs: u32) -> SquareSet {
-        SquareSet(self.0 << rhs)
-    }
+
+#[cfg(not(feature = "serde"))]
+fn load_pieces(_path: &str) -> Pieces {
+    eprintln!("echochess was built without the `serde` feature, so it can't read piece files");
+    std::process::exit(2);
 }
-impl std::ops::Shr<u32> for SquareSet {
-    type Output = SquareSet;
-    fn shr(self, rhs: u32) -> SquareSet {
-        SquareSet(self.0 >> rhs)
-    }
+
+/// Flags that apply to every command that solves puzzles.
+#[derive(Debug, Copy, Clone)]
+struct Flags {
+    /// Print prose descriptions instead of the usual terse output.
+    describe: bool,
+    /// Find solutions with the fewest total moves, not just any solution.
+    min_moves: bool,
+    /// When there are several solutions, pick the first by piece index.
+    deterministic: bool,
+    /// Also print statistics about the breadth-first search.
+    stats: bool,
+    /// How to draw boards: `--unicode` for chess glyphs, and `--color` for those with colors.
+    style: Style,
+    /// Print each puzzle's solution as JSON, instead of the usual output. See `solution_json`.
+    json: bool,
+    /// Print a PGN-style record of each puzzle's solution, instead of the usual output.
+    pgn: bool,
+    /// `--steps`: draw the board after each capture of the solution, under its line.
+    steps: bool,
+    /// `--animate[=<ms>]`: play the solution back on the board, waiting this long between moves.
+    animate: Option<std::time::Duration>,
 }
 
-/// A piece type that moves by zero or more "move steps" followed by exactly one "capture step".
-/// This precisely describes the behavior of every chess piece when the piece is allowed to move an
-/// unbounded number of times and then must capture, as in Echo Chess.
-///
-/// (For pieces other than pawns, `move_steps` and `capture_steps` are the same.)
-trait Stepper {
-    /// If a piece is on one of the given squares, which squares can it move to in one step?
-    fn move_steps(from: SquareSet) -> SquareSet;
-    /// If a piece is on one of the given squares, which squares can it capture in one step?
-    fn capture_steps(from: SquareSet) -> SquareSet;
+/// `echochess grade <fen> --moves <move>...` or `echochess grade <fen> --replay <file>`: grades
+/// an attempt, given as the moves made in order, each in notation (like `Nxd5→R`) or as just the
+/// square captured, either on the command line or in a file.
+fn grade_main(args: &[String], pieces: &Pieces) {
+    let usage = "usage: echochess grade <fen> (--moves <move>... | --replay <file>)";
+    let (fen, text) = match args {
+        [fen, flag, moves @ ..] if flag == "--moves" => (fen, moves.join(" ")),
+        [fen, flag, path] if flag == "--replay" => (
+            fen,
+            std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path, e)),
+        ),
+        _ => panic!("{}", usage),
+    };
+    let puz = Puzzle::parse_with(fen, pieces).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let attempt = read_moves(&puz, &text).unwrap_or_else(|(step, error)| {
+        println!("invalid at step {}: {}", step + 1, error);
+        std::process::exit(1);
+    });
+    match grade(&puz, &attempt) {
+        Grade::Valid {
+            moves,
+            optimal,
+            letter,
+            distance,
+            optimal_distance,
+        } => println!(
+            "{}: solved in {} moves over {} squares, optimal {} moves over {} squares",
+            letter, moves, distance, optimal, optimal_distance
+        ),
+        Grade::Invalid { step, error } => {
+            println!("invalid at step {}: {:?}", step + 1, error);
+            std::process::exit(1);
+        }
+    }
 }
 
-/// Given that a piece of type `S` is on one of the squares in `from`, and may not move onto or
-/// through the squares in `obstacles`, which of the `targets` can it capture?
-fn captures<S: Stepper>(from: SquareSet, obstacles: SquareSet, targets: SquareSet) -> SquareSet {
-    let permeable = !(obstacles | targets);
-    let mut reachable = from & permeable;
-    loop {
-        let next = (reachable | S::move_steps(reachable)) & permeable;
-        if next == reachable {
-            break;
+/// `echochess analyze <fen | file> [--dot | --json]`: reports how hard a puzzle is, and lists
+/// the captures that throw away a win. Given a puzzle file, analyzes its first puzzle, and also
+/// shows its name and how its par compares with the fewest captures that win. With `--dot` or
+/// `--json`, prints the whole state graph in Graphviz's DOT language or as JSON instead.
+fn analyze_main(args: &[String], pieces: &Pieces) {
+    let (fen, export) = match args {
+        [fen] => (fen, None),
+        [fen, flag] if flag == "--dot" || flag == "--json" => (fen, Some(flag.as_str())),
+        _ => panic!("usage: echochess analyze <fen | file> [--dot | --json]"),
+    };
+    let (meta, puz) = if std::path::Path::new(fen).is_file() {
+        let src = std::fs::read_to_string(fen).unwrap_or_else(|e| panic!("{}: {}", fen, e));
+        parse_pack_with(&src, pieces)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| {
+                eprintln!("{}: no puzzles", fen);
+                std::process::exit(2);
+            })
+    } else {
+        let puz = Puzzle::parse_with(fen, pieces).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+        (PuzzleMeta::default(), puz)
+    };
+    if let Some(flag) = export {
+        if flag == "--dot" {
+            print!("{}", state_graph(&puz).to_dot());
+        } else {
+            print_json_graph(&puz);
+        }
+        return;
+    }
+    if let Some(name) = &meta.name {
+        println!("name: {}", name);
+    }
+    if let Some(par) = meta.par {
+        match solve(&puz) {
+            Some(sol) => println!("par: {}, solved in {}", par, sol.len()),
+            None => println!("par: {}, no solution", par),
         }
-        reachable = next;
     }
-    S::capture_steps(reachable) & targets
+    let d = difficulty(&puz);
+    println!("states: {}", d.states);
+    println!("states by depth: {:?}", count_states(&puz, usize::MAX));
+    println!("branching: {:.2}", d.branching);
+    println!("dead ends: {}", d.dead_ends);
+    println!("losing first moves: {:.0}%", d.losing_first_moves * 100.0);
+    println!("solutions: {:?}", d.solutions);
+    println!("score: {:.2}", d.score);
+    for first in first_moves(&puz) {
+        match first.best {
+            Some(moves) => println!("first capture on {}: wins in {} moves", first.square, moves),
+            None => println!("first capture on {}: loses", first.square),
+        }
+    }
+    if let Some(forced) = forced_order(&puz) {
+        for (i, pos) in forced.positions {
+            println!(
+                "always captured at step {}: {}",
+                pos + 1,
+                capture_square(&puz, i)
+            );
+        }
+        for (a, b) in forced.before {
+            println!(
+                "always captured before {}: {}",
+                capture_square(&puz, b),
+                capture_square(&puz, a)
+            );
+        }
+    }
+    let dead = dead_ends(&puz);
+    for trap in &dead.traps {
+        let line: Vec<String> = trap
+            .line
+            .iter()
+            .map(|&i| capture_square(&puz, i).to_string())
+            .collect();
+        println!(
+            "trap: after [{}], capturing on {} can't win",
+            line.join(" "),
+            capture_square(&puz, trap.captured)
+        );
+    }
 }
 
-mod can_move {
-    use super::SquareSet;
+/// `--json`: prints a puzzle's state graph as JSON.
+#[cfg(feature = "serde")]
+fn print_json_graph(puz: &Puzzle) {
+    println!("{}", state_graph(puz).to_json());
+}
 
-    pub(crate) const LEFT: SquareSet = SquareSet(!0x0101010101010101);
-    pub(crate) const RIGHT: SquareSet = SquareSet(!0x8080808080808080);
-    pub(crate) const TWO_LEFT: SquareSet = SquareSet(!0x0303030303030303);
-    pub(crate) const TWO_RIGHT: SquareSet = SquareSet(!0xc0c0c0c0c0c0c0c0);
+#[cfg(not(feature = "serde"))]
+fn print_json_graph(_puz: &Puzzle) {
+    eprintln!("echochess was built without the `serde` feature, so it can't write JSON");
+    std::process::exit(2);
 }
 
-impl Stepper for Pawn {
-    fn move_steps(from: SquareSet) -> SquareSet {
-        from << 8
-    }
-    fn capture_steps(from: SquareSet) -> SquareSet {
-        ((from & can_move::LEFT) << 7) | ((from & can_move::RIGHT) << 9)
-    }
+/// Where the player stands after capturing piece `i`, which may be a wall's slot.
+fn capture_square(puz: &Puzzle, i: u32) -> Square {
+    puz.wall_square(i).unwrap_or_else(|| puz.piece_loc(i))
 }
 
-impl Stepper for Bishop {
-    fn move_steps(from: SquareSet) -> SquareSet {
-        let left = from & can_move::LEFT;
-        let right = from & can_move::RIGHT;
-        (left >> 9) | (right >> 7) | (left << 7) | (right << 9)
+/// `echochess stats <dir> [--json]`: solves every puzzle in the `.txt` files in a directory, and
+/// sums up how many are solvable, how many moves they take, and which pieces they use.
+fn stats_main(args: &[String], pieces: &Pieces) {
+    let (dir, json) = match args {
+        [dir] => (dir, false),
+        [dir, flag] if flag == "--json" => (dir, true),
+        _ => panic!("usage: echochess stats <dir> [--json]"),
+    };
+    let puzzles = load_dir(std::path::Path::new(dir), pieces).unwrap_or_else(|e| {
+        eprintln!("{}: {}", dir, e);
+        std::process::exit(2);
+    });
+    let stats = CorpusStats::of(&puzzles);
+    if json {
+        print_json_stats(&stats);
+        return;
+    }
+    println!("puzzles: {}", stats.puzzles);
+    println!(
+        "solvable: {} ({:.0}%)",
+        stats.solvable,
+        stats.solvable_fraction() * 100.0
+    );
+    for (moves, count) in &stats.optimal_moves {
+        println!("optimal in {} moves: {}", moves, count);
     }
-    fn capture_steps(from: SquareSet) -> SquareSet {
-        Self::move_steps(from)
+    for (name, count) in &stats.piece_types {
+        println!("{} pieces: {}", name, count);
     }
 }
 
-impl Stepper for Rook {
-    fn move_steps(from: SquareSet) -> SquareSet {
-        (from >> 8) | ((from & can_move::LEFT) >> 1) | ((from & can_move::RIGHT) << 1) | (from << 8)
-    }
-    fn capture_steps(from: SquareSet) -> SquareSet {
-        Self::move_steps(from)
-    }
+/// `--json`: prints corpus statistics as JSON.
+#[cfg(feature = "serde")]
+fn print_json_stats(stats: &CorpusStats) {
+    println!("{}", stats.to_json());
 }
 
-impl Stepper for Monarch {
-    fn move_steps(from: SquareSet) -> SquareSet {
-        Rook::move_steps(from) | Bishop::move_steps(from)
-    }
-    fn capture_steps(from: SquareSet) -> SquareSet {
-        Self::move_steps(from)
+#[cfg(not(feature = "serde"))]
+fn print_json_stats(_stats: &CorpusStats) {
+    eprintln!("echochess was built without the `serde` feature, so it can't write JSON");
+    std::process::exit(2);
+}
+
+/// `echochess generate <count> [<seed>]`: prints a level pack of `count` puzzles that get harder
+/// as they go. The seed is in hex, as the levels' notes give theirs.
+fn generate_main(args: &[String]) {
+    let usage = "usage: echochess generate <count> [<seed>]";
+    let (count, seed) = match args {
+        [count] => (count, "0"),
+        [count, seed] => (count, seed.as_str()),
+        _ => panic!("{}", usage),
+    };
+    let count = count.parse().unwrap_or_else(|_| panic!("{}", usage));
+    let seed = u64::from_str_radix(seed, 16).unwrap_or_else(|_| panic!("{}", usage));
+    let levels = Generator::new().pack(seed, count);
+    print!("{}", write_pack(&levels));
+}
+
+/// `echochess svg <fen> <dir>`: draws the puzzle to `board.svg` in `dir`, and if it can be
+/// solved, each step of a solution to `step-1.svg` and so on.
+fn svg_main(args: &[String], pieces: &Pieces) {
+    let [fen, dir] = args else {
+        panic!("usage: echochess svg <fen> <dir>");
+    };
+    let puz = Puzzle::parse_with(fen, pieces).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let dir = std::path::Path::new(dir);
+    let write = |name: String, svg: &str| {
+        let path = dir.join(name);
+        std::fs::write(&path, svg).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        println!("wrote {}", path.display());
+    };
+    std::fs::create_dir_all(dir).unwrap_or_else(|e| panic!("{}: {}", dir.display(), e));
+    write("board.svg".to_string(), &puzzle_svg(&puz));
+    let Some(sol) = solve_with_paths(&puz) else {
+        println!("no solution");
+        return;
+    };
+    for (i, svg) in solution_svgs(&puz, &sol).iter().enumerate().skip(1) {
+        write(format!("step-{}.svg", i), svg);
     }
 }
 
-impl Stepper for Knight {
-    fn move_steps(from: SquareSet) -> SquareSet {
-        let left1 = from & can_move::LEFT;
-        let left2 = from & can_move::TWO_LEFT;
-        let right1 = from & can_move::RIGHT;
-        let right2 = from & can_move::TWO_RIGHT;
-        (left1 >> 17)
-            | (right1 >> 15)
-            | (left2 >> 10)
-            | (right2 >> 6)
-            | (left2 << 6)
-            | (right2 << 10)
-            | (left1 << 15)
-            | (right1 << 17)
+/// `echochess png <fen> <dir> [--size <px>]`: like `echochess svg`, but writes PNG images,
+/// `size` pixels to a square, 48 by default.
+#[cfg(feature = "png")]
+fn png_main(args: &[String], pieces: &Pieces) {
+    let usage = "usage: echochess png <fen> <dir> [--size <px>]";
+    let (fen, dir, size) = match args {
+        [fen, dir] => (fen, dir, 48),
+        [fen, dir, flag, size] if flag == "--size" => (
+            fen,
+            dir,
+            size.parse().unwrap_or_else(|_| panic!("{}", usage)),
+        ),
+        _ => panic!("{}", usage),
+    };
+    let puz = Puzzle::parse_with(fen, pieces).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let dir = std::path::Path::new(dir);
+    let write = |name: String, png: &[u8]| {
+        let path = dir.join(name);
+        std::fs::write(&path, png).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        println!("wrote {}", path.display());
+    };
+    std::fs::create_dir_all(dir).unwrap_or_else(|e| panic!("{}: {}", dir.display(), e));
+    write("board.png".to_string(), &puzzle_png(&puz, size));
+    let Some(sol) = solve_with_paths(&puz) else {
+        println!("no solution");
+        return;
+    };
+    for (i, png) in solution_pngs(&puz, &sol, size).iter().enumerate().skip(1) {
+        write(format!("step-{}.png", i), png);
     }
-    fn capture_steps(from: SquareSet) -> SquareSet {
-        Self::move_steps(from)
+}
+
+#[cfg(not(feature = "png"))]
+fn png_main(_args: &[String], _pieces: &Pieces) {
+    eprintln!("echochess was built without the `png` feature, so it can't write PNG images");
+    std::process::exit(2);
+}
+
+/// `echochess gif <fen> <file> [--size <px>] [--delay <ms>]`: animates a solution of the puzzle
+/// as a GIF, `size` pixels to a square (48 by default), and `delay` milliseconds to a frame
+/// (250 by default).
+#[cfg(feature = "gif")]
+fn gif_main(args: &[String], pieces: &Pieces) {
+    let usage = "usage: echochess gif <fen> <file> [--size <px>] [--delay <ms>]";
+    let [fen, path, rest @ ..] = args else {
+        panic!("{}", usage);
+    };
+    let (mut size, mut delay) = (48, 250);
+    for opt in rest.chunks(2) {
+        match opt {
+            [flag, n] if flag == "--size" => size = n.parse().expect(usage),
+            [flag, n] if flag == "--delay" => delay = n.parse().expect(usage),
+            _ => panic!("{}", usage),
+        }
     }
+    let puz = Puzzle::parse_with(fen, pieces).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let Some(sol) = solve_with_paths(&puz) else {
+        println!("no solution");
+        return;
+    };
+    let gif = solution_gif(&puz, &sol, size, delay);
+    std::fs::write(path, gif).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    println!("wrote {}", path);
 }
 
-#[repr(u8)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum PieceType {
-    Pawn,
-    Bishop,
-    Rook,
-    Monarch,
-    Knight,
+#[cfg(not(feature = "gif"))]
+fn gif_main(_args: &[String], _pieces: &Pieces) {
+    eprintln!("echochess was built without the `gif` feature, so it can't write GIFs");
+    std::process::exit(2);
 }
 
-/// Concise, solver-friendly description of a puzzle with up to 27 pieces.
-///
-/// Pieces in this puzzle are indexed from 0 in order of ascending board location, in rank-major
-/// order. That is, piece A comes before piece B if it is either on a smaller rank (1 is smallest,
-/// 8 is largest), or on the same rank and a smaller file (A is smallest, H is largest).
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct Puzzle {
-    /// Which squares have obstacles?
-    obstacles: SquareSet,
-    /// Maps piece index (`0..27`) to piece type, or `None` if there is no such piece.
-    piece_types: [Option<PieceType>; 32],
-    /// Maps piece index (`0..27`) to board square (`0..64`), or `0xff` if there is no such piece.
-    piece_locs: [u8; 32],
-    /// Maps board square (`0..64`) to piece index (`0..27`), or `0xff` if there is no piece at
-    /// that location.
-    pieces_by_loc: [u8; 64],
-    /// Which piece (`0..27`) is initially controlled by the player?
-    player_start: u32,
+/// `echochess report <file> [--html]`: writes a report on the puzzle in a puzzle file, or the
+/// first one if it's a pack, as Markdown or as an HTML page.
+fn report_main(args: &[String], pieces: &Pieces) {
+    let (path, format) = match args {
+        [path] => (path, Format::Markdown),
+        [path, flag] if flag == "--html" => (path, Format::Html),
+        _ => panic!("usage: echochess report <file> [--html]"),
+    };
+    let src = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let Some((meta, puz)) = parse_pack_with(&src, pieces).into_iter().next() else {
+        eprintln!("{}: no puzzles", path);
+        std::process::exit(2);
+    };
+    print!("{}", write_report(&meta, &puz, format));
 }
 
-/// Bits 0 through 26 (inclusive) indicate which pieces still need to be captured. The integer
-/// formed by bits 27 through 31 (i.e., the value of `z >> 27`) indicates which piece is currently
-/// the player.
-///
-/// Thus, this type can represent puzzles with up to 27 distinct pieces across both colors. The
-/// initial state is `(((1 << num_pieces) - 1) & !(1 << player_start)) | (player_start << 27)`.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-struct PuzzleState(u32);
+/// `echochess campaign <pack>`: solves the levels of a pack as one campaign, in order.
+fn campaign_main(args: &[String], pieces: &Pieces) {
+    let [path] = args else {
+        panic!("usage: echochess campaign <pack>");
+    };
+    let src = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let (metas, levels): (Vec<PuzzleMeta>, Vec<Puzzle>) =
+        parse_pack_with(&src, pieces).into_iter().unzip();
+    let campaign = Campaign { levels };
+    let report = campaign.solve();
+    for (i, (meta, admits)) in metas.iter().zip(&report.admits).enumerate() {
+        let name = meta.name.as_deref().unwrap_or("(untitled)");
+        println!("level {}: {}; can enter as {:?}", i + 1, name, admits);
+    }
+    let Some(chain) = report.chain else {
+        println!("no chain of solutions gets through the whole campaign");
+        if let Some(i) = report.broken {
+            println!(
+                "level {} can't be won as any piece the levels before it can be left as",
+                i + 1
+            );
+        }
+        std::process::exit(1);
+    };
+    for (i, (level, (entry, sol))) in campaign.levels.iter().zip(&chain).enumerate() {
+        let exit = level.piece_type(*sol.last().unwrap()).unwrap();
+        println!(
+            "level {}: enter as {:?}, capture {:?}, exit as {:?}",
+            i + 1,
+            entry,
+            sol,
+            exit
+        );
+    }
+}
 
-impl PuzzleState {
-    /// Computes the initial state for a puzzle.
-    pub fn initial(p: &Puzzle) -> Self {
-        let num_pieces = p.piece_locs.iter().take_while(|z| **z != 0xff).count();
-        let to_capture = ((1 << num_pieces) - 1) & !(1 << p.player_start);
-        PuzzleState(to_capture | (p.player_start << 27))
+/// `echochess fetch <url> [--timeout <seconds>] [--save <file>]`: downloads puzzles over HTTP,
+/// in any format that `parse_any_with` reads, and solves them like the default command, with the
+/// same flags. Exits with status 2 if the download fails, or 3 if it isn't puzzles.
+#[cfg(feature = "net")]
+fn fetch_main(args: &[String], flags: Flags, pieces: &Pieces) {
+    let usage = "usage: echochess fetch <url> [--timeout <seconds>] [--save <file>]";
+    let mut url = None;
+    let mut timeout = std::time::Duration::from_secs(10);
+    let mut save = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--timeout" => {
+                let secs = args.next().and_then(|s| s.parse().ok()).expect(usage);
+                timeout = std::time::Duration::from_secs_f64(secs);
+            }
+            "--save" => save = Some(args.next().expect(usage)),
+            _ if url.is_none() => url = Some(arg),
+            _ => panic!("{}", usage),
+        }
+    }
+    let url = url.expect(usage);
+    let body = match net::get(url, timeout) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("fetching {}: {}", url, e);
+            std::process::exit(2);
+        }
+    };
+    if let Some(path) = save {
+        std::fs::write(path, &body).unwrap_or_else(|e| panic!("{}: {}", path, e));
     }
+    let Ok(body) = String::from_utf8(body) else {
+        eprintln!("{}: not text", url);
+        std::process::exit(3);
+    };
+    let entries = parse_any_with(&body, pieces).unwrap_or_else(|e| {
+        eprintln!("{}: {}", url, e);
+        std::process::exit(3);
+    });
+    print_entries(&entries, flags);
+}
 
-    /// Checks whether the player has won: i.e., if all opposing pieces have been captured.
-    pub fn done(self) -> bool {
-        self.remaining_captures() == 0
-    }
-
-    pub fn current_piece_idx(self) -> u32 {
-        self.0 >> 27
-    }
+#[cfg(not(feature = "net"))]
+fn fetch_main(_args: &[String], _flags: Flags, _pieces: &Pieces) {
+    eprintln!("echochess was built without the `net` feature");
+    std::process::exit(2);
+}
 
-    fn remaining_captures(self) -> u32 {
-        self.0 & 0x07ffffff
-    }
+/// A tiny HTTP/1.1 client: just enough to `GET` a small text file.
+#[cfg(feature = "net")]
+mod net {
+    use std::io::{Read, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::time::{Duration, Instant};
 
-    /// Calls `consume(piece_idx, next_state)` for each successor state, where `piece_idx`
-    /// (`0..27`) is the index of the piece that can be captured to move to `next_state`.
-    pub fn next_states<F: FnMut(PuzzleState)>(self, p: &Puzzle, mut consume: F) {
-        let player_idx = self.current_piece_idx() as usize;
-        let start = SquareSet(1 << p.piece_locs[player_idx]);
-        let obstacles = p.obstacles;
-        let targets = {
-            let mut res = 0;
-            let mut remaining = self.remaining_captures();
-            while remaining != 0 {
-                let i = remaining.trailing_zeros();
-                // `i` (0..27) is the index of a piece that still needs to be captured
-                res |= 1 << p.piece_locs[i as usize];
-                remaining &= remaining - 1;
-            }
-            SquareSet(res)
+    /// Downloads an `http://` URL, giving up if the whole exchange takes longer than `timeout`.
+    /// There's no TLS support, so `https://` URLs are an error.
+    pub(crate) fn get(url: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+        let deadline = Instant::now() + timeout;
+        let rest = match url.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some((scheme, _)) => return Err(format!("unsupported scheme {:?}", scheme)),
+            None => return Err("not a URL".to_string()),
         };
-        let captures = match p.piece_types[player_idx] {
-            Some(PieceType::Pawn) => captures::<Pawn>(start, obstacles, targets),
-            Some(PieceType::Bishop) => captures::<Bishop>(start, obstacles, targets),
-            Some(PieceType::Rook) => captures::<Rook>(start, obstacles, targets),
-            Some(PieceType::Monarch) => captures::<Monarch>(start, obstacles, targets),
-            Some(PieceType::Knight) => captures::<Knight>(start, obstacles, targets),
-            None => panic!("no piece {}", player_idx),
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
         };
-
-        let mut captures = captures.0;
-        while captures != 0 {
-            let i = captures.trailing_zeros();
-            // `i` (0..64) is the board square of a piece that can be captured
-            let piece_idx = u32::from(p.pieces_by_loc[i as usize]);
-            let new_captures = self.remaining_captures() & !(1 << piece_idx);
-            let new_state = Self(new_captures | (piece_idx << 27));
-            consume(new_state);
-            captures &= captures - 1;
-        }
-    }
-}
-
-/// Solves a puzzle, returning a list of piece indices to be captured in order to win, or returns
-/// `None` if no solution is possible.
-fn solve(p: &Puzzle) -> Option<Vec<u32>> {
-    let mut predecessors: HashMap<PuzzleState, PuzzleState> = HashMap::new();
-    let mut frontier: HashSet<PuzzleState> = HashSet::new();
-    let mut new_frontier: HashSet<PuzzleState> = HashSet::new();
-    frontier.insert(PuzzleState::initial(p));
-    while !frontier.is_empty() {
-        for &prev in frontier.iter() {
-            let mut done = None;
-            prev.next_states(p, |next| {
-                use std::collections::hash_map::Entry::*;
-                match predecessors.entry(next) {
-                    Occupied(_) => (),
-                    Vacant(slot) => {
-                        slot.insert(prev);
-                        new_frontier.insert(next);
-                    }
-                }
-                if next.done() {
-                    done = Some(next);
-                }
-            });
-            if let Some(final_state) = done {
-                // unwind
-                let mut res = Vec::new();
-                let mut current = final_state;
-                while let Some(&prev) = predecessors.get(&current) {
-                    res.push(current.current_piece_idx());
-                    current = prev;
+        let addr_str = if authority.contains(':') {
+            authority.to_string()
+        } else {
+            format!("{}:80", authority)
+        };
+        let addr = addr_str
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| format!("no address for {}", authority))?;
+        let mut stream =
+            TcpStream::connect_timeout(&addr, remaining(deadline)?).map_err(|e| e.to_string())?;
+        stream
+            .set_write_timeout(Some(remaining(deadline)?))
+            .map_err(|e| e.to_string())?;
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            path, authority
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| e.to_string())?;
+        // A per-read timeout alone would let a server that drips a byte at a time hold us
+        // forever, so each read only gets whatever is left of the overall budget.
+        let mut response = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            stream
+                .set_read_timeout(Some(remaining(deadline)?))
+                .map_err(|e| e.to_string())?;
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => response.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Err("timed out".to_string())
                 }
-                res.reverse();
-                return Some(res);
+                Err(e) => return Err(e.to_string()),
             }
         }
-        frontier.clear();
-        std::mem::swap(&mut frontier, &mut new_frontier);
+        parse_response(&response)
     }
-    None
-}
 
-// Everything below this point is shoddy frontend code :-)
+    /// How long is left until `deadline`, or an error if it has already passed. Never zero, as
+    /// the socket timeouts reject that.
+    fn remaining(deadline: Instant) -> Result<Duration, String> {
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(left) if !left.is_zero() => Ok(left),
+            _ => Err("timed out".to_string()),
+        }
+    }
 
-impl SquareSet {
-    fn draw(self: SquareSet) -> String {
-        let mut res = String::new();
-        for y in (0..8).rev() {
-            res.push(char::from_u32('a' as u32 + y).unwrap());
-            res.push(' ');
-            for x in 0..8 {
-                if self.0 & (1 << (8 * y + x)) != 0 {
-                    res.push('*');
-                } else {
-                    res.push('.');
-                }
-            }
-            res.push('\n');
-        }
-        res.push_str("  12345678\n");
-        res
-    }
-}
-
-impl Puzzle {
-    /// Parses "compound FEN" (FEN but `X`/`x` is a boundary), or panics on invalid FEN.
-    fn from_compound_fen(fen: &str) -> Puzzle {
-        let mut obstacles = SquareSet(0);
-        let mut piece_types_by_loc: [Option<PieceType>; 64] = [None; 64];
-        let mut player_loc = None;
-        let mut y = 7;
-        let mut x = 0;
-        for c in fen.chars() {
-            let loc = (8 * y + x) as usize;
-            use PieceType::*;
-            match c {
-                '/' => {
-                    y -= 1;
-                    x = 0;
-                    continue;
-                }
-                '0'..='9' => {
-                    x += c as u32 - '0' as u32;
-                    continue;
-                }
-                'X' | 'x' => {
-                    obstacles = obstacles | SquareSet(1 << loc);
-                }
-                'P' | 'p' => piece_types_by_loc[loc] = Some(Pawn),
-                'B' | 'b' => piece_types_by_loc[loc] = Some(Bishop),
-                'R' | 'r' => piece_types_by_loc[loc] = Some(Rook),
-                'N' | 'n' => piece_types_by_loc[loc] = Some(Knight),
-                'K' | 'k' | 'Q' | 'q' => piece_types_by_loc[loc] = Some(Monarch),
-                other => panic!("Unrecognized char in FEN: {:?}", other),
-            }
-            if matches!(c, 'P' | 'B' | 'R' | 'K' | 'Q' | 'N') {
-                player_loc = Some(loc);
+    /// The body of a `200 OK` response, with any chunked transfer encoding undone.
+    fn parse_response(response: &[u8]) -> Result<Vec<u8>, String> {
+        let split = find(response, b"\r\n\r\n").ok_or("malformed HTTP response")?;
+        let head = std::str::from_utf8(&response[..split]).map_err(|_| "malformed HTTP headers")?;
+        let body = &response[split + 4..];
+        let mut lines = head.split("\r\n");
+        let status = lines.next().unwrap_or_default();
+        match status.split_whitespace().nth(1) {
+            Some("200") => (),
+            _ => return Err(format!("server said {:?}", status)),
+        }
+        let chunked = lines.any(|line| {
+            let (name, value) = line.split_once(':').unwrap_or_default();
+            name.eq_ignore_ascii_case("transfer-encoding") && value.trim() == "chunked"
+        });
+        if !chunked {
+            return Ok(body.to_vec());
+        }
+        let mut res = Vec::new();
+        let mut rest = body;
+        loop {
+            let end = find(rest, b"\r\n").ok_or("malformed chunk")?;
+            let size = std::str::from_utf8(&rest[..end]).map_err(|_| "malformed chunk size")?;
+            let size = size.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| "malformed chunk size")?;
+            let after = &rest[end + 2..];
+            if size == 0 {
+                return Ok(res);
             }
-            x += 1;
-        }
-        let player_loc = player_loc.expect("No player location");
-        let mut pz = Puzzle {
-            obstacles,
-            piece_types: [None; 32],
-            piece_locs: [0xff; 32],
-            pieces_by_loc: [0xff; 64],
-            player_start: 0xff,
-        };
-        let mut piece_idx = 0;
-        for (loc, piece_type) in piece_types_by_loc.into_iter().enumerate() {
-            let Some(piece_type) = piece_type else {
-                continue
-            };
-            pz.piece_types[piece_idx] = Some(piece_type);
-            pz.piece_locs[piece_idx] = loc as u8;
-            pz.pieces_by_loc[loc] = piece_idx as u8;
-            if loc == player_loc {
-                pz.player_start = piece_idx as u32;
+            let chunk = after.get(..size).ok_or("truncated chunk")?;
+            res.extend_from_slice(chunk);
+            rest = after[size..]
+                .strip_prefix(b"\r\n")
+                .ok_or("malformed chunk")?;
+        }
+    }
+
+    /// Where `needle` first occurs in `haystack`, if anywhere.
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+    }
+}
+
+/// `echochess [--describe] [--min-moves] [--deterministic] [--stats] [--pieces <file>] [pack]`:
+/// solves each puzzle in a pack, or the built-in demo puzzle. With `--describe`, prints prose
+/// descriptions instead of the usual terse output. With `--min-moves`, finds solutions with the
+/// fewest total moves. With `--deterministic`, picks the lexicographically first solution, so that
+/// output doesn't depend on search order. With `--stats`, also reports how much work the
+/// breadth-first search does. With `--pieces`, the pack can use the custom pieces in that file.
+/// A file of boards bigger than 8×8 goes to `wide`, which only knows the basic rules, so it gets
+/// the basic output whatever the flags.
+fn solve_main(path: Option<&str>, flags: Flags, pieces: &Pieces) {
+    let entries = match path {
+        Some(path) => {
+            let src = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            match try_parse_pack_with(&src, pieces) {
+                Ok(entries) => entries,
+                Err(e) => match parse_wide(&src) {
+                    Some(puzzles) => {
+                        puzzles.iter().for_each(solve_wide_and_print);
+                        return;
+                    }
+                    None => panic!("{}", e),
+                },
             }
-            piece_idx += 1;
         }
-        pz
+        None => vec![(
+            PuzzleMeta::default(),
+            Puzzle::from_compound_fen(
+                "\
+                XXXXXXXX/\
+                Xxxxx1xX/\
+                Xxrnbx1X/\
+                Xpxpx1xX/\
+                XNrb3X/\
+                Xpx1xrxX/\
+                Xxp1nxxX/\
+                XXXXXXXX\
+                ",
+            ),
+        )],
+    };
+    print_entries(&entries, flags);
+}
+
+/// Solves each puzzle and prints the result as `flags` say to.
+fn print_entries(entries: &[(PuzzleMeta, Puzzle)], flags: Flags) {
+    for (meta, puz) in entries {
+        if flags.json {
+            print_json_solution(puz, flags);
+        } else if flags.pgn {
+            print_pgn(meta, puz, flags);
+        } else if flags.describe {
+            describe_and_print(meta, puz, flags);
+        } else {
+            solve_and_print(meta, puz, flags);
+        }
     }
 }
 
-#[allow(dead_code)]
-fn test_steps() {
-    let start = SquareSet(0x8040201008040201);
-    println!("start:\n{}", start.draw());
-    println!("pawn steps:\n{}", Pawn::move_steps(start).draw());
-    println!("pawn captures:\n{}", Pawn::capture_steps(start).draw());
+/// `--json`: prints a puzzle's solution as JSON, on one line, with the search statistics if
+/// `--stats` was given too. For a puzzle with several starts, it solves from the best one.
+#[cfg(feature = "serde")]
+fn print_json_solution(puz: &Puzzle, flags: Flags) {
+    let puz = with_best_start(puz);
+    let sol = find_solution(&puz, flags);
+    let report = flags.stats.then(|| solve_with_report(&puz).1);
+    println!("{}", solution_json(&puz, sol.as_ref(), report.as_ref()));
+}
 
-    let start = SquareSet(0x4000_0010_0000_0001);
-    println!("start:\n{}", start.draw());
-    println!("bishop steps:\n{}", Bishop::move_steps(start).draw());
-    println!("rook steps:\n{}", Rook::move_steps(start).draw());
-    println!("monarch steps:\n{}", Monarch::move_steps(start).draw());
+#[cfg(not(feature = "serde"))]
+fn print_json_solution(_puz: &Puzzle, _flags: Flags) {
+    eprintln!("echochess was built without the `serde` feature, so it can't write JSON");
+    std::process::exit(2);
+}
 
-    let start = SquareSet(0x0000_0010_0000_0000);
-    println!("start:\n{}", start.draw());
-    println!("knight steps:\n{}", Knight::move_steps(start).draw());
-    let start = SquareSet(0x4000_0000_0400_0000);
-    println!("start:\n{}", start.draw());
-    println!("knight steps:\n{}", Knight::move_steps(start).draw());
+/// `--pgn`: prints a record of a puzzle's solution, with a blank line after it. For a puzzle with
+/// several starts, it solves from the best one.
+fn print_pgn(meta: &PuzzleMeta, puz: &Puzzle, flags: Flags) {
+    let puz = with_best_start(puz);
+    match find_solution(&puz, flags) {
+        Some(sol) => println!("{}", write_pgn(meta, &puz, &sol)),
+        None => eprintln!(
+            "{}: no solution",
+            meta.name.as_deref().unwrap_or(&puz.to_fen())
+        ),
+    }
 }
 
-fn main() {
-    let puz = Puzzle::from_compound_fen(
-        "\
-        XXXXXXXX/\
-        Xxxxx1xX/\
-        Xxrnbx1X/\
-        Xpxpx1xX/\
-        XNrb3X/\
-        Xpx1xrxX/\
-        Xxp1nxxX/\
-        XXXXXXXX\
-        ",
-    );
+/// The puzzle as started from whichever of its starts wins in the fewest moves, if it has several.
+fn with_best_start(puz: &Puzzle) -> Puzzle {
+    if puz.starts().len() <= 1 {
+        return puz.clone();
+    }
+    let best = solve_starts(puz)
+        .best
+        .map_or(puz.player_start(), |(start, _, _)| start);
+    puz.with_start(best).unwrap()
+}
+
+fn describe_and_print(meta: &PuzzleMeta, puz: &Puzzle, flags: Flags) {
+    if let Some(name) = &meta.name {
+        println!("Puzzle: {}.", name);
+    }
+    print!("{}", describe_puzzle(puz));
+    match find_solution(puz, flags).map(|sol| sol.captures()) {
+        Some(sol) => {
+            println!("Solution, in {} captures:", sol.len());
+            print!("{}", describe_solution(puz, &sol));
+        }
+        None => {
+            println!("This puzzle has no solution.");
+            if let Some(why) = explain_unsolvable(puz) {
+                print!("{}", describe_unsolvable(puz, &why));
+            }
+        }
+    }
+}
 
+fn solve_and_print(meta: &PuzzleMeta, puz: &Puzzle, flags: Flags) {
+    match (&meta.name, &meta.author) {
+        (Some(name), Some(author)) => println!("{} (by {})", name, author),
+        (Some(name), None) => println!("{}", name),
+        (None, _) => (),
+    }
+    print!("{}", draw_puzzle(puz, flags.style));
+    let chosen;
+    let puz = if puz.starts().len() > 1 {
+        let report = solve_starts(puz);
+        for &start in puz.starts() {
+            let wins = report.winnable.contains(&start);
+            println!(
+                "start as {:?} on {}: {}",
+                puz.piece_type(start).unwrap(),
+                puz.piece_loc(start),
+                if wins { "winnable" } else { "no win" }
+            );
+        }
+        let best = report
+            .best
+            .map_or(puz.player_start(), |(start, _, _)| start);
+        chosen = puz.with_start(best).unwrap();
+        &chosen
+    } else {
+        puz
+    };
     println!("solving...");
     let start = std::time::Instant::now();
-    let sol = solve(&puz);
+    let sol = find_solution(puz, flags);
     let elapsed = start.elapsed();
-    println!("done in {:?}. {:?}", elapsed, sol);
-    if let Some(moves) = sol {
-        for (i, &piece_idx) in moves.iter().enumerate() {
-            let ty = puz.piece_types[piece_idx as usize].unwrap();
-            let loc = puz.piece_locs[piece_idx as usize] as u32;
-            let y = loc / 8;
-            let x = loc % 8;
-            let loc_name = format!("{}{}", char::from_u32(u32::from('a') + x).unwrap(), y + 1);
-            println!("{:2}. capture {:?} on {}", i + 1, ty, loc_name);
+    println!(
+        "done in {:?}. {:?}",
+        elapsed,
+        sol.as_ref().map(Solution::captures)
+    );
+    if let Some(sol) = sol {
+        if let Some(delay) = flags.animate {
+            animate(puz, &sol, flags.style, delay, &mut std::io::stdout().lock())
+                .expect("writing to stdout");
+        }
+        let moves = &sol.moves;
+        let notated = notate_solution(puz, &sol);
+        let positions = steps(puz, &sol);
+        for (i, (mv, notated)) in moves.iter().zip(&notated).enumerate() {
+            let path: Vec<String> = mv.path.iter().map(Square::to_string).collect();
+            let what = match puz.piece_type(mv.captured) {
+                Some(ty) => format!("capture {:?}", ty),
+                None => "break wall".to_string(),
+            };
+            println!(
+                "{:2}. {:<8} {} on {} via {}",
+                i + 1,
+                notated.to_string(),
+                what,
+                mv.path.last().unwrap(),
+                path.join(" ")
+            );
+            if flags.steps {
+                print!("{}", draw_puzzle(&positions[i + 1].0, flags.style));
+            }
+        }
+        if !sol.exit_path.is_empty() {
+            let path: Vec<String> = sol.exit_path.iter().map(Square::to_string).collect();
+            println!("    then exit via {}", path.join(" "));
+        }
+        match meta.par {
+            Some(par) => println!("solved in {}, par {}", moves.len(), par),
+            None => println!("solved in {}", moves.len()),
+        }
+        match puz.budget() {
+            Some(budget) => println!("{} moves in all, budget {}", sol.num_moves(puz), budget),
+            None => println!("{} moves in all", sol.num_moves(puz)),
+        }
+    } else if let (Some(budget), Some((_, moves))) = (puz.budget(), solve_min_moves(puz)) {
+        println!(
+            "no solution within the budget of {} moves; the fewest possible is {}",
+            budget, moves
+        );
+    } else if let Some(why) = explain_unsolvable(puz) {
+        print!("{}", describe_unsolvable(puz, &why));
+        println!("best attempt: {:?}", why.best_attempt);
+    }
+    if flags.stats {
+        let (_, report) = solve_with_report(puz);
+        println!(
+            "search: {} nodes expanded, {} states visited, peak frontier {}, depth {}, {:?}",
+            report.nodes_expanded,
+            report.states_visited,
+            report.frontier_peak,
+            report.depth,
+            report.elapsed
+        );
+        if report.symmetries > 0 {
+            println!("merged states under {} symmetries", report.symmetries);
+        }
+        if report.pruned > 0 {
+            println!("pruned {} states that could never win", report.pruned);
+        }
+        if let Some(rate) = report.cache_hit_rate() {
+            println!("capture cache hit rate: {:.1}%", rate * 100.0);
+        }
+    }
+}
+
+/// Parses each FEN line of `src` as a `wide::Puzzle`, skipping comments, or `None` if any of them
+/// isn't one.
+fn parse_wide(src: &str) -> Option<Vec<wide::Puzzle>> {
+    src.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        .map(|line| wide::Puzzle::parse(line).ok())
+        .collect()
+}
+
+/// Draws and solves a board bigger than 8×8, and prints the captures.
+fn solve_wide_and_print(puz: &wide::Puzzle) {
+    print!("{}", puz);
+    println!("solving...");
+    let start = std::time::Instant::now();
+    let sol = wide::solve(puz);
+    println!("done in {:?}. {:?}", start.elapsed(), sol);
+    if let Some(captures) = sol {
+        for (i, &captured) in captures.iter().enumerate() {
+            println!(
+                "{:2}. capture {:?} on {}",
+                i + 1,
+                puz.piece_type(captured).unwrap(),
+                puz.piece_loc(captured).unwrap()
+            );
+        }
+        println!("solved in {}", captures.len());
+    }
+}
+
+/// Solves a puzzle, optimizing for total moves if asked to.
+fn find_solution(puz: &Puzzle, flags: Flags) -> Option<Solution> {
+    if flags.min_moves {
+        let (captures, moves) = solve_min_moves(puz)?;
+        if puz.budget().is_some_and(|budget| moves > budget) {
+            return None;
         }
+        Solution::from_captures(puz, &captures)
+    } else if flags.deterministic {
+        Solution::from_captures(puz, &solve_lexicographic(puz)?)
+    } else {
+        solve_with_paths(puz)
     }
 }
@@ -0,0 +1,220 @@
+//! Algebraic notation for Echo Chess moves, in the style of SAN: `Nxd5→R` means that the player,
+//! as a knight, captures on d5 and becomes a rook. Breaking a wall doesn't change the player, so
+//! it has no arrow: `Rxe4`. Pieces are written with their FEN letters in uppercase, pawns
+//! included.
+
+use crate::game::Game;
+use crate::grade::AttemptError;
+use crate::{Puzzle, Solution, Square};
+
+/// One move in notation. See the module docs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Notated {
+    /// The FEN letter of the piece the player moves as, in uppercase.
+    pub piece: char,
+    /// The square of the capture.
+    pub square: Square,
+    /// The FEN letter of the piece the player becomes, in uppercase, or `None` for a wall.
+    pub becomes: Option<char>,
+}
+
+impl std::fmt::Display for Notated {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}x{}", self.piece, self.square)?;
+        match self.becomes {
+            Some(c) => write!(f, "→{}", c),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A move that isn't in notation, as given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNotationError(pub String);
+
+impl std::fmt::Display for ParseNotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Bad move: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseNotationError {}
+
+/// Reads a move like `Nxd5→R`. The arrow can also be written `->`, and the letters in either
+/// case.
+///
+/// ```
+/// use echochess::notation::Notated;
+///
+/// let mv: Notated = "Nxd5→R".parse().unwrap();
+/// assert_eq!((mv.piece, mv.square.to_string(), mv.becomes), ('N', "d5".to_string(), Some('R')));
+/// assert_eq!("nxd5->r".parse::<Notated>().unwrap(), mv);
+/// assert_eq!(mv.to_string(), "Nxd5→R");
+/// assert_eq!("Rxe4".parse::<Notated>().unwrap().becomes, None);
+/// assert!("Nd5→R".parse::<Notated>().is_err());
+/// ```
+impl std::str::FromStr for Notated {
+    type Err = ParseNotationError;
+    fn from_str(s: &str) -> Result<Notated, ParseNotationError> {
+        let bad = || ParseNotationError(s.to_string());
+        let (mv, becomes) = match s.split_once('→').or_else(|| s.split_once("->")) {
+            Some((mv, to)) => {
+                let mut to = to.chars();
+                match (to.next(), to.next()) {
+                    (Some(c), None) if c.is_ascii_alphabetic() => (mv, Some(c)),
+                    _ => return Err(bad()),
+                }
+            }
+            None => (s, None),
+        };
+        let mut chars = mv.chars();
+        let piece = chars
+            .next()
+            .filter(char::is_ascii_alphabetic)
+            .ok_or_else(bad)?;
+        if chars.next() != Some('x') {
+            return Err(bad());
+        }
+        Ok(Notated {
+            piece: piece.to_ascii_uppercase(),
+            square: chars.as_str().parse().map_err(|_| bad())?,
+            becomes: becomes.map(|c| c.to_ascii_uppercase()),
+        })
+    }
+}
+
+/// The notation for capturing piece `captured` (or breaking a wall, by its slot) on `square` in
+/// puzzle `p`, when the player is piece `player`.
+pub fn notate(p: &Puzzle, player: u32, captured: u32, square: Square) -> Notated {
+    let letter = |idx| {
+        p.player_type(idx)
+            .expect("the player is a piece")
+            .fen_char()
+            .to_ascii_uppercase()
+    };
+    Notated {
+        piece: letter(player),
+        square,
+        becomes: p.piece_type(captured).map(|_| letter(captured)),
+    }
+}
+
+/// Writes each capture of `sol` in notation.
+///
+/// ```
+/// use echochess::notation::notate_solution;
+/// use echochess::{solve_with_paths, Puzzle};
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let moves = notate_solution(&puzzle, &solve_with_paths(&puzzle).unwrap());
+/// let moves: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+/// assert_eq!(moves, ["Rxb1→N", "Nxc3→B"]);
+/// ```
+pub fn notate_solution(p: &Puzzle, sol: &Solution) -> Vec<Notated> {
+    let mut player = p.player_start();
+    let mut res = Vec::with_capacity(sol.moves.len());
+    for mv in &sol.moves {
+        let square = *mv.path.last().expect("moves end on the captured piece");
+        res.push(notate(p, player, mv.captured, square));
+        player = mv.captured;
+    }
+    res
+}
+
+/// Why moves in notation don't make a line of play. See `read_moves`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    /// The move isn't notation or a square.
+    Syntax(ParseNotationError),
+    /// The player isn't the piece the move says it is.
+    WrongPiece,
+    /// The capture doesn't turn the player into the piece the move says it does.
+    WrongResult,
+    /// The capture can't be made.
+    Attempt(AttemptError),
+}
+
+impl std::fmt::Display for NotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NotationError::Syntax(e) => write!(f, "{}", e),
+            NotationError::WrongPiece => write!(f, "The player isn't that piece"),
+            NotationError::WrongResult => write!(f, "That capture doesn't become that piece"),
+            NotationError::Attempt(e) => write!(f, "{:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+/// Reads a line of play for puzzle `p`, as the squares captured in order, from moves separated
+/// by whitespace. Each move is either in notation, which is checked against the puzzle, or just
+/// the square of the capture, as `grade::verify_attempt` takes them. Move numbers like `1.` are
+/// skipped, so a solution can be copied in as written, and so are tag lines, comments in braces,
+/// and the result, so a record from `pgn::write_pgn` can be read back. On failure, returns the
+/// index of the offending move, counting only moves, and what went wrong.
+///
+/// ```
+/// use echochess::notation::{read_moves, NotationError};
+/// use echochess::Puzzle;
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let squares = read_moves(&puzzle, "1. Rxb1→N 2. c3").unwrap();
+/// assert_eq!(squares, ["b1".parse().unwrap(), "c3".parse().unwrap()]);
+/// assert_eq!(read_moves(&puzzle, "Rxb1→N Bxc3→B"), Err((1, NotationError::WrongPiece)));
+/// assert_eq!(read_moves(&puzzle, "Rxb1→B"), Err((0, NotationError::WrongResult)));
+/// ```
+pub fn read_moves(p: &Puzzle, text: &str) -> Result<Vec<Square>, (usize, NotationError)> {
+    let mut game = Game::new(p.clone());
+    let mut movetext = String::new();
+    let mut in_comment = false;
+    for line in text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('['))
+    {
+        for c in line.chars() {
+            match c {
+                '{' => in_comment = true,
+                '}' => in_comment = false,
+                _ if !in_comment => movetext.push(c),
+                _ => (),
+            }
+        }
+        movetext.push('\n');
+    }
+    let moves = movetext
+        .split_whitespace()
+        .filter(|word| !is_move_number(word) && !RESULTS.contains(word));
+    for (i, word) in moves.enumerate() {
+        let mv = match word.parse::<Square>() {
+            Ok(square) => {
+                game.apply(square)
+                    .map_err(|e| (i, NotationError::Attempt(e)))?;
+                continue;
+            }
+            Err(_) => word
+                .parse::<Notated>()
+                .map_err(|e| (i, NotationError::Syntax(e)))?,
+        };
+        let player = game.state().current_piece_idx();
+        if notate(p, player, player, mv.square).piece != mv.piece {
+            return Err((i, NotationError::WrongPiece));
+        }
+        game.apply(mv.square)
+            .map_err(|e| (i, NotationError::Attempt(e)))?;
+        let captured = game.state().current_piece_idx();
+        if notate(p, player, captured, mv.square).becomes != mv.becomes {
+            return Err((i, NotationError::WrongResult));
+        }
+    }
+    Ok(game.history().collect())
+}
+
+/// The ways a record can end, as in PGN: a win, a loss, a draw, or unknown.
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Is `word` a move number, like `12.`?
+fn is_move_number(word: &str) -> bool {
+    word.strip_suffix('.')
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
@@ -0,0 +1,92 @@
+//! Movement through one-way squares, which a piece can only enter heading a certain way. See
+//! `Puzzle::one_way`.
+//!
+//! A bitboard fill shifts a whole set of squares in each direction at once, with one mask of
+//! permeable squares for all of them, so it can't tell which way a piece came onto a square.
+//! Instead, the steps from each square are split by the way that they head: one of eight, by
+//! which way the file and the rank change. Each way gets its own permeable mask, without the
+//! one-way squares that it can't enter, and keeps only the squares that lie that way. A move or
+//! capture down a line heads the same way all along, so its whole length is checked, not just
+//! where it ends.
+
+use crate::{Heading, Puzzle, Square, SquareSet};
+
+/// The eight ways that a step can head, as the signs of its change in file and rank.
+const WAYS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+/// The squares that a step heading `way` may enter, out of `permeable`: all but the one-way
+/// squares facing some other way. A diagonal step heads two ways at once, so it can enter the
+/// squares of either.
+pub(crate) fn open(p: &Puzzle, permeable: SquareSet, (df, dr): (i8, i8)) -> SquareSet {
+    let mut closed = SquareSet(0);
+    for heading in Heading::ALL {
+        let (hf, hr) = heading.step();
+        if (hf != 0 && hf != df) || (hr != 0 && hr != dr) {
+            closed = closed | p.one_way[heading as usize];
+        }
+    }
+    permeable & !closed
+}
+
+/// The squares that lie `way` from `from`.
+fn toward(from: Square, (df, dr): (i8, i8)) -> SquareSet {
+    let files = (0..8)
+        .filter(|&f| (f - from.file() as i8).signum() == df)
+        .fold(0, |acc, f| acc | 0x0101010101010101 << f);
+    let ranks = (0..8)
+        .filter(|&r| (r - from.rank() as i8).signum() == dr)
+        .fold(0, |acc, r| acc | 0xff << (8 * r));
+    SquareSet(files & ranks)
+}
+
+/// Like `reach` (a `move_reach` or `capture_reach`), but a step can't enter a one-way square
+/// facing against it.
+pub(crate) fn reach(
+    p: &Puzzle,
+    from: SquareSet,
+    permeable: SquareSet,
+    reach: impl Fn(SquareSet, SquareSet) -> SquareSet,
+) -> SquareSet {
+    let mut res = SquareSet(0);
+    for sq in from {
+        for way in WAYS {
+            res = res | (reach(sq.into(), open(p, permeable, way)) & toward(sq, way));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::assert_strategies_agree;
+    use crate::{solve, Puzzle};
+
+    /// Rank 2 only lets pieces in heading right, so nothing on rank 1 can get up to the knight,
+    /// not even the bishop, whose diagonals head up and to one side.
+    #[test]
+    fn strategies_agree_with_breadth_first() {
+        assert_eq!(
+            solve(&Puzzle::parse("1n1/3/R1b").unwrap()),
+            Some(vec![2, 1])
+        );
+        assert_eq!(solve(&Puzzle::parse("1n1/>>>/R1b").unwrap()), None);
+        for fen in [
+            "1n1/>>>/R1b",
+            "b1n/vvv/R2",
+            "3/n>R",
+            "n1b/<1</R2",
+            "2n/1^1/N1b",
+        ] {
+            assert_strategies_agree(&Puzzle::parse(fen).unwrap());
+        }
+    }
+}
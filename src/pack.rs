@@ -0,0 +1,522 @@
+use crate::custom::Pieces;
+use crate::{PieceType, Puzzle, Rules, Square};
+
+/// Human-facing information about a puzzle that doesn't affect how it's solved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PuzzleMeta {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub author: Option<String>,
+    /// Intended length of an optimal solution, if the author gave one.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub par: Option<usize>,
+    /// Free-form notes, one entry per comment line.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub notes: Vec<String>,
+}
+
+impl PuzzleMeta {
+    /// Absorbs one comment line (without its leading `;`). Recognized keys are `name`, `author`,
+    /// `par`, and `notes`; anything else is kept verbatim as a note. Panics if the par isn't a
+    /// number; see `try_add_comment`.
+    pub fn add_comment(&mut self, comment: &str) {
+        self.try_add_comment(comment)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    /// Like `add_comment`, but reports a bad par instead of panicking.
+    pub fn try_add_comment(&mut self, comment: &str) -> Result<(), String> {
+        let comment = comment.trim();
+        let (key, value) = comment.split_once(':').unwrap_or(("", comment));
+        let value = value.trim().to_string();
+        match key.trim() {
+            "name" => self.name = Some(value),
+            "author" => self.author = Some(value),
+            "par" => {
+                let par = value.parse().map_err(|_| format!("Bad par: {:?}", value))?;
+                self.par = Some(par);
+            }
+            "notes" | "note" => self.notes.push(value),
+            _ if comment.is_empty() => (),
+            _ => self.notes.push(comment.to_string()),
+        }
+        Ok(())
+    }
+}
+
+/// A line of a pack that couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackError {
+    /// The line, counting from 1.
+    pub line: usize,
+    /// What's wrong with it.
+    pub message: String,
+}
+
+impl std::fmt::Display for PackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for PackError {}
+
+/// Parses a level pack: a sequence of compound FENs, one per line, each optionally preceded by
+/// metadata comment lines like `; name: Corner Trap`. Blank lines are ignored. A single puzzle
+/// file is just a pack with one entry.
+///
+/// Two comment keys affect the puzzle itself rather than its metadata: `; rules: patrols` turns on
+/// `Rules::patrols` (and likewise `step-queens` and `pawns-down`), and
+/// `; patrol: d1 d2 d3 d2` gives the piece on the first square that patrol route (see
+/// `Puzzle::add_patrol`). `; budget: 20` sets `Puzzle::budget`, and `; breakers: r q` sets
+/// `Puzzle::wall_breakers` by FEN letter. `; terrain: 111/191/111` sets `Puzzle::terrain`: each
+/// digit, from 1 to 9, is what a step onto that square costs, laid out rank by rank like the
+/// FEN, and squares left out cost one move.
+///
+/// Panics if the pack is malformed; see `try_parse_pack_with`.
+pub fn parse_pack(src: &str) -> Vec<(PuzzleMeta, Puzzle)> {
+    parse_pack_with(src, &Pieces::default())
+}
+
+/// Like `parse_pack`, but also accepts the letters of the given custom pieces.
+pub fn parse_pack_with(src: &str, pieces: &Pieces) -> Vec<(PuzzleMeta, Puzzle)> {
+    try_parse_pack_with(src, pieces).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Like `parse_pack_with`, but reports the first malformed line instead of panicking, for packs
+/// that come from elsewhere.
+///
+/// ```
+/// use echochess::custom::Pieces;
+/// use echochess::pack::try_parse_pack_with;
+///
+/// let err = try_parse_pack_with("R1b/3/1n1\n; par: x\nR1b/3/1n1\n", &Pieces::default());
+/// assert_eq!(err.unwrap_err().to_string(), "line 2: Bad par: \"x\"");
+/// ```
+pub fn try_parse_pack_with(
+    src: &str,
+    pieces: &Pieces,
+) -> Result<Vec<(PuzzleMeta, Puzzle)>, PackError> {
+    let mut res = Vec::new();
+    let mut meta = PuzzleMeta::default();
+    let mut rules = Rules::default();
+    let mut patrols: Vec<Vec<Square>> = Vec::new();
+    let mut budget = None;
+    let mut breakers = None;
+    let mut terrain = None;
+    for (i, line) in src.lines().enumerate() {
+        let err = |message: String| PackError {
+            line: i + 1,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix(';') {
+            match comment.split_once(':').map(|(k, v)| (k.trim(), v)) {
+                Some(("rules", names)) => {
+                    for name in names.split_whitespace() {
+                        match name {
+                            "patrols" => rules.patrols = true,
+                            "step-queens" => rules.step_queens = true,
+                            "pawns-down" => rules.pawns_down = true,
+                            "threats" => rules.threats = true,
+                            "threats-block-lines" => {
+                                rules.threats = true;
+                                rules.threats_block_lines = true;
+                            }
+                            other => return Err(err(format!("Unrecognized rule: {:?}", other))),
+                        }
+                    }
+                }
+                Some(("patrol", route)) => {
+                    let route = route
+                        .split_whitespace()
+                        .map(|sq| sq.parse().map_err(|e| err(format!("{}", e))));
+                    let route = route.collect::<Result<Vec<Square>, _>>()?;
+                    if route.is_empty() {
+                        return Err(err("Empty patrol".to_string()));
+                    }
+                    patrols.push(route);
+                }
+                Some(("breakers", letters)) => {
+                    let types = letters.split_whitespace().map(|letter| {
+                        let c = letter.chars().next().unwrap();
+                        PieceType::ALL
+                            .into_iter()
+                            .find(|ty| ty.fen_char() == c)
+                            .or_else(|| pieces.get(c))
+                            .ok_or_else(|| err(format!("Unrecognized piece: {:?}", letter)))
+                    });
+                    breakers = Some(types.collect::<Result<Vec<_>, _>>()?);
+                }
+                Some(("terrain", layer)) => terrain = Some(layer.trim().to_string()),
+                Some(("budget", value)) => {
+                    let value = value.trim();
+                    let moves = value
+                        .parse()
+                        .map_err(|_| err(format!("Bad budget: {:?}", value)))?;
+                    budget = Some(moves);
+                }
+                _ => meta.try_add_comment(comment).map_err(err)?,
+            }
+            continue;
+        }
+        let mut puz = Puzzle::parse_with(line, pieces).map_err(|e| err(format!("{}", e)))?;
+        puz.rules = std::mem::take(&mut rules);
+        puz.budget = budget.take();
+        if let Some(types) = breakers.take() {
+            puz.set_wall_breakers(&types)
+                .map_err(|e| err(format!("{}", e)))?;
+        }
+        if let Some(layer) = terrain.take() {
+            set_terrain(&mut puz, &layer).map_err(err)?;
+        }
+        for route in std::mem::take(&mut patrols) {
+            let piece_idx = puz
+                .piece_on(route[0])
+                .ok_or_else(|| err(format!("No piece to patrol from square {}", route[0])))?;
+            puz.add_patrol(piece_idx, &route)
+                .map_err(|e| err(format!("{}", e)))?;
+        }
+        res.push((std::mem::take(&mut meta), puz));
+    }
+    Ok(res)
+}
+
+/// Writes puzzles as a level pack that `parse_pack_with` reads back, each with its metadata and
+/// whatever its FEN can't hold as comments, and a blank line between entries.
+///
+/// ```
+/// use echochess::pack::{parse_pack, write_pack};
+///
+/// let src = "; name: Corner Trap\n; author: me\n; note: par is a guess\n; rules: patrols\n\
+///            ; patrol: c1 c2\n; budget: 9\nR1x/3/2r\n\n; terrain: 111/191/111\nR1b/3/n2\n";
+/// let pack = parse_pack(src);
+/// assert_eq!(write_pack(&pack), src);
+/// ```
+pub fn write_pack(levels: &[(PuzzleMeta, Puzzle)]) -> String {
+    let mut res = String::new();
+    for (i, (meta, puz)) in levels.iter().enumerate() {
+        if i > 0 {
+            res.push('\n');
+        }
+        let mut comment = |key: &str, value: &str| res.push_str(&format!("; {}: {}\n", key, value));
+        if let Some(name) = &meta.name {
+            comment("name", name);
+        }
+        if let Some(author) = &meta.author {
+            comment("author", author);
+        }
+        if let Some(par) = meta.par {
+            comment("par", &par.to_string());
+        }
+        for note in &meta.notes {
+            comment("note", note);
+        }
+        let rules = puz.rules();
+        let names = [
+            (rules.patrols, "patrols"),
+            (rules.step_queens, "step-queens"),
+            (rules.pawns_down, "pawns-down"),
+            (rules.threats && !rules.threats_block_lines, "threats"),
+            (rules.threats_block_lines, "threats-block-lines"),
+        ];
+        let names: Vec<&str> = names
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|&(_, n)| n)
+            .collect();
+        if !names.is_empty() {
+            comment("rules", &names.join(" "));
+        }
+        for (_, route) in puz.patrols() {
+            let route: Vec<String> = route.iter().map(Square::to_string).collect();
+            comment("patrol", &route.join(" "));
+        }
+        if let Some(budget) = puz.budget() {
+            comment("budget", &budget.to_string());
+        }
+        if puz.wall_breakers() != [PieceType::Rook] {
+            let letters: Vec<String> = puz
+                .wall_breakers()
+                .iter()
+                .map(|ty| ty.fen_char().to_string())
+                .collect();
+            comment("breakers", &letters.join(" "));
+        }
+        if !puz.terrain().is_empty() {
+            let ranks: Vec<String> = (0..puz.height())
+                .rev()
+                .map(|rank| {
+                    (0..puz.width())
+                        .map(|file| {
+                            let loc = Square::from_file_rank(file, rank).unwrap();
+                            char::from_digit(puz.move_cost(loc).min(9), 10).unwrap()
+                        })
+                        .collect()
+                })
+                .collect();
+            comment("terrain", &ranks.join("/"));
+        }
+        res.push_str(&puz.to_fen());
+        res.push('\n');
+    }
+    res
+}
+
+/// Writes puzzles as JSON: an array with one object per puzzle, in the format of `Puzzle`'s
+/// `Serialize` impl, plus whichever of the metadata fields `name`, `author`, `par`, and `notes`
+/// it has.
+///
+/// ```
+/// use echochess::pack::{parse_pack, parse_pack_json, write_pack_json};
+///
+/// let pack = parse_pack("; name: Fork\n; par: 2\nR1b/3/1n1\n");
+/// let json = write_pack_json(&pack);
+/// assert!(json.starts_with(r#"[{"name":"Fork","par":2,"obstacles":"#));
+/// assert_eq!(parse_pack_json(&json).unwrap(), pack);
+/// ```
+#[cfg(feature = "serde")]
+pub fn write_pack_json(levels: &[(PuzzleMeta, Puzzle)]) -> String {
+    let entries: Vec<crate::ser::EntryRepr> = levels
+        .iter()
+        .map(|(meta, puzzle)| crate::ser::EntryRepr {
+            meta: meta.clone(),
+            puzzle: puzzle.clone(),
+        })
+        .collect();
+    serde_json::to_string(&entries).expect("puzzles serialize")
+}
+
+/// Reads puzzles written by `write_pack_json`, or a single puzzle object on its own. Every
+/// metadata field is optional, so plain `Puzzle` JSON reads as a puzzle with no metadata.
+#[cfg(feature = "serde")]
+pub fn parse_pack_json(src: &str) -> Result<Vec<(PuzzleMeta, Puzzle)>, serde_json::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Entries {
+        Many(Vec<crate::ser::EntryRepr>),
+        One(Box<crate::ser::EntryRepr>),
+    }
+    let entries = match serde_json::from_str(src)? {
+        Entries::Many(entries) => entries,
+        Entries::One(entry) => vec![*entry],
+    };
+    Ok(entries.into_iter().map(|e| (e.meta, e.puzzle)).collect())
+}
+
+/// Applies a `; terrain:` layer to a puzzle, unless it isn't all nonzero digits, rank by rank.
+fn set_terrain(puz: &mut Puzzle, layer: &str) -> Result<(), String> {
+    let height = puz.height();
+    for (i, rank) in layer.split('/').enumerate() {
+        for (file, c) in rank.chars().enumerate() {
+            let cost = c
+                .to_digit(10)
+                .filter(|&cost| cost > 0)
+                .ok_or_else(|| format!("Bad terrain: {:?}", layer))?;
+            let loc = u8::try_from(i)
+                .ok()
+                .and_then(|i| height.checked_sub(i + 1))
+                .and_then(|rank| Square::from_file_rank(file as u8, rank))
+                .filter(|&loc| puz.squares().contains(loc))
+                .ok_or_else(|| format!("Terrain off the board: {:?}", layer))?;
+            puz.set_move_cost(loc, cost);
+        }
+    }
+    Ok(())
+}
+
+/// Does this look like a puzzle file that `parse_pack` can read: metadata comments and at least
+/// one valid compound FEN?
+pub fn looks_like_pack(src: &str) -> bool {
+    let mut fens = src
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        .peekable();
+    fens.peek().is_some() && fens.all(|fen| Puzzle::parse(fen).is_ok())
+}
+
+/// A puzzle's share code: its compound FEN, with `-` between ranks instead of `/` and anything
+/// else that isn't safe in a URL percent-encoded, so that it can end a link.
+///
+/// ```
+/// use echochess::custom::Pieces;
+/// use echochess::pack::{parse_share_code, share_code};
+/// use echochess::Puzzle;
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n+").unwrap();
+/// assert_eq!(share_code(&puzzle), "R1b-3-1n%2B");
+/// let link = "https://echochess.com/#R1b-3-1n%2B";
+/// assert_eq!(parse_share_code(link, &Pieces::default()), Some(puzzle));
+/// assert_eq!(parse_share_code("R1b-3-9", &Pieces::default()), None);
+/// ```
+pub fn share_code(p: &Puzzle) -> String {
+    let mut res = String::new();
+    for c in p.to_fen().chars() {
+        match c {
+            '/' => res.push('-'),
+            _ if c.is_ascii_alphanumeric() || c == '~' => res.push(c),
+            _ => {
+                for b in c.to_string().bytes() {
+                    res.push_str(&format!("%{:02X}", b));
+                }
+            }
+        }
+    }
+    res
+}
+
+/// Reads a share code, as `share_code` writes it, either alone or at the end of a link, after
+/// its last `/`, `#`, or `=`. Returns `None` if it isn't one.
+pub fn parse_share_code(code: &str, pieces: &Pieces) -> Option<Puzzle> {
+    let code = code.trim().rsplit(['/', '#', '=']).next()?;
+    let mut bytes = Vec::with_capacity(code.len());
+    let mut rest = code.bytes();
+    while let Some(b) = rest.next() {
+        bytes.push(match b {
+            b'-' => b'/',
+            b'%' => {
+                let hex = [rest.next()?, rest.next()?];
+                u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?
+            }
+            _ => b,
+        });
+    }
+    Puzzle::parse_with(&String::from_utf8(bytes).ok()?, pieces).ok()
+}
+
+/// Reads puzzles in whichever format `src` is in: JSON, as `parse_pack_json` reads it (with the
+/// `serde` feature); a share code or link, as `parse_share_code` reads it; or otherwise a level
+/// pack, as `try_parse_pack_with` reads it. It's an error for there to be no puzzles.
+///
+/// ```
+/// use echochess::custom::Pieces;
+/// use echochess::pack::parse_any_with;
+///
+/// let pieces = Pieces::default();
+/// let fen = &parse_any_with("; name: Fork\nR1b/3/1n1\n", &pieces).unwrap()[0];
+/// let code = &parse_any_with("https://echochess.com/#R1b-3-1n1\n", &pieces).unwrap()[0];
+/// assert_eq!(fen.0.name.as_deref(), Some("Fork"));
+/// assert_eq!(fen.1, code.1);
+/// assert_eq!(parse_any_with("hello\n", &pieces).unwrap_err().line, 1);
+/// assert_eq!(parse_any_with("; just a comment\n", &pieces).unwrap_err().message, "No puzzles");
+/// ```
+pub fn parse_any_with(src: &str, pieces: &Pieces) -> Result<Vec<(PuzzleMeta, Puzzle)>, PackError> {
+    if src.trim_start().starts_with(['{', '[']) {
+        return parse_json_entries(src);
+    }
+    let res = try_parse_pack_with(src, pieces).or_else(|e| {
+        let mut lines = src.lines().filter(|line| !line.trim().is_empty());
+        match (lines.next(), lines.next()) {
+            (Some(line), None) => parse_share_code(line, pieces)
+                .map(|puz| vec![(PuzzleMeta::default(), puz)])
+                .ok_or(e),
+            _ => Err(e),
+        }
+    })?;
+    if res.is_empty() {
+        return Err(PackError {
+            line: src.lines().count().max(1),
+            message: "No puzzles".to_string(),
+        });
+    }
+    Ok(res)
+}
+
+#[cfg(feature = "serde")]
+fn parse_json_entries(src: &str) -> Result<Vec<(PuzzleMeta, Puzzle)>, PackError> {
+    parse_pack_json(src).map_err(|e| {
+        let position = format!(" at line {} column {}", e.line(), e.column());
+        let message = e.to_string();
+        PackError {
+            line: e.line(),
+            message: message
+                .strip_suffix(&position)
+                .unwrap_or(&message)
+                .to_string(),
+        }
+    })
+}
+
+#[cfg(not(feature = "serde"))]
+fn parse_json_entries(_src: &str) -> Result<Vec<(PuzzleMeta, Puzzle)>, PackError> {
+    Err(PackError {
+        line: 1,
+        message: "Reading JSON puzzles needs the `serde` feature".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Annotated and bare entries in one pack, so metadata mustn't leak from one to the next.
+    const MIXED: &str = "\
+        R1b/3/1n1\n\
+        \n\
+        ; name: Corner Trap\n\
+        ; author: me\n\
+        ; par: 2\n\
+        ; a stray remark\n\
+        R1x/3/2r\n\
+        R2/3/2b\n\
+        ; name: Last\n\
+        N2/3/1b1\n\
+    ";
+
+    #[test]
+    fn mixed_annotated_and_bare_entries() {
+        let pack = parse_pack(MIXED);
+        let metas: Vec<&PuzzleMeta> = pack.iter().map(|(meta, _)| meta).collect();
+        assert_eq!(
+            metas,
+            [
+                &PuzzleMeta::default(),
+                &PuzzleMeta {
+                    name: Some("Corner Trap".to_string()),
+                    author: Some("me".to_string()),
+                    par: Some(2),
+                    notes: vec!["a stray remark".to_string()],
+                },
+                &PuzzleMeta::default(),
+                &PuzzleMeta {
+                    name: Some("Last".to_string()),
+                    ..PuzzleMeta::default()
+                },
+            ]
+        );
+        let fens: Vec<String> = pack.iter().map(|(_, p)| p.to_fen()).collect();
+        assert_eq!(fens, ["R1b/3/1n1", "R1x/3/2r", "R2/3/2b", "N2/3/1b1"]);
+        assert_eq!(parse_pack(&write_pack(&pack)), pack);
+    }
+
+    /// A step always costs at least one move, so a `0` in the terrain is a mistake.
+    #[test]
+    fn terrain_costs_start_at_one() {
+        let pack =
+            try_parse_pack_with("; terrain: 111/191/111\nR1b/3/n2\n", &Pieces::default()).unwrap();
+        assert_eq!(pack[0].1.move_cost("b2".parse().unwrap()), 9);
+        let err = try_parse_pack_with("; terrain: 111/101/111\nR1b/3/n2\n", &Pieces::default())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "line 2: Bad terrain: \"111/101/111\"");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn mixed_entries_in_json() {
+        let pack = parse_pack(MIXED);
+        let json = write_pack_json(&pack);
+        assert_eq!(json.matches("\"name\"").count(), 2);
+        assert_eq!(json.matches("\"par\"").count(), 1);
+        assert_eq!(parse_pack_json(&json).unwrap(), pack);
+
+        let bare = serde_json::to_string(&pack[0].1).unwrap();
+        assert_eq!(parse_pack_json(&bare).unwrap(), [pack[0].clone()]);
+    }
+}
@@ -0,0 +1,101 @@
+//! Records of solved puzzles in the style of PGN, chess's game record format, so that solutions
+//! can be archived and diffed like games.
+
+use crate::analyze::difficulty;
+use crate::notation::notate_solution;
+use crate::pack::PuzzleMeta;
+use crate::{Puzzle, Solution, Square};
+
+/// How long a line of movetext gets before it wraps, as PGN recommends.
+const LINE: usize = 79;
+
+/// Writes a record of solution `sol` to puzzle `p`: a tag for each of the puzzle's name and
+/// author, if it has them, its `FEN` (see `Puzzle::to_fen`), its `Difficulty` score (see
+/// `analyze::difficulty`), its generator `Seed`, if one of its notes gives it as
+/// `Generator::pack` does, its `Par`, and the total `Moves` the solution takes; then the moves,
+/// numbered, in the notation of the `notation` module, with the way to the exit, if any, in a
+/// comment. A solved puzzle counts as a win, `1-0`.
+///
+/// `notation::read_moves` reads a record's moves back, skipping the rest.
+///
+/// ```
+/// use echochess::notation::read_moves;
+/// use echochess::pack::PuzzleMeta;
+/// use echochess::pgn::write_pgn;
+/// use echochess::{solve_with_paths, Puzzle};
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let sol = solve_with_paths(&puzzle).unwrap();
+/// let meta = PuzzleMeta {
+///     name: Some("Fork".to_string()),
+///     notes: vec!["seed: 2a".to_string()],
+///     ..PuzzleMeta::default()
+/// };
+/// let pgn = write_pgn(&meta, &puzzle, &sol);
+/// assert_eq!(
+///     pgn,
+///     "[Event \"Fork\"]\n\
+///      [FEN \"R1b/3/1n1\"]\n\
+///      [Difficulty \"1.00\"]\n\
+///      [Seed \"2a\"]\n\
+///      [Moves \"4\"]\n\
+///      [Result \"1-0\"]\n\
+///      \n\
+///      1. Rxb1→N 2. Nxc3→B 1-0\n"
+/// );
+/// assert_eq!(read_moves(&puzzle, &pgn).unwrap().len(), 2);
+/// ```
+pub fn write_pgn(meta: &PuzzleMeta, p: &Puzzle, sol: &Solution) -> String {
+    let mut res = String::new();
+    let mut tag = |name: &str, value: &str| {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        res.push_str(&format!("[{} \"{}\"]\n", name, value));
+    };
+    if let Some(name) = &meta.name {
+        tag("Event", name);
+    }
+    if let Some(author) = &meta.author {
+        tag("Author", author);
+    }
+    tag("FEN", &p.to_fen());
+    tag("Difficulty", &format!("{:.2}", difficulty(p).score));
+    let seed = meta.notes.iter().find_map(|note| {
+        let (key, value) = note.split_once(':')?;
+        (key.trim() == "seed").then(|| value.trim())
+    });
+    if let Some(seed) = seed {
+        tag("Seed", seed);
+    }
+    if let Some(par) = meta.par {
+        tag("Par", &par.to_string());
+    }
+    tag("Moves", &sol.num_moves(p).to_string());
+    tag("Result", "1-0");
+    res.push('\n');
+
+    let mut words: Vec<String> = Vec::new();
+    for (i, mv) in notate_solution(p, sol).iter().enumerate() {
+        words.push(format!("{}.", i + 1));
+        words.push(mv.to_string());
+    }
+    if !sol.exit_path.is_empty() {
+        let path: Vec<String> = sol.exit_path.iter().map(Square::to_string).collect();
+        words.push(format!("{{exit via {}}}", path.join(" ")));
+    }
+    words.push("1-0".to_string());
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > LINE {
+            res.push_str(&line);
+            res.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    res.push_str(&line);
+    res.push('\n');
+    res
+}
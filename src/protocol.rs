@@ -0,0 +1,136 @@
+//! A line-based protocol for driving the solver and grader from another program, such as a bot
+//! that grades a community's submissions, without starting a process per request. Each request
+//! is one line, and gets one line in reply:
+//!
+//! - `puzzle <fen>` sets the puzzle for the requests after it, and replies `ok`.
+//! - `grade <move>...` grades an attempt at that puzzle, with moves as `notation::read_moves`
+//!   reads them, and replies `valid <letter> moves <moves> <optimal> distance <distance>
+//!   <optimal>` or `invalid <step> <reason>`, counting steps from 1.
+//! - `solve` replies `solution <move>...`, in notation, or `no solution`.
+//! - `quit` ends the session, as does the end of the input.
+//!
+//! Anything else, or a request that needs a puzzle before one is set, gets `error <reason>`.
+//! Blank lines are skipped. One `Grader` serves the whole session, so grading many attempts at
+//! the same puzzle only solves it once.
+
+use std::io::{self, BufRead, Write};
+
+use crate::custom::Pieces;
+use crate::grade::{Grade, Grader};
+use crate::notation::{notate_solution, read_moves};
+use crate::{solve_with_paths, Puzzle};
+
+/// Answers requests from `input` on `output` until `quit` or the end of the input, reading
+/// puzzles with the given custom pieces.
+///
+/// ```
+/// use echochess::custom::Pieces;
+/// use echochess::protocol::serve;
+///
+/// let input = "puzzle R1b/3/1n1\ngrade Rxb1→N c3\nsolve\nquit\n";
+/// let mut output = Vec::new();
+/// serve(input.as_bytes(), &mut output, &Pieces::default()).unwrap();
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "ok\nvalid S moves 4 4 distance 4 4\nsolution Rxb1→N Nxc3→B\n"
+/// );
+/// ```
+pub fn serve(input: impl BufRead, mut output: impl Write, pieces: &Pieces) -> io::Result<()> {
+    let mut grader = Grader::default();
+    let mut puzzle: Option<Puzzle> = None;
+    for line in input.lines() {
+        let line = line?;
+        let (command, rest) = line
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((line.trim(), ""));
+        let reply = match (command, &puzzle) {
+            ("", _) => continue,
+            ("quit", _) => break,
+            ("puzzle", _) => match Puzzle::parse_with(rest.trim(), pieces) {
+                Ok(p) => {
+                    puzzle = Some(p);
+                    "ok".to_string()
+                }
+                Err(e) => format!("error {}", e),
+            },
+            ("grade" | "solve", None) => "error no puzzle".to_string(),
+            ("grade", Some(p)) => match read_moves(p, rest) {
+                Err((step, e)) => format!("invalid {} {}", step + 1, e),
+                Ok(attempt) => match grader.grade(p, &attempt) {
+                    Grade::Invalid { step, error } => format!("invalid {} {:?}", step + 1, error),
+                    Grade::Valid {
+                        moves,
+                        optimal,
+                        distance,
+                        optimal_distance,
+                        letter,
+                    } => format!(
+                        "valid {} moves {} {} distance {} {}",
+                        letter, moves, optimal, distance, optimal_distance
+                    ),
+                },
+            },
+            ("solve", Some(p)) => match solve_with_paths(p) {
+                Some(sol) => {
+                    let moves: Vec<String> = notate_solution(p, &sol)
+                        .iter()
+                        .map(|mv| mv.to_string())
+                        .collect();
+                    format!("solution {}", moves.join(" "))
+                }
+                None => "no solution".to_string(),
+            },
+            (other, _) => format!("error unknown request {:?}", other),
+        };
+        writeln!(output, "{}", reply)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(input: &str) -> String {
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output, &Pieces::default()).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn grades_against_the_current_puzzle() {
+        let replies = session(
+            "grade a1\n\
+             puzzle R1b/3/1n1\n\
+             \n\
+             grade b1 c3\n\
+             grade b1\n\
+             grade a1\n\
+             grade Rxb1→B\n\
+             puzzle 9\n\
+             frobnicate\n\
+             puzzle B2/3/1n1\n\
+             solve\n",
+        );
+        assert_eq!(
+            replies,
+            "error no puzzle\n\
+             ok\n\
+             valid S moves 4 4 distance 4 4\n\
+             invalid 2 Unfinished\n\
+             invalid 1 IllegalCapture\n\
+             invalid 1 That capture doesn't become that piece\n\
+             error Too many files on rank 1 in FEN (max 8)\n\
+             error unknown request \"frobnicate\"\n\
+             ok\n\
+             no solution\n"
+        );
+    }
+
+    #[test]
+    fn stops_at_quit() {
+        assert_eq!(session("puzzle R1b/3/1n1\nquit\nsolve\n"), "ok\n");
+    }
+}
@@ -0,0 +1,559 @@
+//! Drawing boards as PNG images, at any size, for places that can't show SVG, and with the
+//! `gif` feature, solutions as animated GIFs.
+//!
+//! There's no font to draw with, so the standard pieces are little pixel-art silhouettes, and
+//! fairy and custom pieces are their letters in a 5×7 bitmap font.
+
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgba, RgbaImage};
+
+use crate::render::steps;
+use crate::{Heading, PieceType, Puzzle, Solution, Square};
+
+/// The smallest square size that `puzzle_png` draws at: below this, pieces are unrecognizable.
+pub const MIN_SQUARE: u32 = 8;
+
+const LIGHT: Rgba<u8> = Rgba([0xee, 0xee, 0xd2, 0xff]);
+const DARK: Rgba<u8> = Rgba([0xb0, 0xc4, 0x90, 0xff]);
+const OBSTACLE: Rgba<u8> = Rgba([0x4a, 0x4a, 0x4a, 0xff]);
+const START: Rgba<u8> = Rgba([0xf6, 0xf6, 0x69, 0xff]);
+const ICE: Rgba<u8> = Rgba([0xcf, 0xef, 0xff, 0xff]);
+const EXIT: Rgba<u8> = Rgba([0x1a, 0x7f, 0x37, 0xff]);
+const WALL: Rgba<u8> = Rgba([0x8b, 0x45, 0x13, 0xff]);
+const ONE_WAY: Rgba<u8> = Rgba([0x24, 0x56, 0xa6, 0xff]);
+const PORTAL: Rgba<u8> = Rgba([0x8a, 0x2b, 0xe2, 0xff]);
+const TERRAIN: Rgba<u8> = Rgba([0x7a, 0x4a, 0x1a, 0xff]);
+const PATH: Rgba<u8> = Rgba([0xdd, 0x33, 0x33, 0xb3]);
+const WHITE: Rgba<u8> = Rgba([0xff, 0xff, 0xff, 0xff]);
+const BLACK: Rgba<u8> = Rgba([0x00, 0x00, 0x00, 0xff]);
+
+/// Draws the board as a PNG image, `square` pixels to a square (at least `MIN_SQUARE`), with
+/// the same colors as `svg::puzzle_svg`: obstacles shaded dark, the player's starting square
+/// highlighted, and pieces white if the player could start as them and black otherwise.
+/// Unlike the SVG, there are no rank numbers or file letters.
+///
+/// ```
+/// use echochess::raster::puzzle_png;
+/// use echochess::Puzzle;
+///
+/// let png = puzzle_png(&Puzzle::parse("R1b/1x1/1n1").unwrap(), 32);
+/// assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+/// // The IHDR chunk gives the width and height: three squares of 32 pixels each way.
+/// assert_eq!(png[16..24], [0, 0, 0, 96, 0, 0, 0, 96]);
+/// ```
+pub fn puzzle_png(p: &Puzzle, square: u32) -> Vec<u8> {
+    encode(&draw(p, &[], None, square))
+}
+
+/// Draws the board before and after each capture of `sol`, as `puzzle_png` does, for showing a
+/// solution step by step. Each image after the first traces the path of the move just made, as
+/// `render::steps` gives it.
+///
+/// ```
+/// use echochess::raster::solution_pngs;
+/// use echochess::{solve_with_paths, Puzzle};
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let frames = solution_pngs(&puzzle, &solve_with_paths(&puzzle).unwrap(), 16);
+/// assert_eq!(frames.len(), 3);
+/// assert_ne!(frames[0], frames[1]);
+/// ```
+pub fn solution_pngs(p: &Puzzle, sol: &Solution, square: u32) -> Vec<Vec<u8>> {
+    steps(p, sol)
+        .iter()
+        .map(|(position, path)| encode(&draw(position, path, None, square)))
+        .collect()
+}
+
+/// Animates `sol` as a GIF that loops forever, `square` pixels to a square, showing the player
+/// walking each move one square at a time, as the solver's paths give them, with the path so
+/// far traced behind it. Each frame shows for `delay_ms` milliseconds, except the first and the
+/// last, which hold four times as long so it's clear where the loop starts.
+///
+/// ```
+/// use echochess::raster::solution_gif;
+/// use echochess::{solve_with_paths, Puzzle};
+/// use image::codecs::gif::GifDecoder;
+/// use image::AnimationDecoder;
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let gif = solution_gif(&puzzle, &solve_with_paths(&puzzle).unwrap(), 16, 200);
+/// assert!(gif.starts_with(b"GIF89a"));
+/// // The start, three steps for the rook from a3 down to b1, and the knight's one leap.
+/// let frames = GifDecoder::new(std::io::Cursor::new(gif)).unwrap().into_frames();
+/// assert_eq!(frames.count(), 5);
+/// ```
+#[cfg(feature = "gif")]
+pub fn solution_gif(p: &Puzzle, sol: &Solution, square: u32, delay_ms: u32) -> Vec<u8> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+    use image::{Delay, Frame};
+
+    let steps = steps(p, sol);
+    let mut frames = vec![draw(p, &[], None, square)];
+    for pair in steps.windows(2) {
+        let (before, (after, path)) = (&pair[0].0, &pair[1]);
+        // The player stands still on the board before the capture, and on the one after once
+        // it's made it, for the way to the exit.
+        let landed = after.piece_loc(after.player_start());
+        let capture = path.iter().position(|&sq| sq == landed);
+        for k in 1..path.len() {
+            let board = if capture.is_some_and(|c| k >= c) {
+                after
+            } else {
+                before
+            };
+            frames.push(draw(board, &path[..=k], Some(path[k]), square));
+        }
+    }
+    let mut res = Vec::new();
+    let mut encoder = GifEncoder::new_with_speed(&mut res, 10);
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .expect("writing to memory can't fail");
+    let last = frames.len() - 1;
+    for (i, img) in frames.into_iter().enumerate() {
+        let ms = if i == 0 || i == last {
+            delay_ms * 4
+        } else {
+            delay_ms
+        };
+        let delay = Delay::from_numer_denom_ms(ms, 1);
+        encoder
+            .encode_frame(Frame::from_parts(img, 0, 0, delay))
+            .expect("writing to memory can't fail");
+    }
+    drop(encoder);
+    res
+}
+
+/// Encodes an image as PNG.
+fn encode(img: &RgbaImage) -> Vec<u8> {
+    let mut res = Vec::new();
+    img.write_to(&mut Cursor::new(&mut res), ImageFormat::Png)
+        .expect("writing to memory can't fail");
+    res
+}
+
+/// Draws `p`, `square` pixels to a square, with `path` traced over it. If `player` is given,
+/// the player's piece is drawn there instead of on the square it stands on, for showing it
+/// partway through a move.
+fn draw(p: &Puzzle, path: &[Square], player: Option<Square>, square: u32) -> RgbaImage {
+    let square = square.max(MIN_SQUARE);
+    let (w, h) = (u32::from(p.width()), u32::from(p.height()));
+    let mut img = RgbaImage::new(w * square, h * square);
+    let here = player.unwrap_or_else(|| p.piece_loc(p.player_start()));
+    for sq in p.squares().iter() {
+        let (x, y) = corner(p, sq, square);
+        let light = (sq.file() + sq.rank()) % 2 == 1;
+        let fill = if p.obstacles().contains(sq) {
+            OBSTACLE
+        } else if sq == here {
+            START
+        } else if p.ice().contains(sq) {
+            ICE
+        } else if light {
+            LIGHT
+        } else {
+            DARK
+        };
+        fill_rect(&mut img, x, y, square, square, fill);
+        match p.piece_on(sq) {
+            Some(idx) if idx == p.player_start() && player.is_some() => {}
+            Some(idx) => piece(&mut img, p, idx, (x, y), square),
+            None => mark(&mut img, p, sq, (x, y), square),
+        }
+        // Each extra step of cost is a dot in the corner.
+        let dot = (square / 10).max(1);
+        for i in 1..p.move_cost(sq).min(5) {
+            fill_rect(&mut img, x + dot * (2 * i - 1), y + dot, dot, dot, TERRAIN);
+        }
+    }
+    let radius = (square as f32 / 14.0).max(1.0);
+    for pair in path.windows(2) {
+        let (x0, y0) = center(p, pair[0], square);
+        let (x1, y1) = center(p, pair[1], square);
+        line(&mut img, (x0, y0), (x1, y1), radius);
+    }
+    if let Some(sq) = player {
+        piece(&mut img, p, p.player_start(), corner(p, sq, square), square);
+    }
+    img
+}
+
+/// Draws piece `idx` on the square whose top left corner is at `at`.
+fn piece(img: &mut RgbaImage, p: &Puzzle, idx: u32, at: (u32, u32), square: u32) {
+    let Some(ty) = p.piece_type(idx) else {
+        return;
+    };
+    let (fill, edge) = if p.starts().contains(&idx) {
+        (WHITE, BLACK)
+    } else {
+        (BLACK, WHITE)
+    };
+    let mask = sprite(ty);
+    let inner = square - 2 * (square / 8);
+    // Keep the letters' proportions, rather than stretching them square.
+    let mw = inner * mask.width / mask.height.max(mask.width);
+    let mh = inner * mask.height / mask.height.max(mask.width);
+    let (mx, my) = (at.0 + (square - mw) / 2, at.1 + (square - mh) / 2);
+    stamp(img, &mask, (mx, my), (mw, mh), fill, edge);
+}
+
+/// Draws the marking for a special square with no piece on it, whose top left corner is at
+/// `(x, y)`.
+fn mark(img: &mut RgbaImage, p: &Puzzle, sq: Square, (x, y): (u32, u32), square: u32) {
+    let inset = square / 8;
+    let inner = square - 2 * inset;
+    let thick = (square / 12).max(1);
+    if p.exit() == Some(sq) {
+        outline(img, x + inset, y + inset, inner, thick, EXIT);
+        outline(
+            img,
+            x + 2 * inset,
+            y + 2 * inset,
+            square - 4 * inset,
+            thick,
+            EXIT,
+        );
+    } else if p.walls().contains(&sq) {
+        fill_rect(
+            img,
+            x + inset,
+            y + square / 2 - thick,
+            inner,
+            2 * thick,
+            WALL,
+        );
+        fill_rect(
+            img,
+            x + square / 2 - thick,
+            y + inset,
+            2 * thick,
+            inner,
+            WALL,
+        );
+    } else if let Some(h) = Heading::ALL
+        .into_iter()
+        .find(|&h| p.one_way(h).contains(sq))
+    {
+        triangle(img, x + inset, y + inset, inner, h);
+    } else if p.portals().iter().any(|&(a, b)| sq == a || sq == b) {
+        ring(
+            img,
+            center(p, sq, square),
+            inner as f32 / 2.0,
+            thick as f32,
+            PORTAL,
+        );
+    }
+}
+
+/// The top left corner of square `sq`, in pixels.
+fn corner(p: &Puzzle, sq: Square, square: u32) -> (u32, u32) {
+    let row = u32::from(p.height() - 1 - sq.rank());
+    (u32::from(sq.file()) * square, row * square)
+}
+
+/// The middle of square `sq`, in pixels.
+fn center(p: &Puzzle, sq: Square, square: u32) -> (f32, f32) {
+    let (x, y) = corner(p, sq, square);
+    let half = square as f32 / 2.0;
+    (x as f32 + half, y as f32 + half)
+}
+
+/// Paints `color` over the pixel at `(x, y)`, blending by its alpha, if it's on the image.
+fn blend(img: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+    if x < 0 || y < 0 || x >= i64::from(img.width()) || y >= i64::from(img.height()) {
+        return;
+    }
+    let px = img.get_pixel_mut(x as u32, y as u32);
+    let a = u32::from(color[3]);
+    for i in 0..3 {
+        let (old, new) = (u32::from(px[i]), u32::from(color[i]));
+        px[i] = ((new * a + old * (255 - a)) / 255) as u8;
+    }
+    px[3] = 0xff;
+}
+
+fn fill_rect(img: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, color: Rgba<u8>) {
+    for j in y..y + h {
+        for i in x..x + w {
+            blend(img, i64::from(i), i64::from(j), color);
+        }
+    }
+}
+
+/// The border of a `size`-pixel square, `thick` pixels wide.
+fn outline(img: &mut RgbaImage, x: u32, y: u32, size: u32, thick: u32, color: Rgba<u8>) {
+    fill_rect(img, x, y, size, thick, color);
+    fill_rect(img, x, y + size - thick, size, thick, color);
+    fill_rect(img, x, y, thick, size, color);
+    fill_rect(img, x + size - thick, y, thick, size, color);
+}
+
+/// A circle of radius `r` around `c`, `thick` pixels wide.
+fn ring(img: &mut RgbaImage, c: (f32, f32), r: f32, thick: f32, color: Rgba<u8>) {
+    let (x0, y0) = ((c.0 - r).floor() as i64, (c.1 - r).floor() as i64);
+    let (x1, y1) = ((c.0 + r).ceil() as i64, (c.1 + r).ceil() as i64);
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let d = (x as f32 + 0.5 - c.0).hypot(y as f32 + 0.5 - c.1);
+            if d <= r && d > r - thick {
+                blend(img, x, y, color);
+            }
+        }
+    }
+}
+
+/// A triangle filling a `size`-pixel square, pointing toward `h`.
+fn triangle(img: &mut RgbaImage, x: u32, y: u32, size: u32, h: Heading) {
+    for j in 0..size {
+        for i in 0..size {
+            // How far along the heading, and how far off its center line, from 0 to 1.
+            let (along, across) = match h {
+                Heading::Up => (size - 1 - j, i),
+                Heading::Down => (j, i),
+                Heading::Left => (size - 1 - i, j),
+                Heading::Right => (i, j),
+            };
+            let along = (along as f32 + 0.5) / size as f32;
+            let across = ((across as f32 + 0.5) / size as f32 - 0.5).abs() * 2.0;
+            if across <= 1.0 - along {
+                blend(img, i64::from(x + i), i64::from(y + j), ONE_WAY);
+            }
+        }
+    }
+}
+
+/// A line from `a` to `b`, with round ends, drawn as a disc of radius `r` slid along it.
+fn line(img: &mut RgbaImage, a: (f32, f32), b: (f32, f32), r: f32) {
+    let (x0, x1) = (
+        (a.0.min(b.0) - r).floor() as i64,
+        (a.0.max(b.0) + r).ceil() as i64,
+    );
+    let (y0, y1) = (
+        (a.1.min(b.1) - r).floor() as i64,
+        (a.1.max(b.1) + r).ceil() as i64,
+    );
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = (dx * dx + dy * dy).max(f32::EPSILON);
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let t = (((px - a.0) * dx + (py - a.1) * dy) / len2).clamp(0.0, 1.0);
+            if (px - a.0 - t * dx).hypot(py - a.1 - t * dy) <= r {
+                blend(img, x, y, PATH);
+            }
+        }
+    }
+}
+
+/// A grid of on and off cells, scaled up to draw a piece.
+struct Mask {
+    width: u32,
+    height: u32,
+    cells: Vec<bool>,
+}
+
+impl Mask {
+    fn from_rows(rows: &[&str]) -> Mask {
+        Mask {
+            width: rows[0].len() as u32,
+            height: rows.len() as u32,
+            cells: rows
+                .iter()
+                .flat_map(|r| r.bytes().map(|b| b == b'#'))
+                .collect(),
+        }
+    }
+
+    fn from_bits(rows: [u8; 7]) -> Mask {
+        Mask {
+            width: 5,
+            height: 7,
+            cells: rows
+                .iter()
+                .flat_map(|r| (0..5).rev().map(move |i| r >> i & 1 == 1))
+                .collect(),
+        }
+    }
+
+    /// Whether the cell under pixel `(x, y)` is on, when the mask is drawn `w` by `h` pixels.
+    fn at(&self, x: i64, y: i64, w: u32, h: u32) -> bool {
+        if x < 0 || y < 0 || x >= i64::from(w) || y >= i64::from(h) {
+            return false;
+        }
+        let col = x as u32 * self.width / w;
+        let row = y as u32 * self.height / h;
+        self.cells[(row * self.width + col) as usize]
+    }
+}
+
+/// Draws `mask` at `at`, scaled to `size`, in `fill` with a one-pixel border of `edge`.
+fn stamp(
+    img: &mut RgbaImage,
+    mask: &Mask,
+    at: (u32, u32),
+    size: (u32, u32),
+    fill: Rgba<u8>,
+    edge: Rgba<u8>,
+) {
+    let (w, h) = size;
+    for y in -1..=i64::from(h) {
+        for x in -1..=i64::from(w) {
+            let (px, py) = (i64::from(at.0) + x, i64::from(at.1) + y);
+            if mask.at(x, y, w, h) {
+                blend(img, px, py, fill);
+            } else if [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .any(|&(dx, dy)| mask.at(x + dx, y + dy, w, h))
+            {
+                blend(img, px, py, edge);
+            }
+        }
+    }
+}
+
+/// The silhouette for a piece type: a picture for the standard pieces and a letter otherwise.
+fn sprite(ty: PieceType) -> Mask {
+    let rows: &[&str] = match ty {
+        PieceType::King => &KING,
+        PieceType::Queen => &QUEEN,
+        PieceType::Rook => &ROOK,
+        PieceType::Bishop => &BISHOP,
+        PieceType::Knight => &KNIGHT,
+        PieceType::Pawn | PieceType::PawnDown => &PAWN,
+        _ => {
+            let c = ty.fen_char().to_ascii_uppercase();
+            return match c {
+                'A'..='Z' => Mask::from_bits(FONT[(c as u8 - b'A') as usize]),
+                _ => Mask::from_rows(&BLOB),
+            };
+        }
+    };
+    Mask::from_rows(rows)
+}
+
+const KING: [&str; 12] = [
+    ".....##.....",
+    "...######...",
+    ".....##.....",
+    "..########..",
+    ".##########.",
+    ".##########.",
+    "..########..",
+    "...######...",
+    "...######...",
+    "..########..",
+    ".##########.",
+    ".##########.",
+];
+
+const QUEEN: [&str; 12] = [
+    "#...#..#...#",
+    "##..#..#..##",
+    "##.##..##.##",
+    "############",
+    ".##########.",
+    "..########..",
+    "...######...",
+    "...######...",
+    "..########..",
+    ".##########.",
+    "############",
+    "############",
+];
+
+const ROOK: [&str; 12] = [
+    "##.##..##.##",
+    "##.##..##.##",
+    "############",
+    ".##########.",
+    "..########..",
+    "..########..",
+    "..########..",
+    "..########..",
+    ".##########.",
+    "############",
+    "############",
+    "############",
+];
+
+const BISHOP: [&str; 12] = [
+    ".....##.....",
+    "....####....",
+    "...####.#...",
+    "..####.###..",
+    "..###.####..",
+    "...######...",
+    "....####....",
+    ".....##.....",
+    "....####....",
+    "..########..",
+    ".##########.",
+    ".##########.",
+];
+
+const KNIGHT: [&str; 12] = [
+    ".....#.#....",
+    "....######..",
+    "...########.",
+    "..###.#####.",
+    ".##########.",
+    "#####.#####.",
+    "###..######.",
+    ".....######.",
+    "....######..",
+    "...########.",
+    "..#########.",
+    "..#########.",
+];
+
+const PAWN: [&str; 12] = [
+    "............",
+    ".....##.....",
+    "....####....",
+    "....####....",
+    ".....##.....",
+    "....####....",
+    ".....##.....",
+    ".....##.....",
+    "....####....",
+    "...######...",
+    "..########..",
+    "..########..",
+];
+
+/// For a custom piece whose letter isn't in `FONT`.
+const BLOB: [&str; 6] = ["..##..", ".####.", "######", "######", ".####.", "..##.."];
+
+/// Capital letters, 5 pixels wide and 7 high, one row to a byte, leftmost pixel highest.
+const FONT: [[u8; 7]; 26] = [
+    [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+    [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+    [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+    [0x1e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1e],
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+    [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+    [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+    [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+    [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+    [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+    [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+    [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+    [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+    [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+    [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+    [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+    [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+];
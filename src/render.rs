@@ -0,0 +1,241 @@
+//! Drawing boards as text, for the terminal: plain ASCII, or chess glyphs with colors, and
+//! playing solutions back on them.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::{Heading, PieceType, Puzzle, PuzzleState, Solution, Square, SquareSet, PORTAL_GLYPHS};
+
+/// How to draw a board. See `draw_puzzle`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Style {
+    /// Chess glyphs like `♞` for pieces, `█` for obstacles, `·` for empty squares, and arrows
+    /// for one-way squares, instead of FEN letters, `#`, `.`, and `^>v<`.
+    pub unicode: bool,
+    /// ANSI colors: obstacles dimmed, the player's starting square highlighted, and special
+    /// squares each in their own color.
+    pub color: bool,
+}
+
+impl Style {
+    /// Plain ASCII, as `Puzzle` displays normally.
+    pub const PLAIN: Style = Style {
+        unicode: false,
+        color: false,
+    };
+    /// Chess glyphs without colors, as `Puzzle` displays with `{:#}`.
+    pub const UNICODE: Style = Style {
+        unicode: true,
+        color: false,
+    };
+    /// Chess glyphs and colors, for a terminal that can show them.
+    pub const COLOR: Style = Style {
+        unicode: true,
+        color: true,
+    };
+}
+
+/// Draws the board as a grid, top rank first, with rank numbers down the side and file letters
+/// along the bottom. Pieces that the player could start as are drawn as white pieces, or in
+/// uppercase, and the rest as black ones, or in lowercase. Fairy and custom pieces have no
+/// chess glyphs, so they keep their letters.
+///
+/// ```
+/// use echochess::render::{draw_puzzle, Style};
+/// use echochess::Puzzle;
+///
+/// let puzzle = Puzzle::parse("Rxn/3/b2").unwrap();
+/// assert_eq!(draw_puzzle(&puzzle, Style::PLAIN), "3 R#n\n2 ...\n1 b..\n  abc\n");
+/// assert_eq!(draw_puzzle(&puzzle, Style::UNICODE), "3 ♖█♞\n2 ···\n1 ♝··\n  abc\n");
+/// assert_eq!(format!("{:#}", puzzle), draw_puzzle(&puzzle, Style::UNICODE));
+/// assert!(draw_puzzle(&puzzle, Style::COLOR).starts_with("3 \x1b[1;30;43m♖\x1b[0m"));
+/// ```
+pub fn draw_puzzle(p: &Puzzle, style: Style) -> String {
+    let mut res = String::new();
+    for rank in (0..p.height).rev() {
+        res.push_str(&format!("{} ", rank + 1));
+        for file in 0..p.width {
+            let sq = Square::from_file_rank(file, rank).unwrap();
+            let (c, color) = cell(p, sq, style.unicode);
+            push_cell(&mut res, c, color.filter(|_| style.color));
+        }
+        res.push('\n');
+    }
+    res.push_str(&format!("  {}\n", &"abcdefgh"[..p.width as usize]));
+    res
+}
+
+/// Draws a set of squares as an 8×8 grid, marking its members with `*`, or `●` in green.
+///
+/// ```
+/// use echochess::render::{draw_squares, Style};
+/// use echochess::SquareSet;
+///
+/// let corners = SquareSet(0x8100_0000_0000_0081);
+/// assert!(draw_squares(corners, Style::PLAIN).starts_with("8 *......*\n7 ........\n"));
+/// assert!(draw_squares(corners, Style::UNICODE).starts_with("8 ●······●\n"));
+/// ```
+pub fn draw_squares(set: SquareSet, style: Style) -> String {
+    let mut res = String::new();
+    for rank in (0..8).rev() {
+        res.push_str(&format!("{} ", rank + 1));
+        for file in 0..8 {
+            let sq = Square::from_file_rank(file, rank).unwrap();
+            match (set.contains(sq), style.unicode) {
+                (true, true) => push_cell(&mut res, '●', Some("32").filter(|_| style.color)),
+                (true, false) => res.push('*'),
+                (false, true) => res.push('·'),
+                (false, false) => res.push('.'),
+            }
+        }
+        res.push('\n');
+    }
+    res.push_str("  abcdefgh\n");
+    res
+}
+
+/// The board before and after each capture of `sol`, for showing a solution step by step. Each
+/// position after the first comes with the squares the player went through to make that
+/// capture, starting from the square it left, and the last with the way to the exit too, if
+/// there is one. See `Puzzle::position`.
+///
+/// ```
+/// use echochess::render::steps;
+/// use echochess::{solve_with_paths, Puzzle};
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let steps = steps(&puzzle, &solve_with_paths(&puzzle).unwrap());
+/// assert_eq!(steps.len(), 3);
+/// assert_eq!(steps[0], (puzzle, vec![]));
+/// let path: Vec<String> = steps[1].1.iter().map(|sq| sq.to_string()).collect();
+/// assert_eq!(path, ["a3", "a2", "a1", "b1"]);
+/// assert_eq!(steps[2].0.to_fen(), "2B/3/3");
+/// ```
+pub fn steps(p: &Puzzle, sol: &Solution) -> Vec<(Puzzle, Vec<Square>)> {
+    let mut state = PuzzleState::initial(p);
+    let mut res = vec![(p.clone(), Vec::new())];
+    for (i, mv) in sol.moves.iter().enumerate() {
+        let mut path = vec![p.player_loc_at(state.current_piece_idx(), state.phase(p))];
+        path.extend(&mv.path);
+        if i + 1 == sol.moves.len() {
+            path.extend(&sol.exit_path);
+        }
+        let loc = *mv.path.last().expect("moves end on the captured piece");
+        state = state.capture(p, loc).expect("solution captures are legal");
+        res.push((p.position(state), path));
+    }
+    res
+}
+
+/// Plays `sol` back in the terminal: clears the screen and draws the board before and after each
+/// capture, as `steps` gives them, with the squares of the move just made under it, waiting
+/// `delay` between them.
+///
+/// ```
+/// use echochess::render::{animate, Style};
+/// use echochess::{solve_with_paths, Puzzle};
+/// use std::time::Duration;
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let sol = solve_with_paths(&puzzle).unwrap();
+/// let mut out = Vec::new();
+/// animate(&puzzle, &sol, Style::PLAIN, Duration::ZERO, &mut out).unwrap();
+/// let out = String::from_utf8(out).unwrap();
+/// let frames: Vec<&str> = out.split("\x1b[H\x1b[2J").skip(1).collect();
+/// assert_eq!(frames.len(), 3);
+/// assert_eq!(frames[1], "3 ..b\n2 ...\n1 .N.\n  abc\nmove 1 of 2: a3 a2 a1 b1\n");
+/// ```
+pub fn animate(
+    p: &Puzzle,
+    sol: &Solution,
+    style: Style,
+    delay: Duration,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let steps = steps(p, sol);
+    for (i, (position, path)) in steps.iter().enumerate() {
+        if i > 0 {
+            out.flush()?;
+            std::thread::sleep(delay);
+        }
+        write!(out, "\x1b[H\x1b[2J{}", draw_puzzle(position, style))?;
+        if i == 0 {
+            writeln!(out, "start")?;
+        } else {
+            let path: Vec<String> = path.iter().map(Square::to_string).collect();
+            writeln!(out, "move {} of {}: {}", i, steps.len() - 1, path.join(" "))?;
+        }
+    }
+    out.flush()
+}
+
+/// Writes one square's character, in the given ANSI color if any.
+fn push_cell(res: &mut String, c: char, color: Option<&str>) {
+    match color {
+        Some(color) => res.push_str(&format!("\x1b[{}m{}\x1b[0m", color, c)),
+        None => res.push(c),
+    }
+}
+
+/// The character for square `sq`, and the ANSI color to draw it in, if any.
+fn cell(p: &Puzzle, sq: Square, unicode: bool) -> (char, Option<&'static str>) {
+    if let Some(idx) = p.piece_on(sq) {
+        let ty = p.piece_types[idx as usize].unwrap();
+        let white = p.starts.contains(&idx);
+        let color = if idx == p.player_start {
+            Some("1;30;43")
+        } else if white {
+            Some("1")
+        } else {
+            None
+        };
+        let c = match glyph(ty, white) {
+            Some(c) if unicode => c,
+            _ if white => ty.fen_char().to_ascii_uppercase(),
+            _ => ty.fen_char(),
+        };
+        return (c, color);
+    }
+    if p.obstacles.contains(sq) {
+        (if unicode { '█' } else { '#' }, Some("90"))
+    } else if p.exit == Some(sq) {
+        ('E', Some("32"))
+    } else if p.ice.contains(sq) {
+        ('~', Some("36"))
+    } else if p.walls.contains(&sq) {
+        ('+', Some("33"))
+    } else if let Some(h) = Heading::ALL
+        .into_iter()
+        .find(|&h| p.one_way(h).contains(sq))
+    {
+        (if unicode { arrow(h) } else { h.glyph() }, Some("34"))
+    } else if let Some(i) = p.portals.iter().position(|&(a, b)| sq == a || sq == b) {
+        (PORTAL_GLYPHS[i], Some("35"))
+    } else {
+        (if unicode { '·' } else { '.' }, None)
+    }
+}
+
+/// The arrow for a one-way square facing `h`.
+pub(crate) fn arrow(h: Heading) -> char {
+    match h {
+        Heading::Up => '↑',
+        Heading::Right => '→',
+        Heading::Down => '↓',
+        Heading::Left => '←',
+    }
+}
+
+/// The chess glyph for a piece type, white or black, if it has one.
+pub(crate) fn glyph(ty: PieceType, white: bool) -> Option<char> {
+    let (w, b) = match ty {
+        PieceType::King => ('♔', '♚'),
+        PieceType::Queen => ('♕', '♛'),
+        PieceType::Rook => ('♖', '♜'),
+        PieceType::Bishop => ('♗', '♝'),
+        PieceType::Knight => ('♘', '♞'),
+        PieceType::Pawn | PieceType::PawnDown => ('♙', '♟'),
+        _ => return None,
+    };
+    Some(if white { w } else { b })
+}
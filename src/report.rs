@@ -0,0 +1,238 @@
+//! Reports on single puzzles, for sharing analyses: one Markdown or HTML document with the
+//! board, its metadata, how hard it is, and its solutions, step by step.
+
+use crate::analyze::difficulty;
+use crate::notation::notate_solution;
+use crate::pack::PuzzleMeta;
+use crate::render::{draw_puzzle, steps, Style};
+use crate::svg::{puzzle_svg, solution_svgs};
+use crate::{solve_all, solve_min_moves, Puzzle, Solution, SolutionCount, Square};
+
+/// How many solutions besides the optimal one a report lists.
+const ALTERNATIVES: usize = 5;
+
+/// What kind of document to write.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// Markdown, with boards drawn in chess glyphs in code blocks.
+    #[default]
+    Markdown,
+    /// A standalone HTML page, with boards drawn as inline SVG.
+    Html,
+}
+
+/// Writes a report on puzzle `p`: its name and the rest of `meta`, the board, the metrics from
+/// `analyze::difficulty`, a solution with the fewest moves, with the board after each capture,
+/// and up to five other solutions, in the notation of the `notation` module. Finding every
+/// solution can take a while on big puzzles.
+///
+/// ```
+/// use echochess::pack::PuzzleMeta;
+/// use echochess::report::{write_report, Format};
+/// use echochess::Puzzle;
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let meta = PuzzleMeta {
+///     name: Some("Fork".to_string()),
+///     ..PuzzleMeta::default()
+/// };
+/// let md = write_report(&meta, &puzzle, Format::Markdown);
+/// assert!(md.starts_with("# Fork\n"));
+/// assert!(md.contains("\n### 1. Rxb1→N\n"));
+/// assert!(md.contains("```\n3 ··♗\n2 ···\n1 ···\n  abc\n```\n"));
+///
+/// let html = write_report(&meta, &puzzle, Format::Html);
+/// assert!(html.starts_with("<!DOCTYPE html>"));
+/// assert_eq!(html.matches("<svg").count(), 3);
+/// ```
+pub fn write_report(meta: &PuzzleMeta, p: &Puzzle, format: Format) -> String {
+    let mut doc = Doc {
+        format,
+        out: String::new(),
+    };
+    let title = meta.name.as_deref().unwrap_or("Echo Chess puzzle");
+    if format == Format::Html {
+        doc.out.push_str(&format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             </head>\n<body>\n",
+            escape(title)
+        ));
+    }
+    doc.heading(1, title);
+    let mut facts = vec![format!("FEN: {}", p.to_fen())];
+    if let Some(author) = &meta.author {
+        facts.push(format!("Author: {}", author));
+    }
+    if let Some(par) = meta.par {
+        facts.push(format!("Par: {} captures", par));
+    }
+    facts.extend(meta.notes.iter().map(|note| format!("Note: {}", note)));
+    doc.list(&facts);
+    doc.board(&draw_puzzle(p, Style::UNICODE), &puzzle_svg(p));
+
+    doc.heading(2, "Difficulty");
+    let d = difficulty(p);
+    doc.table(&[
+        ("Reachable states", d.states.to_string()),
+        ("Average choices", format!("{:.2}", d.branching)),
+        ("Dead ends", d.dead_ends.to_string()),
+        (
+            "Losing first moves",
+            format!("{:.0}%", d.losing_first_moves * 100.0),
+        ),
+        (
+            "Solutions",
+            match d.solutions {
+                SolutionCount::Exactly(n) => n.to_string(),
+                SolutionCount::AtLeast(n) => format!("at least {}", n),
+            },
+        ),
+        ("Score", format!("{:.2}", d.score)),
+    ]);
+
+    doc.heading(2, "Solution");
+    let Some(best) =
+        solve_min_moves(p).and_then(|(captures, _)| Solution::from_captures(p, &captures))
+    else {
+        doc.paragraph("This puzzle has no solution.");
+        return doc.finish();
+    };
+    doc.paragraph(&format!(
+        "In {} captures and {} moves: {}",
+        best.moves.len(),
+        best.num_moves(p),
+        movetext(p, &best)
+    ));
+    let boards = steps(p, &best);
+    let svgs = solution_svgs(p, &best);
+    for (i, mv) in notate_solution(p, &best).iter().enumerate() {
+        doc.heading(3, &format!("{}. {}", i + 1, mv));
+        doc.paragraph(&format!("Via {}.", squares(&best.moves[i].path)));
+        doc.board(&draw_puzzle(&boards[i + 1].0, Style::UNICODE), &svgs[i + 1]);
+    }
+    if !best.exit_path.is_empty() {
+        doc.paragraph(&format!("Then exit via {}.", squares(&best.exit_path)));
+    }
+
+    doc.heading(2, "Alternatives");
+    let others: Vec<String> = solve_all(p)
+        .filter(|sol| sol.captures() != best.captures())
+        .take(ALTERNATIVES)
+        .map(|sol| format!("In {} moves: {}", sol.num_moves(p), movetext(p, &sol)))
+        .collect();
+    if others.is_empty() {
+        doc.paragraph("This is the only solution.");
+    } else {
+        doc.list(&others);
+    }
+    doc.finish()
+}
+
+/// The moves of `sol`, numbered, in notation.
+fn movetext(p: &Puzzle, sol: &Solution) -> String {
+    let moves: Vec<String> = notate_solution(p, sol)
+        .iter()
+        .enumerate()
+        .map(|(i, mv)| format!("{}. {}", i + 1, mv))
+        .collect();
+    moves.join(" ")
+}
+
+/// The squares of a path, separated by spaces.
+fn squares(path: &[Square]) -> String {
+    let path: Vec<String> = path.iter().map(Square::to_string).collect();
+    path.join(" ")
+}
+
+/// A document being written, in either format.
+struct Doc {
+    format: Format,
+    out: String,
+}
+
+impl Doc {
+    fn heading(&mut self, level: usize, text: &str) {
+        match self.format {
+            Format::Markdown => {
+                if !self.out.is_empty() {
+                    self.out.push('\n');
+                }
+                self.out
+                    .push_str(&format!("{} {}\n", "#".repeat(level), text));
+            }
+            Format::Html => self
+                .out
+                .push_str(&format!("<h{level}>{}</h{level}>\n", escape(text))),
+        }
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        match self.format {
+            Format::Markdown => self.out.push_str(&format!("\n{}\n", text)),
+            Format::Html => self.out.push_str(&format!("<p>{}</p>\n", escape(text))),
+        }
+    }
+
+    fn list(&mut self, items: &[String]) {
+        match self.format {
+            Format::Markdown => {
+                self.out.push('\n');
+                for item in items {
+                    self.out.push_str(&format!("- {}\n", item));
+                }
+            }
+            Format::Html => {
+                self.out.push_str("<ul>\n");
+                for item in items {
+                    self.out.push_str(&format!("<li>{}</li>\n", escape(item)));
+                }
+                self.out.push_str("</ul>\n");
+            }
+        }
+    }
+
+    fn table(&mut self, rows: &[(&str, String)]) {
+        match self.format {
+            Format::Markdown => {
+                self.out.push_str("\n| Metric | Value |\n| --- | --- |\n");
+                for (name, value) in rows {
+                    self.out.push_str(&format!("| {} | {} |\n", name, value));
+                }
+            }
+            Format::Html => {
+                self.out.push_str("<table>\n");
+                for (name, value) in rows {
+                    self.out.push_str(&format!(
+                        "<tr><th>{}</th><td>{}</td></tr>\n",
+                        escape(name),
+                        escape(value)
+                    ));
+                }
+                self.out.push_str("</table>\n");
+            }
+        }
+    }
+
+    /// A board, drawn as `text` in Markdown or as `svg` in HTML.
+    fn board(&mut self, text: &str, svg: &str) {
+        match self.format {
+            Format::Markdown => self.out.push_str(&format!("\n```\n{}```\n", text)),
+            Format::Html => self.out.push_str(svg),
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if self.format == Format::Html {
+            self.out.push_str("</body>\n</html>\n");
+        }
+        self.out
+    }
+}
+
+/// Escapes text for HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
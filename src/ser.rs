@@ -0,0 +1,320 @@
+//! Serialized forms for types whose in-memory layout isn't a good interchange format. A `Puzzle`
+//! is stored as a list of pieces rather than its lookup tables, and squares are stored in
+//! algebraic notation.
+
+use serde::{Deserialize, Serialize};
+
+use crate::analyze::{Graph, Outcome};
+use crate::pack::PuzzleMeta;
+use crate::{
+    Heading, PieceType, Puzzle, PuzzleBuilder, Rules, Solution, SolveReport, Square, SquareSet,
+};
+
+impl From<Square> for String {
+    fn from(sq: Square) -> String {
+        sq.to_string()
+    }
+}
+
+impl TryFrom<String> for Square {
+    type Error = crate::ParseSquareError;
+    fn try_from(s: String) -> Result<Square, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<SquareSet> for Vec<Square> {
+    fn from(set: SquareSet) -> Vec<Square> {
+        set.iter().collect()
+    }
+}
+
+impl From<Vec<Square>> for SquareSet {
+    fn from(squares: Vec<Square>) -> SquareSet {
+        squares.into_iter().collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PuzzleRepr {
+    obstacles: SquareSet,
+    pieces: Vec<PieceRepr>,
+    player: Square,
+    #[serde(default)]
+    starts: Vec<Square>,
+    #[serde(default)]
+    patrols: Vec<Vec<Square>>,
+    #[serde(default)]
+    rules: Rules,
+    #[serde(default = "full_board")]
+    width: u8,
+    #[serde(default = "full_board")]
+    height: u8,
+    #[serde(default)]
+    exit: Option<Square>,
+    #[serde(default)]
+    budget: Option<u32>,
+    #[serde(default)]
+    portals: Vec<(Square, Square)>,
+    #[serde(default)]
+    ice: SquareSet,
+    #[serde(default)]
+    one_way: Vec<(Square, Heading)>,
+    #[serde(default)]
+    walls: Vec<Square>,
+    #[serde(default = "default_breakers")]
+    wall_breakers: Vec<PieceType>,
+    #[serde(default)]
+    terrain: Vec<(Square, u32)>,
+    #[serde(default)]
+    optional: Vec<Square>,
+}
+
+fn default_breakers() -> Vec<PieceType> {
+    vec![PieceType::Rook]
+}
+
+fn full_board() -> u8 {
+    8
+}
+
+/// One puzzle of a pack in JSON: the puzzle, with its metadata alongside. See
+/// `pack::write_pack_json`.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct EntryRepr {
+    #[serde(flatten)]
+    pub(crate) meta: PuzzleMeta,
+    #[serde(flatten)]
+    pub(crate) puzzle: Puzzle,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PieceRepr {
+    square: Square,
+    #[serde(rename = "type")]
+    piece_type: PieceType,
+}
+
+impl From<Puzzle> for PuzzleRepr {
+    fn from(p: Puzzle) -> PuzzleRepr {
+        let pieces = (0..p.num_pieces())
+            .map(|idx| PieceRepr {
+                square: p.piece_loc(idx),
+                piece_type: p.piece_type(idx).unwrap(),
+            })
+            .collect();
+        PuzzleRepr {
+            obstacles: p.obstacles() & p.squares(),
+            pieces,
+            player: p.piece_loc(p.player_start()),
+            starts: p
+                .starts()
+                .iter()
+                .filter(|&&i| i != p.player_start())
+                .map(|&i| p.piece_loc(i))
+                .collect(),
+            patrols: p.patrols().iter().map(|(_, route)| route.clone()).collect(),
+            rules: p.rules(),
+            width: p.width(),
+            height: p.height(),
+            exit: p.exit(),
+            budget: p.budget(),
+            portals: p.portals().to_vec(),
+            ice: p.ice(),
+            one_way: Heading::ALL
+                .into_iter()
+                .flat_map(|h| p.one_way(h).iter().map(move |sq| (sq, h)))
+                .collect(),
+            walls: p.walls().to_vec(),
+            wall_breakers: p.wall_breakers().to_vec(),
+            terrain: p.terrain().to_vec(),
+            optional: (0..p.num_pieces())
+                .filter(|&i| !p.required(i))
+                .map(|i| p.piece_loc(i))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<PuzzleRepr> for Puzzle {
+    type Error = String;
+    fn try_from(repr: PuzzleRepr) -> Result<Puzzle, String> {
+        let mut builder = PuzzleBuilder::new();
+        builder.set_size(repr.width, repr.height);
+        for sq in repr.obstacles {
+            builder.add_obstacle(sq);
+        }
+        for piece in &repr.pieces {
+            builder.add_piece(piece.square, piece.piece_type);
+        }
+        builder.set_player(repr.player);
+        for &sq in &repr.starts {
+            builder.add_start(sq);
+        }
+        if let Some(exit) = repr.exit {
+            builder.set_exit(exit);
+        }
+        for &(a, b) in &repr.portals {
+            builder.add_portal(a, b);
+        }
+        for sq in repr.ice {
+            builder.add_ice(sq);
+        }
+        for &(sq, heading) in &repr.one_way {
+            builder.add_one_way(sq, heading);
+        }
+        for &sq in &repr.walls {
+            builder.add_wall(sq);
+        }
+        builder.set_wall_breakers(&repr.wall_breakers);
+        for &(sq, cost) in &repr.terrain {
+            builder.set_move_cost(sq, cost);
+        }
+        let mut puz = builder.build().map_err(|e| e.to_string())?;
+        for route in &repr.patrols {
+            let start = *route.first().ok_or("Empty patrol route")?;
+            let piece_idx = puz
+                .piece_on(start)
+                .ok_or_else(|| format!("No piece to patrol on square {}", start))?;
+            puz.add_patrol(piece_idx, route)
+                .map_err(|e| e.to_string())?;
+        }
+        for &sq in &repr.optional {
+            let piece_idx = puz
+                .piece_on(sq)
+                .ok_or_else(|| format!("No optional piece on square {}", sq))?;
+            puz.set_required(piece_idx, false);
+        }
+        puz.set_rules(repr.rules);
+        puz.set_budget(repr.budget);
+        Ok(puz)
+    }
+}
+
+/// A `Graph` for the web: nodes carry their ids, and pieces are named rather than stored, since
+/// custom pieces can't be.
+#[derive(Serialize)]
+pub(crate) struct GraphRepr {
+    nodes: Vec<NodeRepr>,
+    edges: Vec<EdgeRepr>,
+}
+
+#[derive(Serialize)]
+struct NodeRepr {
+    id: usize,
+    piece: &'static str,
+    remaining: u32,
+    outcome: Outcome,
+}
+
+#[derive(Serialize)]
+struct EdgeRepr {
+    from: usize,
+    to: usize,
+    captured: u32,
+    square: Square,
+}
+
+impl From<&Graph> for GraphRepr {
+    fn from(graph: &Graph) -> GraphRepr {
+        GraphRepr {
+            nodes: graph
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(id, node)| NodeRepr {
+                    id,
+                    piece: node.piece.name(),
+                    remaining: node.remaining,
+                    outcome: node.outcome,
+                })
+                .collect(),
+            edges: graph
+                .edges
+                .iter()
+                .map(|edge| EdgeRepr {
+                    from: edge.from,
+                    to: edge.to,
+                    captured: edge.captured,
+                    square: edge.square,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The format of `solution_json`.
+#[derive(Serialize)]
+pub(crate) struct SolutionRepr<'a> {
+    version: u32,
+    fen: String,
+    puzzle: &'a Puzzle,
+    solvable: bool,
+    captures: Vec<CaptureRepr>,
+    exit_path: &'a [Square],
+    stats: StatsRepr,
+}
+
+#[derive(Serialize)]
+struct CaptureRepr {
+    piece: u32,
+    player: &'static str,
+    captured: &'static str,
+    square: Square,
+    path: Vec<Square>,
+}
+
+#[derive(Serialize)]
+struct StatsRepr {
+    moves: Option<u32>,
+    search: Option<SearchRepr>,
+}
+
+#[derive(Serialize)]
+struct SearchRepr {
+    nodes_expanded: usize,
+    states_visited: usize,
+    frontier_peak: usize,
+    depth: usize,
+    elapsed_ms: f64,
+}
+
+impl<'a> SolutionRepr<'a> {
+    pub(crate) fn new(
+        p: &'a Puzzle,
+        sol: Option<&'a Solution>,
+        report: Option<&SolveReport>,
+    ) -> SolutionRepr<'a> {
+        let name = |ty: Option<PieceType>| ty.map_or("wall", PieceType::name);
+        let mut player = p.player_start();
+        let mut captures = Vec::new();
+        for mv in sol.iter().flat_map(|sol| &sol.moves) {
+            captures.push(CaptureRepr {
+                piece: mv.captured,
+                player: name(p.player_type(player)),
+                captured: name(p.piece_type(mv.captured)),
+                square: *mv.path.last().expect("moves end on the captured piece"),
+                path: mv.path.clone(),
+            });
+            player = mv.captured;
+        }
+        SolutionRepr {
+            version: 1,
+            fen: p.to_fen(),
+            puzzle: p,
+            solvable: sol.is_some(),
+            captures,
+            exit_path: sol.map_or(&[], |sol| &sol.exit_path),
+            stats: StatsRepr {
+                moves: sol.map(|sol| sol.num_moves(p)),
+                search: report.map(|report| SearchRepr {
+                    nodes_expanded: report.nodes_expanded,
+                    states_visited: report.states_visited,
+                    frontier_peak: report.frontier_peak,
+                    depth: report.depth,
+                    elapsed_ms: report.elapsed.as_secs_f64() * 1000.0,
+                }),
+            },
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Drawing boards as SVG images, for embedding in web pages and documentation.
+
+use crate::render::{arrow, glyph, steps};
+use crate::{Heading, Puzzle, Solution, Square, PORTAL_GLYPHS};
+
+/// How many pixels across each square is.
+const SQUARE: u32 = 48;
+
+/// How many pixels the rank numbers and file letters take up, to the left and below.
+const MARGIN: u32 = 20;
+
+/// Draws the board as an SVG image: a checkered board with obstacles shaded dark, the player's
+/// starting square highlighted, and chess glyphs for the pieces, white for the ones that the
+/// player could start as and black for the rest.
+///
+/// ```
+/// use echochess::svg::puzzle_svg;
+/// use echochess::Puzzle;
+///
+/// let svg = puzzle_svg(&Puzzle::parse("R1b/1x1/1n1").unwrap());
+/// assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+/// assert!(svg.contains(">♝</text>"));
+/// assert!(svg.trim_end().ends_with("</svg>"));
+/// ```
+pub fn puzzle_svg(p: &Puzzle) -> String {
+    draw(p, None)
+}
+
+/// Draws the board before and after each capture of `sol`, as `puzzle_svg` does, for showing a
+/// solution step by step. Each image after the first traces the path of the move just made, as
+/// `render::steps` gives it.
+///
+/// ```
+/// use echochess::svg::solution_svgs;
+/// use echochess::{solve, Puzzle, Solution};
+///
+/// let puzzle = Puzzle::parse("R1b/3/1n1").unwrap();
+/// let sol = Solution::from_captures(&puzzle, &solve(&puzzle).unwrap()).unwrap();
+/// let frames = solution_svgs(&puzzle, &sol);
+/// assert_eq!(frames.len(), 3);
+/// assert!(!frames[0].contains("<polyline"));
+/// assert!(frames[1].contains("<polyline"));
+/// ```
+pub fn solution_svgs(p: &Puzzle, sol: &Solution) -> Vec<String> {
+    steps(p, sol)
+        .iter()
+        .map(|(position, path)| {
+            draw(
+                position,
+                Some(path.as_slice()).filter(|path| !path.is_empty()),
+            )
+        })
+        .collect()
+}
+
+/// Draws `p`, with `path` traced over it if given.
+fn draw(p: &Puzzle, path: Option<&[Square]>) -> String {
+    let (width, height) = (u32::from(p.width()), u32::from(p.height()));
+    let (w, h) = (MARGIN + width * SQUARE, height * SQUARE + MARGIN);
+    let mut res = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\">\n"
+    );
+    for sq in p.squares().iter() {
+        let (x, y) = corner(p, sq);
+        let light = (sq.file() + sq.rank()) % 2 == 1;
+        let fill = if p.obstacles().contains(sq) {
+            "#4a4a4a"
+        } else if p.piece_on(sq) == Some(p.player_start()) {
+            "#f6f669"
+        } else if p.ice().contains(sq) {
+            "#cfefff"
+        } else if light {
+            "#eeeed2"
+        } else {
+            "#b0c490"
+        };
+        res.push_str(&format!(
+            "<rect x=\"{x}\" y=\"{y}\" width=\"{SQUARE}\" height=\"{SQUARE}\" fill=\"{fill}\"/>\n"
+        ));
+        if let Some((text, color)) = label(p, sq) {
+            let (cx, cy) = (x + SQUARE / 2, y + SQUARE / 2);
+            res.push_str(&format!(
+                "<text x=\"{cx}\" y=\"{cy}\" font-size=\"{}\" text-anchor=\"middle\" \
+                 dominant-baseline=\"central\" {color}>{text}</text>\n",
+                SQUARE * 3 / 4
+            ));
+        }
+        let cost = p.move_cost(sq);
+        if cost > 1 {
+            res.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"11\" fill=\"#7a4a1a\">{cost}</text>\n",
+                x + 3,
+                y + 12
+            ));
+        }
+    }
+    for i in 0..p.width() {
+        res.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"12\" text-anchor=\"middle\">{}</text>\n",
+            MARGIN + u32::from(i) * SQUARE + SQUARE / 2,
+            h - 5,
+            char::from(b'a' + i)
+        ));
+    }
+    for i in 0..p.height() {
+        res.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"12\" text-anchor=\"middle\" \
+             dominant-baseline=\"central\">{}</text>\n",
+            MARGIN / 2,
+            (height - 1 - u32::from(i)) * SQUARE + SQUARE / 2,
+            i + 1
+        ));
+    }
+    if let Some(path) = path {
+        let points: Vec<String> = path
+            .iter()
+            .map(|&sq| {
+                let (x, y) = corner(p, sq);
+                format!("{},{}", x + SQUARE / 2, y + SQUARE / 2)
+            })
+            .collect();
+        res.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"#d33\" stroke-width=\"4\" \
+             stroke-linejoin=\"round\" stroke-opacity=\"0.7\"/>\n",
+            points.join(" ")
+        ));
+    }
+    res.push_str("</svg>\n");
+    res
+}
+
+/// The top left corner of square `sq`, in pixels.
+fn corner(p: &Puzzle, sq: Square) -> (u32, u32) {
+    let row = u32::from(p.height() - 1 - sq.rank());
+    (MARGIN + u32::from(sq.file()) * SQUARE, row * SQUARE)
+}
+
+/// What to write on square `sq`, if anything, and the attributes to color it with. Pieces
+/// all use the solid glyphs, filled white or black, which read better than the outlined ones.
+fn label(p: &Puzzle, sq: Square) -> Option<(String, &'static str)> {
+    if let Some(idx) = p.piece_on(sq) {
+        let ty = p.piece_type(idx)?;
+        let white = p.starts().contains(&idx);
+        let text = match glyph(ty, false) {
+            Some(c) => c.to_string(),
+            None if white => ty.fen_char().to_ascii_uppercase().to_string(),
+            None => ty.fen_char().to_string(),
+        };
+        let color = if white {
+            "fill=\"#fff\" stroke=\"#000\" stroke-width=\"1.5\""
+        } else {
+            "fill=\"#000\""
+        };
+        return Some((text, color));
+    }
+    if p.exit() == Some(sq) {
+        Some(("E".to_string(), "fill=\"#1a7f37\" font-weight=\"bold\""))
+    } else if p.walls().contains(&sq) {
+        Some(("+".to_string(), "fill=\"#8b4513\" font-weight=\"bold\""))
+    } else if let Some(h) = Heading::ALL
+        .into_iter()
+        .find(|&h| p.one_way(h).contains(sq))
+    {
+        Some((arrow(h).to_string(), "fill=\"#2456a6\""))
+    } else {
+        let i = p.portals().iter().position(|&(a, b)| sq == a || sq == b)?;
+        Some((PORTAL_GLYPHS[i].to_string(), "fill=\"#8a2be2\""))
+    }
+}
@@ -0,0 +1,179 @@
+//! Counting moves over terrain, where stepping onto some squares costs more than one move. See
+//! `Puzzle::terrain`.
+//!
+//! The bitboard searches count moves by growing the reachable set one step at a time, so each
+//! square is first reached in the fewest steps. With costs, the fewest steps needn't be the
+//! fewest moves, so these go one square at a time instead, cheapest first, as in Dijkstra's
+//! algorithm. Boards have at most 64 squares, so a linear scan for the cheapest one will do.
+
+use crate::{PieceType, Puzzle, Square, SquareSet};
+
+/// The cheapest ways from the squares in `from` to every square reachable through `permeable`
+/// by steps of `step`: for each square, the fewest moves to get there, and the square that the
+/// last step came from (`None` for the squares of `from`).
+struct Walks {
+    cost: [Option<u32>; 64],
+    came_from: [Option<Square>; 64],
+}
+
+impl Walks {
+    fn new(
+        p: &Puzzle,
+        from: SquareSet,
+        permeable: SquareSet,
+        step: impl Fn(Square) -> SquareSet,
+    ) -> Walks {
+        let mut walks = Walks {
+            cost: [None; 64],
+            came_from: [None; 64],
+        };
+        for sq in from & permeable {
+            walks.cost[sq.index() as usize] = Some(0);
+        }
+        let mut settled = SquareSet(0);
+        loop {
+            let cheapest = (0..64)
+                .filter_map(|i| Some((walks.cost[i]?, Square::new(i as u8)?)))
+                .filter(|&(_, sq)| !settled.contains(sq))
+                .min();
+            let Some((cost, sq)) = cheapest else {
+                return walks;
+            };
+            settled.insert(sq);
+            for next in step(sq) & permeable & !settled {
+                let new_cost = cost + p.move_cost(next);
+                let old = &mut walks.cost[next.index() as usize];
+                if old.is_none_or(|old| new_cost < old) {
+                    *old = Some(new_cost);
+                    walks.came_from[next.index() as usize] = Some(sq);
+                }
+            }
+        }
+    }
+
+    /// The fewest moves to get to `sq`, if it can be reached at all.
+    fn cost(&self, sq: Square) -> Option<u32> {
+        self.cost[sq.index() as usize]
+    }
+
+    /// The squares stepped onto on the way to `to`, in order, ending with `to` itself.
+    fn path(&self, mut to: Square) -> Vec<Square> {
+        let mut path = Vec::new();
+        while let Some(prev) = self.came_from[to.index() as usize] {
+            path.push(to);
+            to = prev;
+        }
+        path.reverse();
+        path
+    }
+
+    /// For each target square in `targets` that a piece of type `ty` can capture on from a
+    /// reachable square, the fewest moves to capture there, and the square it captures from.
+    fn captures(
+        &self,
+        p: &Puzzle,
+        ty: PieceType,
+        permeable: SquareSet,
+        targets: SquareSet,
+    ) -> Vec<(Square, u32, Square)> {
+        let mut best: Vec<(Square, u32, Square)> = Vec::new();
+        for sq in (0..64).filter_map(Square::new) {
+            let Some(cost) = self.cost(sq) else {
+                continue;
+            };
+            for target in p.capture_reach(ty, sq.into(), permeable) & targets {
+                let total = cost + p.move_cost(target);
+                match best.iter_mut().find(|(t, _, _)| *t == target) {
+                    Some(entry) if total < entry.1 => *entry = (target, total, sq),
+                    Some(_) => (),
+                    None => best.push((target, total, sq)),
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Like `Puzzle::capture_moves`, counting each step by what its square costs.
+pub(crate) fn capture_moves<F: FnMut(Square, u32)>(
+    p: &Puzzle,
+    ty: PieceType,
+    from: SquareSet,
+    obstacles: SquareSet,
+    targets: SquareSet,
+    avoid: SquareSet,
+    mut consume: F,
+) {
+    let permeable = !(obstacles | targets);
+    let walks = Walks::new(p, from, permeable, |sq| {
+        p.move_reach(ty, sq.into(), permeable) & !avoid
+    });
+    for (target, moves, _) in walks.captures(p, ty, permeable, targets) {
+        consume(target, moves);
+    }
+}
+
+/// Like `Puzzle::capture_path`, for a path with the fewest moves rather than the fewest steps.
+pub(crate) fn capture_path(
+    p: &Puzzle,
+    ty: PieceType,
+    from: Square,
+    obstacles: SquareSet,
+    targets: SquareSet,
+    avoid: SquareSet,
+    to: Square,
+) -> Option<Vec<Square>> {
+    let permeable = !(obstacles | targets);
+    let walks = Walks::new(p, from.into(), permeable, |sq| {
+        p.move_reach(ty, sq.into(), permeable) & !avoid
+    });
+    let captures = walks.captures(p, ty, permeable, targets & SquareSet::from(to));
+    let &(_, _, last) = captures.first()?;
+    let mut path = walks.path(last);
+    path.push(to);
+    Some(path)
+}
+
+/// Like `Puzzle::exit_path`, for a walk with the fewest moves rather than the fewest steps.
+pub(crate) fn walk(
+    p: &Puzzle,
+    ty: PieceType,
+    from: Square,
+    permeable: SquareSet,
+    to: Square,
+) -> Option<Vec<Square>> {
+    let walks = Walks::new(p, from.into(), permeable, |sq| {
+        p.move_reach(ty, sq.into(), permeable)
+    });
+    walks.cost(to)?;
+    Some(walks.path(to))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::assert_strategies_agree;
+    use crate::{solve, solve_min_moves, Puzzle};
+
+    /// Terrain only changes how many moves solutions take, so every strategy still wins exactly
+    /// when breadth-first search does, and `solve_min_moves` counts the mud as `verify` does.
+    #[test]
+    fn strategies_agree_with_breadth_first() {
+        let muddy: [(&str, &[&str]); 3] = [
+            ("3/K1n/3", &["b1", "b2", "b3"]),
+            ("R1n/3/n1n", &["b1", "b3", "c2"]),
+            ("B2/3/1n1", &["b2"]),
+        ];
+        for (fen, mud) in muddy {
+            let mut puzzle = Puzzle::parse(fen).unwrap();
+            let flat = solve(&puzzle);
+            for sq in mud {
+                puzzle.set_move_cost(sq.parse().unwrap(), 3);
+            }
+            assert_eq!(solve(&puzzle), flat, "{}", fen);
+            assert_strategies_agree(&puzzle);
+            if let Some((captures, moves)) = solve_min_moves(&puzzle) {
+                assert_eq!(puzzle.verify(&captures).unwrap().moves, moves, "{}", fen);
+            }
+        }
+    }
+}
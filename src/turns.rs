@@ -0,0 +1,206 @@
+//! A turn-based variant where some enemy pieces move after each of the player's captures,
+//! choosing where to go as they like rather than following fixed patrol routes.
+//!
+//! A `PuzzleState` can only say where a patroller is because its route is a function of how many
+//! captures have been made. Pieces that react to the player can end up anywhere, so this search
+//! keeps their squares in its states, along with the player's own, and is correspondingly slower.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{Puzzle, Square, SquareSet};
+
+/// How a moving enemy picks where to go after each of the player's captures.
+pub trait EnemyPolicy {
+    /// The square that piece `piece_idx`, now on `from`, moves to after the player's `turn`th
+    /// capture (counting from 1), which left the player on square `player`. The squares in
+    /// `blocked` hold obstacles and the other pieces still to be captured. Returning `from`
+    /// stays put; moving onto `player` captures the player, which loses the game.
+    fn next_square(
+        &self,
+        p: &Puzzle,
+        piece_idx: u32,
+        from: Square,
+        turn: u32,
+        player: Square,
+        blocked: SquareSet,
+    ) -> Square;
+}
+
+/// A scripted cycle of squares, like a patrol route: from any square on the route, the piece
+/// goes to the next one, wrapping around at the end. Off the route, or when the next square is
+/// blocked, it stays put.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route(pub Vec<Square>);
+
+impl EnemyPolicy for Route {
+    fn next_square(
+        &self,
+        _p: &Puzzle,
+        _piece_idx: u32,
+        from: Square,
+        _turn: u32,
+        _player: Square,
+        blocked: SquareSet,
+    ) -> Square {
+        let Some(pos) = self.0.iter().position(|&sq| sq == from) else {
+            return from;
+        };
+        let next = self.0[(pos + 1) % self.0.len()];
+        if blocked.contains(next) {
+            from
+        } else {
+            next
+        }
+    }
+}
+
+/// Chases the player: takes the one move step that brings the piece closest to the player, by
+/// king moves, capturing the player if it can. It stays put if no step gets any closer, and
+/// breaks ties toward the lowest square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Greedy;
+
+impl EnemyPolicy for Greedy {
+    fn next_square(
+        &self,
+        p: &Puzzle,
+        piece_idx: u32,
+        from: Square,
+        _turn: u32,
+        player: Square,
+        blocked: SquareSet,
+    ) -> Square {
+        let distance = |sq: Square| {
+            let df = sq.file().abs_diff(player.file());
+            let dr = sq.rank().abs_diff(player.rank());
+            df.max(dr)
+        };
+        let Some(ty) = p.mover(piece_idx) else {
+            return from;
+        };
+        let steps = p.move_reach(ty, from.into(), !blocked);
+        steps
+            .iter()
+            .filter(|&sq| distance(sq) < distance(from))
+            .min_by_key(|&sq| (distance(sq), sq))
+            .unwrap_or(from)
+    }
+}
+
+/// A position in the turn-based search: the pieces still to be captured, which piece the player
+/// is and where it stands, and where each moving enemy is, in the order they were given.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TurnState {
+    remaining: u32,
+    player: u32,
+    player_loc: Square,
+    mover_locs: Vec<Square>,
+}
+
+impl TurnState {
+    /// Where piece `piece_idx` stands, whether it moves or not.
+    fn loc(&self, p: &Puzzle, movers: &[(u32, &dyn EnemyPolicy)], piece_idx: u32) -> Square {
+        match movers.iter().position(|&(i, _)| i == piece_idx) {
+            Some(k) => self.mover_locs[k],
+            None => p.piece_loc(piece_idx),
+        }
+    }
+
+    /// The pieces still to be captured.
+    fn remaining(&self, p: &Puzzle) -> impl Iterator<Item = u32> + '_ {
+        (0..p.num_pieces()).filter(|&i| self.remaining & (1 << i) != 0)
+    }
+}
+
+/// Solves a puzzle where, after each of the player's captures, each piece `piece_idx` in
+/// `movers` that's still on the board moves where its policy says, one after another. Returns the
+/// piece indices to be captured in order to win, like `solve`, or `None` if there's no way.
+///
+/// The puzzle's own patrol routes are ignored, so give those pieces `Route`s here instead, and
+/// its walls stand for good. The exit still has to be reachable after the last capture, but the
+/// other rule variants don't apply.
+///
+/// ```
+/// use echochess::turns::{solve_turns, EnemyPolicy, Greedy};
+/// use echochess::Puzzle;
+///
+/// // The bishop takes the rook on a3, and then the rook takes the king next to it. But a king
+/// // that chases the player steps onto a3 first.
+/// let puzzle = Puzzle::parse("rkB/3/3").unwrap();
+/// assert_eq!(solve_turns(&puzzle, &[]), Some(vec![0, 1]));
+/// let chaser: &dyn EnemyPolicy = &Greedy;
+/// assert_eq!(solve_turns(&puzzle, &[(1, chaser)]), None);
+/// ```
+pub fn solve_turns(p: &Puzzle, movers: &[(u32, &dyn EnemyPolicy)]) -> Option<Vec<u32>> {
+    let walls: SquareSet = p.walls().iter().copied().collect();
+    let obstacles = p.obstacles() | walls;
+    let start_idx = p.player_start();
+    let initial = TurnState {
+        remaining: ((1 << p.num_pieces()) - 1) & !(1 << start_idx),
+        player: start_idx,
+        player_loc: p.piece_loc(start_idx),
+        mover_locs: movers.iter().map(|&(i, _)| p.piece_loc(i)).collect(),
+    };
+    if initial.remaining & p.required_bits() == 0 {
+        return Some(Vec::new());
+    }
+    // Each state seen, with the one before it and the piece captured in between.
+    let mut parents: HashMap<TurnState, Option<(TurnState, u32)>> = HashMap::new();
+    parents.insert(initial.clone(), None);
+    let mut queue = VecDeque::from([initial]);
+    while let Some(state) = queue.pop_front() {
+        let targets: SquareSet = state
+            .remaining(p)
+            .map(|i| state.loc(p, movers, i))
+            .collect();
+        let ty = p.mover(state.player)?;
+        let start = SquareSet::from(state.player_loc);
+        for loc in p.captures(ty, start, obstacles, targets, SquareSet(0)) {
+            let captured = state
+                .remaining(p)
+                .find(|&i| state.loc(p, movers, i) == loc)
+                .unwrap();
+            let mut next = TurnState {
+                remaining: state.remaining & !(1 << captured),
+                player: captured,
+                player_loc: loc,
+                mover_locs: state.mover_locs.clone(),
+            };
+            if next.remaining & p.required_bits() == 0 && !p.can_exit(captured, loc, walls) {
+                continue;
+            }
+            let turn = p.num_pieces() - 1 - next.remaining.count_ones();
+            let mut caught = false;
+            for (k, &(i, policy)) in movers.iter().enumerate() {
+                if next.remaining & (1 << i) == 0 {
+                    continue;
+                }
+                let from = next.mover_locs[k];
+                let others: SquareSet = next
+                    .remaining(p)
+                    .filter(|&j| j != i)
+                    .map(|j| next.loc(p, movers, j))
+                    .collect();
+                let to = policy.next_square(p, i, from, turn, loc, obstacles | others);
+                caught |= to == loc;
+                next.mover_locs[k] = to;
+            }
+            if caught || parents.contains_key(&next) {
+                continue;
+            }
+            parents.insert(next.clone(), Some((state.clone(), captured)));
+            if next.remaining & p.required_bits() == 0 {
+                let mut res = Vec::new();
+                let mut cur = next;
+                while let Some((prev, captured)) = parents[&cur].clone() {
+                    res.push(captured);
+                    cur = prev;
+                }
+                res.reverse();
+                return Some(res);
+            }
+            queue.push_back(next);
+        }
+    }
+    None
+}
@@ -0,0 +1,725 @@
+//! Boards bigger than 8×8, up to 16×16.
+//!
+//! The rest of the crate keeps a set of squares in one `u64`, which is a lot of what makes the
+//! solver fast, and so it stops at 8×8. This module has its own `SquareSet`, of four `u64`s with
+//! sixteen files to a rank, and its own `Square` and `Puzzle` on top, with a plain breadth-first
+//! `solve`. It knows every built-in piece, obstacles, and optional pieces, but none of the other
+//! rule variants or custom pieces. Boards that fit in 8×8 should stay with `crate::Puzzle`.
+//!
+//! ```
+//! use echochess::wide::{solve, Puzzle};
+//!
+//! // A rook in the corner of a 16×16 board, a bishop along its first rank, and a knight on the far
+//! // side.
+//! let puzzle = Puzzle::parse("88/88/88/88/88/88/88/87n/88/88/88/88/88/88/88/R6b8").unwrap();
+//! assert_eq!((puzzle.width(), puzzle.height()), (16, 16));
+//! assert_eq!(solve(&puzzle), Some(vec![1, 2]));
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{ParseSquareError, PieceType, MAX_PIECES};
+
+/// The most files or ranks that a board here can have.
+pub const MAX_SIZE: u8 = 16;
+
+/// A subset of the squares on a board up to 16×16.
+///
+/// Square in file `x` and rank `y` is indicated by bit `16 * y + x`, counting up from bit `0` of
+/// the first `u64`. For instance, B1 is bit `1` and A2 is bit `16`.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone, Hash)]
+pub struct SquareSet(pub [u64; 4]);
+
+impl std::ops::BitAnd<SquareSet> for SquareSet {
+    type Output = SquareSet;
+    fn bitand(self, rhs: SquareSet) -> SquareSet {
+        SquareSet(std::array::from_fn(|i| self.0[i] & rhs.0[i]))
+    }
+}
+impl std::ops::BitOr<SquareSet> for SquareSet {
+    type Output = SquareSet;
+    fn bitor(self, rhs: SquareSet) -> SquareSet {
+        SquareSet(std::array::from_fn(|i| self.0[i] | rhs.0[i]))
+    }
+}
+impl std::ops::Not for SquareSet {
+    type Output = SquareSet;
+    fn not(self) -> SquareSet {
+        SquareSet(self.0.map(|word| !word))
+    }
+}
+impl std::ops::Shl<u32> for SquareSet {
+    type Output = SquareSet;
+    /// Moves every square `rhs` bits higher, carrying from one word into the next.
+    fn shl(self, rhs: u32) -> SquareSet {
+        let (words, bits) = ((rhs / 64) as usize, rhs % 64);
+        SquareSet(std::array::from_fn(|i| {
+            let Some(from) = i.checked_sub(words) else {
+                return 0;
+            };
+            let carry = match from.checked_sub(1) {
+                Some(below) if bits > 0 => self.0[below] >> (64 - bits),
+                _ => 0,
+            };
+            (self.0[from] << bits) | carry
+        }))
+    }
+}
+impl std::ops::Shr<u32> for SquareSet {
+    type Output = SquareSet;
+    /// Moves every square `rhs` bits lower, carrying from one word into the next.
+    fn shr(self, rhs: u32) -> SquareSet {
+        let (words, bits) = ((rhs / 64) as usize, rhs % 64);
+        SquareSet(std::array::from_fn(|i| {
+            let from = i + words;
+            if from >= 4 {
+                return 0;
+            }
+            let carry = match self.0.get(from + 1) {
+                Some(above) if bits > 0 => above << (64 - bits),
+                _ => 0,
+            };
+            (self.0[from] >> bits) | carry
+        }))
+    }
+}
+
+impl SquareSet {
+    /// Whether this set has no squares.
+    pub fn is_empty(self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    /// How many squares are in this set?
+    pub fn count(self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+
+    pub fn contains(self, sq: Square) -> bool {
+        !(self & sq.into()).is_empty()
+    }
+
+    /// Adds a square to this set, returning whether it was newly added.
+    pub fn insert(&mut self, sq: Square) -> bool {
+        let added = !self.contains(sq);
+        *self = *self | sq.into();
+        added
+    }
+
+    /// Iterates over the squares in this set, in increasing order of index.
+    pub fn iter(self) -> SquareSetIter {
+        SquareSetIter(self)
+    }
+}
+
+/// Iterator over the squares in a `SquareSet`. See `SquareSet::iter`.
+#[derive(Debug, Clone)]
+pub struct SquareSetIter(SquareSet);
+
+impl Iterator for SquareSetIter {
+    type Item = Square;
+    fn next(&mut self) -> Option<Square> {
+        let i = self.0 .0.iter().position(|&word| word != 0)?;
+        let word = &mut self.0 .0[i];
+        let sq = Square(64 * i as u8 + word.trailing_zeros() as u8);
+        *word &= *word - 1;
+        Some(sq)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.0.count() as usize;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for SquareSetIter {}
+
+impl IntoIterator for SquareSet {
+    type Item = Square;
+    type IntoIter = SquareSetIter;
+    fn into_iter(self) -> SquareSetIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<Square> for SquareSet {
+    fn from_iter<I: IntoIterator<Item = Square>>(iter: I) -> SquareSet {
+        let mut res = SquareSet::default();
+        for sq in iter {
+            res.insert(sq);
+        }
+        res
+    }
+}
+
+/// One square on a board up to 16×16: file `x` (`0..16`, for A through P) and rank `y` (`0..16`,
+/// for 1 through 16) is `Square(16 * y + x)`, matching the bit layout of `SquareSet`.
+///
+/// Squares parse from and display as algebraic notation, like `j12`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Square(u8);
+
+impl Square {
+    /// The square with the given index. Every `u8` is one.
+    pub fn new(index: u8) -> Square {
+        Square(index)
+    }
+
+    /// The square on the given file (`0..16`) and rank (`0..16`), or `None` if either is out of
+    /// range.
+    pub fn from_file_rank(file: u8, rank: u8) -> Option<Square> {
+        (file < MAX_SIZE && rank < MAX_SIZE).then(|| Square(MAX_SIZE * rank + file))
+    }
+
+    /// This square's index, which is also its bit in a `SquareSet`.
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    /// This square's file, from `0` (the A-file) to `15` (the P-file).
+    pub fn file(self) -> u8 {
+        self.0 % MAX_SIZE
+    }
+
+    /// This square's rank, from `0` (rank 1) to `15` (rank 16).
+    pub fn rank(self) -> u8 {
+        self.0 / MAX_SIZE
+    }
+}
+
+impl From<Square> for SquareSet {
+    fn from(sq: Square) -> SquareSet {
+        let mut res = SquareSet::default();
+        res.0[sq.0 as usize / 64] = 1 << (sq.0 % 64);
+        res
+    }
+}
+
+impl std::fmt::Display for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", char::from(b'a' + self.file()), self.rank() + 1)
+    }
+}
+
+impl std::str::FromStr for Square {
+    type Err = ParseSquareError;
+    fn from_str(name: &str) -> Result<Square, ParseSquareError> {
+        let err = || ParseSquareError(name.to_string());
+        let (file, rank_name) = name.split_at_checked(1).ok_or_else(err)?;
+        let file = match file.as_bytes() {
+            &[c @ b'a'..=b'p'] => c - b'a',
+            _ => return Err(err()),
+        };
+        let rank = match rank_name.parse::<u8>() {
+            Ok(rank @ 1..=MAX_SIZE) if !rank_name.starts_with('0') => rank - 1,
+            _ => return Err(err()),
+        };
+        Square::from_file_rank(file, rank).ok_or_else(err)
+    }
+}
+
+/// A step of a piece, as files and ranks to move by.
+type Leap = (i8, i8);
+
+const ORTHOGONAL: [Leap; 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const DIAGONAL: [Leap; 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+const KNIGHT: [Leap; 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+/// The squares on files `lo..=hi`.
+fn files(lo: i8, hi: i8) -> SquareSet {
+    let rank = (lo..=hi).fold(0u64, |acc, file| acc | 1 << file);
+    SquareSet([rank * 0x0001_0001_0001_0001; 4])
+}
+
+/// Where pieces on `set` land after `leap`. Pieces that it would take off the side of the board
+/// are dropped rather than wrapped around to the other side.
+fn leap(set: SquareSet, (dx, dy): Leap) -> SquareSet {
+    let from = set
+        & files(
+            0.max(-dx),
+            (MAX_SIZE as i8 - 1).min(MAX_SIZE as i8 - 1 - dx),
+        );
+    let by = i32::from(MAX_SIZE) * i32::from(dy) + i32::from(dx);
+    if by >= 0 {
+        from << by as u32
+    } else {
+        from >> by.unsigned_abs()
+    }
+}
+
+/// The leaps that `ty` moves by, in groups that all apply. Custom pieces are only defined on
+/// boards up to 8×8, so `Puzzle::parse` never makes one.
+fn move_leaps(ty: PieceType) -> &'static [&'static [Leap]] {
+    match ty {
+        PieceType::Pawn => &[&[(0, 1)]],
+        PieceType::PawnDown => &[&[(0, -1)]],
+        PieceType::Bishop => &[&DIAGONAL],
+        PieceType::Rook | PieceType::Cannon => &[&ORTHOGONAL],
+        PieceType::King | PieceType::Queen => &[&ORTHOGONAL, &DIAGONAL],
+        PieceType::Knight | PieceType::Nightrider => &[&KNIGHT],
+        PieceType::Archbishop => &[&DIAGONAL, &KNIGHT],
+        PieceType::Chancellor => &[&ORTHOGONAL, &KNIGHT],
+        PieceType::Amazon => &[&ORTHOGONAL, &DIAGONAL, &KNIGHT],
+        PieceType::Grasshopper => &[],
+        PieceType::Custom(_) => unreachable!("custom pieces aren't on wide boards"),
+    }
+}
+
+/// The leaps that `ty` captures by, like `move_leaps`. A queen or nightrider captures down a line
+/// too, but it could as well move down the line first, so that's left to `fill`.
+fn capture_leaps(ty: PieceType) -> &'static [&'static [Leap]] {
+    match ty {
+        PieceType::Pawn => &[&[(-1, 1), (1, 1)]],
+        PieceType::PawnDown => &[&[(-1, -1), (1, -1)]],
+        PieceType::Grasshopper | PieceType::Cannon => &[],
+        ty => move_leaps(ty),
+    }
+}
+
+/// Where pieces on `set` land after any one of `leaps`.
+fn steps(set: SquareSet, leaps: &[&[Leap]]) -> SquareSet {
+    leaps
+        .iter()
+        .flat_map(|group| group.iter())
+        .fold(SquareSet::default(), |res, &l| res | leap(set, l))
+}
+
+/// Slides from `gen` by `l`, again and again, through `pro`, keeping `gen`.
+fn line(gen: SquareSet, pro: SquareSet, l: Leap) -> SquareSet {
+    let mut line = gen;
+    loop {
+        let next = line | (leap(line, l) & pro);
+        if next == line {
+            return line;
+        }
+        line = next;
+    }
+}
+
+/// Squares that a grasshopper on `gen` can land on by sliding along a line through `pro`, hopping
+/// over the first square that's not in `pro`, and landing just beyond it.
+fn hops(gen: SquareSet, pro: SquareSet) -> SquareSet {
+    ORTHOGONAL
+        .iter()
+        .chain(&DIAGONAL)
+        .fold(SquareSet::default(), |res, &l| {
+            let hurdles = leap(line(gen, pro, l), l) & !pro;
+            res | leap(hurdles, l)
+        })
+}
+
+/// Squares that a cannon on `gen` can capture by sliding along a rank or file through `pro`,
+/// jumping the first square that's not in `pro`, and sliding on through `pro` to the next.
+fn screen_captures(gen: SquareSet, pro: SquareSet) -> SquareSet {
+    ORTHOGONAL.iter().fold(SquareSet::default(), |res, &l| {
+        let screens = leap(line(gen, pro, l), l) & !pro;
+        res | (leap(line(screens, pro, l), l) & !pro)
+    })
+}
+
+/// Which squares in `permeable` can a piece of type `ty` on one of the squares in `from` move to
+/// in one step? See `Stepper::move_reach`.
+fn move_reach(ty: PieceType, from: SquareSet, permeable: SquareSet) -> SquareSet {
+    match ty {
+        PieceType::Grasshopper => hops(from, permeable) & permeable,
+        ty => steps(from, move_leaps(ty)) & permeable,
+    }
+}
+
+/// Which squares can a piece of type `ty` on one of the squares in `from` capture in one step?
+/// See `Stepper::capture_reach`.
+fn capture_reach(ty: PieceType, from: SquareSet, permeable: SquareSet) -> SquareSet {
+    match ty {
+        PieceType::Grasshopper => hops(from, permeable),
+        PieceType::Cannon => screen_captures(from, permeable),
+        ty => steps(from, capture_leaps(ty)),
+    }
+}
+
+/// Given that a piece of type `ty` is on one of the squares in `from`, and may not move onto or
+/// through the squares in `obstacles`, which of the `targets` can it capture? This is
+/// `crate::captures`, on a bigger board.
+pub fn captures(
+    ty: PieceType,
+    from: SquareSet,
+    obstacles: SquareSet,
+    targets: SquareSet,
+) -> SquareSet {
+    let permeable = !(obstacles | targets);
+    let mut reachable = from & permeable;
+    loop {
+        let next = reachable | move_reach(ty, reachable, permeable);
+        if next == reachable {
+            break;
+        }
+        reachable = next;
+    }
+    capture_reach(ty, reachable, permeable) & targets
+}
+
+/// A puzzle on a board up to 16×16. See the module docs for what it leaves out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Puzzle {
+    /// Which squares have obstacles? Every square off the board is one too.
+    obstacles: SquareSet,
+    /// Each piece's type and square, in square order.
+    pieces: Vec<(PieceType, Square)>,
+    /// Which piece is initially controlled by the player?
+    player_start: u32,
+    /// Bitmask of the pieces that needn't be captured.
+    optional: u32,
+    width: u8,
+    height: u8,
+}
+
+/// Why a FEN string couldn't be parsed as a `Puzzle`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// Character `c`, at (0-based, in `char`s) `index` in the input, isn't valid here.
+    UnexpectedChar { c: char, index: usize },
+    /// There are more than 16 ranks.
+    TooManyRanks,
+    /// The given rank, counting up from `1` at the bottom of the board, has more than 16 files.
+    TooManyFiles { rank: u32 },
+    /// No piece is marked (in uppercase) as the player's.
+    MissingPlayer,
+    /// There are this many pieces, but a puzzle can have at most `MAX_PIECES`.
+    TooManyPieces(usize),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FenError::UnexpectedChar { c, index } => {
+                write!(f, "Unrecognized char in FEN at index {}: {:?}", index, c)
+            }
+            FenError::TooManyRanks => write!(f, "Too many ranks in FEN (max {})", MAX_SIZE),
+            FenError::TooManyFiles { rank } => {
+                write!(
+                    f,
+                    "Too many files on rank {} in FEN (max {})",
+                    rank, MAX_SIZE
+                )
+            }
+            FenError::MissingPlayer => write!(f, "No player location"),
+            FenError::TooManyPieces(n) => {
+                write!(f, "Too many pieces in FEN: {} (max {})", n, MAX_PIECES)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Puzzle {
+    /// Parses FEN for a board up to 16×16, as `crate::Puzzle::parse` does for 8×8: `X`/`x` is an
+    /// obstacle, the player's piece is in uppercase (the last one, if there are several), and `?`
+    /// after a piece makes it optional. Digits count empty squares one at a time, as they always
+    /// do, so sixteen empty squares are `88`, say, or `97`.
+    ///
+    /// ```
+    /// let puzzle = echochess::wide::Puzzle::parse("R8x1n/xx").unwrap();
+    /// assert_eq!((puzzle.width(), puzzle.height()), (12, 2));
+    /// assert_eq!(
+    ///     puzzle.to_string(),
+    ///     "2 R........#.n\n1 ##..........\n  abcdefghijkl\n"
+    /// );
+    /// ```
+    pub fn parse(fen: &str) -> Result<Puzzle, FenError> {
+        let height = fen.split('/').count() as u32;
+        if height > u32::from(MAX_SIZE) {
+            return Err(FenError::TooManyRanks);
+        }
+        let mut obstacles = SquareSet::default();
+        let mut pieces: Vec<(PieceType, Square)> = Vec::new();
+        let mut player_loc = None;
+        let mut optional = Vec::new();
+        let mut y = height - 1;
+        let mut x = 0;
+        let mut width = 0;
+        for (index, c) in fen.chars().enumerate() {
+            match c {
+                '/' => {
+                    y -= 1;
+                    width = width.max(x);
+                    x = 0;
+                    continue;
+                }
+                '0'..='9' => {
+                    x += c as u32 - '0' as u32;
+                    if x > u32::from(MAX_SIZE) {
+                        return Err(FenError::TooManyFiles { rank: y + 1 });
+                    }
+                    continue;
+                }
+                '?' => {
+                    // Marks the piece just before it as optional.
+                    let prev = x
+                        .checked_sub(1)
+                        .map(|x| Square(MAX_SIZE * y as u8 + x as u8));
+                    match pieces.last() {
+                        Some(&(_, sq)) if Some(sq) == prev => optional.push(sq),
+                        _ => return Err(FenError::UnexpectedChar { c, index }),
+                    }
+                    continue;
+                }
+                _ if x >= u32::from(MAX_SIZE) && c.is_ascii_alphabetic() => {
+                    return Err(FenError::TooManyFiles { rank: y + 1 });
+                }
+                _ => (),
+            }
+            // Only reached when `x < 16`, so always in range.
+            let loc = Square(MAX_SIZE * y as u8 + x as u8);
+            if let 'X' | 'x' = c {
+                obstacles.insert(loc);
+            } else {
+                let lower = c.to_ascii_lowercase();
+                let ty = PieceType::ALL
+                    .into_iter()
+                    .find(|ty| ty.fen_char() == lower)
+                    .ok_or(FenError::UnexpectedChar { c, index })?;
+                pieces.push((ty, loc));
+                if c.is_ascii_uppercase() {
+                    player_loc = Some(loc);
+                }
+            }
+            x += 1;
+        }
+        let player_loc = player_loc.ok_or(FenError::MissingPlayer)?;
+        if pieces.len() > MAX_PIECES {
+            return Err(FenError::TooManyPieces(pieces.len()));
+        }
+        let (width, height) = (width.max(x) as u8, height as u8);
+        pieces.sort_by_key(|&(_, sq)| sq);
+        let index_of = |loc| pieces.iter().position(|&(_, sq)| sq == loc).unwrap() as u32;
+        let board: SquareSet = (0..height)
+            .flat_map(|rank| (0..width).map(move |file| Square(MAX_SIZE * rank + file)))
+            .collect();
+        Ok(Puzzle {
+            obstacles: obstacles | !board,
+            player_start: index_of(player_loc),
+            optional: optional
+                .iter()
+                .fold(0, |acc, &loc| acc | 1 << index_of(loc)),
+            pieces,
+            width,
+            height,
+        })
+    }
+
+    /// How many files the board has.
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    /// How many ranks the board has.
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    /// Which squares have obstacles, counting every square off the board.
+    pub fn obstacles(&self) -> SquareSet {
+        self.obstacles
+    }
+
+    pub fn num_pieces(&self) -> u32 {
+        self.pieces.len() as u32
+    }
+
+    /// Which piece the player starts as.
+    pub fn player_start(&self) -> u32 {
+        self.player_start
+    }
+
+    /// The type of piece `piece_idx`, or `None` if there's no such piece.
+    pub fn piece_type(&self, piece_idx: u32) -> Option<PieceType> {
+        self.pieces.get(piece_idx as usize).map(|&(ty, _)| ty)
+    }
+
+    /// The square of piece `piece_idx`, or `None` if there's no such piece.
+    pub fn piece_loc(&self, piece_idx: u32) -> Option<Square> {
+        self.pieces.get(piece_idx as usize).map(|&(_, sq)| sq)
+    }
+
+    /// Whether piece `piece_idx` has to be captured to win.
+    pub fn required(&self, piece_idx: u32) -> bool {
+        self.optional & (1 << piece_idx) == 0
+    }
+}
+
+impl std::fmt::Display for Puzzle {
+    /// Draws the board like `crate::render::draw_puzzle` in plain style, with `#` for obstacles
+    /// and the player's piece in uppercase.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let label = self.height.to_string().len();
+        for rank in (0..self.height).rev() {
+            write!(f, "{:>label$} ", rank + 1)?;
+            for file in 0..self.width {
+                let sq = Square(MAX_SIZE * rank + file);
+                let c = match self.pieces.iter().position(|&(_, loc)| loc == sq) {
+                    Some(i) if i as u32 == self.player_start => {
+                        self.pieces[i].0.fen_char().to_ascii_uppercase()
+                    }
+                    Some(i) => self.pieces[i].0.fen_char(),
+                    None if self.obstacles.contains(sq) => '#',
+                    None => '.',
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        let files: String = (0..self.width)
+            .map(|file| char::from(b'a' + file))
+            .collect();
+        writeln!(f, "{:label$} {}", "", files)
+    }
+}
+
+/// A position in the search: the pieces still on the board, and which one the player is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct WideState {
+    remaining: u32,
+    current: u32,
+}
+
+/// Solves a puzzle with breadth-first search, like `crate::solve`. Returns the indices of the
+/// pieces to capture, in order, or `None` if there's no way to win.
+pub fn solve(p: &Puzzle) -> Option<Vec<u32>> {
+    let required = ((1 << p.num_pieces()) - 1) & !p.optional;
+    let initial = WideState {
+        remaining: ((1 << p.num_pieces()) - 1) & !(1 << p.player_start),
+        current: p.player_start,
+    };
+    if initial.remaining & required == 0 {
+        return Some(Vec::new());
+    }
+    // Each state seen, with the one before it.
+    let mut parents: HashMap<WideState, Option<WideState>> = HashMap::new();
+    parents.insert(initial, None);
+    let mut queue = VecDeque::from([initial]);
+    while let Some(state) = queue.pop_front() {
+        let targets: SquareSet = (0..p.num_pieces())
+            .filter(|&i| state.remaining & (1 << i) != 0)
+            .map(|i| p.pieces[i as usize].1)
+            .collect();
+        let (ty, loc) = p.pieces[state.current as usize];
+        for target in captures(ty, loc.into(), p.obstacles, targets) {
+            let captured = p.pieces.iter().position(|&(_, sq)| sq == target)? as u32;
+            let next = WideState {
+                remaining: state.remaining & !(1 << captured),
+                current: captured,
+            };
+            if parents.contains_key(&next) {
+                continue;
+            }
+            parents.insert(next, Some(state));
+            if next.remaining & required == 0 {
+                let mut res = Vec::new();
+                let mut cur = next;
+                while let Some(prev) = parents[&cur] {
+                    res.push(cur.current);
+                    cur = prev;
+                }
+                res.reverse();
+                return Some(res);
+            }
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifts_carry_between_words() {
+        let h4 = "h4".parse::<Square>().unwrap();
+        assert_eq!(h4.index(), 55);
+        let set = SquareSet::from(h4);
+        assert_eq!(set << 17, SquareSet::from("i5".parse::<Square>().unwrap()));
+        assert_eq!((set << 17) >> 17, set);
+        assert_eq!(set << 200, SquareSet::from(Square::new(255)));
+        assert_eq!(set << 201, SquareSet::default());
+        let p16: Square = "p16".parse().unwrap();
+        assert_eq!(p16.index(), 255);
+        assert_eq!(SquareSet::from(p16).iter().collect::<Vec<_>>(), vec![p16]);
+        assert_eq!(p16.to_string(), "p16");
+        for bad in ["q1", "a17", "a0", "a01", "", "a"] {
+            assert!(bad.parse::<Square>().is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn leaps_stop_at_the_edges() {
+        let sq = |name: &str| SquareSet::from(name.parse::<Square>().unwrap());
+        assert_eq!(leap(sq("p1"), (1, 0)), SquareSet::default());
+        assert_eq!(leap(sq("a9"), (-2, 1)), SquareSet::default());
+        assert_eq!(leap(sq("a9"), (2, -1)), sq("c8"));
+        assert_eq!(leap(sq("o16"), (0, 1)), SquareSet::default());
+        assert_eq!(steps(sq("b2"), move_leaps(PieceType::Knight)).count(), 4);
+    }
+
+    /// On boards that fit in 8×8, this module has to agree with the rest of the crate.
+    #[test]
+    fn agrees_with_small_boards() {
+        for fen in [
+            "R1b/3/1n1",
+            "Rxn/3/b2",
+            "R1n/3/n1n",
+            "Q7/8/8/8/8/8/8/q6q",
+            "rnbkbrn1/pp2pp2/8/3N4/8/2ppp3/8/8",
+            "4q3/2g5/8/1G2o1n1/8/3p4/1z6/2amc3",
+            "r?2b/1O3/x1n2/2p2",
+            "K1n/x1x/p1P",
+        ] {
+            let small = crate::Puzzle::parse(fen).unwrap();
+            let wide = Puzzle::parse(fen).unwrap();
+            let solution = solve(&wide);
+            assert_eq!(
+                solution.as_ref().map(Vec::len),
+                crate::solve(&small).map(|captures| captures.len()),
+                "{}",
+                fen
+            );
+            if let Some(captures) = solution {
+                assert!(small.verify(&captures).is_ok(), "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn big_boards() {
+        // A cannon has to jump the knight to take the rook, far across the board, and then the
+        // rook can take the knight.
+        let fen = "r8/88/88/88/88/88/88/88/88/88/88/88/88/88/n8/O8";
+        let puzzle = Puzzle::parse(fen).unwrap();
+        assert_eq!((puzzle.width(), puzzle.height()), (16, 16));
+        assert_eq!(solve(&puzzle), Some(vec![2, 1]));
+        assert_eq!(
+            Puzzle::parse(&format!("8/{}", fen)),
+            Err(FenError::TooManyRanks)
+        );
+        assert_eq!(
+            Puzzle::parse("R88x"),
+            Err(FenError::TooManyFiles { rank: 1 })
+        );
+        assert_eq!(
+            Puzzle::parse("n/R97"),
+            Err(FenError::TooManyFiles { rank: 1 })
+        );
+        // The bishop is walled off from everything.
+        let stuck = Puzzle::parse("1x6n/B").unwrap();
+        assert_eq!(solve(&stuck), None);
+    }
+}
@@ -0,0 +1,295 @@
+//! Runs the `echochess` binary end to end.
+
+use std::process::{Command, Output};
+
+/// Runs `echochess` with `args`.
+fn echochess(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_echochess"))
+        .args(args)
+        .output()
+        .expect("echochess runs")
+}
+
+/// Writes `contents` to a file named `name` in a fresh temporary directory, for one test.
+fn fixture(name: &str, contents: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("echochess-cli-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn steps_draws_the_board_after_each_capture() {
+    let path = fixture("fork.txt", "R1b/3/1n1\n");
+    let out = echochess(&[path.to_str().unwrap(), "--steps"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(
+        stdout.contains(" 1. Rxb1→N   capture Knight on b1 via a2 a1 b1\n3 ..b\n2 ...\n1 .N.\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(" 2. Nxc3→B   capture Bishop on c3 via c3\n3 ..B\n2 ...\n1 ...\n"));
+}
+
+#[test]
+fn steps_before_the_path() {
+    let path = fixture("fork-first.txt", "R1b/3/1n1\n");
+    let out = echochess(&["--steps", path.to_str().unwrap()]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .contains("solved in 2\n"));
+}
+
+#[test]
+fn solves_boards_bigger_than_8x8() {
+    let path = fixture("wide.txt", "; big\nR8x1n/xx\n");
+    let out = echochess(&[path.to_str().unwrap()]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(
+        stdout.starts_with("2 R........#.n\n1 ##..........\n  abcdefghijkl\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.ends_with(" 1. capture Knight on l2\nsolved in 1\n"));
+}
+
+#[test]
+fn analyze_shows_name_and_par() {
+    let path = fixture("named.txt", "; name: Fork\n; par: 2\nR1b/3/1n1\n");
+    let out = echochess(&["analyze", path.to_str().unwrap()]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(
+        stdout.starts_with("name: Fork\npar: 2, solved in 2\nstates: 4\n"),
+        "{}",
+        stdout
+    );
+}
+
+/// Serves `response` to the first connection on an ephemeral port, and returns a URL there.
+#[cfg(feature = "net")]
+fn serve(response: Vec<u8>) -> String {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).unwrap();
+            if n == 0 {
+                return;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream.write_all(&response).unwrap();
+    });
+    format!("http://{}/daily", addr)
+}
+
+/// A `200 OK` response with `body`.
+#[cfg(feature = "net")]
+fn ok(body: &str) -> Vec<u8> {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+/// Runs `echochess fetch` on a URL serving `response`, with `args` after it.
+#[cfg(feature = "net")]
+fn fetch(response: Vec<u8>, args: &[&str]) -> Output {
+    let url = serve(response);
+    let mut all = vec!["fetch", url.as_str()];
+    all.extend_from_slice(args);
+    echochess(&all)
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn fetch_compound_fen() {
+    let out = fetch(ok("; name: Fork\n; par: 2\nR1b/3/1n1\n"), &[]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.starts_with("Fork\n3 R.b\n"), "{}", stdout);
+    assert!(stdout.contains("solved in 2, par 2\n"));
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn fetch_share_code() {
+    for body in ["R1b-3-1n1\n", "https://echochess.com/#R1b-3-1n1"] {
+        let out = fetch(ok(body), &[]);
+        assert!(out.status.success(), "{:?}", body);
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(stdout.starts_with("3 R.b\n2 ...\n1 .n.\n"), "{}", stdout);
+        assert!(stdout.contains("solved in 2\n"));
+    }
+}
+
+#[cfg(all(feature = "net", feature = "serde"))]
+#[test]
+fn fetch_json() {
+    use echochess::pack::{parse_pack, write_pack_json};
+
+    let json = write_pack_json(&parse_pack("; name: Fork\nR1b/3/1n1\n"));
+    let out = fetch(ok(&json), &[]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.starts_with("Fork\n3 R.b\n"), "{}", stdout);
+}
+
+#[cfg(all(feature = "net", feature = "serde"))]
+#[test]
+fn fetch_with_output_flags() {
+    let out = fetch(ok("R1b/3/1n1\n"), &["--json"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.starts_with("{\"version\":1,"), "{}", stdout);
+
+    let out = fetch(ok("; name: Fork\nR1b/3/1n1\n"), &["--pgn"]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.starts_with("[Event \"Fork\"]\n"), "{}", stdout);
+}
+
+#[cfg(all(feature = "net", feature = "serde"))]
+#[test]
+fn fetch_with_custom_pieces() {
+    let pieces = fixture(
+        "pieces.json",
+        r#"[{"letter": "y", "name": "knight-rook",
+             "moves": [{"step": [1, 2]}, {"step": [1, 0], "range": 7}], "symmetric": true}]"#,
+    );
+    let out = fetch(ok("Y1b/3/1n1\n"), &["--pieces", pieces.to_str().unwrap()]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .contains("solved in 2\n"));
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn fetch_chunked_text() {
+    // The name's `É` is split across chunks, which must be joined as bytes.
+    let name = "; name: Échec\n".as_bytes();
+    let fen = b"R1b/3/1n1\n";
+    let mut response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+    for chunk in [&name[..9], &name[9..], fen] {
+        response.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+        response.extend_from_slice(chunk);
+        response.extend_from_slice(b"\r\n");
+    }
+    response.extend_from_slice(b"0\r\n\r\n");
+    let out = fetch(response, &[]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .starts_with("Échec\n"));
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn fetch_exit_codes() {
+    let not_found = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec();
+    assert_eq!(fetch(not_found, &[]).status.code(), Some(2));
+    for body in [
+        "<html>hello</html>\n",
+        "; par: x\nR1b/3/1n1\n",
+        "; just a comment\n",
+    ] {
+        let out = fetch(ok(body), &[]);
+        assert_eq!(out.status.code(), Some(3), "{:?}", body);
+    }
+    let out = fetch(ok("; par: x\nR1b/3/1n1\n"), &[]);
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .ends_with("line 1: Bad par: \"x\"\n"));
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn fetch_times_out_on_a_slow_server() {
+    use std::io::{Read, Write};
+    use std::time::{Duration, Instant};
+
+    // Each byte arrives well within the timeout, but the whole response never does.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = stream.read(&mut [0; 1024]);
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n");
+        for _ in 0..1000 {
+            std::thread::sleep(Duration::from_millis(100));
+            if stream.write_all(b"1").is_err() {
+                return;
+            }
+        }
+    });
+    let url = format!("http://{}/daily", addr);
+    let start = Instant::now();
+    let out = echochess(&["fetch", &url, "--timeout", "0.5"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .ends_with("timed out\n"));
+}
+
+#[test]
+fn protocol_over_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_echochess"))
+        .arg("protocol")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all("puzzle R1n/3/n1n\ngrade c3 c1 a1\ngrade Rxa1→N c3 c1\n".as_bytes())
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "ok\nvalid S moves 6 6 distance 6 6\nvalid C moves 8 6 distance 8 6\n"
+    );
+}